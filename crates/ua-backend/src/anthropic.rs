@@ -7,9 +7,9 @@ use futures::Stream;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use thiserror::Error;
 use ua_protocol::{AgentRequest, StreamEvent};
 
+use crate::backend::{send_with_retry, Backend, BackendError, BackendFuture, EventStream};
 use crate::sse::{parse_sse_stream, SseEvent};
 
 const API_URL: &str = "https://api.anthropic.com/v1/messages";
@@ -35,16 +35,6 @@ struct SystemBlock {
     cache_control: Option<CacheControl>,
 }
 
-#[derive(Debug, Error)]
-pub enum AnthropicError {
-    #[error("HTTP error: {0}")]
-    Http(#[from] reqwest::Error),
-    #[error("JSON error: {0}")]
-    Json(#[from] serde_json::Error),
-    #[error("API error: {0}")]
-    Api(String),
-}
-
 /// Anthropic API client.
 pub struct AnthropicClient {
     api_key: String,
@@ -53,7 +43,7 @@ pub struct AnthropicClient {
 }
 
 /// Build an HTTP client with appropriate timeouts and connection limits.
-fn build_http_client() -> Client {
+pub(crate) fn build_http_client() -> Client {
     Client::builder()
         .timeout(Duration::from_secs(120))
         .connect_timeout(Duration::from_secs(10))
@@ -87,7 +77,7 @@ impl AnthropicClient {
         &self,
         system_prompt: &str,
         user_message: &str,
-    ) -> Result<String, AnthropicError> {
+    ) -> Result<String, BackendError> {
         let body = NonStreamingRequest {
             model: self.model.clone(),
             max_tokens: 1024,
@@ -107,7 +97,7 @@ impl AnthropicClient {
                 ResponseContentBlock::Text { text } => text,
             })
             .next()
-            .ok_or_else(|| AnthropicError::Api("no text content in response".to_string()))
+            .ok_or_else(|| BackendError::Api("no text content in response".to_string()))
     }
 
     /// Send a request and return a stream of events.
@@ -150,12 +140,30 @@ impl AnthropicClient {
     }
 }
 
+impl Backend for AnthropicClient {
+    fn send(&self, request: &AgentRequest) -> EventStream {
+        Box::pin(AnthropicClient::send(self, request))
+    }
+
+    fn send_non_streaming<'a>(
+        &'a self,
+        system_prompt: &'a str,
+        user_message: &'a str,
+    ) -> BackendFuture<'a, Result<String, BackendError>> {
+        Box::pin(AnthropicClient::send_non_streaming(
+            self,
+            system_prompt,
+            user_message,
+        ))
+    }
+}
+
 async fn send_request(
     http: &Client,
     api_key: &str,
     model: &str,
     request: &AgentRequest,
-) -> Result<reqwest::Response, AnthropicError> {
+) -> Result<reqwest::Response, BackendError> {
     let system = build_system_blocks(request);
     let messages = build_messages(request);
 
@@ -175,81 +183,48 @@ async fn send_request(
     post_with_retry(http, api_key, &body).await
 }
 
-fn parse_retry_after(response: &reqwest::Response) -> Option<Duration> {
-    response
-        .headers()
-        .get("retry-after")
-        .and_then(|v| v.to_str().ok())
-        .and_then(|s| s.parse::<f64>().ok())
-        .map(Duration::from_secs_f64)
-}
-
 async fn post_with_retry(
     http: &Client,
     api_key: &str,
     body: &impl Serialize,
-) -> Result<reqwest::Response, AnthropicError> {
-    let max_retries = 5;
-    let mut attempt = 0;
-
-    loop {
-        let response = http
-            .post(API_URL)
+) -> Result<reqwest::Response, BackendError> {
+    send_with_retry(|| {
+        http.post(API_URL)
             .header("x-api-key", api_key)
             .header("anthropic-version", API_VERSION)
             .header("content-type", "application/json")
             .json(body)
-            .send()
-            .await?;
-
-        if response.status() == 429 && attempt < max_retries {
-            let delay =
-                parse_retry_after(&response).unwrap_or_else(|| Duration::from_secs(1 << attempt));
-            eprintln!("[ua] rate limited, retrying in {}s", delay.as_secs());
-            tokio::time::sleep(delay).await;
-            attempt += 1;
-            continue;
-        }
-
-        if response.status() == 529 && attempt < max_retries {
-            let delay =
-                parse_retry_after(&response).unwrap_or_else(|| Duration::from_secs(1 << attempt));
-            eprintln!("[ua] API overloaded, retrying in {}s", delay.as_secs());
-            tokio::time::sleep(delay).await;
-            attempt += 1;
-            continue;
-        }
+    })
+    .await
+}
 
-        return if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            Err(AnthropicError::Api(format!("{status}: {body}")))
-        } else {
-            Ok(response)
-        };
-    }
+pub(crate) const SHELL_TOOL_DESCRIPTION: &str =
+    "Execute a shell command. The command runs in the user's terminal via PTY.";
+
+/// JSON schema for the `shell` tool input, shared by all providers.
+pub(crate) fn shell_tool_input_schema() -> Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "command": {
+                "type": "string",
+                "description": "Shell command to execute. Chain multiple commands with && if needed."
+            },
+            "output_mode": {
+                "type": "string",
+                "enum": ["full", "final"],
+                "description": "How to capture output. 'full' (default): all output including dynamic content like progress bars. 'final': only the final state of each line (collapses \\r-overwritten content)."
+            }
+        },
+        "required": ["command"]
+    })
 }
 
 fn build_shell_tool() -> ApiTool {
     ApiTool {
         name: "shell".to_string(),
-        description: "Execute a shell command. The command runs in the user's terminal via PTY."
-            .to_string(),
-        input_schema: serde_json::json!({
-            "type": "object",
-            "properties": {
-                "command": {
-                    "type": "string",
-                    "description": "Shell command to execute. Chain multiple commands with && if needed."
-                },
-                "output_mode": {
-                    "type": "string",
-                    "enum": ["full", "final"],
-                    "description": "How to capture output. 'full' (default): all output including dynamic content like progress bars. 'final': only the final state of each line (collapses \\r-overwritten content)."
-                }
-            },
-            "required": ["command"]
-        }),
+        description: SHELL_TOOL_DESCRIPTION.to_string(),
+        input_schema: shell_tool_input_schema(),
         cache_control: Some(EPHEMERAL),
     }
 }
@@ -276,6 +251,21 @@ Terminal: {}x{}",
         ctx.cwd, ctx.shell, ctx.platform, ctx.arch, cols, rows
     );
 
+    if !ctx.levels.is_empty() {
+        prompt.push_str("\n\nNested shells (outermost first; your commands run in the last one):");
+        for (depth, level) in ctx.levels.iter().enumerate() {
+            let host = if level.host.is_empty() {
+                "unknown host"
+            } else {
+                &level.host
+            };
+            prompt.push_str(&format!("\n  {depth}. {} on {host}", level.shell));
+            if !level.cwd.is_empty() {
+                prompt.push_str(&format!(" in {}", level.cwd));
+            }
+        }
+    }
+
     if !ctx.env_vars.is_empty() {
         prompt.push_str("\n\nEnvironment variables:");
         for (key, value) in &ctx.env_vars {
@@ -290,6 +280,13 @@ Terminal: {}x{}",
         }
     }
 
+    if let Some(ref screen) = request.screen {
+        prompt.push_str(
+            "\n\nScreen (a full-screen program is running; this is what the user sees):\n",
+        );
+        prompt.push_str(screen);
+    }
+
     prompt.push_str(
         "\n\nUse the shell tool to execute commands. \
          Each command runs and you will see the output. \
@@ -739,8 +736,8 @@ enum ResponseContentBlock {
 mod tests {
     use super::*;
     use ua_protocol::{
-        Attachment, ConversationMessage, ShellContext, TerminalHistory, ToolResultRecord,
-        ToolUseRecord,
+        Attachment, ConversationMessage, ShellContext, ShellLevel, TerminalHistory,
+        ToolResultRecord, ToolUseRecord,
     };
 
     #[test]
@@ -754,11 +751,13 @@ mod tests {
                 arch: "x86_64".to_string(),
                 env_vars: vec![],
                 terminal_size: (80, 24),
+                levels: vec![],
             },
             terminal_history: TerminalHistory::new(),
             conversation: vec![],
             system_prompt_extra: None,
             attachments: vec![],
+            screen: None,
         };
 
         let prompt = build_system_prompt(&request);
@@ -783,11 +782,13 @@ mod tests {
                     ("HOME".to_string(), "/home/user".to_string()),
                 ],
                 terminal_size: (120, 40),
+                levels: vec![],
             },
             terminal_history: TerminalHistory::new(),
             conversation: vec![],
             system_prompt_extra: None,
             attachments: vec![],
+            screen: None,
         };
 
         let prompt = build_system_prompt(&request);
@@ -796,6 +797,38 @@ mod tests {
         assert!(prompt.contains("HOME=/home/user"));
     }
 
+    #[test]
+    fn build_system_prompt_with_nested_shells() {
+        let level = |shell: &str, host: &str, cwd: &str| ShellLevel {
+            shell: shell.to_string(),
+            host: host.to_string(),
+            cwd: cwd.to_string(),
+        };
+        let request = AgentRequest {
+            instruction: "test".to_string(),
+            context: ShellContext {
+                cwd: "/srv/app".to_string(),
+                levels: vec![
+                    level("/bin/zsh", "laptop", "/home/me"),
+                    level("bash", "web-1", "/srv/app"),
+                    level("sh", "", ""),
+                ],
+                ..ShellContext::default()
+            },
+            terminal_history: TerminalHistory::new(),
+            conversation: vec![],
+            system_prompt_extra: None,
+            attachments: vec![],
+            screen: None,
+        };
+
+        let prompt = build_system_prompt(&request);
+        assert!(prompt.contains("Nested shells"));
+        assert!(prompt.contains("\n  0. /bin/zsh on laptop in /home/me"));
+        assert!(prompt.contains("\n  1. bash on web-1 in /srv/app"));
+        assert!(prompt.contains("\n  2. sh on unknown host\n"));
+    }
+
     #[test]
     fn build_system_prompt_with_history() {
         let request = AgentRequest {
@@ -808,12 +841,26 @@ mod tests {
             conversation: vec![],
             system_prompt_extra: None,
             attachments: vec![],
+            screen: None,
         };
 
         let prompt = build_system_prompt(&request);
         assert!(prompt.contains("Recent terminal output:"));
         assert!(prompt.contains("$ ls"));
         assert!(prompt.contains("file1.txt  file2.txt"));
+        assert!(!prompt.contains("Screen ("));
+    }
+
+    #[test]
+    fn build_system_prompt_with_screen() {
+        let request = AgentRequest::new("why is it slow", ShellContext::default())
+            .with_screen("  PID USER  %CPU COMMAND\n 4242 me    99.0 cargo".to_string());
+
+        let prompt = build_system_prompt(&request);
+        assert!(prompt.contains(
+            "Screen (a full-screen program is running; this is what the user sees):\n  PID USER"
+        ));
+        assert!(prompt.contains("4242 me    99.0 cargo"));
     }
 
     #[test]
@@ -825,6 +872,7 @@ mod tests {
             conversation: vec![],
             system_prompt_extra: None,
             attachments: vec![],
+            screen: None,
         };
 
         let messages = build_messages(&request);
@@ -844,6 +892,7 @@ mod tests {
             ],
             system_prompt_extra: None,
             attachments: vec![],
+            screen: None,
         };
 
         let messages = build_messages(&request);
@@ -1023,6 +1072,7 @@ mod tests {
             ],
             system_prompt_extra: None,
             attachments: vec![],
+            screen: None,
         };
 
         let messages = build_messages(&request);
@@ -1050,6 +1100,7 @@ mod tests {
             ])],
             system_prompt_extra: None,
             attachments: vec![],
+            screen: None,
         };
 
         let messages = build_messages(&request);
@@ -1087,6 +1138,7 @@ mod tests {
             ],
             system_prompt_extra: None,
             attachments: vec![],
+            screen: None,
         };
 
         let messages = build_messages(&request);
@@ -1109,6 +1161,7 @@ mod tests {
             ],
             system_prompt_extra: None,
             attachments: vec![],
+            screen: None,
         };
 
         let messages = build_messages(&request);
@@ -1132,6 +1185,7 @@ mod tests {
             )],
             system_prompt_extra: None,
             attachments: vec![],
+            screen: None,
         };
 
         let messages = build_messages(&request);
@@ -1308,6 +1362,7 @@ mod tests {
             conversation: vec![],
             system_prompt_extra: None,
             attachments: vec![],
+            screen: None,
         };
 
         let messages = build_messages(&request);
@@ -1334,6 +1389,7 @@ mod tests {
             ])],
             system_prompt_extra: None,
             attachments: vec![],
+            screen: None,
         };
 
         let messages = build_messages(&request);
@@ -1357,6 +1413,7 @@ mod tests {
             ],
             system_prompt_extra: None,
             attachments: vec![],
+            screen: None,
         };
 
         let messages = build_messages(&request);
@@ -1372,48 +1429,6 @@ mod tests {
         assert_eq!(blocks[0]["cache_control"]["type"], "ephemeral");
     }
 
-    #[test]
-    fn parse_retry_after_valid() {
-        let resp = http::Response::builder()
-            .status(429)
-            .header("retry-after", "1.5")
-            .body("")
-            .unwrap();
-        let reqwest_resp = reqwest::Response::from(resp);
-        let duration = parse_retry_after(&reqwest_resp).unwrap();
-        assert_eq!(duration, Duration::from_secs_f64(1.5));
-    }
-
-    #[test]
-    fn parse_retry_after_integer() {
-        let resp = http::Response::builder()
-            .status(429)
-            .header("retry-after", "3")
-            .body("")
-            .unwrap();
-        let reqwest_resp = reqwest::Response::from(resp);
-        let duration = parse_retry_after(&reqwest_resp).unwrap();
-        assert_eq!(duration, Duration::from_secs(3));
-    }
-
-    #[test]
-    fn parse_retry_after_missing() {
-        let resp = http::Response::builder().status(429).body("").unwrap();
-        let reqwest_resp = reqwest::Response::from(resp);
-        assert!(parse_retry_after(&reqwest_resp).is_none());
-    }
-
-    #[test]
-    fn parse_retry_after_invalid() {
-        let resp = http::Response::builder()
-            .status(429)
-            .header("retry-after", "not-a-number")
-            .body("")
-            .unwrap();
-        let reqwest_resp = reqwest::Response::from(resp);
-        assert!(parse_retry_after(&reqwest_resp).is_none());
-    }
-
     #[test]
     fn image_content_block_serialization() {
         let block = ApiContentBlock::Image {
@@ -1445,6 +1460,7 @@ mod tests {
                 media_type: "image/png".to_string(),
                 data: "base64data".to_string(),
             }],
+            screen: None,
         };
 
         let messages = build_messages(&request);
@@ -1474,6 +1490,7 @@ mod tests {
                 media_type: "image/jpeg".to_string(),
                 data: "jpegdata".to_string(),
             }],
+            screen: None,
         };
 
         let messages = build_messages(&request);
@@ -1506,6 +1523,7 @@ mod tests {
                     data: "data2".to_string(),
                 },
             ],
+            screen: None,
         };
 
         let messages = build_messages(&request);
@@ -1570,6 +1588,7 @@ mod tests {
             conversation: vec![ConversationMessage::tool_result(vec![tr])],
             system_prompt_extra: None,
             attachments: vec![],
+            screen: None,
         };
 
         let messages = build_messages(&request);
@@ -1597,6 +1616,7 @@ mod tests {
             ])],
             system_prompt_extra: None,
             attachments: vec![],
+            screen: None,
         };
 
         let messages = build_messages(&request);
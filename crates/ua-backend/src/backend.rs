@@ -0,0 +1,228 @@
+//! Provider-agnostic backend trait.
+//!
+//! Every LLM provider (and the test mock) implements [`Backend`], so the
+//! REPL, batch loop, and security judge never depend on a concrete client.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use futures::Stream;
+use thiserror::Error;
+use ua_protocol::{AgentRequest, StreamEvent};
+
+/// Boxed stream of events returned by [`Backend::send`].
+pub type EventStream = Pin<Box<dyn Stream<Item = StreamEvent> + Send + 'static>>;
+
+/// Boxed future returned by [`Backend::send_non_streaming`].
+pub type BackendFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+#[derive(Debug, Error)]
+pub enum BackendError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("API error: {0}")]
+    Api(String),
+}
+
+/// An LLM provider the agent can talk to.
+///
+/// Object-safe so callers can hold an `Arc<dyn Backend>` chosen at startup.
+pub trait Backend: Send + Sync {
+    /// Send an agent request and return a stream of events.
+    ///
+    /// The stream always terminates with `StreamEvent::Done` or
+    /// `StreamEvent::Error`. It owns everything it needs, so it can be moved
+    /// into a spawned task.
+    fn send(&self, request: &AgentRequest) -> EventStream;
+
+    /// Send a single system prompt + user message without tools or thinking.
+    /// Returns the text content of the response.
+    fn send_non_streaming<'a>(
+        &'a self,
+        system_prompt: &'a str,
+        user_message: &'a str,
+    ) -> BackendFuture<'a, Result<String, BackendError>>;
+}
+
+/// Builds a backend, or says why it can't (e.g. no API key).
+type Resolve = Box<dyn Fn() -> Result<Arc<dyn Backend>, String> + Send + Sync>;
+
+/// A backend built on first use.
+///
+/// Lets the REPL start without credentials: a missing API key surfaces as
+/// the error of the first request, and is looked up again on the next one
+/// until a backend has been built.
+pub struct LazyBackend {
+    resolve: Resolve,
+    backend: OnceLock<Arc<dyn Backend>>,
+}
+
+impl LazyBackend {
+    pub fn new(
+        resolve: impl Fn() -> Result<Arc<dyn Backend>, String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            resolve: Box::new(resolve),
+            backend: OnceLock::new(),
+        }
+    }
+
+    fn backend(&self) -> Result<Arc<dyn Backend>, String> {
+        if let Some(backend) = self.backend.get() {
+            return Ok(Arc::clone(backend));
+        }
+        let backend = (self.resolve)()?;
+        Ok(Arc::clone(self.backend.get_or_init(|| backend)))
+    }
+}
+
+impl Backend for LazyBackend {
+    fn send(&self, request: &AgentRequest) -> EventStream {
+        match self.backend() {
+            Ok(backend) => backend.send(request),
+            Err(e) => Box::pin(futures::stream::iter([StreamEvent::Error(e)])),
+        }
+    }
+
+    fn send_non_streaming<'a>(
+        &'a self,
+        system_prompt: &'a str,
+        user_message: &'a str,
+    ) -> BackendFuture<'a, Result<String, BackendError>> {
+        let backend = self.backend();
+        Box::pin(async move {
+            backend
+                .map_err(BackendError::Api)?
+                .send_non_streaming(system_prompt, user_message)
+                .await
+        })
+    }
+}
+
+/// Retries of a request the server turned away as busy.
+const MAX_RETRIES: u32 = 5;
+
+/// Send the request `build` makes, retrying while the server is busy: rate
+/// limited (429), unavailable (503) or overloaded (529). Waits as long as
+/// `retry-after` says, or backs off exponentially. Any other failure status
+/// is an [`BackendError::Api`] with the response body.
+pub(crate) async fn send_with_retry(
+    build: impl Fn() -> reqwest::RequestBuilder,
+) -> Result<reqwest::Response, BackendError> {
+    let mut attempt = 0;
+    loop {
+        let response = build().send().await?;
+        let status = response.status();
+
+        let busy = match status.as_u16() {
+            429 => Some("rate limited"),
+            503 => Some("server unavailable"),
+            529 => Some("API overloaded"),
+            _ => None,
+        };
+        if let Some(busy) = busy.filter(|_| attempt < MAX_RETRIES) {
+            let delay =
+                parse_retry_after(&response).unwrap_or_else(|| Duration::from_secs(1 << attempt));
+            eprintln!("[ua] {busy}, retrying in {}s", delay.as_secs());
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+            continue;
+        }
+
+        return if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            Err(BackendError::Api(format!("{status}: {body}")))
+        } else {
+            Ok(response)
+        };
+    }
+}
+
+pub(crate) fn parse_retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get("retry-after")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.parse::<f64>().ok())
+        .map(Duration::from_secs_f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::mock::{fixtures, MockBackend};
+
+    #[tokio::test]
+    async fn lazy_backend_reports_errors_until_resolved() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let lazy = LazyBackend::new(move || {
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err("no API key".to_string());
+            }
+            let mock = MockBackend::new().with_turn(fixtures::streaming_text(&["hi"]));
+            Ok(Arc::new(mock) as Arc<dyn Backend>)
+        });
+        let request = AgentRequest::new("list files", ua_protocol::ShellContext::default());
+
+        let events: Vec<_> = lazy.send(&request).collect().await;
+        assert_eq!(events, vec![StreamEvent::Error("no API key".to_string())]);
+
+        let events: Vec<_> = lazy.send(&request).collect().await;
+        assert_eq!(events[0], StreamEvent::TextDelta("hi".to_string()));
+
+        // Built once; later requests reuse it.
+        let _ = lazy.send(&request);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn parse_retry_after_valid() {
+        let resp = http::Response::builder()
+            .status(429)
+            .header("retry-after", "1.5")
+            .body("")
+            .unwrap();
+        let reqwest_resp = reqwest::Response::from(resp);
+        let duration = parse_retry_after(&reqwest_resp).unwrap();
+        assert_eq!(duration, Duration::from_secs_f64(1.5));
+    }
+
+    #[test]
+    fn parse_retry_after_integer() {
+        let resp = http::Response::builder()
+            .status(429)
+            .header("retry-after", "3")
+            .body("")
+            .unwrap();
+        let reqwest_resp = reqwest::Response::from(resp);
+        let duration = parse_retry_after(&reqwest_resp).unwrap();
+        assert_eq!(duration, Duration::from_secs(3));
+    }
+
+    #[test]
+    fn parse_retry_after_missing() {
+        let resp = http::Response::builder().status(429).body("").unwrap();
+        let reqwest_resp = reqwest::Response::from(resp);
+        assert!(parse_retry_after(&reqwest_resp).is_none());
+    }
+
+    #[test]
+    fn parse_retry_after_invalid() {
+        let resp = http::Response::builder()
+            .status(429)
+            .header("retry-after", "not-a-number")
+            .body("")
+            .unwrap();
+        let reqwest_resp = reqwest::Response::from(resp);
+        assert!(parse_retry_after(&reqwest_resp).is_none());
+    }
+}
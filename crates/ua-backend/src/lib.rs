@@ -4,8 +4,12 @@
 //! (Anthropic, OpenAI, etc.) using streaming responses.
 
 pub mod anthropic;
+pub mod backend;
 pub mod mock;
+pub mod openai;
 pub mod sse;
 
 pub use anthropic::AnthropicClient;
-pub use mock::{MockConfig, MockResponse};
+pub use backend::{Backend, BackendError, EventStream, LazyBackend};
+pub use mock::{MockBackend, MockConfig, MockResponse, MockScript};
+pub use openai::OpenAiClient;
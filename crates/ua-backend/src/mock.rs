@@ -3,15 +3,22 @@
 //! Produces the exact same `StreamEvent` sequence as the real Anthropic adapter,
 //! allowing tests at every layer to use the mock instead of real HTTP.
 
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::Mutex;
 use std::time::Duration;
 
 use async_stream::stream;
 use futures::Stream;
+use serde::Deserialize;
 use tokio::time::sleep;
-use ua_protocol::StreamEvent;
+use ua_protocol::{AgentRequest, StreamEvent};
+
+use crate::backend::{Backend, BackendError, BackendFuture, EventStream};
 
 /// Configurable mock responses for testing.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum MockResponse {
     /// Emit a thinking delta.
     Thinking { content: String },
@@ -95,6 +102,100 @@ pub fn mock_stream(config: MockConfig) -> impl Stream<Item = StreamEvent> {
     }
 }
 
+/// Scripted conversation for [`MockBackend`], loadable from JSON.
+///
+/// ```json
+/// {
+///   "turns": [
+///     [{"type": "tool_use", "id": "toolu_1", "name": "shell", "input_json": "{\"command\":\"ls\"}"}],
+///     [{"type": "text", "content": "Done."}]
+///   ],
+///   "replies": ["{\"safe\": true, \"reasoning\": \"read-only\"}"]
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MockScript {
+    /// One response sequence per streaming turn, consumed in order.
+    pub turns: Vec<Vec<MockResponse>>,
+    /// Replies to non-streaming requests (e.g. the judge), consumed in order.
+    pub replies: Vec<String>,
+}
+
+/// Backend that replays scripted turns instead of calling a provider.
+///
+/// Each `send` consumes the next turn; each `send_non_streaming` consumes the
+/// next reply. Running out of either is reported as an error so a runaway
+/// agent loop fails fast instead of spinning.
+#[derive(Debug, Default)]
+pub struct MockBackend {
+    turns: Mutex<VecDeque<MockConfig>>,
+    replies: Mutex<VecDeque<String>>,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a streaming turn.
+    pub fn with_turn(self, turn: MockConfig) -> Self {
+        self.turns.lock().unwrap().push_back(turn);
+        self
+    }
+
+    /// Queue a non-streaming reply.
+    pub fn with_reply(self, reply: impl Into<String>) -> Self {
+        self.replies.lock().unwrap().push_back(reply.into());
+        self
+    }
+
+    /// Build a backend from a parsed script.
+    pub fn from_script(script: MockScript) -> Self {
+        let backend = script.turns.into_iter().fold(Self::new(), |b, responses| {
+            b.with_turn(MockConfig::new().with_responses(responses))
+        });
+        script
+            .replies
+            .into_iter()
+            .fold(backend, |b, reply| b.with_reply(reply))
+    }
+
+    /// Load a JSON script (see [`MockScript`]) from disk.
+    pub fn from_file(path: &Path) -> Result<Self, BackendError> {
+        let data = std::fs::read_to_string(path).map_err(|e| {
+            BackendError::Api(format!(
+                "failed to read mock script {}: {e}",
+                path.display()
+            ))
+        })?;
+        let script: MockScript = serde_json::from_str(&data)?;
+        Ok(Self::from_script(script))
+    }
+}
+
+impl Backend for MockBackend {
+    fn send(&self, _request: &AgentRequest) -> EventStream {
+        match self.turns.lock().unwrap().pop_front() {
+            Some(config) => Box::pin(mock_stream(config)),
+            None => Box::pin(futures::stream::iter([StreamEvent::Error(
+                "mock backend: no scripted turns left".to_string(),
+            )])),
+        }
+    }
+
+    fn send_non_streaming<'a>(
+        &'a self,
+        _system_prompt: &'a str,
+        _user_message: &'a str,
+    ) -> BackendFuture<'a, Result<String, BackendError>> {
+        let reply = self.replies.lock().unwrap().pop_front();
+        Box::pin(async move {
+            reply.ok_or_else(|| BackendError::Api("mock backend: no scripted replies left".into()))
+        })
+    }
+}
+
 /// Built-in test fixtures for common scenarios.
 pub mod fixtures {
     use super::*;
@@ -295,4 +396,51 @@ mod tests {
         );
         assert_eq!(events[1], StreamEvent::Error("Rate limited".to_string()));
     }
+
+    fn test_request() -> AgentRequest {
+        AgentRequest::new("list files", ua_protocol::ShellContext::default())
+    }
+
+    #[tokio::test]
+    async fn mock_backend_replays_turns_in_order() {
+        let backend = MockBackend::new()
+            .with_turn(fixtures::text_with_commands("Listing", &["ls"]))
+            .with_turn(fixtures::streaming_text(&["Done."]));
+        let request = test_request();
+
+        let first: Vec<_> = backend.send(&request).collect().await;
+        assert!(matches!(first[1], StreamEvent::ToolUse { .. }));
+
+        let second: Vec<_> = backend.send(&request).collect().await;
+        assert_eq!(second[0], StreamEvent::TextDelta("Done.".to_string()));
+
+        let third: Vec<_> = backend.send(&request).collect().await;
+        assert!(matches!(third.as_slice(), [StreamEvent::Error(_)]));
+    }
+
+    #[tokio::test]
+    async fn mock_backend_non_streaming_replies() {
+        let backend = MockBackend::new().with_reply("first");
+        assert_eq!(
+            backend.send_non_streaming("sys", "msg").await.unwrap(),
+            "first"
+        );
+        assert!(backend.send_non_streaming("sys", "msg").await.is_err());
+    }
+
+    #[test]
+    fn mock_script_parses_json() {
+        let json = r#"{
+            "turns": [
+                [{"type": "thinking", "content": "hmm"},
+                 {"type": "tool_use", "id": "t1", "name": "shell", "input_json": "{}"}],
+                [{"type": "text", "content": "ok"}]
+            ],
+            "replies": ["{\"safe\": true, \"reasoning\": \"fine\"}"]
+        }"#;
+        let script: MockScript = serde_json::from_str(json).unwrap();
+        assert_eq!(script.turns.len(), 2);
+        assert!(matches!(script.turns[0][1], MockResponse::ToolUse { .. }));
+        assert_eq!(script.replies.len(), 1);
+    }
 }
@@ -0,0 +1,845 @@
+//! OpenAI-compatible chat-completions client with SSE streaming support.
+//!
+//! Speaks `POST {base_url}/chat/completions`, so it works against the OpenAI
+//! API as well as local servers exposing the same protocol (llama.cpp,
+//! vLLM, Ollama).
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use async_stream::stream;
+use futures::Stream;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use ua_protocol::{AgentRequest, StreamEvent};
+
+use crate::anthropic::{
+    build_http_client, build_system_prompt, shell_tool_input_schema, SHELL_TOOL_DESCRIPTION,
+};
+use crate::backend::{send_with_retry, Backend, BackendError, BackendFuture, EventStream};
+use crate::sse::{parse_sse_stream, SseEvent};
+
+pub const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_MODEL: &str = "gpt-4o";
+
+/// Numbers the ids made up for tool calls a server sent without one.
+static NEXT_CALL_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Client for OpenAI-compatible `/v1/chat/completions` endpoints.
+pub struct OpenAiClient {
+    /// Bearer token. Empty means no `Authorization` header (local servers).
+    api_key: String,
+    model: String,
+    base_url: String,
+    http: Client,
+}
+
+impl OpenAiClient {
+    /// Create a new client for the OpenAI API with the given API key.
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self::with_model(api_key, DEFAULT_MODEL)
+    }
+
+    /// Create a new client with a custom model.
+    pub fn with_model(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            model: model.into(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            http: build_http_client(),
+        }
+    }
+
+    /// Point the client at a different server, e.g. `http://localhost:8080/v1`.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    fn endpoint(&self) -> String {
+        format!("{}/chat/completions", self.base_url)
+    }
+
+    /// Send a non-streaming request with a system prompt and user message.
+    /// Returns the text content of the response. No tools.
+    pub async fn send_non_streaming(
+        &self,
+        system_prompt: &str,
+        user_message: &str,
+    ) -> Result<String, BackendError> {
+        let body = ChatRequest {
+            model: self.model.clone(),
+            stream: false,
+            stream_options: None,
+            messages: vec![
+                ChatMessage::text("system", system_prompt),
+                ChatMessage::text("user", user_message),
+            ],
+            tools: Vec::new(),
+        };
+
+        let response = post_with_retry(&self.http, &self.endpoint(), &self.api_key, &body).await?;
+
+        let resp: ChatResponse = response.json().await?;
+        resp.choices
+            .into_iter()
+            .find_map(|choice| choice.message.content)
+            .ok_or_else(|| BackendError::Api("no text content in response".to_string()))
+    }
+
+    /// Send a request and return a stream of events.
+    pub fn send(&self, request: &AgentRequest) -> impl Stream<Item = StreamEvent> + Send + 'static {
+        let api_key = self.api_key.clone();
+        let url = self.endpoint();
+        let http = self.http.clone();
+        let body = ChatRequest {
+            model: self.model.clone(),
+            stream: true,
+            stream_options: Some(StreamOptions {
+                include_usage: true,
+            }),
+            messages: build_messages(request),
+            tools: vec![build_shell_tool()],
+        };
+
+        stream! {
+            match post_with_retry(&http, &url, &api_key, &body).await {
+                Ok(response) => {
+                    let byte_stream = response.bytes_stream();
+                    let mut sse_stream = parse_sse_stream(byte_stream);
+                    let mut processor = ChunkProcessor::new();
+
+                    use futures::StreamExt;
+
+                    while let Some(result) = sse_stream.next().await {
+                        match result {
+                            Ok(sse_event) => {
+                                if sse_event.data.trim() == "[DONE]" {
+                                    break;
+                                }
+                                for stream_event in processor.process(&sse_event) {
+                                    yield stream_event;
+                                }
+                            }
+                            Err(e) => {
+                                yield StreamEvent::Error(format!("Stream error: {e}"));
+                                return;
+                            }
+                        }
+                    }
+
+                    // Servers that omit finish_reason still get their tool calls out.
+                    for stream_event in processor.flush_tool_calls() {
+                        yield stream_event;
+                    }
+                    yield StreamEvent::Done;
+                }
+                Err(e) => {
+                    yield StreamEvent::Error(e.to_string());
+                }
+            }
+        }
+    }
+}
+
+impl Backend for OpenAiClient {
+    fn send(&self, request: &AgentRequest) -> EventStream {
+        Box::pin(OpenAiClient::send(self, request))
+    }
+
+    fn send_non_streaming<'a>(
+        &'a self,
+        system_prompt: &'a str,
+        user_message: &'a str,
+    ) -> BackendFuture<'a, Result<String, BackendError>> {
+        Box::pin(OpenAiClient::send_non_streaming(
+            self,
+            system_prompt,
+            user_message,
+        ))
+    }
+}
+
+async fn post_with_retry(
+    http: &Client,
+    url: &str,
+    api_key: &str,
+    body: &impl Serialize,
+) -> Result<reqwest::Response, BackendError> {
+    send_with_retry(|| {
+        let builder = http
+            .post(url)
+            .header("content-type", "application/json")
+            .json(body);
+        if api_key.is_empty() {
+            builder
+        } else {
+            builder.bearer_auth(api_key)
+        }
+    })
+    .await
+}
+
+fn build_shell_tool() -> ChatTool {
+    ChatTool {
+        tool_type: "function",
+        function: ChatFunction {
+            name: "shell".to_string(),
+            description: SHELL_TOOL_DESCRIPTION.to_string(),
+            parameters: shell_tool_input_schema(),
+        },
+    }
+}
+
+/// Convert an agent request into chat-completions messages.
+///
+/// The system prompt becomes a leading `system` message, tool results become
+/// one `tool` message per call, and images (attachments or tool-result media)
+/// are sent as `image_url` data URLs in a user message.
+fn build_messages(request: &AgentRequest) -> Vec<ChatMessage> {
+    let mut messages = vec![ChatMessage::text("system", &build_system_prompt(request))];
+
+    for msg in &request.conversation {
+        let role = match msg.role {
+            ua_protocol::Role::User => "user",
+            ua_protocol::Role::Assistant => "assistant",
+        };
+
+        if !msg.tool_uses.is_empty() {
+            messages.push(ChatMessage {
+                role: role.to_string(),
+                content: if msg.content.is_empty() {
+                    None
+                } else {
+                    Some(ChatContent::Text(msg.content.clone()))
+                },
+                tool_calls: msg
+                    .tool_uses
+                    .iter()
+                    .map(|tu| ChatToolCall {
+                        id: tu.id.clone(),
+                        call_type: "function",
+                        function: ChatToolCallFunction {
+                            name: tu.name.clone(),
+                            arguments: tu.input_json.clone(),
+                        },
+                    })
+                    .collect(),
+                tool_call_id: None,
+            });
+        } else if !msg.tool_results.is_empty() {
+            let mut images = Vec::new();
+            for tr in &msg.tool_results {
+                messages.push(ChatMessage {
+                    role: "tool".to_string(),
+                    content: Some(ChatContent::Text(tr.content.clone())),
+                    tool_calls: Vec::new(),
+                    tool_call_id: Some(tr.tool_use_id.clone()),
+                });
+                images.extend(
+                    tr.resolved_media
+                        .iter()
+                        .map(|rm| ChatContentPart::image(&rm.media_type, &rm.data)),
+                );
+            }
+            // Tool messages are text-only; forward captured images separately.
+            if !images.is_empty() {
+                messages.push(ChatMessage::parts("user", images));
+            }
+        } else {
+            messages.push(ChatMessage::text(role, &msg.content));
+        }
+    }
+
+    // Add current instruction (skip if empty — agentic continuation).
+    if !request.attachments.is_empty() {
+        let mut parts: Vec<ChatContentPart> = request
+            .attachments
+            .iter()
+            .map(|att| ChatContentPart::image(&att.media_type, &att.data))
+            .collect();
+        if !request.instruction.is_empty() {
+            parts.push(ChatContentPart::Text {
+                text: request.instruction.clone(),
+            });
+        }
+        messages.push(ChatMessage::parts("user", parts));
+    } else if !request.instruction.is_empty() {
+        messages.push(ChatMessage::text("user", &request.instruction));
+    }
+
+    messages
+}
+
+/// Tracks state across streamed chunks for tool_call accumulation.
+///
+/// Tool calls arrive as `choices[0].delta.tool_calls[]` fragments keyed by
+/// `index`: the first fragment carries `id` and `function.name`, later ones
+/// append to `function.arguments`. They are emitted once the choice reports a
+/// `finish_reason` (or the stream ends).
+struct ChunkProcessor {
+    tool_calls: BTreeMap<u64, ToolCallAccumulator>,
+}
+
+#[derive(Default)]
+struct ToolCallAccumulator {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+impl ChunkProcessor {
+    fn new() -> Self {
+        Self {
+            tool_calls: BTreeMap::new(),
+        }
+    }
+
+    fn process(&mut self, event: &SseEvent) -> Vec<StreamEvent> {
+        let mut events = Vec::new();
+
+        let data: Value = match serde_json::from_str(&event.data) {
+            Ok(v) => v,
+            Err(_) => return events,
+        };
+
+        if let Some(error) = data.get("error") {
+            let error_msg = error
+                .get("message")
+                .and_then(|m| m.as_str())
+                .unwrap_or("Unknown error")
+                .to_string();
+            events.push(StreamEvent::Error(error_msg));
+            return events;
+        }
+
+        if let Some(choice) = data
+            .get("choices")
+            .and_then(|c| c.as_array())
+            .and_then(|c| c.first())
+        {
+            if let Some(delta) = choice.get("delta") {
+                // Reasoning models served by vLLM / llama.cpp expose their
+                // chain of thought as `reasoning_content`.
+                if let Some(text) = delta.get("reasoning_content").and_then(|t| t.as_str()) {
+                    if !text.is_empty() {
+                        events.push(StreamEvent::ThinkingDelta(text.to_string()));
+                    }
+                }
+                if let Some(text) = delta.get("content").and_then(|t| t.as_str()) {
+                    if !text.is_empty() {
+                        events.push(StreamEvent::TextDelta(text.to_string()));
+                    }
+                }
+                if let Some(calls) = delta.get("tool_calls").and_then(|t| t.as_array()) {
+                    for call in calls {
+                        let index = call.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+                        let acc = self.tool_calls.entry(index).or_default();
+                        if let Some(id) = call.get("id").and_then(|v| v.as_str()) {
+                            acc.id = id.to_string();
+                        }
+                        if let Some(function) = call.get("function") {
+                            if let Some(name) = function.get("name").and_then(|v| v.as_str()) {
+                                acc.name.push_str(name);
+                            }
+                            if let Some(args) = function.get("arguments").and_then(|v| v.as_str()) {
+                                acc.arguments.push_str(args);
+                            }
+                        }
+                    }
+                }
+            }
+
+            if choice
+                .get("finish_reason")
+                .is_some_and(|reason| !reason.is_null())
+            {
+                events.extend(self.flush_tool_calls());
+            }
+        }
+
+        if let Some(usage) = data.get("usage") {
+            if let (Some(input), Some(output)) = (
+                usage.get("prompt_tokens").and_then(|v| v.as_u64()),
+                usage.get("completion_tokens").and_then(|v| v.as_u64()),
+            ) {
+                events.push(StreamEvent::Usage {
+                    input_tokens: input as u32,
+                    output_tokens: output as u32,
+                });
+            }
+        }
+
+        events
+    }
+
+    /// Emit all accumulated tool calls in index order. A call without an
+    /// `id` gets one, since its result has to name the call it answers.
+    fn flush_tool_calls(&mut self) -> Vec<StreamEvent> {
+        std::mem::take(&mut self.tool_calls)
+            .into_values()
+            .map(|tc| StreamEvent::ToolUse {
+                id: if tc.id.is_empty() {
+                    format!("call_ua_{}", NEXT_CALL_ID.fetch_add(1, Ordering::Relaxed))
+                } else {
+                    tc.id
+                },
+                name: tc.name,
+                // Some servers send no arguments for parameterless calls.
+                input_json: if tc.arguments.is_empty() {
+                    "{}".to_string()
+                } else {
+                    tc.arguments
+                },
+            })
+            .collect()
+    }
+}
+
+// API request/response types
+
+#[derive(Debug, Serialize)]
+struct ChatRequest {
+    model: String,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+    messages: Vec<ChatMessage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<ChatTool>,
+}
+
+#[derive(Debug, Serialize)]
+struct StreamOptions {
+    include_usage: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ChatTool {
+    #[serde(rename = "type")]
+    tool_type: &'static str,
+    function: ChatFunction,
+}
+
+#[derive(Debug, Serialize)]
+struct ChatFunction {
+    name: String,
+    description: String,
+    parameters: Value,
+}
+
+#[derive(Debug, Serialize)]
+struct ChatMessage {
+    role: String,
+    /// `None` serializes as `null`, which the API expects for assistant
+    /// messages that only carry tool calls.
+    content: Option<ChatContent>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<ChatToolCall>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    fn text(role: &str, text: &str) -> Self {
+        Self {
+            role: role.to_string(),
+            content: Some(ChatContent::Text(text.to_string())),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+
+    fn parts(role: &str, parts: Vec<ChatContentPart>) -> Self {
+        Self {
+            role: role.to_string(),
+            content: Some(ChatContent::Parts(parts)),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum ChatContent {
+    Text(String),
+    Parts(Vec<ChatContentPart>),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type")]
+enum ChatContentPart {
+    #[serde(rename = "text")]
+    Text { text: String },
+    #[serde(rename = "image_url")]
+    ImageUrl { image_url: ImageUrl },
+}
+
+impl ChatContentPart {
+    /// Build an inline image part from base64 data.
+    fn image(media_type: &str, data: &str) -> Self {
+        ChatContentPart::ImageUrl {
+            image_url: ImageUrl {
+                url: format!("data:{media_type};base64,{data}"),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ImageUrl {
+    url: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ChatToolCall {
+    id: String,
+    #[serde(rename = "type")]
+    call_type: &'static str,
+    function: ChatToolCallFunction,
+}
+
+#[derive(Debug, Serialize)]
+struct ChatToolCallFunction {
+    name: String,
+    arguments: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatResponse {
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatChoice {
+    message: ChatResponseMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatResponseMessage {
+    content: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use ua_protocol::{
+        Attachment, ConversationMessage, ShellContext, ToolResultRecord, ToolUseRecord,
+    };
+
+    fn chunk(data: &str) -> SseEvent {
+        SseEvent {
+            event_type: None,
+            data: data.to_string(),
+        }
+    }
+
+    fn test_request(instruction: &str) -> AgentRequest {
+        AgentRequest::new(instruction, ShellContext::default())
+    }
+
+    #[test]
+    fn process_text_delta() {
+        let mut processor = ChunkProcessor::new();
+        let events = processor.process(&chunk(
+            r#"{"choices":[{"index":0,"delta":{"content":"Hello"}}]}"#,
+        ));
+        assert_eq!(events, vec![StreamEvent::TextDelta("Hello".to_string())]);
+    }
+
+    #[test]
+    fn process_empty_content_ignored() {
+        let mut processor = ChunkProcessor::new();
+        let events = processor.process(&chunk(
+            r#"{"choices":[{"index":0,"delta":{"role":"assistant","content":""}}]}"#,
+        ));
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn process_reasoning_content_is_thinking() {
+        let mut processor = ChunkProcessor::new();
+        let events = processor.process(&chunk(
+            r#"{"choices":[{"index":0,"delta":{"reasoning_content":"hmm"}}]}"#,
+        ));
+        assert_eq!(events, vec![StreamEvent::ThinkingDelta("hmm".to_string())]);
+    }
+
+    #[test]
+    fn process_tool_call_deltas_accumulate() {
+        let mut processor = ChunkProcessor::new();
+
+        let first = chunk(
+            r#"{"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"id":"call_1","type":"function","function":{"name":"shell","arguments":""}}]}}]}"#,
+        );
+        assert!(processor.process(&first).is_empty());
+
+        let second = chunk(
+            r#"{"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"command\":"}}]}}]}"#,
+        );
+        assert!(processor.process(&second).is_empty());
+
+        let third = chunk(
+            r#"{"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"ls /tmp\"}"}}]}}]}"#,
+        );
+        assert!(processor.process(&third).is_empty());
+
+        let finish = chunk(r#"{"choices":[{"index":0,"delta":{},"finish_reason":"tool_calls"}]}"#);
+        let events = processor.process(&finish);
+        assert_eq!(
+            events,
+            vec![StreamEvent::ToolUse {
+                id: "call_1".to_string(),
+                name: "shell".to_string(),
+                input_json: r#"{"command":"ls /tmp"}"#.to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn process_tool_calls_without_id_get_distinct_ids() {
+        let mut processor = ChunkProcessor::new();
+        let events = processor.process(&chunk(
+            r#"{"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"name":"shell","arguments":"{\"command\":\"ls\"}"}},{"index":1,"function":{"name":"shell","arguments":"{\"command\":\"pwd\"}"}}]},"finish_reason":"tool_calls"}]}"#,
+        ));
+        let ids: Vec<&str> = events
+            .iter()
+            .filter_map(|e| match e {
+                StreamEvent::ToolUse { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.iter().all(|id| id.starts_with("call_ua_")), "{ids:?}");
+        assert_ne!(ids[0], ids[1]);
+    }
+
+    #[test]
+    fn process_parallel_tool_calls_in_index_order() {
+        let mut processor = ChunkProcessor::new();
+        processor.process(&chunk(
+            r#"{"choices":[{"index":0,"delta":{"tool_calls":[{"index":1,"id":"call_b","function":{"name":"shell","arguments":"{\"command\":\"pwd\"}"}},{"index":0,"id":"call_a","function":{"name":"shell","arguments":"{\"command\":\"ls\"}"}}]}}]}"#,
+        ));
+
+        let events = processor.flush_tool_calls();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(&events[0], StreamEvent::ToolUse { id, .. } if id == "call_a"));
+        assert!(matches!(&events[1], StreamEvent::ToolUse { id, .. } if id == "call_b"));
+        assert!(processor.flush_tool_calls().is_empty());
+    }
+
+    #[test]
+    fn process_tool_call_without_arguments_gets_empty_object() {
+        let mut processor = ChunkProcessor::new();
+        processor.process(&chunk(
+            r#"{"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"id":"call_1","function":{"name":"shell"}}]}}]}"#,
+        ));
+        let events = processor.flush_tool_calls();
+        assert!(
+            matches!(&events[0], StreamEvent::ToolUse { input_json, .. } if input_json == "{}")
+        );
+    }
+
+    #[test]
+    fn process_usage_chunk() {
+        let mut processor = ChunkProcessor::new();
+        let events = processor.process(&chunk(
+            r#"{"choices":[],"usage":{"prompt_tokens":120,"completion_tokens":30,"total_tokens":150}}"#,
+        ));
+        assert_eq!(
+            events,
+            vec![StreamEvent::Usage {
+                input_tokens: 120,
+                output_tokens: 30,
+            }]
+        );
+    }
+
+    #[test]
+    fn process_error_chunk() {
+        let mut processor = ChunkProcessor::new();
+        let events = processor.process(&chunk(r#"{"error":{"message":"model not found"}}"#));
+        assert_eq!(
+            events,
+            vec![StreamEvent::Error("model not found".to_string())]
+        );
+    }
+
+    #[test]
+    fn process_invalid_json_ignored() {
+        let mut processor = ChunkProcessor::new();
+        assert!(processor.process(&chunk("not json")).is_empty());
+    }
+
+    #[test]
+    fn build_messages_starts_with_system_prompt() {
+        let messages = build_messages(&test_request("list files"));
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].role, "system");
+        assert_eq!(messages[1].role, "user");
+        let json = serde_json::to_value(&messages[1]).unwrap();
+        assert_eq!(json["content"], "list files");
+    }
+
+    #[test]
+    fn build_messages_skips_empty_instruction() {
+        let messages = build_messages(&test_request(""));
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].role, "system");
+    }
+
+    #[test]
+    fn build_messages_tool_use_then_result_roundtrip() {
+        let request = test_request("").with_conversation(vec![
+            ConversationMessage::user("list files"),
+            ConversationMessage::assistant_with_tool_use(
+                "",
+                vec![ToolUseRecord {
+                    id: "call_1".to_string(),
+                    name: "shell".to_string(),
+                    input_json: r#"{"command":"ls"}"#.to_string(),
+                }],
+            ),
+            ConversationMessage::tool_result(vec![ToolResultRecord::text(
+                "call_1".to_string(),
+                "a.txt".to_string(),
+            )]),
+        ]);
+
+        let messages = build_messages(&request);
+        let json = serde_json::to_value(&messages).unwrap();
+
+        assert_eq!(json[2]["role"], "assistant");
+        assert!(json[2]["content"].is_null());
+        assert_eq!(json[2]["tool_calls"][0]["id"], "call_1");
+        assert_eq!(json[2]["tool_calls"][0]["type"], "function");
+        assert_eq!(json[2]["tool_calls"][0]["function"]["name"], "shell");
+        assert_eq!(
+            json[2]["tool_calls"][0]["function"]["arguments"],
+            r#"{"command":"ls"}"#
+        );
+
+        assert_eq!(json[3]["role"], "tool");
+        assert_eq!(json[3]["tool_call_id"], "call_1");
+        assert_eq!(json[3]["content"], "a.txt");
+    }
+
+    #[test]
+    fn build_messages_with_attachments_uses_data_urls() {
+        let request = test_request("describe").with_attachments(vec![Attachment {
+            filename: "shot.png".to_string(),
+            media_type: "image/png".to_string(),
+            data: "AAAA".to_string(),
+        }]);
+
+        let messages = build_messages(&request);
+        let json = serde_json::to_value(&messages[1]).unwrap();
+        assert_eq!(json["content"][0]["type"], "image_url");
+        assert_eq!(
+            json["content"][0]["image_url"]["url"],
+            "data:image/png;base64,AAAA"
+        );
+        assert_eq!(json["content"][1]["type"], "text");
+        assert_eq!(json["content"][1]["text"], "describe");
+    }
+
+    #[test]
+    fn shell_tool_is_function() {
+        let json = serde_json::to_value(build_shell_tool()).unwrap();
+        assert_eq!(json["type"], "function");
+        assert_eq!(json["function"]["name"], "shell");
+        assert_eq!(json["function"]["parameters"]["required"][0], "command");
+    }
+
+    #[test]
+    fn non_streaming_request_has_no_tools() {
+        let body = ChatRequest {
+            model: "gpt-4o".to_string(),
+            stream: false,
+            stream_options: None,
+            messages: vec![ChatMessage::text("user", "hi")],
+            tools: Vec::new(),
+        };
+        let json = serde_json::to_value(&body).unwrap();
+        assert!(json.get("tools").is_none());
+        assert!(json.get("stream_options").is_none());
+    }
+
+    #[test]
+    fn base_url_trailing_slash_trimmed() {
+        let client = OpenAiClient::new("").with_base_url("http://localhost:8080/v1/");
+        assert_eq!(
+            client.endpoint(),
+            "http://localhost:8080/v1/chat/completions"
+        );
+    }
+
+    /// Serve a single canned HTTP response on a local port and return the base URL.
+    async fn serve_once(content_type: &'static str, body: String) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 64 * 1024];
+            let _ = socket.read(&mut buf).await;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: {content_type}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            let _ = socket.shutdown().await;
+        });
+        format!("http://{addr}/v1")
+    }
+
+    #[tokio::test]
+    async fn send_streams_from_local_server() {
+        let sse = [
+            r#"{"choices":[{"index":0,"delta":{"role":"assistant","content":"Listing"}}]}"#,
+            r#"{"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"id":"call_1","type":"function","function":{"name":"shell","arguments":"{\"command\":\"ls\"}"}}]}}]}"#,
+            r#"{"choices":[{"index":0,"delta":{},"finish_reason":"tool_calls"}]}"#,
+            r#"{"choices":[],"usage":{"prompt_tokens":10,"completion_tokens":5}}"#,
+            "[DONE]",
+        ]
+        .iter()
+        .map(|d| format!("data: {d}\n\n"))
+        .collect::<String>();
+        let base_url = serve_once("text/event-stream", sse).await;
+
+        let client = OpenAiClient::with_model("", "local").with_base_url(base_url);
+        let events: Vec<_> = client.send(&test_request("list")).collect().await;
+
+        assert_eq!(
+            events,
+            vec![
+                StreamEvent::TextDelta("Listing".to_string()),
+                StreamEvent::ToolUse {
+                    id: "call_1".to_string(),
+                    name: "shell".to_string(),
+                    input_json: r#"{"command":"ls"}"#.to_string(),
+                },
+                StreamEvent::Usage {
+                    input_tokens: 10,
+                    output_tokens: 5,
+                },
+                StreamEvent::Done,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn send_non_streaming_from_local_server() {
+        let body = r#"{"choices":[{"index":0,"message":{"role":"assistant","content":"ok"}}]}"#
+            .to_string();
+        let base_url = serve_once("application/json", body).await;
+
+        let client = OpenAiClient::with_model("", "local").with_base_url(base_url);
+        let text = client.send_non_streaming("sys", "hi").await.unwrap();
+        assert_eq!(text, "ok");
+    }
+}
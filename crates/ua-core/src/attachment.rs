@@ -6,7 +6,7 @@ use base64::Engine;
 use ua_protocol::Attachment;
 
 /// Maximum file size in bytes (20 MB — Anthropic's limit).
-const MAX_FILE_SIZE: u64 = 20 * 1024 * 1024;
+pub const MAX_FILE_SIZE: u64 = 20 * 1024 * 1024;
 
 /// Detect media type from magic bytes at the start of raw output.
 /// Returns the MIME type if recognized, `None` for text/unknown.
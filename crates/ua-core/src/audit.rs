@@ -1,7 +1,7 @@
 //! Append-only JSONL audit logger for command execution events.
 //!
 //! Writes one JSON object per line to a log file, recording proposed commands,
-//! approvals, denials, blocks, and executions.
+//! approvals, denials, blocks, hook decisions, and executions.
 
 use std::fs::{self, File, OpenOptions};
 use std::io::{self, BufWriter, Write};
@@ -106,15 +106,47 @@ impl AuditLogger {
         }));
     }
 
-    /// Log a command execution result.
-    pub fn log_executed(&mut self, command: &str, exit_code: Option<i32>, duration_ms: u64) {
-        self.write_event(serde_json::json!({
+    /// Log a command execution result. `killed` is set when the sandbox
+    /// killed the command (e.g. "timeout after 300s").
+    pub fn log_executed(
+        &mut self,
+        command: &str,
+        exit_code: Option<i32>,
+        duration_ms: u64,
+        killed: Option<&str>,
+    ) {
+        let mut event = serde_json::json!({
             "ts": epoch_secs(),
             "session": self.session_id,
             "type": "executed",
             "command": command,
             "exit_code": exit_code,
             "duration_ms": duration_ms,
+        });
+        if let Some(reason) = killed {
+            event["killed"] = reason.into();
+        }
+        self.write_event(event);
+    }
+
+    /// Log a pre-exec hook decision (`allow`, `deny`, or `modify`).
+    pub fn log_hook(
+        &mut self,
+        command: &str,
+        hook: &str,
+        decision: &str,
+        reason: &str,
+        new_command: Option<&str>,
+    ) {
+        self.write_event(serde_json::json!({
+            "ts": epoch_secs(),
+            "session": self.session_id,
+            "type": "hook",
+            "command": command,
+            "hook": hook,
+            "decision": decision,
+            "reason": reason,
+            "new_command": new_command,
         }));
     }
 
@@ -190,6 +222,23 @@ mod tests {
         assert_eq!(lines[0]["method"], "keystroke");
     }
 
+    #[test]
+    fn log_hook_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let mut logger = AuditLogger::new(&path).unwrap();
+
+        logger.log_hook("ls", "pre-exec.sh", "allow", "", None);
+        logger.log_hook("rm x", "pre-exec.sh", "modify", "safer", Some("rm -i x"));
+
+        let lines = read_log_lines(&path);
+        assert_eq!(lines[0]["type"], "hook");
+        assert_eq!(lines[0]["decision"], "allow");
+        assert!(lines[0]["new_command"].is_null());
+        assert_eq!(lines[1]["hook"], "pre-exec.sh");
+        assert_eq!(lines[1]["new_command"], "rm -i x");
+    }
+
     #[test]
     fn log_denied_fields() {
         let dir = tempfile::tempdir().unwrap();
@@ -223,13 +272,27 @@ mod tests {
         let path = dir.path().join("audit.jsonl");
         let mut logger = AuditLogger::new(&path).unwrap();
 
-        logger.log_executed("ls /tmp", Some(0), 42);
+        logger.log_executed("ls /tmp", Some(0), 42, None);
 
         let lines = read_log_lines(&path);
         assert_eq!(lines[0]["type"], "executed");
         assert_eq!(lines[0]["command"], "ls /tmp");
         assert_eq!(lines[0]["exit_code"], 0);
         assert_eq!(lines[0]["duration_ms"], 42);
+        assert!(lines[0].get("killed").is_none());
+    }
+
+    #[test]
+    fn log_executed_killed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let mut logger = AuditLogger::new(&path).unwrap();
+
+        logger.log_executed("yes", Some(124), 300_000, Some("timeout after 300s"));
+
+        let lines = read_log_lines(&path);
+        assert_eq!(lines[0]["exit_code"], 124);
+        assert_eq!(lines[0]["killed"], "timeout after 300s");
     }
 
     #[test]
@@ -240,7 +303,7 @@ mod tests {
 
         logger.log_proposed(0, &["ls".to_string()], &["read_only"], "llm");
         logger.log_approved(0, "keystroke", "y");
-        logger.log_executed("ls", Some(0), 10);
+        logger.log_executed("ls", Some(0), 10, None);
 
         let lines = read_log_lines(&path);
         assert_eq!(lines.len(), 3);
@@ -313,7 +376,7 @@ mod tests {
         let path = dir.path().join("audit.jsonl");
         let mut logger = AuditLogger::new(&path).unwrap();
 
-        logger.log_executed("ls", None, 10);
+        logger.log_executed("ls", None, 10, None);
 
         let lines = read_log_lines(&path);
         assert!(lines[0]["exit_code"].is_null());
@@ -5,32 +5,38 @@
 //! No PTY, no OSC parsing, no approval UI, no raw mode.
 
 use std::fmt::Write as FmtWrite;
-use std::io::Write;
-use std::process::Command;
-use std::time::Instant;
+use std::io::{self, Read, Write};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
 
 use base64::Engine;
 use futures::StreamExt;
 use ua_backend::anthropic::build_system_prompt;
-use ua_backend::AnthropicClient;
+use ua_backend::Backend;
 use ua_protocol::{MediaRef, ResolvedMedia, StreamEvent, ToolResultRecord, ToolUseRecord};
 
 use crate::attachment::detect_media_type;
 use crate::audit::AuditLogger;
+use crate::capture::OutputCapture;
 use crate::config::{Config, JudgeMode};
 use crate::context::{
     build_agent_capabilities_prompt, build_agent_request, scrub_injection_markers, OutputHistory,
     TOOL_RESULT_PREFIX,
 };
+use crate::events::{BatchEvent, EventWriter, SCHEMA_VERSION};
+use crate::hooks::{self, HookContext, HookRunner};
 use crate::journal::{
     build_conversation_from_journal, epoch_secs, message_tokens, resolve_media_refs,
     AttachmentMeta, JournalEntry, SessionJournal,
 };
 use crate::judge;
 use crate::policy::{analyze_pipe_chain, RiskLevel};
-use crate::style::{format_tokens, Style};
+use crate::style::{format_bytes, format_tokens, Style};
 
 const MAX_OUTPUT_BYTES: usize = 100_000;
+/// Minimum time between live output updates on a TTY.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
 const MAX_CONSECUTIVE_DENIALS: usize = 3;
 
 /// Encapsulates all stderr formatting for batch mode output.
@@ -173,6 +179,30 @@ impl<W: Write> BatchOutput<W> {
         }
     }
 
+    /// Emit live progress for the running command: bytes of output so far
+    /// and its latest line (overwritten; TTY only).
+    pub fn emit_output_progress(&mut self, bytes: usize, last_line: &str) {
+        if !self.is_tty {
+            return;
+        }
+        let status = format!(
+            "({}) {}  {}",
+            self.step_count,
+            format_bytes(bytes),
+            last_line
+        );
+        let display = self.truncate_to_width(&status);
+        let _ = write!(
+            self.writer,
+            "\r\x1b[K{} {}{}{}",
+            self.colored_prefix(),
+            self.style.dim_start(),
+            display,
+            self.style.reset(),
+        );
+        let _ = self.writer.flush();
+    }
+
     /// Emit a denied command (persists — red).
     pub fn emit_denied(&mut self, cmd: &str) {
         let display_cmd = self.truncate_to_width(cmd);
@@ -190,6 +220,45 @@ impl<W: Write> BatchOutput<W> {
         }
     }
 
+    /// Emit a command denied by a pre-exec hook (persists — red).
+    pub fn emit_hook_denied(&mut self, cmd: &str, hook: &str, reason: &str) {
+        let display_cmd = self.truncate_to_width(cmd);
+        if self.is_tty {
+            let _ = writeln!(
+                self.writer,
+                "\r\x1b[K{} {}DENIED ({hook}: {reason}): {}{}",
+                self.colored_prefix(),
+                self.style.red_start(),
+                display_cmd,
+                self.style.reset(),
+            );
+        } else {
+            let _ = writeln!(
+                self.writer,
+                "{} DENIED ({hook}: {reason}): {}",
+                self.prefix(),
+                display_cmd,
+            );
+        }
+    }
+
+    /// Emit a command rewritten by a pre-exec hook (persists — yellow).
+    pub fn emit_hook_modified(&mut self, hook: &str, new_cmd: &str) {
+        let display_cmd = self.truncate_to_width(new_cmd);
+        if self.is_tty {
+            let _ = writeln!(
+                self.writer,
+                "\r\x1b[K{} {}↻ {hook}: {}{}",
+                self.colored_prefix(),
+                self.style.yellow_start(),
+                display_cmd,
+                self.style.reset(),
+            );
+        } else {
+            let _ = writeln!(self.writer, "{} ↻ {hook}: {}", self.prefix(), display_cmd,);
+        }
+    }
+
     /// Emit an error (persists — red).
     pub fn emit_error(&mut self, msg: &str) {
         if self.is_tty {
@@ -279,6 +348,100 @@ impl<W: Write> BatchOutput<W> {
     }
 }
 
+/// `sh -c <cmd>`. With a policy, runs through `unixagent --sandbox-exec` so
+/// the network rules and seccomp filter apply on top of the filesystem
+/// sandbox the agent process already carries. `--sandbox-exec` reports
+/// blocked syscalls and kills to `reports`.
+fn shell_command(
+    cmd: &str,
+    sandbox: Option<&ua_sandbox::SandboxPolicy>,
+    reports: Option<&ua_sandbox::ReportPipe>,
+) -> Command {
+    let exe = sandbox.and_then(|p| std::env::current_exe().ok().map(|exe| (p, exe)));
+    let mut c = match exe {
+        Some((policy, exe)) => {
+            let mut c = Command::new(exe);
+            c.arg("--sandbox-exec").arg("sh");
+            let policy = policy
+                .clone()
+                .with_report_fd(reports.and_then(|r| r.write_fd()));
+            c.env(ua_sandbox::policy::SANDBOX_ENV_VAR, policy.to_json());
+            if let Some(reports) = reports {
+                reports.share_with(&mut c);
+            }
+            c
+        }
+        None => Command::new("sh"),
+    };
+    c.arg("-c").arg(cmd);
+    c
+}
+
+/// A finished command's exit status and bounded output.
+struct StreamedOutput {
+    status: ExitStatus,
+    stdout: OutputCapture,
+    stderr: OutputCapture,
+}
+
+/// Run `command`, reading stdout and stderr as they are produced instead of
+/// buffering until exit. `on_progress(total_bytes, last_line)` is called at
+/// most every [`PROGRESS_INTERVAL`].
+fn run_streaming(
+    mut command: Command,
+    mut on_progress: impl FnMut(usize, &str),
+) -> io::Result<StreamedOutput> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = command.spawn()?;
+
+    let (tx, rx) = mpsc::channel::<(bool, Vec<u8>)>();
+    let pipes: [(bool, Option<Box<dyn Read + Send>>); 2] = [
+        (false, child.stdout.take().map(|p| Box::new(p) as _)),
+        (true, child.stderr.take().map(|p| Box::new(p) as _)),
+    ];
+    let readers: Vec<_> = pipes
+        .into_iter()
+        .filter_map(|(is_stderr, pipe)| pipe.map(|p| (is_stderr, p)))
+        .map(|(is_stderr, mut pipe)| {
+            let tx = tx.clone();
+            std::thread::spawn(move || {
+                let mut buf = [0u8; 8192];
+                while let Ok(n @ 1..) = pipe.read(&mut buf) {
+                    if tx.send((is_stderr, buf[..n].to_vec())).is_err() {
+                        break;
+                    }
+                }
+            })
+        })
+        .collect();
+    drop(tx);
+
+    let mut stdout = OutputCapture::new(MAX_OUTPUT_BYTES);
+    let mut stderr = OutputCapture::new(MAX_OUTPUT_BYTES);
+    let mut last_progress = Instant::now();
+    for (is_stderr, chunk) in rx {
+        let capture = if is_stderr { &mut stderr } else { &mut stdout };
+        capture.push(&chunk);
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            let line = capture.last_line();
+            on_progress(stdout.total_bytes() + stderr.total_bytes(), &line);
+            last_progress = Instant::now();
+        }
+    }
+    for reader in readers {
+        let _ = reader.join();
+    }
+
+    Ok(StreamedOutput {
+        status: child.wait()?,
+        stdout,
+        stderr,
+    })
+}
+
 /// Build the batch-mode system prompt.
 fn build_batch_system_prompt(depth: u32, max_depth: u32) -> String {
     let mut prompt = String::from(
@@ -304,36 +467,40 @@ fn build_batch_system_prompt(depth: u32, max_depth: u32) -> String {
 ///
 /// Streams LLM responses, executes tool calls via `sh -c`, feeds results back,
 /// and prints the final text answer to stdout. Returns the exit code.
+///
+/// With `json`, stdout carries the JSONL event stream described in
+/// [`crate::events`] instead of the bare answer.
+#[allow(clippy::too_many_arguments)]
 pub async fn run_batch(
     config: &Config,
+    backend: &dyn Backend,
     instruction: &str,
     depth: u32,
     sandbox_active: bool,
     attachments: Vec<ua_protocol::Attachment>,
     system_prompt_file: Option<&str>,
     computer_use: bool,
+    json: bool,
 ) -> i32 {
     let is_tty = std::io::stderr().is_terminal();
     let style = Style::new();
     let mut output = BatchOutput::new(std::io::stderr(), is_tty, depth, instruction, style);
+    let mut events = if json {
+        EventWriter::new(std::io::stdout())
+    } else {
+        EventWriter::noop()
+    };
 
     let mut system_extra = build_batch_system_prompt(depth, config.security.max_agent_depth);
+    if let Some(extra) = &config.context.system_prompt {
+        system_extra.push_str("\n\n");
+        system_extra.push_str(extra);
+    }
     if let Some(extra) = system_prompt_file {
         system_extra.push_str("\n\n");
         system_extra.push_str(extra);
     }
 
-    // Resolve API key
-    let api_key = match config.backend.anthropic.resolve_api_key() {
-        Ok(key) => key,
-        Err(e) => {
-            output.emit_error(&e.to_string());
-            return 1;
-        }
-    };
-
-    let client = AnthropicClient::with_model(&api_key, &config.backend.anthropic.model);
-
     // Initialize audit logger
     let mut audit = if config.security.audit_enabled {
         let path = config.security.resolve_audit_path();
@@ -350,6 +517,17 @@ pub async fn run_batch(
         AuditLogger::noop()
     };
 
+    let sandbox_policy = sandbox_active.then(|| config.sandbox.to_policy(&config.hooks));
+    let hooks = match &sandbox_policy {
+        Some(policy) if hooks::sandbox_can_plant(policy, &config.hooks) => {
+            output.emit_error(&format!(
+                "warning: not running hooks from {}: sandboxed commands can write there",
+                config.hooks.resolve_dir().display()
+            ));
+            HookRunner::none()
+        }
+        _ => HookRunner::from_config(&config.hooks),
+    };
     let empty_history = OutputHistory::new(0);
     let mut consecutive_denials: usize = 0;
 
@@ -388,6 +566,11 @@ pub async fn run_batch(
     }
 
     output.emit_start();
+    events.emit(&BatchEvent::Start {
+        version: SCHEMA_VERSION,
+        instruction,
+        depth,
+    });
 
     let mut total_input_tokens: u32 = 0;
     let mut total_output_tokens: u32 = 0;
@@ -396,8 +579,16 @@ pub async fn run_batch(
 
     /// Helper macro to write Summary to journal before returning.
     macro_rules! write_summary {
-        ($journal:expr, $output:expr, $exit_code:expr, $instruction:expr,
+        ($journal:expr, $output:expr, $events:expr, $exit_code:expr, $instruction:expr,
          $in_tok:expr, $out_tok:expr, $cmds:expr, $denied:expr) => {
+            $events.emit(&BatchEvent::Summary {
+                exit_code: $exit_code,
+                input_tokens: $in_tok,
+                output_tokens: $out_tok,
+                commands_run: $cmds,
+                commands_denied: $denied,
+                elapsed_secs: $output.elapsed_secs(),
+            });
             if let Some(ref mut j) = $journal {
                 j.append(&JournalEntry::Summary {
                     ts: epoch_secs(),
@@ -438,6 +629,7 @@ pub async fn run_batch(
             conversation.clone(),
             (80, 24),
             None,
+            None,
         );
         request.system_prompt_extra = Some(system_extra.clone());
         request.attachments = attachments.clone();
@@ -475,6 +667,7 @@ pub async fn run_batch(
                 conversation.clone(),
                 (80, 24),
                 None,
+                None,
             );
             request.system_prompt_extra = Some(system_extra.clone());
             request.attachments = attachments.clone();
@@ -497,13 +690,13 @@ pub async fn run_batch(
             conversation.clone(),
             (80, 24),
             None, // No PTY child in batch mode
+            None,
         );
         request.system_prompt_extra = Some(system_extra.clone());
         request.attachments = attachments.clone();
 
         // Stream response
-        let stream = client.send(&request);
-        let mut stream = std::pin::pin!(stream);
+        let mut stream = backend.send(&request);
 
         let mut text = String::new();
         let mut thinking_text = String::new();
@@ -511,6 +704,7 @@ pub async fn run_batch(
         let mut tool_commands: Vec<String> = Vec::new();
 
         output.emit_thinking(iteration);
+        events.emit(&BatchEvent::Thinking { iteration });
 
         while let Some(event) = stream.next().await {
             match event {
@@ -534,9 +728,11 @@ pub async fn run_batch(
                 }
                 StreamEvent::Error(e) => {
                     output.emit_error(&e);
+                    events.emit(&BatchEvent::Error { message: &e });
                     write_summary!(
                         journal,
                         output,
+                        events,
                         1,
                         instruction,
                         total_input_tokens,
@@ -583,9 +779,11 @@ pub async fn run_batch(
         // No tool calls = final answer
         if tool_commands.is_empty() {
             output.emit_done(total_input_tokens, total_output_tokens);
+            events.emit(&BatchEvent::Answer { text: &text });
             write_summary!(
                 journal,
                 output,
+                events,
                 0,
                 instruction,
                 total_input_tokens,
@@ -593,32 +791,87 @@ pub async fn run_batch(
                 total_commands,
                 total_denied
             );
-            print!("{text}");
+            if !events.is_enabled() {
+                print!("{text}");
+            }
             return 0;
         }
 
         // Classify and check deny list
         let tool_use_ids: Vec<String> = tool_uses.iter().map(|t| t.id.clone()).collect();
-        let risk_levels: Vec<RiskLevel> = tool_commands
+        let mut risk_levels: Vec<RiskLevel> = tool_commands
             .iter()
             .map(|cmd| analyze_pipe_chain(cmd))
             .collect();
         let risk_labels: Vec<&str> = risk_levels.iter().map(|r| r.as_str()).collect();
 
         audit.log_proposed(iteration, &tool_commands, &risk_labels, "llm");
+        for (i, cmd) in tool_commands.iter().enumerate() {
+            events.emit(&BatchEvent::Proposed {
+                iteration,
+                tool_use_id: &tool_use_ids[i],
+                command: cmd,
+                risk: risk_labels[i],
+            });
+        }
 
         // Check for denied commands
-        let mut any_denied = false;
+        let mut blocked_msg: Option<String> = None;
         for (i, risk) in risk_levels.iter().enumerate() {
             if *risk == RiskLevel::Denied {
                 output.emit_denied(&tool_commands[i]);
                 audit.log_blocked(&tool_commands[i], risk.as_str(), "denied by policy");
-                any_denied = true;
+                events.emit(&BatchEvent::Denied {
+                    iteration,
+                    tool_use_id: &tool_use_ids[i],
+                    command: &tool_commands[i],
+                    risk: risk.as_str(),
+                    reason: "denied by policy",
+                });
+                blocked_msg = Some(
+                    "Command blocked by security policy. Suggest a safer alternative.".to_string(),
+                );
                 total_denied += 1;
             }
         }
 
-        if any_denied {
+        // Run pre-exec hooks — they may rewrite or deny commands
+        if blocked_msg.is_none() {
+            let hook_ctx = HookContext {
+                host: String::new(),
+                cwd: std::env::current_dir()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                depth,
+                instruction: instruction.to_string(),
+            };
+            match hooks.check_commands(&mut tool_commands, &mut risk_levels, &hook_ctx, &mut audit)
+            {
+                Ok(rewrites) => {
+                    for rewrite in &rewrites {
+                        output.emit_hook_modified(&rewrite.hook, &rewrite.command);
+                    }
+                }
+                Err(denial) => {
+                    output.emit_hook_denied(&denial.command, &denial.hook, &denial.reason);
+                    events.emit(&BatchEvent::Denied {
+                        iteration,
+                        tool_use_id: &tool_use_ids[denial.index],
+                        command: &denial.command,
+                        risk: analyze_pipe_chain(&denial.command).as_str(),
+                        reason: &denial.reason,
+                    });
+                    total_denied += 1;
+                    blocked_msg = Some(format!(
+                        "Command blocked by pre-exec hook {}: {}. Suggest a safer alternative.",
+                        denial.hook, denial.reason
+                    ));
+                }
+            }
+        }
+
+        if let Some(denial_msg) = blocked_msg {
             consecutive_denials += 1;
             if consecutive_denials >= MAX_CONSECUTIVE_DENIALS {
                 let mut msg = String::new();
@@ -627,9 +880,11 @@ pub async fn run_batch(
                     "{MAX_CONSECUTIVE_DENIALS} consecutive denials, aborting"
                 );
                 output.emit_error(&msg);
+                events.emit(&BatchEvent::Error { message: &msg });
                 write_summary!(
                     journal,
                     output,
+                    events,
                     1,
                     instruction,
                     total_input_tokens,
@@ -639,10 +894,9 @@ pub async fn run_batch(
                 );
                 return 1;
             }
-            let denial_msg = "Command blocked by security policy. Suggest a safer alternative.";
             let tool_results: Vec<ToolResultRecord> = tool_use_ids
                 .iter()
-                .map(|id| ToolResultRecord::text(id.clone(), denial_msg.to_string()))
+                .map(|id| ToolResultRecord::text(id.clone(), denial_msg.clone()))
                 .collect();
             if let Some(ref mut j) = journal {
                 j.append(&JournalEntry::Blocked {
@@ -670,7 +924,7 @@ pub async fn run_batch(
         if sandbox_active && config.security.judge_enabled && has_dangerous {
             let judge_mode = config.security.resolve_judge_mode(depth);
             let verdict = judge::evaluate_commands(
-                &client,
+                backend,
                 &tool_commands,
                 instruction,
                 &std::env::current_dir()
@@ -687,6 +941,12 @@ pub async fn run_batch(
                             output.emit_judge_blocked(cmd, &reasoning);
                         }
                         audit.log_judge_result(iteration, false, &reasoning);
+                        events.emit(&BatchEvent::Judge {
+                            iteration,
+                            verdict: "unsafe",
+                            reasoning: &reasoning,
+                            blocked: true,
+                        });
                         let block_msg =
                             format!("Exception judge: {reasoning}. Please find another way.");
                         let tool_results: Vec<ToolResultRecord> = tool_use_ids
@@ -709,14 +969,32 @@ pub async fn run_batch(
                     JudgeMode::Warn => {
                         output.emit_judge_warning(&reasoning);
                         audit.log_judge_result(iteration, false, &reasoning);
+                        events.emit(&BatchEvent::Judge {
+                            iteration,
+                            verdict: "unsafe",
+                            reasoning: &reasoning,
+                            blocked: false,
+                        });
                         // Proceed with execution
                     }
                 },
                 judge::JudgeVerdict::Safe => {
                     audit.log_judge_result(iteration, true, "safe");
+                    events.emit(&BatchEvent::Judge {
+                        iteration,
+                        verdict: "safe",
+                        reasoning: "",
+                        blocked: false,
+                    });
                 }
                 judge::JudgeVerdict::Error(e) => {
                     output.emit_error(&format!("judge: {e}"));
+                    events.emit(&BatchEvent::Judge {
+                        iteration,
+                        verdict: "error",
+                        reasoning: &e,
+                        blocked: false,
+                    });
                     // Non-blocking: proceed with execution
                 }
             }
@@ -731,28 +1009,45 @@ pub async fn run_batch(
             output.emit_command(cmd, iteration);
             let start = Instant::now();
 
-            // Children inherit OS sandbox from parent process.
-            let cmd_output = Command::new("sh").arg("-c").arg(cmd).output();
+            // Children inherit the filesystem sandbox from this process.
+            let mut reports = sandbox_policy
+                .as_ref()
+                .and_then(|_| ua_sandbox::ReportPipe::new().ok());
+            let cmd_output = run_streaming(
+                shell_command(cmd, sandbox_policy.as_ref(), reports.as_ref()),
+                |bytes, line| output.emit_output_progress(bytes, line),
+            );
 
             let duration_ms = start.elapsed().as_millis() as u64;
 
             match cmd_output {
                 Ok(out) => {
                     let exit_code = out.status.code();
-                    audit.log_executed(cmd, exit_code, duration_ms);
+                    let stderr_text = &out.stderr.render();
+                    // Stderr is the command's own and stays whole; blocked
+                    // syscalls and kills come from the report pipe.
+                    let reports = reports
+                        .as_mut()
+                        .map(ua_sandbox::ReportPipe::take)
+                        .unwrap_or_default();
+                    audit.log_executed(cmd, exit_code, duration_ms, reports.killed.as_deref());
 
                     let mut media_refs = Vec::new();
                     let mut resolved = Vec::new();
                     let stdout_text;
 
-                    if let Some(media_type) = detect_media_type(&out.stdout) {
+                    let media = out
+                        .stdout
+                        .bytes()
+                        .and_then(|b| detect_media_type(b).map(|m| (m, b)));
+                    if let Some((media_type, bytes)) = media {
                         // Binary output — store as sidecar, encode for API
                         let ext = media_type.rsplit('/').next().unwrap_or("bin");
                         let filename = format!("{}.{}", &tool_use_ids[i], ext);
                         if let Some(ref j) = journal {
-                            let _ = j.store_media(&filename, &out.stdout);
+                            let _ = j.store_media(&filename, bytes);
                         }
-                        let encoded = base64::engine::general_purpose::STANDARD.encode(&out.stdout);
+                        let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
                         media_refs.push(MediaRef {
                             media_type: media_type.to_string(),
                             filename,
@@ -761,31 +1056,40 @@ pub async fn run_batch(
                             media_type: media_type.to_string(),
                             data: encoded,
                         });
-                        stdout_text =
-                            format!("[binary: {} bytes, {}]", out.stdout.len(), media_type);
+                        stdout_text = format!("[binary: {} bytes, {}]", bytes.len(), media_type);
                     } else {
-                        let mut text = String::from_utf8_lossy(&out.stdout).to_string();
-                        if text.len() > MAX_OUTPUT_BYTES {
-                            let total = text.len();
-                            text.truncate(MAX_OUTPUT_BYTES);
-                            text.push_str(&format!("\n[truncated, {total} bytes total]"));
-                        }
-                        stdout_text = text;
+                        // Long output keeps its head and tail
+                        stdout_text = out.stdout.render();
                     }
 
-                    let stderr_text = String::from_utf8_lossy(&out.stderr).to_string();
-
-                    let mut result = String::from(TOOL_RESULT_PREFIX);
+                    let mut body = String::new();
                     if !stdout_text.is_empty() {
-                        result.push_str(&stdout_text);
+                        body.push_str(&stdout_text);
                     }
                     if !stderr_text.is_empty() {
                         if !stdout_text.is_empty() {
-                            result.push('\n');
+                            body.push('\n');
                         }
-                        result.push_str("STDERR:\n");
-                        result.push_str(&stderr_text);
+                        body.push_str("STDERR:\n");
+                        body.push_str(stderr_text);
                     }
+                    for note in reports.notes() {
+                        if !body.is_empty() && !body.ends_with('\n') {
+                            body.push('\n');
+                        }
+                        body.push_str(&note);
+                    }
+                    events.emit_result(
+                        iteration,
+                        &tool_use_ids[i],
+                        cmd,
+                        exit_code,
+                        duration_ms,
+                        &body,
+                    );
+
+                    let mut result = String::from(TOOL_RESULT_PREFIX);
+                    result.push_str(&body);
                     if let Some(code) = exit_code {
                         if code != 0 {
                             result.push_str(&format!("\n[exit code: {code}]"));
@@ -801,11 +1105,10 @@ pub async fn run_batch(
                     });
                 }
                 Err(e) => {
-                    audit.log_executed(cmd, None, duration_ms);
-                    all_results.push(ToolResultRecord::text(
-                        tool_use_ids[i].clone(),
-                        format!("Failed to execute: {e}"),
-                    ));
+                    audit.log_executed(cmd, None, duration_ms, None);
+                    let msg = format!("Failed to execute: {e}");
+                    events.emit_result(iteration, &tool_use_ids[i], cmd, None, duration_ms, &msg);
+                    all_results.push(ToolResultRecord::text(tool_use_ids[i].clone(), msg));
                 }
             }
         }
@@ -1150,4 +1453,216 @@ mod tests {
         assert!(s.contains("Filesystem destruction"));
         assert!(!s.contains("\x1b["), "non-TTY should not have ANSI codes");
     }
+
+    #[test]
+    fn batch_output_progress_tty_only() {
+        let mut out = make_output(true, 0, "test");
+        out.emit_command("cargo build", 0);
+        out.emit_output_progress(12_600, "Compiling serde v1.0");
+        let s = output_str(&out);
+        assert!(s.contains("(1) 12.3 KB  Compiling serde v1.0"), "{s:?}");
+
+        let mut out = make_output(false, 0, "test");
+        out.emit_output_progress(12_600, "Compiling serde v1.0");
+        assert!(output_str(&out).is_empty());
+    }
+
+    #[test]
+    fn run_streaming_reports_progress_and_keeps_tail() {
+        let mut c = Command::new("sh");
+        c.arg("-c")
+            .arg("seq 1 50000; sleep 0.2; echo 'error: build failed' >&2; exit 3");
+        let mut updates = 0;
+        let out = run_streaming(c, |_, _| updates += 1).unwrap();
+
+        assert_eq!(out.status.code(), Some(3));
+        assert!(updates >= 1, "should report progress while running");
+        let stdout = out.stdout.render();
+        assert!(stdout.starts_with("1\n2\n"));
+        assert!(stdout.contains("elided"));
+        assert!(stdout.ends_with("49999\n50000\n"));
+        assert_eq!(out.stderr.render(), "error: build failed\n");
+    }
+
+    // --- End-to-end loop against the mock backend ---
+
+    use ua_backend::mock::fixtures;
+    use ua_backend::MockBackend;
+
+    fn mock_config(dir: &std::path::Path) -> Config {
+        let mut config = Config::default();
+        config.journal.sessions_dir = Some(dir.join("sessions").to_string_lossy().into());
+        config.security.audit_log_path = Some(dir.join("audit.jsonl").to_string_lossy().into());
+        config.hooks.dir = Some(dir.join("hooks").to_string_lossy().into());
+        config
+    }
+
+    /// Install an executable `pre-exec.sh` hook for a `mock_config(dir)` config.
+    fn write_hook(dir: &std::path::Path, body: &str) {
+        use std::os::unix::fs::PermissionsExt;
+        let hooks_dir = dir.join("hooks");
+        std::fs::create_dir_all(&hooks_dir).unwrap();
+        let script = hooks_dir.join("pre-exec.sh");
+        std::fs::write(&script, format!("#!/bin/sh\ncat >/dev/null\n{body}\n")).unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    fn read_journal(dir: &std::path::Path) -> String {
+        let sessions = dir.join("sessions");
+        let path = std::fs::read_dir(&sessions)
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .find(|p| p.extension().is_some_and(|ext| ext == "jsonl"))
+            .expect("journal file should exist");
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[tokio::test]
+    async fn run_batch_executes_mock_commands_then_answers() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = mock_config(dir.path());
+        let backend = MockBackend::new()
+            .with_turn(fixtures::text_with_commands(
+                "Printing marker",
+                &["echo ua_mock_batch_marker"],
+            ))
+            .with_turn(fixtures::streaming_text(&["All done."]));
+
+        let code = run_batch(
+            &config,
+            &backend,
+            "print a marker",
+            0,
+            false,
+            vec![],
+            None,
+            false,
+            false,
+        )
+        .await;
+        assert_eq!(code, 0);
+
+        let journal = read_journal(dir.path());
+        assert!(
+            journal.contains("ua_mock_batch_marker"),
+            "tool result should be journaled"
+        );
+        assert!(
+            journal.contains("All done."),
+            "final answer should be journaled"
+        );
+
+        let audit = std::fs::read_to_string(dir.path().join("audit.jsonl")).unwrap();
+        assert!(audit.contains("\"type\":\"executed\""));
+    }
+
+    #[tokio::test]
+    async fn run_batch_aborts_after_consecutive_denials() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = mock_config(dir.path());
+        let backend = (0..MAX_CONSECUTIVE_DENIALS).fold(MockBackend::new(), |b, _| {
+            b.with_turn(fixtures::text_with_commands("Wiping", &["rm -rf /"]))
+        });
+
+        let code = run_batch(
+            &config,
+            &backend,
+            "wipe",
+            0,
+            false,
+            vec![],
+            None,
+            false,
+            false,
+        )
+        .await;
+        assert_eq!(code, 1);
+
+        let journal = read_journal(dir.path());
+        assert!(journal.contains("blocked by security policy"));
+    }
+
+    #[tokio::test]
+    async fn run_batch_hook_rewrites_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = mock_config(dir.path());
+        write_hook(
+            dir.path(),
+            r#"echo '{"decision": "modify", "command": "echo hook_rewrote_this"}'"#,
+        );
+        let backend = MockBackend::new()
+            .with_turn(fixtures::text_with_commands("Running", &["echo original"]))
+            .with_turn(fixtures::streaming_text(&["Done."]));
+
+        let code = run_batch(
+            &config,
+            &backend,
+            "echo something",
+            0,
+            false,
+            vec![],
+            None,
+            false,
+            false,
+        )
+        .await;
+        assert_eq!(code, 0);
+
+        let journal = read_journal(dir.path());
+        assert!(journal.contains("hook_rewrote_this"));
+        let audit = std::fs::read_to_string(dir.path().join("audit.jsonl")).unwrap();
+        assert!(audit.contains(r#""decision":"modify""#));
+    }
+
+    #[tokio::test]
+    async fn run_batch_hook_deny_blocks_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = mock_config(dir.path());
+        write_hook(
+            dir.path(),
+            r#"echo '{"decision": "deny", "reason": "maintenance window"}'"#,
+        );
+        let backend = MockBackend::new()
+            .with_turn(fixtures::text_with_commands("Listing", &["ls"]))
+            .with_turn(fixtures::streaming_text(&["Could not list."]));
+
+        let code = run_batch(
+            &config,
+            &backend,
+            "list files",
+            0,
+            false,
+            vec![],
+            None,
+            false,
+            false,
+        )
+        .await;
+        assert_eq!(code, 0);
+
+        let journal = read_journal(dir.path());
+        assert!(journal.contains("blocked by pre-exec hook pre-exec.sh: maintenance window"));
+    }
+
+    #[tokio::test]
+    async fn run_batch_backend_error_exits_nonzero() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = mock_config(dir.path());
+        let backend = MockBackend::new();
+
+        let code = run_batch(
+            &config,
+            &backend,
+            "anything",
+            0,
+            false,
+            vec![],
+            None,
+            false,
+            false,
+        )
+        .await;
+        assert_eq!(code, 1);
+    }
 }
@@ -0,0 +1,287 @@
+//! Bounded capture of a command's output stream.
+//!
+//! Batch mode streams command output instead of buffering it whole. Only
+//! the start and the end are kept — the first [`HEAD_LINES`] lines and the
+//! last [`TAIL_LINES`] — so the tool result shows the error at the bottom of
+//! a long compile log without pulling megabytes into the conversation.
+
+use std::collections::VecDeque;
+
+use crate::attachment::{detect_media_type, MAX_FILE_SIZE};
+
+/// Lines kept from the start of long output.
+pub const HEAD_LINES: usize = 100;
+/// Lines kept from the end of long output.
+pub const TAIL_LINES: usize = 200;
+
+/// Keeps the first `limit` bytes of a stream and the last `limit` bytes
+/// after that, counting everything in between.
+///
+/// Output that starts with an image or audio signature is kept whole so it
+/// can be attached as media, up to the attachment size limit. Past that it
+/// is elided like text.
+#[derive(Debug)]
+pub struct OutputCapture {
+    head: Vec<u8>,
+    tail: VecDeque<u8>,
+    limit: usize,
+    total_bytes: usize,
+    total_newlines: usize,
+    /// Bytes dropped between `head` and `tail`.
+    dropped: usize,
+    /// The output looked like media but grew too big to attach.
+    oversized: bool,
+}
+
+impl OutputCapture {
+    /// A capture keeping up to `limit` bytes at each end.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            head: Vec::new(),
+            tail: VecDeque::new(),
+            limit,
+            total_bytes: 0,
+            total_newlines: 0,
+            dropped: 0,
+            oversized: false,
+        }
+    }
+
+    /// Append a chunk of output.
+    pub fn push(&mut self, data: &[u8]) {
+        self.total_bytes += data.len();
+        self.total_newlines += data.iter().filter(|&&b| b == b'\n').count();
+
+        let room = self.limit.saturating_sub(self.head.len());
+        let (front, rest) = data.split_at(room.min(data.len()));
+        self.head.extend_from_slice(front);
+        if rest.is_empty() {
+            return;
+        }
+        if !self.oversized && detect_media_type(&self.head).is_some() {
+            if self.head.len() + rest.len() <= MAX_FILE_SIZE as usize {
+                self.head.extend_from_slice(rest);
+                return;
+            }
+            self.oversized = true;
+            let spilled = self.head.split_off(self.limit);
+            self.push_tail(&spilled);
+        }
+        self.push_tail(rest);
+    }
+
+    fn push_tail(&mut self, data: &[u8]) {
+        self.tail.extend(data);
+        let excess = self.tail.len().saturating_sub(self.limit);
+        self.tail.drain(..excess);
+        self.dropped += excess;
+    }
+
+    /// Total bytes seen so far.
+    pub fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+
+    /// The full output, if it was kept whole: binary media, or text that
+    /// never outgrew the head.
+    pub fn bytes(&self) -> Option<&[u8]> {
+        self.tail.is_empty().then_some(self.head.as_slice())
+    }
+
+    /// The last line seen, for a live progress display. Escape sequences
+    /// are dropped and only the text after the last `\r` is kept,
+    /// so progress bars show their current state.
+    pub fn last_line(&self) -> String {
+        let tail: Vec<u8>;
+        let buf = if self.tail.is_empty() {
+            &self.head[..]
+        } else {
+            tail = self.tail.iter().copied().collect();
+            &tail[..]
+        };
+        let text = String::from_utf8_lossy(last_lines(buf, 1, 1024));
+        let line = text.trim_end_matches(['\n', '\r']);
+        let line = line.rsplit('\r').next().unwrap_or("");
+        strip_controls(line)
+    }
+
+    /// Render as text: everything if it fits in [`HEAD_LINES`] +
+    /// [`TAIL_LINES`] lines and `limit` bytes, otherwise the head and tail
+    /// with an elision marker between them.
+    pub fn render(&self) -> String {
+        let mut buf = self.head.clone();
+        let contiguous = self.dropped == 0;
+        let tail_start = if contiguous { 0 } else { buf.len() };
+        buf.extend(self.tail.iter());
+
+        if contiguous && buf.len() <= self.limit && self.line_count() <= HEAD_LINES + TAIL_LINES {
+            return String::from_utf8_lossy(&buf).into_owned();
+        }
+
+        let half = self.limit / 2;
+        let head = first_lines(&self.head, HEAD_LINES, half);
+        let tail_region = &buf[tail_start.max(head.len())..];
+        let tail = last_lines(tail_region, TAIL_LINES, half);
+
+        let newlines = |b: &[u8]| b.iter().filter(|&&c| c == b'\n').count();
+        let elided_bytes = self.total_bytes - head.len() - tail.len();
+        let elided_lines = self
+            .total_newlines
+            .saturating_sub(newlines(head) + newlines(tail));
+
+        let mut out = String::from_utf8_lossy(head).into_owned();
+        if !out.is_empty() && !out.ends_with('\n') {
+            out.push('\n');
+        }
+        out.push_str(&format!(
+            "[... {elided_lines} lines ({elided_bytes} bytes) elided ...]\n"
+        ));
+        out.push_str(&String::from_utf8_lossy(tail));
+        out
+    }
+
+    fn line_count(&self) -> usize {
+        let ends_with_newline = match self.tail.back() {
+            Some(&b) => b == b'\n',
+            None => self.head.last().is_none_or(|&b| b == b'\n'),
+        };
+        self.total_newlines + usize::from(!ends_with_newline)
+    }
+}
+
+/// The first `n` lines of `buf`, at most `max_bytes` long.
+fn first_lines(buf: &[u8], n: usize, max_bytes: usize) -> &[u8] {
+    let end = buf
+        .iter()
+        .enumerate()
+        .filter(|(_, &b)| b == b'\n')
+        .nth(n.saturating_sub(1))
+        .map_or(buf.len(), |(i, _)| i + 1);
+    &buf[..end.min(max_bytes)]
+}
+
+/// The last `n` lines of `buf` (a trailing newline doesn't start a new
+/// line), at most `max_bytes` long.
+fn last_lines(buf: &[u8], n: usize, max_bytes: usize) -> &[u8] {
+    let body = buf.strip_suffix(b"\n").unwrap_or(buf);
+    let start = body
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, &b)| b == b'\n')
+        .nth(n.saturating_sub(1))
+        .map_or(0, |(i, _)| i + 1);
+    &buf[start.max(buf.len().saturating_sub(max_bytes))..]
+}
+
+/// Drop ANSI CSI sequences (`ESC [ ... final`) and other control characters.
+fn strip_controls(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            if chars.clone().next() == Some('[') {
+                chars.next();
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else if !c.is_control() {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(n: usize) -> String {
+        (1..=n).map(|i| format!("line {i}\n")).collect()
+    }
+
+    #[test]
+    fn short_output_is_kept_whole() {
+        let mut cap = OutputCapture::new(1000);
+        cap.push(b"hello\n");
+        cap.push(b"world");
+        assert_eq!(cap.render(), "hello\nworld");
+        assert_eq!(cap.bytes(), Some(&b"hello\nworld"[..]));
+        assert_eq!(cap.total_bytes(), 11);
+    }
+
+    #[test]
+    fn many_lines_keep_head_and_tail() {
+        let mut cap = OutputCapture::new(1_000_000);
+        cap.push(numbered(1000).as_bytes());
+        let text = cap.render();
+        assert!(text.starts_with("line 1\nline 2\n"));
+        assert!(text.contains(&format!("line {HEAD_LINES}\n[... 700 lines")));
+        assert!(text.ends_with("line 999\nline 1000\n"), "{text}");
+        assert!(!text.contains("line 500\n"));
+    }
+
+    #[test]
+    fn overflowing_bytes_drop_the_middle() {
+        let mut cap = OutputCapture::new(10_000);
+        for chunk in numbered(100_000).as_bytes().chunks(4096) {
+            cap.push(chunk);
+        }
+        assert!(cap.bytes().is_none());
+        let text = cap.render();
+        assert!(text.starts_with("line 1\n"));
+        assert!(
+            text.ends_with("line 100000\n"),
+            "{}",
+            &text[text.len() - 100..]
+        );
+        assert!(text.contains("elided"));
+        assert!(text.len() < 11_000);
+    }
+
+    #[test]
+    fn elision_marker_counts_are_exact_without_drops() {
+        let mut cap = OutputCapture::new(1_000_000);
+        cap.push(numbered(HEAD_LINES + TAIL_LINES + 5).as_bytes());
+        assert!(cap.render().contains("[... 5 lines (45 bytes) elided ...]"));
+    }
+
+    #[test]
+    fn media_is_kept_whole() {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.extend(std::iter::repeat_n(0u8, 50_000));
+        let mut cap = OutputCapture::new(1000);
+        for chunk in png.chunks(4096) {
+            cap.push(chunk);
+        }
+        assert_eq!(cap.bytes(), Some(&png[..]));
+    }
+
+    #[test]
+    fn media_past_the_attachment_limit_is_elided() {
+        let mut cap = OutputCapture::new(1000);
+        cap.push(b"\x89PNG\r\n\x1a\n");
+        let chunk = vec![b'x'; 1024 * 1024];
+        let chunks = MAX_FILE_SIZE as usize / chunk.len() + 2;
+        for _ in 0..chunks {
+            cap.push(&chunk);
+        }
+        assert_eq!(cap.bytes(), None);
+        assert_eq!(cap.head.len(), 1000);
+        assert_eq!(cap.tail.len(), 1000);
+        assert_eq!(cap.total_bytes(), 8 + chunks * chunk.len());
+        assert!(cap.render().contains("bytes) elided ...]"));
+    }
+
+    #[test]
+    fn last_line_shows_progress_state() {
+        let mut cap = OutputCapture::new(1000);
+        cap.push(b"Compiling foo\n");
+        assert_eq!(cap.last_line(), "Compiling foo");
+        cap.push(b"\x1b[1m 10%\r\x1b[32m 55%\x1b[0m");
+        assert_eq!(cap.last_line(), " 55%");
+    }
+}
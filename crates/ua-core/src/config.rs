@@ -1,10 +1,18 @@
-use serde::Deserialize;
-use std::io;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Arc;
 
-#[derive(Debug, Default, Deserialize, PartialEq)]
-#[serde(default)]
+use ua_backend::{AnthropicClient, Backend, MockBackend, OpenAiClient};
+
+use crate::policy::{PolicyRules, RiskLevel};
+use crate::process;
+use crate::trust::{self, TrustStore};
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     pub shell: ShellConfig,
     pub backend: BackendConfig,
@@ -12,13 +20,23 @@ pub struct Config {
     pub security: SecurityConfig,
     pub journal: JournalConfig,
     pub sandbox: SandboxConfig,
+    pub hooks: HooksConfig,
+    pub policy: PolicyConfig,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
-#[serde(default)]
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
 pub struct ShellConfig {
     pub command: Option<String>,
     pub integration: bool,
+    /// Guess prompts from the output when no shell integration reports them
+    /// (`--no-integration`, unknown shells, `ssh` to hosts without unixagent).
+    pub prompt_detection: bool,
+    /// Regexes matched against the line the cursor sits on.
+    pub prompt_patterns: Vec<String>,
+    /// How long the output must stay quiet before a matching line counts
+    /// as a prompt.
+    pub prompt_idle_ms: u64,
 }
 
 impl Default for ShellConfig {
@@ -26,17 +44,38 @@ impl Default for ShellConfig {
         Self {
             command: None,
             integration: true,
+            prompt_detection: true,
+            prompt_patterns: default_prompt_patterns(),
+            prompt_idle_ms: 200,
         }
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
-#[serde(default)]
+/// `user@host:~/src$ `, `[root@box ~]# `; `$ `, `bash-5.2# `, `/ # `,
+/// `~/app % `; `PS /home/me> `. The sigil must end the line followed by a
+/// space, and anything before a bare sigil must hold a letter, `/` or `~`,
+/// so `100% ` or a lone `#` in output is not taken for a prompt.
+fn default_prompt_patterns() -> Vec<String> {
+    [
+        r"^\S+@\S+.*[$#%>] $",
+        r"^(\S*[A-Za-z/~\]]\S* ?)?[$#%] $",
+        r"^PS .*> $",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
 pub struct BackendConfig {
-    /// Which backend to use by default.
+    /// Which backend to use by default ("anthropic", "openai", or "mock").
     pub default: String,
     /// Anthropic-specific configuration.
     pub anthropic: AnthropicConfig,
+    /// OpenAI-compatible (chat completions) configuration.
+    pub openai: OpenAiConfig,
+    /// Mock backend configuration (testing only).
+    pub mock: MockBackendConfig,
 }
 
 impl Default for BackendConfig {
@@ -44,12 +83,119 @@ impl Default for BackendConfig {
         Self {
             default: "anthropic".to_string(),
             anthropic: AnthropicConfig::default(),
+            openai: OpenAiConfig::default(),
+            mock: MockBackendConfig::default(),
+        }
+    }
+}
+
+impl BackendConfig {
+    /// Instantiate the backend named by `default`.
+    ///
+    /// For Anthropic this resolves the API key, so it fails early when no key
+    /// is configured.
+    pub fn create_backend(&self) -> io::Result<Arc<dyn Backend>> {
+        match self.default.as_str() {
+            "anthropic" => {
+                let api_key = self.anthropic.resolve_api_key()?;
+                Ok(Arc::new(AnthropicClient::with_model(
+                    &api_key,
+                    &self.anthropic.model,
+                )))
+            }
+            "openai" => {
+                let api_key = self.openai.resolve_api_key()?;
+                Ok(Arc::new(
+                    OpenAiClient::with_model(api_key, &self.openai.model)
+                        .with_base_url(&self.openai.base_url),
+                ))
+            }
+            "mock" => match &self.mock.script {
+                Some(path) => MockBackend::from_file(path)
+                    .map(|b| Arc::new(b) as Arc<dyn Backend>)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string())),
+                None => Ok(Arc::new(MockBackend::new())),
+            },
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown backend '{other}' (expected \"anthropic\", \"openai\" or \"mock\")"
+                ),
+            )),
+        }
+    }
+}
+
+/// Run `api_key_cmd` via `sh -c`, returning its trimmed stdout if it succeeded
+/// and printed something.
+fn run_api_key_cmd(cmd: Option<&str>) -> io::Result<Option<String>> {
+    let Some(cmd) = cmd else {
+        return Ok(None);
+    };
+    let output = Command::new("sh").arg("-c").arg(cmd).output()?;
+    if output.status.success() {
+        let key = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if !key.is_empty() {
+            return Ok(Some(key));
         }
     }
+    Ok(None)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct OpenAiConfig {
+    /// Command to run to get API key. The command is run via `sh -c`.
+    pub api_key_cmd: Option<String>,
+    /// Model to use.
+    pub model: String,
+    /// Base URL of the API, without the `/chat/completions` suffix.
+    /// Point this at a local llama.cpp / vLLM / Ollama server
+    /// (e.g. "http://localhost:11434/v1").
+    pub base_url: String,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
-#[serde(default)]
+impl Default for OpenAiConfig {
+    fn default() -> Self {
+        Self {
+            api_key_cmd: None,
+            model: "gpt-4o".to_string(),
+            base_url: ua_backend::openai::DEFAULT_BASE_URL.to_string(),
+        }
+    }
+}
+
+impl OpenAiConfig {
+    /// Resolve the API key from api_key_cmd or OPENAI_API_KEY env var.
+    ///
+    /// A key is only required for the official OpenAI endpoint; local
+    /// servers usually accept unauthenticated requests, so a custom
+    /// `base_url` without a key resolves to an empty key.
+    pub fn resolve_api_key(&self) -> io::Result<String> {
+        if let Some(key) = run_api_key_cmd(self.api_key_cmd.as_deref())? {
+            return Ok(key);
+        }
+
+        match std::env::var("OPENAI_API_KEY") {
+            Ok(key) => Ok(key),
+            Err(_) if self.base_url != ua_backend::openai::DEFAULT_BASE_URL => Ok(String::new()),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "OPENAI_API_KEY not set and no api_key_cmd configured",
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MockBackendConfig {
+    /// JSON script of scripted turns and replies (see `ua_backend::MockScript`).
+    pub script: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
 pub struct AnthropicConfig {
     /// Command to run to get API key (e.g., "security find-generic-password -s anthropic -w").
     /// The command is run via `sh -c`.
@@ -71,15 +217,8 @@ impl AnthropicConfig {
     /// Resolve the API key from api_key_cmd or ANTHROPIC_API_KEY env var.
     pub fn resolve_api_key(&self) -> io::Result<String> {
         // Try api_key_cmd first
-        if let Some(cmd) = &self.api_key_cmd {
-            let output = Command::new("sh").arg("-c").arg(cmd).output()?;
-
-            if output.status.success() {
-                let key = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                if !key.is_empty() {
-                    return Ok(key);
-                }
-            }
+        if let Some(key) = run_api_key_cmd(self.api_key_cmd.as_deref())? {
+            return Ok(key);
         }
 
         // Fall back to env var
@@ -92,8 +231,8 @@ impl AnthropicConfig {
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
-#[serde(default)]
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
 pub struct ContextConfig {
     /// Maximum number of terminal output lines to include in context.
     pub max_terminal_lines: usize,
@@ -101,6 +240,9 @@ pub struct ContextConfig {
     pub max_conversation_turns: usize,
     /// Environment variables to include in context.
     pub include_env: Vec<String>,
+    /// Extra instructions appended to the system prompt. Additions from
+    /// every config layer are kept, outermost first.
+    pub system_prompt: Option<String>,
 }
 
 impl Default for ContextConfig {
@@ -116,12 +258,13 @@ impl Default for ContextConfig {
                 "TERM".to_string(),
                 "LANG".to_string(),
             ],
+            system_prompt: None,
         }
     }
 }
 
 /// How the judge handles dangerous commands.
-#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum JudgeMode {
     /// Show warning, still allow execution (main agent default).
@@ -130,8 +273,8 @@ pub enum JudgeMode {
     Block,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
-#[serde(default)]
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
 pub struct SecurityConfig {
     /// Auto-approve read-only commands without prompting.
     pub auto_approve_read_only: bool,
@@ -190,18 +333,12 @@ impl SecurityConfig {
             return PathBuf::from(custom);
         }
 
-        let base = std::env::var("XDG_DATA_HOME")
-            .map(PathBuf::from)
-            .unwrap_or_else(|_| {
-                let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-                PathBuf::from(home).join(".local").join("share")
-            });
-        base.join("unixagent").join("audit.jsonl")
+        data_dir().join("audit.jsonl")
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
-#[serde(default)]
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
 pub struct JournalConfig {
     /// Enable session journaling.
     pub enabled: bool,
@@ -228,33 +365,60 @@ impl JournalConfig {
             return PathBuf::from(custom);
         }
 
-        let base = std::env::var("XDG_DATA_HOME")
-            .map(PathBuf::from)
-            .unwrap_or_else(|_| {
-                let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-                PathBuf::from(home).join(".local").join("share")
-            });
-        base.join("unixagent").join("sessions")
+        data_dir().join("sessions")
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
-#[serde(default)]
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
 pub struct SandboxConfig {
     /// Enable OS-level filesystem sandbox for batch-mode commands.
     pub enabled: bool,
+    /// Also sandbox agent commands in the interactive REPL by sending them
+    /// to the shell as `unixagent --sandbox-exec sh -c '...'`. They then run
+    /// in a subshell, so `cd` and `export` don't carry over. Off by default.
+    pub repl: bool,
+    /// Filesystem sandbox on Linux: "landlock" (default) or "namespace",
+    /// which also enforces denied paths beneath writable ones.
+    pub backend: ua_sandbox::SandboxBackend,
     /// Paths the sandboxed process may write. Supports `$CWD` and `$HOME` placeholders.
     pub writable_paths: Vec<String>,
     /// Paths the sandboxed process may read. System paths added automatically.
     pub readable_paths: Vec<String>,
+    /// Writable paths added to `writable_paths` rather than replacing it.
+    /// Collected from every config layer; relative entries in a project
+    /// file are taken from the project directory.
+    pub extra_writable_paths: Vec<String>,
+    /// Readable paths added to `readable_paths`, e.g. a project toolchain.
+    /// Collected like `extra_writable_paths`.
+    pub extra_readable_paths: Vec<String>,
     /// Paths explicitly denied. Overrides readable/writable on supported platforms.
     pub denied_paths: Vec<String>,
+    /// Paths that may be read but never written. The config file is always
+    /// included so the agent cannot edit its own policy.
+    pub deny_write_paths: Vec<String>,
+    /// `[sandbox.network]`: egress rules for `--sandbox-exec` children
+    /// (Linux). `mode` is "allow" (default), "deny", "loopback", or "ports"
+    /// with `allow_ports`.
+    pub network: ua_sandbox::NetworkPolicy,
+    /// Syscall filter for `--sandbox-exec` children (Linux): "default",
+    /// "strict", or "off".
+    pub seccomp: ua_sandbox::SeccompProfile,
+    /// `[sandbox.limits]`: rlimits (`cpu_secs`, `memory_mb`,
+    /// `max_processes`, `max_file_size_mb`) and `timeout_secs` (default 300)
+    /// for `--sandbox-exec` children. Zero means unlimited.
+    pub limits: ua_sandbox::ResourceLimits,
+    /// Log every access the sandbox denies to the kernel audit log
+    /// (Landlock backend, Linux 6.15+).
+    pub debug: bool,
 }
 
 impl Default for SandboxConfig {
     fn default() -> Self {
         Self {
             enabled: true,
+            repl: false,
+            backend: ua_sandbox::SandboxBackend::default(),
             writable_paths: vec![
                 "$CWD".to_string(),
                 "/tmp".to_string(),
@@ -272,36 +436,185 @@ impl Default for SandboxConfig {
                 "/dev/urandom".to_string(),
                 "/dev/tty".to_string(),
             ],
+            extra_writable_paths: Vec::new(),
+            extra_readable_paths: Vec::new(),
             denied_paths: vec![
                 "$HOME/.ssh".to_string(),
                 "$HOME/.gnupg".to_string(),
                 "$HOME/.aws".to_string(),
             ],
+            deny_write_paths: Vec::new(),
+            network: ua_sandbox::NetworkPolicy::default(),
+            seccomp: ua_sandbox::SeccompProfile::default(),
+            limits: ua_sandbox::ResourceLimits::default(),
+            debug: false,
         }
     }
 }
 
 impl SandboxConfig {
     /// Build a `SandboxPolicy` from this config, resolving path placeholders.
-    pub fn to_policy(&self) -> ua_sandbox::SandboxPolicy {
-        ua_sandbox::SandboxPolicy::from_config(
-            &self.writable_paths,
-            &self.readable_paths,
-            &self.denied_paths,
-        )
+    /// The `hooks` directory is write-protected along with the config files.
+    pub fn to_policy(&self, hooks: &HooksConfig) -> ua_sandbox::SandboxPolicy {
+        let mut deny_write = self.deny_write_paths.clone();
+        // Sandboxed commands must not edit the policy, nor write a project
+        // config and vouch for it, nor add a hook: hooks run unsandboxed.
+        deny_write.push(config_path().to_string_lossy().into_owned());
+        deny_write.push(hooks.resolve_dir().to_string_lossy().into_owned());
+        deny_write.push(trust::store_path().to_string_lossy().into_owned());
+        if let Ok(cwd) = std::env::current_dir() {
+            deny_write.extend(
+                discover_layers(&cwd)
+                    .into_iter()
+                    .filter(|(kind, _)| *kind == LayerKind::Project)
+                    .map(|(_, path)| path.to_string_lossy().into_owned()),
+            );
+        }
+        let writable = [&self.writable_paths[..], &self.extra_writable_paths[..]].concat();
+        let readable = [&self.readable_paths[..], &self.extra_readable_paths[..]].concat();
+        ua_sandbox::SandboxPolicy::from_config(&writable, &readable, &self.denied_paths)
+            .with_deny_write(&deny_write)
+            .with_backend(self.backend)
+            .with_network(self.network.clone())
+            .with_seccomp(self.seccomp)
+            .with_limits(self.limits.clone())
+            .with_debug(self.debug)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Run pre-exec hooks before commands are approved or executed.
+    pub enabled: bool,
+    /// Directory containing hook executables. Defaults to
+    /// ~/.config/unixagent/hooks.
+    pub dir: Option<String>,
+    /// Maximum time a single hook may run before it is killed (fails closed).
+    pub timeout_secs: u64,
+}
+
+impl Default for HooksConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: None,
+            timeout_secs: 5,
+        }
+    }
+}
+
+impl HooksConfig {
+    /// Resolve the hooks directory, using the configured path or the XDG default.
+    pub fn resolve_dir(&self) -> PathBuf {
+        match self.dir {
+            Some(ref custom) => PathBuf::from(custom),
+            None => config_dir().join("hooks"),
+        }
+    }
+}
+
+/// User command-classification rules, consulted before the built-in tables.
+///
+/// Each risk bucket lists commands as a binary optionally followed by
+/// subcommand words, e.g. `"kubectl get"` or `"terraform"`.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolicyConfig {
+    pub read_only: Vec<String>,
+    pub build_test: Vec<String>,
+    pub write: Vec<String>,
+    pub destructive: Vec<String>,
+    pub network: Vec<String>,
+    pub privileged: Vec<String>,
+    /// Commands that are always refused, like the built-in deny list.
+    pub denied: Vec<String>,
+    /// Regular expressions; any matching command is denied.
+    pub deny_regex: Vec<String>,
+    /// Shell-style globs (`*`, `?`) matched against the whole command.
+    pub deny_glob: Vec<String>,
+}
+
+impl PolicyConfig {
+    /// Compile this config into rules for the policy engine.
+    pub fn to_rules(&self) -> Result<PolicyRules, String> {
+        let mut rules = PolicyRules::new();
+        let buckets = [
+            (&self.read_only, RiskLevel::ReadOnly),
+            (&self.build_test, RiskLevel::BuildTest),
+            (&self.write, RiskLevel::Write),
+            (&self.destructive, RiskLevel::Destructive),
+            (&self.network, RiskLevel::Network),
+            (&self.privileged, RiskLevel::Privileged),
+            (&self.denied, RiskLevel::Denied),
+        ];
+        for (specs, risk) in buckets {
+            for spec in specs {
+                rules.add_command(spec, risk)?;
+            }
+        }
+        for pattern in &self.deny_regex {
+            rules.add_deny_regex(pattern)?;
+        }
+        for pattern in &self.deny_glob {
+            rules.add_deny_glob(pattern)?;
+        }
+        Ok(rules)
     }
 }
 
 impl Config {
-    pub fn load_or_default() -> Self {
-        let path = config_path();
-        match std::fs::read_to_string(&path) {
-            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
-                eprintln!("warning: failed to parse {}: {e}", path.display());
-                Config::default()
-            }),
-            Err(_) => Config::default(),
+    /// Load the layered config for the current directory: system, user,
+    /// then project files. Project files must be trusted first; see
+    /// [`crate::trust`]. `UNIXAGENT_*` variables and then `overrides` from
+    /// the command line go on top; in a subagent, hooks, security, policy
+    /// and sandbox overrides only count if its parent agent passed them.
+    /// Files and overrides with errors are left out of the result and
+    /// reported in [`LoadedConfig::issues`].
+    pub fn load(overrides: &[Override]) -> LoadedConfig {
+        let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let interactive = io::stdin().is_terminal() && io::stderr().is_terminal();
+        let mut store = TrustStore::load(trust::store_path());
+
+        let mut files = Vec::new();
+        // The sandbox as the system and user files set it up, to tell which
+        // project files sandboxed commands could have written.
+        let mut policy: Option<Option<ua_sandbox::SandboxPolicy>> = None;
+        for (kind, path) in discover_layers(&cwd) {
+            let contents = match std::fs::read_to_string(&path) {
+                Ok(contents) => contents,
+                Err(_) => continue,
+            };
+            if kind == LayerKind::Project {
+                let policy = policy.get_or_insert_with(|| {
+                    let config = load_layers(files.clone(), &[]).config;
+                    let sandbox = &config.sandbox;
+                    sandbox.enabled.then(|| sandbox.to_policy(&config.hooks))
+                });
+                let forgeable = policy
+                    .as_ref()
+                    .is_some_and(|policy| trust::sandbox_can_forge(policy, &path));
+                if !trust::confirm(&mut store, &path, &contents, interactive, forgeable) {
+                    continue;
+                }
+            }
+            files.push((kind, path, contents));
         }
+        let mut all = env_overrides();
+        all.extend_from_slice(overrides);
+        let (all, ignored) = subagent_overrides(all);
+        let mut loaded = load_layers(files, &all);
+        loaded.issues.extend(ignored);
+        loaded
+    }
+
+    /// Merge `layers` (lowest precedence first) over the defaults.
+    pub fn from_layers(layers: &[ConfigLayer]) -> Result<Self, toml::de::Error> {
+        let mut merged = toml::Table::new();
+        for layer in layers {
+            merge_table(&mut merged, &layer.table, "");
+        }
+        toml::Value::Table(merged).try_into()
     }
 
     pub fn shell_command(&self) -> String {
@@ -313,14 +626,652 @@ impl Config {
     }
 }
 
-fn config_path() -> PathBuf {
+/// `$XDG_CONFIG_HOME/unixagent`, falling back to `~/.config/unixagent`.
+pub fn config_dir() -> PathBuf {
     let base = std::env::var("XDG_CONFIG_HOME")
         .map(PathBuf::from)
         .unwrap_or_else(|_| {
             let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
             PathBuf::from(home).join(".config")
         });
-    base.join("unixagent").join("config.toml")
+    base.join("unixagent")
+}
+
+/// Path of the user config file.
+pub fn config_path() -> PathBuf {
+    config_dir().join("config.toml")
+}
+
+/// `$XDG_DATA_HOME/unixagent`, falling back to `~/.local/share/unixagent`.
+pub fn data_dir() -> PathBuf {
+    let base = std::env::var("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| {
+            let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+            PathBuf::from(home).join(".local").join("share")
+        });
+    base.join("unixagent")
+}
+
+/// System-wide config, the lowest-precedence layer.
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/unixagent/config.toml";
+
+/// Per-project config file, looked up in the working directory and its
+/// parents.
+pub const PROJECT_CONFIG_NAME: &str = ".unixagent.toml";
+
+/// Keys whose values from different layers are combined instead of the
+/// later one winning: arrays are concatenated, strings joined by a blank
+/// line.
+const ACCUMULATED_KEYS: &[&str] = &[
+    "context.system_prompt",
+    "sandbox.extra_writable_paths",
+    "sandbox.extra_readable_paths",
+];
+
+/// Sandbox path lists whose relative entries in a project file are taken
+/// from the project directory.
+const SANDBOX_PATH_KEYS: &[&str] = &[
+    "writable_paths",
+    "readable_paths",
+    "extra_writable_paths",
+    "extra_readable_paths",
+    "denied_paths",
+    "deny_write_paths",
+];
+
+/// Where a config layer comes from. Later kinds take precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LayerKind {
+    System,
+    User,
+    Project,
+    /// A `UNIXAGENT_<SECTION>__<FIELD>` variable.
+    Environment,
+    /// A `--set section.field=value` flag.
+    CommandLine,
+}
+
+impl LayerKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LayerKind::System => "system",
+            LayerKind::User => "user",
+            LayerKind::Project => "project",
+            LayerKind::Environment => "environment",
+            LayerKind::CommandLine => "command-line",
+        }
+    }
+}
+
+/// One parsed config file, or one override.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigLayer {
+    pub kind: LayerKind,
+    /// The file, or for an override the variable or flag that set it.
+    pub path: PathBuf,
+    pub table: toml::Table,
+}
+
+impl ConfigLayer {
+    /// Parse a config file. In a project file, relative sandbox paths are
+    /// made absolute against the file's directory.
+    pub fn parse(kind: LayerKind, path: &Path, contents: &str) -> Result<Self, toml::de::Error> {
+        let mut table: toml::Table = toml::from_str(contents)?;
+        if kind == LayerKind::Project {
+            let dir = path.parent().unwrap_or(Path::new("/"));
+            if let Some(toml::Value::Table(sandbox)) = table.get_mut("sandbox") {
+                for key in SANDBOX_PATH_KEYS {
+                    if let Some(toml::Value::Array(entries)) = sandbox.get_mut(*key) {
+                        for entry in entries.iter_mut() {
+                            if let toml::Value::String(p) = entry {
+                                if !p.starts_with('/') && !p.starts_with('$') {
+                                    *p = dir.join(&*p).to_string_lossy().into_owned();
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(Self {
+            kind,
+            path: path.to_path_buf(),
+            table,
+        })
+    }
+}
+
+/// Config files that apply in `cwd`, lowest precedence first: the system
+/// file, the user file, then `.unixagent.toml` from the outermost directory
+/// down to `cwd`. Only files that exist are returned.
+pub fn discover_layers(cwd: &Path) -> Vec<(LayerKind, PathBuf)> {
+    let mut layers = vec![
+        (LayerKind::System, PathBuf::from(SYSTEM_CONFIG_PATH)),
+        (LayerKind::User, config_path()),
+    ];
+    let mut projects: Vec<PathBuf> = cwd
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_NAME))
+        .collect();
+    projects.reverse();
+    layers.extend(projects.into_iter().map(|p| (LayerKind::Project, p)));
+    layers.retain(|(_, path)| path.is_file());
+    layers
+}
+
+/// Merge `layer` into `base`: tables recursively, [`ACCUMULATED_KEYS`] by
+/// concatenation, anything else replaced.
+fn merge_table(base: &mut toml::Table, layer: &toml::Table, prefix: &str) {
+    for (key, value) in layer {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(incoming)) => {
+                merge_table(existing, incoming, &path);
+            }
+            (Some(toml::Value::Array(existing)), toml::Value::Array(incoming))
+                if ACCUMULATED_KEYS.contains(&path.as_str()) =>
+            {
+                existing.extend(incoming.iter().cloned());
+            }
+            (Some(toml::Value::String(existing)), toml::Value::String(incoming))
+                if ACCUMULATED_KEYS.contains(&path.as_str()) =>
+            {
+                existing.push_str("\n\n");
+                existing.push_str(incoming);
+            }
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// The layered config and what was wrong with it.
+#[derive(Debug)]
+pub struct LoadedConfig {
+    pub config: Config,
+    /// Layers merged into `config`, lowest precedence first.
+    pub layers: Vec<ConfigLayer>,
+    /// Problems found while loading. A file with errors is not merged.
+    pub issues: Vec<ConfigIssue>,
+}
+
+impl LoadedConfig {
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|i| i.severity == Severity::Error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The file is not used; batch mode refuses to start.
+    Error,
+    /// A setting that is valid but probably not what was meant.
+    Warning,
+}
+
+/// One problem in a config file, printed as `path:line: error: message`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigIssue {
+    pub severity: Severity,
+    /// The file the problem is in. `None` for problems in the merged
+    /// config that no single file causes.
+    pub path: Option<PathBuf>,
+    /// 1-based line of the offending key or value, when it can be found.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    fn error(path: &Path, line: Option<usize>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            path: Some(path.to_path_buf()),
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.path, self.line) {
+            (Some(path), Some(line)) => write!(f, "{}:{line}: ", path.display())?,
+            (Some(path), None) => write!(f, "{}: ", path.display())?,
+            (None, _) => {}
+        }
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{severity}: {}", self.message)
+    }
+}
+
+/// Validate and merge config files given as `(kind, path, contents)`,
+/// lowest precedence first, then `overrides` in order. Files and
+/// overrides with errors are skipped.
+pub fn load_layers(
+    files: Vec<(LayerKind, PathBuf, String)>,
+    overrides: &[Override],
+) -> LoadedConfig {
+    let mut layers = Vec::new();
+    let mut issues = Vec::new();
+    for (kind, path, contents) in files {
+        let found = check_layer(kind, &path, &contents);
+        let failed = found.iter().any(|i| i.severity == Severity::Error);
+        issues.extend(found);
+        if !failed {
+            if let Ok(layer) = ConfigLayer::parse(kind, &path, &contents) {
+                layers.push(layer);
+            }
+        }
+    }
+    for o in overrides {
+        match o.to_layer() {
+            Ok(layer) => layers.push(layer),
+            Err(message) => issues.push(ConfigIssue {
+                severity: Severity::Error,
+                path: Some(PathBuf::from(o.source())),
+                line: None,
+                message,
+            }),
+        }
+    }
+
+    let config = match Config::from_layers(&layers) {
+        Ok(config) => config,
+        Err(e) => {
+            issues.push(ConfigIssue {
+                severity: Severity::Error,
+                path: None,
+                line: None,
+                message: format!("merged config: {}", e.message()),
+            });
+            Config::default()
+        }
+    };
+    issues.extend(check_merged(&config, &layers));
+    LoadedConfig {
+        config,
+        layers,
+        issues,
+    }
+}
+
+/// Check one config file on its own: TOML syntax, unknown keys, value
+/// types, and paths that would not resolve the way they look.
+pub fn check_layer(kind: LayerKind, path: &Path, contents: &str) -> Vec<ConfigIssue> {
+    let parse_error = |e: toml::de::Error| {
+        let line = e.span().map(|span| line_at(contents, span.start));
+        vec![ConfigIssue::error(path, line, e.message().trim_end())]
+    };
+    let table: toml::Table = match toml::from_str(contents) {
+        Ok(table) => table,
+        Err(e) => return parse_error(e),
+    };
+    if let Err(e) = toml::from_str::<Config>(contents) {
+        return parse_error(e);
+    }
+
+    // Project files may use paths relative to their directory.
+    path_problems(&table, kind == LayerKind::Project)
+        .into_iter()
+        .map(|p| ConfigIssue::error(path, find_line(contents, p.key, Some(&p.value)), p.message))
+        .collect()
+}
+
+/// A path setting that would not resolve the way it looks.
+struct PathProblem {
+    key: &'static str,
+    value: String,
+    message: String,
+}
+
+/// Sandbox paths that are relative (unless `relative_ok`) or use `~` or an
+/// unknown placeholder, and other path settings that are not absolute.
+fn path_problems(table: &toml::Table, relative_ok: bool) -> Vec<PathProblem> {
+    let mut problems = Vec::new();
+    let sandbox = table.get("sandbox").and_then(toml::Value::as_table);
+    for key in SANDBOX_PATH_KEYS {
+        let entries = sandbox
+            .and_then(|t| t.get(*key))
+            .and_then(toml::Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(toml::Value::as_str);
+        for entry in entries {
+            if let Some(problem) = sandbox_path_problem(entry, relative_ok) {
+                problems.push(PathProblem {
+                    key,
+                    value: entry.to_string(),
+                    message: format!("sandbox.{key}: {problem}"),
+                });
+            }
+        }
+    }
+
+    let single_paths = [
+        ("security", "audit_log_path"),
+        ("journal", "sessions_dir"),
+        ("hooks", "dir"),
+    ];
+    for (section, key) in single_paths {
+        let value = table
+            .get(section)
+            .and_then(|t| t.get(key))
+            .and_then(toml::Value::as_str);
+        if let Some(value) = value.filter(|v| !v.starts_with('/')) {
+            problems.push(PathProblem {
+                key,
+                value: value.to_string(),
+                message: format!("{section}.{key}: \"{value}\" must be an absolute path"),
+            });
+        }
+    }
+    problems
+}
+
+/// Prefix of the environment variables that override config fields.
+const ENV_PREFIX: &str = "UNIXAGENT_";
+
+/// One `section.field = value` setting from the environment or `--set`.
+/// The value is a TOML literal (`true`, `3`, `["a", "b"]`); anything that
+/// doesn't parse as one is taken as a string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Override {
+    pub kind: LayerKind,
+    /// Dotted key, e.g. `backend.anthropic.model`.
+    pub key: String,
+    pub value: String,
+}
+
+impl Override {
+    /// Parse a `--set section.field=value` argument.
+    pub fn from_set(arg: &str) -> Result<Self, String> {
+        match arg.split_once('=') {
+            Some((key, value)) if valid_key(key.trim()) => Ok(Self {
+                kind: LayerKind::CommandLine,
+                key: key.trim().to_string(),
+                value: value.to_string(),
+            }),
+            _ => Err(format!(
+                "--set {arg}: expected section.field=value, e.g. security.judge_enabled=true"
+            )),
+        }
+    }
+
+    /// The variable that carries this override, e.g.
+    /// `UNIXAGENT_SECURITY__JUDGE_ENABLED`.
+    pub fn env_name(&self) -> String {
+        format!("{ENV_PREFIX}{}", self.key.to_uppercase().replace('.', "__"))
+    }
+
+    /// Where the override came from, for messages.
+    pub fn source(&self) -> String {
+        match self.kind {
+            LayerKind::CommandLine => format!("--set {}", self.key),
+            _ => self.env_name(),
+        }
+    }
+
+    /// A layer holding just this setting, checked like a config file.
+    fn to_layer(&self) -> Result<ConfigLayer, String> {
+        let value = toml::from_str::<toml::Table>(&format!("v = {}", self.value))
+            .ok()
+            .and_then(|mut t| t.remove("v"))
+            .unwrap_or_else(|| toml::Value::String(self.value.clone()));
+        let mut table = toml::Table::new();
+        let mut keys: Vec<&str> = self.key.split('.').collect();
+        let last = keys.pop().unwrap_or_default();
+        let mut current = &mut table;
+        for key in keys {
+            current = match current
+                .entry(key)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            {
+                toml::Value::Table(t) => t,
+                _ => unreachable!("only tables are inserted above"),
+            };
+        }
+        current.insert(last.to_string(), value);
+
+        toml::Value::Table(table.clone())
+            .try_into::<Config>()
+            .map_err(|e| e.message().trim_end().to_string())?;
+        if let Some(p) = path_problems(&table, false).into_iter().next() {
+            return Err(p.message);
+        }
+        Ok(ConfigLayer {
+            kind: self.kind,
+            path: PathBuf::from(self.source()),
+            table,
+        })
+    }
+}
+
+fn valid_key(key: &str) -> bool {
+    !key.is_empty() && key.split('.').all(|part| !part.is_empty())
+}
+
+/// Overrides from `UNIXAGENT_<SECTION>__<FIELD>` variables, sorted by name.
+/// Variables without a `__` (like `UNIXAGENT_JOURNAL`) are not config.
+pub fn env_overrides() -> Vec<Override> {
+    overrides_from_vars(std::env::vars())
+}
+
+fn overrides_from_vars(vars: impl Iterator<Item = (String, String)>) -> Vec<Override> {
+    let mut overrides: Vec<Override> = vars
+        .filter_map(|(name, value)| {
+            let rest = name.strip_prefix(ENV_PREFIX)?;
+            let key = rest.to_lowercase().replace("__", ".");
+            (rest.contains("__") && valid_key(&key)).then_some(Override {
+                kind: LayerKind::Environment,
+                key,
+                value,
+            })
+        })
+        .collect();
+    overrides.sort_by_key(Override::env_name);
+    overrides
+}
+
+/// Sections a subagent takes overrides for only from its parent agent.
+const GUARDED_SECTIONS: &[&str] = &["hooks", "security", "policy", "sandbox"];
+
+/// The overrides an agent passed to the agents it starts: the
+/// `UNIXAGENT_*` variables it was started with, and its `--set` flags,
+/// which [`export_overrides`] puts into its environment.
+fn exported_overrides(args: &[String], env: &[String]) -> Vec<Override> {
+    let mut exported = overrides_from_vars(env.iter().filter_map(|var| {
+        let (name, value) = var.split_once('=')?;
+        Some((name.to_string(), value.to_string()))
+    }));
+    for pair in args.windows(2) {
+        if pair[0] == "--set" {
+            exported.extend(Override::from_set(&pair[1]).ok());
+        }
+    }
+    exported
+}
+
+/// In a subagent, drop the overrides of [`GUARDED_SECTIONS`] its parent
+/// agent didn't pass, returning a warning for each. The subagent was
+/// started by the parent's commands, which could set anything; what the
+/// parent itself was started with is read from the OS. If it can't be,
+/// none are kept.
+pub fn subagent_overrides(overrides: Vec<Override>) -> (Vec<Override>, Vec<ConfigIssue>) {
+    let Some(parent) = process::parent_agent() else {
+        return (overrides, Vec::new());
+    };
+    let exported = process::launch_info(parent)
+        .map(|(args, env)| exported_overrides(&args, &env))
+        .unwrap_or_default();
+    guard_overrides(overrides, &exported)
+}
+
+/// Drop overrides of [`GUARDED_SECTIONS`] that `exported` doesn't have
+/// with the same value, returning a warning for each.
+fn guard_overrides(
+    overrides: Vec<Override>,
+    exported: &[Override],
+) -> (Vec<Override>, Vec<ConfigIssue>) {
+    let mut kept = Vec::new();
+    let mut ignored = Vec::new();
+    for o in overrides {
+        let section = o.key.split('.').next().unwrap_or_default();
+        let passed_down = exported
+            .iter()
+            .any(|e| e.key == o.key && e.value == o.value);
+        if !GUARDED_SECTIONS.contains(&section) || passed_down {
+            kept.push(o);
+            continue;
+        }
+        ignored.push(ConfigIssue {
+            severity: Severity::Warning,
+            path: Some(PathBuf::from(o.source())),
+            line: None,
+            message: format!(
+                "ignored: a subagent takes {section} settings only from config files and \
+                 the agent that started it"
+            ),
+        });
+    }
+    (kept, ignored)
+}
+
+/// Put `--set` overrides into the environment, so agents started from
+/// this one (delegated subagents, or `unixagent` run in the REPL's shell)
+/// load the same settings. Those agents check guarded settings against
+/// our command line, not this environment, which our commands can change.
+pub fn export_overrides(overrides: &[Override]) {
+    for o in overrides {
+        std::env::set_var(o.env_name(), &o.value);
+    }
+}
+
+/// What is wrong with a sandbox path entry, if anything. Only `$CWD`,
+/// `$HOME` and `$HOME/...` are expanded.
+fn sandbox_path_problem(entry: &str, relative_ok: bool) -> Option<String> {
+    if entry.starts_with('/') || entry == "$CWD" || entry == "$HOME" || entry.starts_with("$HOME/")
+    {
+        return None;
+    }
+    if entry.starts_with('~') {
+        return Some(format!("\"{entry}\": `~` is not expanded; use $HOME"));
+    }
+    if entry.starts_with('$') {
+        return Some(format!(
+            "\"{entry}\": only $CWD, $HOME and $HOME/... are expanded"
+        ));
+    }
+    if relative_ok {
+        return None;
+    }
+    Some(format!(
+        "\"{entry}\" must be an absolute path or start with $CWD or $HOME"
+    ))
+}
+
+/// Settings that are valid in each file but conflict once merged. Each
+/// issue points at the file that set the key last.
+fn check_merged(config: &Config, layers: &[ConfigLayer]) -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+    let mut report = |severity, section: &str, key: &str, message: String| {
+        let origin = layers
+            .iter()
+            .rev()
+            .find(|layer| layer.table.get(section).and_then(|t| t.get(key)).is_some());
+        let line = origin
+            .filter(|layer| layer.kind <= LayerKind::Project)
+            .and_then(|layer| {
+                let contents = std::fs::read_to_string(&layer.path).ok()?;
+                find_line(&contents, key, None)
+            });
+        issues.push(ConfigIssue {
+            severity,
+            path: origin.map(|layer| layer.path.clone()),
+            line,
+            message,
+        });
+    };
+
+    let backend = config.backend.default.as_str();
+    if !matches!(backend, "anthropic" | "openai" | "mock") {
+        report(
+            Severity::Error,
+            "backend",
+            "default",
+            format!(
+                "backend.default: unknown backend \"{backend}\" (expected \"anthropic\", \"openai\" or \"mock\")"
+            ),
+        );
+    }
+
+    for pattern in &config.shell.prompt_patterns {
+        if let Err(e) = regex::Regex::new(pattern) {
+            report(
+                Severity::Error,
+                "shell",
+                "prompt_patterns",
+                format!("shell.prompt_patterns: invalid regex \"{pattern}\": {e}"),
+            );
+        }
+    }
+
+    let security = &config.security;
+    match &security.judge_mode {
+        Some(_) if !security.judge_enabled => report(
+            Severity::Warning,
+            "security",
+            "judge_mode",
+            "security.judge_mode has no effect while security.judge_enabled is false".to_string(),
+        ),
+        Some(JudgeMode::Warn) if security.max_agent_depth > 0 => report(
+            Severity::Error,
+            "security",
+            "judge_mode",
+            format!(
+                "security.judge_mode = \"warn\" also applies to subagents (depth 1 to {}), which otherwise block; remove it or set security.max_agent_depth = 0",
+                security.max_agent_depth
+            ),
+        ),
+        _ => {}
+    }
+    issues
+}
+
+/// 1-based line containing byte `offset` of `contents`.
+fn line_at(contents: &str, offset: usize) -> usize {
+    contents[..offset.min(contents.len())].matches('\n').count() + 1
+}
+
+/// 1-based line where `key` is assigned, or where `value` appears as a
+/// string if given (array entries may sit on lines of their own).
+fn find_line(contents: &str, key: &str, value: Option<&str>) -> Option<usize> {
+    let lines: Vec<&str> = contents.lines().collect();
+    if let Some(value) = value {
+        let quoted = [format!("\"{value}\""), format!("'{value}'")];
+        if let Some(i) = lines
+            .iter()
+            .position(|l| quoted.iter().any(|q| l.contains(q.as_str())))
+        {
+            return Some(i + 1);
+        }
+    }
+    lines
+        .iter()
+        .position(|l| {
+            l.trim_start()
+                .strip_prefix(key)
+                .is_some_and(|rest| rest.trim_start().starts_with('='))
+        })
+        .map(|i| i + 1)
 }
 
 #[cfg(test)]
@@ -393,6 +1344,126 @@ include_env = ["PATH", "HOME"]
         assert!(!cmd.is_empty());
     }
 
+    #[test]
+    fn create_backend_unknown_name_errors() {
+        let cfg = BackendConfig {
+            default: "nonexistent".to_string(),
+            ..Default::default()
+        };
+        let err = cfg.create_backend().err().expect("should fail");
+        assert!(err.to_string().contains("unknown backend 'nonexistent'"));
+    }
+
+    #[test]
+    fn create_backend_mock_from_script() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("script.json");
+        std::fs::write(&path, r#"{"turns": [[{"type": "text", "content": "hi"}]]}"#).unwrap();
+
+        let toml_str = format!(
+            "[backend]\ndefault = \"mock\"\n\n[backend.mock]\nscript = \"{}\"\n",
+            path.display()
+        );
+        let cfg: Config = toml::from_str(&toml_str).unwrap();
+        assert_eq!(cfg.backend.mock.script.as_deref(), Some(path.as_path()));
+        assert!(cfg.backend.create_backend().is_ok());
+    }
+
+    #[test]
+    fn create_backend_mock_bad_script_errors() {
+        let cfg = BackendConfig {
+            default: "mock".to_string(),
+            mock: MockBackendConfig {
+                script: Some(PathBuf::from("/nonexistent/script.json")),
+            },
+            ..Default::default()
+        };
+        assert!(cfg.create_backend().is_err());
+    }
+
+    #[test]
+    fn parse_openai_config() {
+        let toml_str = r#"
+[backend]
+default = "openai"
+
+[backend.openai]
+model = "qwen2.5-coder"
+base_url = "http://localhost:8080/v1"
+"#;
+        let cfg: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(cfg.backend.default, "openai");
+        assert_eq!(cfg.backend.openai.model, "qwen2.5-coder");
+        assert_eq!(cfg.backend.openai.base_url, "http://localhost:8080/v1");
+        assert!(cfg.backend.openai.api_key_cmd.is_none());
+    }
+
+    #[test]
+    fn openai_default_config() {
+        let cfg = OpenAiConfig::default();
+        assert_eq!(cfg.model, "gpt-4o");
+        assert_eq!(cfg.base_url, "https://api.openai.com/v1");
+    }
+
+    #[test]
+    fn openai_api_key_from_cmd() {
+        let cfg = OpenAiConfig {
+            api_key_cmd: Some("echo sk-local".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(cfg.resolve_api_key().unwrap(), "sk-local");
+    }
+
+    #[test]
+    fn openai_local_base_url_allows_missing_key() {
+        let cfg = OpenAiConfig {
+            api_key_cmd: Some("exit 1".to_string()),
+            base_url: "http://localhost:11434/v1".to_string(),
+            ..Default::default()
+        };
+        // Either the env var or the empty-key fallback — never an error.
+        assert!(cfg.resolve_api_key().is_ok());
+    }
+
+    #[test]
+    fn create_backend_openai_local() {
+        let cfg = BackendConfig {
+            default: "openai".to_string(),
+            openai: OpenAiConfig {
+                base_url: "http://localhost:8080/v1".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(cfg.create_backend().is_ok());
+    }
+
+    #[test]
+    fn hooks_config_defaults() {
+        let cfg = HooksConfig::default();
+        assert!(cfg.enabled);
+        assert!(cfg.dir.is_none());
+        assert_eq!(cfg.timeout_secs, 5);
+        assert!(cfg.resolve_dir().ends_with("unixagent/hooks"));
+    }
+
+    #[test]
+    fn parse_hooks_config() {
+        let toml_str = r#"
+[hooks]
+enabled = false
+dir = "/etc/unixagent/hooks"
+timeout_secs = 2
+"#;
+        let cfg: Config = toml::from_str(toml_str).unwrap();
+        assert!(!cfg.hooks.enabled);
+        assert_eq!(cfg.hooks.timeout_secs, 2);
+        assert_eq!(
+            cfg.hooks.resolve_dir(),
+            PathBuf::from("/etc/unixagent/hooks")
+        );
+    }
+
     #[test]
     fn anthropic_default_model() {
         let cfg = AnthropicConfig::default();
@@ -567,7 +1638,7 @@ denied_paths = ["$HOME/.ssh"]
     #[test]
     fn sandbox_config_to_policy() {
         let cfg = SandboxConfig::default();
-        let policy = cfg.to_policy();
+        let policy = cfg.to_policy(&HooksConfig::default());
         // Policy should have CWD resolved
         let cwd = std::env::current_dir().unwrap();
         assert!(policy.writable.contains(&cwd));
@@ -671,6 +1742,161 @@ judge_mode = "block"
         assert!(!cfg.judge_enabled);
     }
 
+    #[test]
+    fn sandbox_policy_write_protects_config_file() {
+        let policy = SandboxConfig::default().to_policy(&HooksConfig::default());
+        assert!(policy.deny_write.contains(&config_path()));
+        // Still not denied outright — subagents need to read it
+        assert!(!policy.denied.contains(&config_path()));
+    }
+
+    #[test]
+    fn sandbox_policy_write_protects_hooks_dir() {
+        let hooks = HooksConfig::default();
+        let policy = SandboxConfig::default().to_policy(&hooks);
+        assert!(policy.deny_write.contains(&hooks.resolve_dir()));
+
+        let hooks = HooksConfig {
+            dir: Some("/srv/hooks".to_string()),
+            ..HooksConfig::default()
+        };
+        let policy = SandboxConfig::default().to_policy(&hooks);
+        assert!(policy.deny_write.contains(&PathBuf::from("/srv/hooks")));
+    }
+
+    #[test]
+    fn parse_sandbox_network_config() {
+        let toml_str = r#"
+[sandbox.network]
+mode = "ports"
+allow_ports = [443, 22]
+"#;
+        let cfg: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(cfg.sandbox.network.mode, ua_sandbox::NetworkMode::Ports);
+        assert_eq!(cfg.sandbox.network.allow_ports, vec![443, 22]);
+        // Other sandbox settings keep their defaults
+        assert!(cfg.sandbox.enabled);
+
+        let policy = cfg.sandbox.to_policy(&cfg.hooks);
+        assert_eq!(policy.network, cfg.sandbox.network);
+    }
+
+    #[test]
+    fn parse_sandbox_seccomp_profile() {
+        let cfg: Config = toml::from_str("[sandbox]\nseccomp = \"strict\"\n").unwrap();
+        assert_eq!(cfg.sandbox.seccomp, ua_sandbox::SeccompProfile::Strict);
+        assert_eq!(
+            cfg.sandbox.to_policy(&cfg.hooks).seccomp,
+            ua_sandbox::SeccompProfile::Strict
+        );
+        assert_eq!(
+            SandboxConfig::default().seccomp,
+            ua_sandbox::SeccompProfile::Default
+        );
+    }
+
+    #[test]
+    fn parse_sandbox_repl_opt_in() {
+        assert!(!SandboxConfig::default().repl);
+        let cfg: Config = toml::from_str("[sandbox]\nrepl = true\n").unwrap();
+        assert!(cfg.sandbox.repl);
+    }
+
+    #[test]
+    fn parse_sandbox_limits() {
+        let cfg: Config =
+            toml::from_str("[sandbox.limits]\ncpu_secs = 60\nmemory_mb = 2048\n").unwrap();
+        let limits = cfg.sandbox.to_policy(&cfg.hooks).limits;
+        assert_eq!(limits.cpu_secs, 60);
+        assert_eq!(limits.memory_mb, 2048);
+        assert_eq!(limits.max_processes, 0);
+        assert_eq!(limits.timeout_secs, 300);
+    }
+
+    #[test]
+    fn parse_sandbox_debug() {
+        assert!(
+            !Config::default()
+                .sandbox
+                .to_policy(&HooksConfig::default())
+                .debug
+        );
+        let cfg: Config = toml::from_str("[sandbox]\ndebug = true\n").unwrap();
+        assert!(cfg.sandbox.to_policy(&cfg.hooks).debug);
+    }
+
+    #[test]
+    fn parse_sandbox_backend() {
+        let cfg: Config = toml::from_str("[sandbox]\nbackend = \"namespace\"\n").unwrap();
+        assert_eq!(
+            cfg.sandbox.to_policy(&cfg.hooks).backend,
+            ua_sandbox::SandboxBackend::Namespace
+        );
+        assert_eq!(
+            SandboxConfig::default().backend,
+            ua_sandbox::SandboxBackend::Landlock
+        );
+    }
+
+    #[test]
+    fn sandbox_network_defaults_to_allow() {
+        let cfg: Config = toml::from_str("[sandbox]\nenabled = true\n").unwrap();
+        assert!(!cfg.sandbox.network.is_restricted());
+    }
+
+    #[test]
+    fn parse_policy_config() {
+        use crate::policy::analyze_pipe_chain_with;
+
+        let toml_str = r#"
+[policy]
+read_only = ["kubectl get", "kubectl describe"]
+destructive = ["kubectl delete"]
+network = ["kubectl"]
+deny_regex = ["--context[= ]prod"]
+deny_glob = ["terraform destroy*"]
+"#;
+        let cfg: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(
+            cfg.policy.read_only,
+            vec!["kubectl get", "kubectl describe"]
+        );
+        assert!(cfg.policy.write.is_empty());
+
+        let rules = cfg.policy.to_rules().unwrap();
+        assert_eq!(
+            analyze_pipe_chain_with("kubectl get pods", &rules),
+            RiskLevel::ReadOnly
+        );
+        assert_eq!(
+            analyze_pipe_chain_with("kubectl delete pod x", &rules),
+            RiskLevel::Destructive
+        );
+        assert_eq!(
+            analyze_pipe_chain_with("kubectl --context prod get pods", &rules),
+            RiskLevel::Denied
+        );
+        assert_eq!(
+            analyze_pipe_chain_with("terraform destroy", &rules),
+            RiskLevel::Denied
+        );
+    }
+
+    #[test]
+    fn policy_config_invalid_regex_errors() {
+        let cfg = PolicyConfig {
+            deny_regex: vec!["(".to_string()],
+            ..Default::default()
+        };
+        let err = cfg.to_rules().unwrap_err();
+        assert!(err.contains("invalid deny regex"), "{err}");
+    }
+
+    #[test]
+    fn policy_config_default_is_empty() {
+        assert!(PolicyConfig::default().to_rules().unwrap().is_empty());
+    }
+
     #[test]
     fn sandbox_default_denied_paths_no_config_dir() {
         let cfg = SandboxConfig::default();
@@ -679,4 +1905,311 @@ judge_mode = "block"
             .contains(&"$HOME/.config/unixagent".to_string()));
         assert!(cfg.denied_paths.contains(&"$HOME/.ssh".to_string()));
     }
+
+    fn layer(kind: LayerKind, path: &str, contents: &str) -> ConfigLayer {
+        ConfigLayer::parse(kind, Path::new(path), contents).unwrap()
+    }
+
+    #[test]
+    fn later_layers_override_field_by_field() {
+        let cfg = Config::from_layers(&[
+            layer(
+                LayerKind::System,
+                "/etc/unixagent/config.toml",
+                "[shell]\ncommand = \"/bin/bash\"\nintegration = false\n",
+            ),
+            layer(
+                LayerKind::User,
+                "/home/u/.config/unixagent/config.toml",
+                "[shell]\ncommand = \"/bin/zsh\"\n",
+            ),
+        ])
+        .unwrap();
+        assert_eq!(cfg.shell.command.as_deref(), Some("/bin/zsh"));
+        assert!(!cfg.shell.integration);
+        assert_eq!(cfg.context.max_terminal_lines, 200);
+    }
+
+    #[test]
+    fn extra_paths_and_system_prompt_accumulate() {
+        let cfg = Config::from_layers(&[
+            layer(
+                LayerKind::User,
+                "/home/u/.config/unixagent/config.toml",
+                "[sandbox]\nextra_readable_paths = [\"/opt/go\"]\n\
+                 [context]\nsystem_prompt = \"Be brief.\"\n",
+            ),
+            layer(
+                LayerKind::Project,
+                "/src/app/.unixagent.toml",
+                "[sandbox]\nextra_readable_paths = [\"$HOME/.rustup\"]\n\
+                 extra_writable_paths = [\"target\"]\n\
+                 [context]\nsystem_prompt = \"Use cargo.\"\n",
+            ),
+        ])
+        .unwrap();
+        assert_eq!(
+            cfg.sandbox.extra_readable_paths,
+            vec!["/opt/go", "$HOME/.rustup"]
+        );
+        assert_eq!(cfg.sandbox.extra_writable_paths, vec!["/src/app/target"]);
+        assert_eq!(
+            cfg.context.system_prompt.as_deref(),
+            Some("Be brief.\n\nUse cargo.")
+        );
+        // Extras add to the defaults instead of replacing them.
+        let policy = cfg.sandbox.to_policy(&cfg.hooks);
+        assert!(policy.readable.contains(&PathBuf::from("/usr")));
+        assert!(policy.readable.contains(&PathBuf::from("/opt/go")));
+        assert!(policy.writable.contains(&PathBuf::from("/src/app/target")));
+    }
+
+    #[test]
+    fn user_layer_keeps_relative_paths() {
+        let l = layer(
+            LayerKind::User,
+            "/home/u/.config/unixagent/config.toml",
+            "[sandbox]\nwritable_paths = [\"build\"]\n",
+        );
+        let cfg = Config::from_layers(&[l]).unwrap();
+        assert_eq!(cfg.sandbox.writable_paths, vec!["build"]);
+    }
+
+    #[test]
+    fn invalid_layer_value_is_an_error() {
+        let l = layer(
+            LayerKind::User,
+            "/home/u/.config/unixagent/config.toml",
+            "[shell]\nintegration = \"yes\"\n",
+        );
+        assert!(Config::from_layers(&[l]).is_err());
+    }
+
+    #[test]
+    fn discover_layers_lists_projects_outermost_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let inner = root.join("pkg").join("src");
+        std::fs::create_dir_all(&inner).unwrap();
+        std::fs::write(root.join(PROJECT_CONFIG_NAME), "").unwrap();
+        std::fs::write(root.join("pkg").join(PROJECT_CONFIG_NAME), "").unwrap();
+
+        let projects: Vec<PathBuf> = discover_layers(&inner)
+            .into_iter()
+            .filter(|(kind, _)| *kind == LayerKind::Project)
+            .map(|(_, path)| path)
+            .collect();
+        assert_eq!(
+            projects,
+            vec![
+                root.join(PROJECT_CONFIG_NAME),
+                root.join("pkg").join(PROJECT_CONFIG_NAME)
+            ]
+        );
+    }
+
+    #[test]
+    fn check_layer_reports_unknown_keys_with_line() {
+        let contents = "[security]\njudge_enabled = true\njudge_mod = \"block\"\n";
+        let issues = check_layer(LayerKind::User, Path::new("/u/config.toml"), contents);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, Some(3));
+        assert!(issues[0]
+            .to_string()
+            .starts_with("/u/config.toml:3: error: unknown field `judge_mod`"));
+    }
+
+    #[test]
+    fn check_layer_rejects_paths_that_do_not_resolve() {
+        let contents = "[sandbox]\nwritable_paths = [\n  \"$CWD\",\n  \"build\",\n  \"~/tmp\",\n  \"$CWD/out\",\n]\n\n[journal]\nsessions_dir = \"sessions\"\n";
+        let path = Path::new("/u/config.toml");
+        let lines: Vec<Option<usize>> = check_layer(LayerKind::User, path, contents)
+            .iter()
+            .map(|i| i.line)
+            .collect();
+        assert_eq!(lines, vec![Some(4), Some(5), Some(6), Some(10)]);
+
+        // A project file may use paths relative to its directory.
+        let issues = check_layer(LayerKind::Project, path, contents);
+        assert_eq!(issues.len(), 3);
+        assert!(issues[0].message.contains("`~` is not expanded"));
+    }
+
+    #[test]
+    fn load_layers_skips_invalid_files_and_reports_conflicts() {
+        let files = vec![
+            (
+                LayerKind::User,
+                PathBuf::from("/u/config.toml"),
+                "[security]\njudge_enabled = true\njudge_mode = \"warn\"\n".to_string(),
+            ),
+            (
+                LayerKind::Project,
+                PathBuf::from("/p/.unixagent.toml"),
+                "[shell]\nintegration = \"no\"\n".to_string(),
+            ),
+        ];
+        let loaded = load_layers(files, &[]);
+        assert!(loaded.has_errors());
+        assert_eq!(loaded.layers.len(), 1);
+        assert!(loaded.config.shell.integration);
+        assert_eq!(loaded.config.security.judge_mode, Some(JudgeMode::Warn));
+
+        let paths: Vec<_> = loaded.issues.iter().map(|i| i.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Some(PathBuf::from("/p/.unixagent.toml")),
+                Some(PathBuf::from("/u/config.toml")),
+            ]
+        );
+        assert!(loaded.issues[1]
+            .message
+            .contains("also applies to subagents"));
+    }
+
+    #[test]
+    fn judge_mode_without_judge_is_a_warning() {
+        let files = vec![(
+            LayerKind::User,
+            PathBuf::from("/u/config.toml"),
+            "[security]\njudge_mode = \"block\"\n".to_string(),
+        )];
+        let loaded = load_layers(files, &[]);
+        assert!(!loaded.has_errors());
+        assert_eq!(loaded.issues.len(), 1);
+        assert_eq!(loaded.issues[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn invalid_prompt_pattern_is_an_error() {
+        let files = vec![(
+            LayerKind::User,
+            PathBuf::from("/u/config.toml"),
+            "[shell]\nprompt_patterns = ['^\\$ $', '(unclosed']\n".to_string(),
+        )];
+        let loaded = load_layers(files, &[]);
+        assert!(loaded.has_errors());
+        assert_eq!(loaded.issues.len(), 1);
+        assert!(loaded.issues[0].message.contains("(unclosed"));
+        assert!(Config::default()
+            .shell
+            .prompt_patterns
+            .iter()
+            .all(|p| regex::Regex::new(p).is_ok()));
+    }
+
+    #[test]
+    fn override_from_set_and_env_name() {
+        let o = Override::from_set("backend.anthropic.model=claude-x").unwrap();
+        assert_eq!(o.key, "backend.anthropic.model");
+        assert_eq!(o.env_name(), "UNIXAGENT_BACKEND__ANTHROPIC__MODEL");
+        assert_eq!(o.source(), "--set backend.anthropic.model");
+        assert!(Override::from_set("judge_enabled").is_err());
+        assert!(Override::from_set("security..judge_enabled=true").is_err());
+    }
+
+    #[test]
+    fn subagent_guarded_overrides_must_match_the_parent() {
+        let env = |name: &str, value: &str| Override {
+            kind: LayerKind::Environment,
+            key: name.to_string(),
+            value: value.to_string(),
+        };
+        let exported = exported_overrides(
+            &["unixagent", "--set", "policy.deny=[\"curl\"]"].map(String::from),
+            &[
+                "HOME=/home/me",
+                "UNIXAGENT_SECURITY__JUDGE_ENABLED=true",
+                "UNIXAGENT_JOURNAL=/tmp/j.jsonl",
+            ]
+            .map(String::from),
+        );
+        assert_eq!(exported.len(), 2);
+
+        let (kept, ignored) = guard_overrides(
+            vec![
+                env("security.judge_enabled", "true"),
+                env("policy.deny", "[\"curl\"]"),
+                env("hooks.enabled", "false"),
+                env("sandbox.enabled", "false"),
+                env("backend.anthropic.model", "claude-opus-4-20250514"),
+            ],
+            &exported,
+        );
+        let keys: Vec<&str> = kept.iter().map(|o| o.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "security.judge_enabled",
+                "policy.deny",
+                "backend.anthropic.model"
+            ]
+        );
+        assert_eq!(ignored.len(), 2);
+        assert!(ignored.iter().all(|i| i.severity == Severity::Warning));
+        assert_eq!(
+            ignored[0].path.as_deref(),
+            Some(Path::new("UNIXAGENT_HOOKS__ENABLED"))
+        );
+
+        // A changed value is not what the parent passed.
+        let (kept, _) = guard_overrides(vec![env("security.judge_enabled", "false")], &exported);
+        assert!(kept.is_empty());
+    }
+
+    #[test]
+    fn env_overrides_skip_other_unixagent_vars() {
+        let vars = [
+            ("UNIXAGENT_SECURITY__JUDGE_ENABLED", "true"),
+            ("UNIXAGENT_JOURNAL", "/tmp/j.jsonl"),
+            ("UNIXAGENT_COMPUTER_USE", "macos"),
+            (
+                "UNIXAGENT_BACKEND__OPENAI__BASE_URL",
+                "http://localhost:8080/v1",
+            ),
+            ("HOME", "/home/u"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+        let keys: Vec<String> = overrides_from_vars(vars.into_iter())
+            .into_iter()
+            .map(|o| o.key)
+            .collect();
+        assert_eq!(
+            keys,
+            vec!["backend.openai.base_url", "security.judge_enabled"]
+        );
+    }
+
+    #[test]
+    fn overrides_apply_after_files_in_order() {
+        let files = vec![(
+            LayerKind::User,
+            PathBuf::from("/u/config.toml"),
+            "[backend.anthropic]\nmodel = \"from-file\"\n[sandbox]\nextra_readable_paths = [\"/opt/a\"]\n".to_string(),
+        )];
+        let env = |key: &str, value: &str| Override {
+            kind: LayerKind::Environment,
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        let overrides = vec![
+            env("backend.anthropic.model", "from-env"),
+            env("security.judge_enabled", "true"),
+            env("sandbox.extra_readable_paths", "[\"/srv\"]"),
+            env("context.max_terminal_lines", "lots"),
+            Override::from_set("backend.anthropic.model=from-flag").unwrap(),
+        ];
+        let loaded = load_layers(files, &overrides);
+        let config = &loaded.config;
+        assert_eq!(config.backend.anthropic.model, "from-flag");
+        assert!(config.security.judge_enabled);
+        assert_eq!(config.sandbox.extra_readable_paths, vec!["/opt/a", "/srv"]);
+        assert_eq!(config.context.max_terminal_lines, 200);
+
+        assert_eq!(loaded.issues.len(), 1);
+        assert!(loaded.issues[0]
+            .to_string()
+            .starts_with("UNIXAGENT_CONTEXT__MAX_TERMINAL_LINES: error: invalid type"));
+    }
 }
@@ -8,6 +8,7 @@ use std::collections::VecDeque;
 use ua_protocol::{AgentRequest, ConversationMessage, ShellContext, TerminalHistory};
 
 use crate::config::{Config, ContextConfig};
+use crate::osc::ShellReports;
 use crate::process::cwd_of_pid;
 
 /// Ring buffer for terminal output history.
@@ -119,6 +120,12 @@ impl OutputHistory {
         self.lines.iter().cloned().collect()
     }
 
+    /// The line being written, not yet ended by a newline — where the
+    /// cursor sits after a prompt.
+    pub fn current_line(&self) -> &str {
+        &self.current_line
+    }
+
     /// Approximate token count (chars / 4).
     pub fn approx_tokens(&self) -> usize {
         let total_chars: usize = self.lines.iter().map(|l| l.len()).sum();
@@ -141,19 +148,18 @@ impl OutputHistory {
 
 /// Build a ShellContext from the current environment.
 ///
-/// If `child_pid` is provided, resolves the CWD from the child process
-/// (the PTY shell) instead of the parent process. This ensures the system
-/// prompt shows the correct directory after the user runs `cd`.
+/// The CWD is the one the shell last reported (OSC 7), if any; otherwise,
+/// if `child_pid` is provided, it is resolved from the child process (the
+/// PTY shell) instead of the parent process. This ensures the system
+/// prompt shows the correct directory after the user runs `cd`. Shells
+/// nested with `#!integrate` come from `reports` too.
 pub fn build_shell_context(
     config: &Config,
     terminal_size: (u16, u16),
     child_pid: Option<u32>,
+    reports: Option<&ShellReports>,
 ) -> ShellContext {
-    let cwd = child_pid.and_then(cwd_of_pid).unwrap_or_else(|| {
-        std::env::current_dir()
-            .map(|p| p.to_string_lossy().to_string())
-            .unwrap_or_else(|_| ".".to_string())
-    });
+    let cwd = shell_cwd(child_pid, reports.and_then(|r| r.cwd.as_deref()));
 
     let shell = config.shell_command();
 
@@ -166,9 +172,23 @@ pub fn build_shell_context(
         arch: std::env::consts::ARCH.to_string(),
         env_vars,
         terminal_size,
+        levels: reports.map(ShellReports::nested_levels).unwrap_or_default(),
     }
 }
 
+/// Working directory of the shell: what it reported, else what the OS says
+/// about its process, else our own.
+pub fn shell_cwd(child_pid: Option<u32>, reported_cwd: Option<&str>) -> String {
+    reported_cwd
+        .map(str::to_string)
+        .or_else(|| child_pid.and_then(cwd_of_pid))
+        .unwrap_or_else(|| {
+            std::env::current_dir()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_else(|_| ".".to_string())
+        })
+}
+
 /// Collect filtered environment variables based on config.
 fn collect_env_vars(config: &ContextConfig) -> Vec<(String, String)> {
     let sensitive_suffixes = ["_KEY", "_SECRET", "_TOKEN", "_PASSWORD", "_CREDENTIALS"];
@@ -398,23 +418,27 @@ pub fn build_agent_request(
     conversation: Vec<ConversationMessage>,
     terminal_size: (u16, u16),
     child_pid: Option<u32>,
+    reports: Option<&ShellReports>,
 ) -> AgentRequest {
-    let context = build_shell_context(config, terminal_size, child_pid);
+    let context = build_shell_context(config, terminal_size, child_pid, reports);
     let terminal_history = TerminalHistory::from_lines(history.lines());
 
     // REPL is always depth 0 — add agent capabilities (journal docs + delegation)
-    let system_prompt_extra = Some(build_agent_capabilities_prompt(
-        0,
-        config.security.max_agent_depth,
-    ));
+    let mut system_prompt_extra =
+        build_agent_capabilities_prompt(0, config.security.max_agent_depth);
+    if let Some(extra) = &config.context.system_prompt {
+        system_prompt_extra.push_str("\n\n");
+        system_prompt_extra.push_str(extra);
+    }
 
     AgentRequest {
         instruction: instruction.to_string(),
         context,
         terminal_history,
         conversation,
-        system_prompt_extra,
+        system_prompt_extra: Some(system_prompt_extra),
         attachments: Vec::new(),
+        screen: None,
     }
 }
 
@@ -587,6 +611,7 @@ mod tests {
                 "HOME".to_string(),
                 "API_KEY".to_string(), // Should be filtered
             ],
+            system_prompt: None,
         };
 
         let vars = collect_env_vars(&config);
@@ -598,13 +623,46 @@ mod tests {
     #[test]
     fn build_shell_context_basic() {
         let config = Config::default();
-        let ctx = build_shell_context(&config, (80, 24), None);
+        let ctx = build_shell_context(&config, (80, 24), None, None);
 
         assert!(!ctx.cwd.is_empty());
         assert!(!ctx.shell.is_empty());
         assert!(!ctx.platform.is_empty());
         assert!(!ctx.arch.is_empty());
         assert_eq!(ctx.terminal_size, (80, 24));
+        assert!(ctx.levels.is_empty());
+    }
+
+    #[test]
+    fn build_shell_context_lists_nested_shells() {
+        use crate::osc::OscEvent;
+
+        let config = Config::default();
+        let mut reports = ShellReports::new("bash");
+        for (depth, host, path) in [("0", "laptop", "/home/me"), ("1", "web-1", "/srv")] {
+            reports.observe(&OscEvent::Osc133D { exit_code: Some(0) });
+            reports.observe(&OscEvent::Osc633P {
+                key: "UaDepth".to_string(),
+                value: depth.to_string(),
+            });
+            reports.observe(&OscEvent::Osc7 {
+                host: host.to_string(),
+                path: path.to_string(),
+            });
+        }
+        let ctx = build_shell_context(&config, (80, 24), None, Some(&reports));
+        assert_eq!(ctx.cwd, "/srv");
+        assert_eq!(ctx.levels.len(), 2);
+        assert_eq!(ctx.levels[1].host, "web-1");
+    }
+
+    #[test]
+    fn shell_cwd_prefers_the_shells_own_report() {
+        // Over SSH the shell's process is remote; only its report is right.
+        let pid = Some(std::process::id());
+        assert_eq!(shell_cwd(pid, Some("/srv/remote")), "/srv/remote");
+        let own = std::env::current_dir().unwrap();
+        assert_eq!(shell_cwd(pid, None), own.to_string_lossy());
     }
 
     // --- scrub_injection_markers tests ---
@@ -708,12 +766,25 @@ mod tests {
             vec![],
             (80, 24),
             None,
+            None,
         );
 
         assert_eq!(request.instruction, "what files are here");
         assert_eq!(request.terminal_history.lines, vec!["$ ls", "file.txt"]);
     }
 
+    #[test]
+    fn build_agent_request_appends_configured_system_prompt() {
+        let mut config = Config::default();
+        config.context.system_prompt = Some("Use pnpm, not npm.".to_string());
+        let history = OutputHistory::new(100);
+        let request = build_agent_request("build", &config, &history, vec![], (80, 24), None, None);
+        assert!(request
+            .system_prompt_extra
+            .unwrap()
+            .ends_with("\n\nUse pnpm, not npm."));
+    }
+
     // --- Agent capabilities prompt tests ---
 
     #[test]
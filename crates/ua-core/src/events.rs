@@ -0,0 +1,293 @@
+//! Machine-readable JSONL event stream for batch mode (`--json`).
+//!
+//! One JSON object per line on stdout. Every event has a `type` field; the
+//! schema below is stable — fields may be added, never renamed or removed.
+//!
+//! | `type`     | Fields |
+//! |------------|--------|
+//! | `start`    | `version` (schema version, currently 1), `instruction`, `depth` |
+//! | `thinking` | `iteration` |
+//! | `proposed` | `iteration`, `tool_use_id`, `command`, `risk` |
+//! | `judge`    | `iteration`, `verdict` (`safe`/`unsafe`/`error`), `reasoning`, `blocked` |
+//! | `denied`   | `iteration`, `tool_use_id`, `command`, `risk`, `reason` |
+//! | `result`   | `iteration`, `tool_use_id`, `command`, `exit_code` (null if killed or not run), `duration_ms`, `output`, `truncated` |
+//! | `answer`   | `text` |
+//! | `error`    | `message` |
+//! | `summary`  | `exit_code`, `input_tokens`, `output_tokens`, `commands_run`, `commands_denied`, `elapsed_secs` |
+//!
+//! `risk` uses the audit log names (`read_only`, `build_test`, `write`,
+//! `destructive`, `network`, `privileged`, `denied`). `output` is the
+//! command's stdout followed by `STDERR:\n...` if any, cut at
+//! [`MAX_EVENT_OUTPUT_BYTES`]. `summary` is always the last event.
+
+use std::io::Write;
+
+use serde::Serialize;
+
+/// Version of the event schema, reported in the `start` event.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Maximum bytes of command output included in a `result` event.
+pub const MAX_EVENT_OUTPUT_BYTES: usize = 4096;
+
+/// A single batch-mode event.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BatchEvent<'a> {
+    Start {
+        version: u32,
+        instruction: &'a str,
+        depth: u32,
+    },
+    Thinking {
+        iteration: usize,
+    },
+    Proposed {
+        iteration: usize,
+        tool_use_id: &'a str,
+        command: &'a str,
+        risk: &'a str,
+    },
+    Judge {
+        iteration: usize,
+        verdict: &'a str,
+        reasoning: &'a str,
+        blocked: bool,
+    },
+    Denied {
+        iteration: usize,
+        tool_use_id: &'a str,
+        command: &'a str,
+        risk: &'a str,
+        reason: &'a str,
+    },
+    Result {
+        iteration: usize,
+        tool_use_id: &'a str,
+        command: &'a str,
+        exit_code: Option<i32>,
+        duration_ms: u64,
+        output: &'a str,
+        truncated: bool,
+    },
+    Answer {
+        text: &'a str,
+    },
+    Error {
+        message: &'a str,
+    },
+    Summary {
+        exit_code: i32,
+        input_tokens: u32,
+        output_tokens: u32,
+        commands_run: u32,
+        commands_denied: u32,
+        elapsed_secs: f64,
+    },
+}
+
+/// Writes [`BatchEvent`]s as JSONL. A no-op writer discards everything, so
+/// call sites don't need to check whether `--json` is active.
+pub struct EventWriter {
+    writer: Option<Box<dyn Write + Send>>,
+}
+
+impl EventWriter {
+    /// Create an event writer that writes to `writer` (usually stdout).
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Some(Box::new(writer)),
+        }
+    }
+
+    /// Create a no-op writer that discards all events.
+    pub fn noop() -> Self {
+        Self { writer: None }
+    }
+
+    /// Whether events are being written.
+    pub fn is_enabled(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    /// Serialize and write one event, flushing so consumers see it immediately.
+    pub fn emit(&mut self, event: &BatchEvent<'_>) {
+        if let Some(ref mut w) = self.writer {
+            if let Ok(line) = serde_json::to_string(event) {
+                let _ = writeln!(w, "{line}");
+                let _ = w.flush();
+            }
+        }
+    }
+
+    /// Emit a `result` event, truncating `output` to [`MAX_EVENT_OUTPUT_BYTES`].
+    pub fn emit_result(
+        &mut self,
+        iteration: usize,
+        tool_use_id: &str,
+        command: &str,
+        exit_code: Option<i32>,
+        duration_ms: u64,
+        output: &str,
+    ) {
+        let (output, truncated) = truncate_at_char_boundary(output, MAX_EVENT_OUTPUT_BYTES);
+        self.emit(&BatchEvent::Result {
+            iteration,
+            tool_use_id,
+            command,
+            exit_code,
+            duration_ms,
+            output,
+            truncated,
+        });
+    }
+}
+
+/// Cut `s` to at most `max` bytes without splitting a UTF-8 character.
+fn truncate_at_char_boundary(s: &str, max: usize) -> (&str, bool) {
+    if s.len() <= max {
+        return (s, false);
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    (&s[..end], true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Shared buffer so tests can read back what the writer produced.
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn lines(buf: &SharedBuf) -> Vec<serde_json::Value> {
+        String::from_utf8(buf.0.lock().unwrap().clone())
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn start_event_schema() {
+        let buf = SharedBuf::default();
+        let mut w = EventWriter::new(buf.clone());
+        w.emit(&BatchEvent::Start {
+            version: SCHEMA_VERSION,
+            instruction: "list files",
+            depth: 0,
+        });
+        let events = lines(&buf);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["type"], "start");
+        assert_eq!(events[0]["version"], 1);
+        assert_eq!(events[0]["instruction"], "list files");
+        assert_eq!(events[0]["depth"], 0);
+    }
+
+    #[test]
+    fn one_line_per_event() {
+        let buf = SharedBuf::default();
+        let mut w = EventWriter::new(buf.clone());
+        w.emit(&BatchEvent::Thinking { iteration: 0 });
+        w.emit(&BatchEvent::Answer {
+            text: "multi\nline",
+        });
+        let events = lines(&buf);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["type"], "thinking");
+        assert_eq!(events[1]["type"], "answer");
+        assert_eq!(events[1]["text"], "multi\nline");
+    }
+
+    #[test]
+    fn judge_and_denied_events() {
+        let buf = SharedBuf::default();
+        let mut w = EventWriter::new(buf.clone());
+        w.emit(&BatchEvent::Judge {
+            iteration: 2,
+            verdict: "unsafe",
+            reasoning: "exfiltration",
+            blocked: true,
+        });
+        w.emit(&BatchEvent::Denied {
+            iteration: 2,
+            tool_use_id: "t1",
+            command: "rm -rf /",
+            risk: "denied",
+            reason: "denied by policy",
+        });
+        let events = lines(&buf);
+        assert_eq!(events[0]["verdict"], "unsafe");
+        assert_eq!(events[0]["blocked"], true);
+        assert_eq!(events[1]["type"], "denied");
+        assert_eq!(events[1]["risk"], "denied");
+    }
+
+    #[test]
+    fn result_event_truncates_output() {
+        let buf = SharedBuf::default();
+        let mut w = EventWriter::new(buf.clone());
+        let long = "x".repeat(MAX_EVENT_OUTPUT_BYTES + 10);
+        w.emit_result(0, "t1", "cat big", Some(0), 12, &long);
+        w.emit_result(0, "t2", "true", None, 1, "short");
+        let events = lines(&buf);
+        assert_eq!(events[0]["type"], "result");
+        assert_eq!(events[0]["truncated"], true);
+        assert_eq!(
+            events[0]["output"].as_str().unwrap().len(),
+            MAX_EVENT_OUTPUT_BYTES
+        );
+        assert_eq!(events[0]["exit_code"], 0);
+        assert_eq!(events[0]["duration_ms"], 12);
+        assert_eq!(events[1]["truncated"], false);
+        assert!(events[1]["exit_code"].is_null());
+    }
+
+    #[test]
+    fn truncate_respects_char_boundary() {
+        let s = "é".repeat(10); // 2 bytes each
+        let (cut, truncated) = truncate_at_char_boundary(&s, 5);
+        assert!(truncated);
+        assert_eq!(cut, "éé");
+    }
+
+    #[test]
+    fn summary_event_schema() {
+        let buf = SharedBuf::default();
+        let mut w = EventWriter::new(buf.clone());
+        w.emit(&BatchEvent::Summary {
+            exit_code: 0,
+            input_tokens: 100,
+            output_tokens: 20,
+            commands_run: 3,
+            commands_denied: 1,
+            elapsed_secs: 1.5,
+        });
+        let events = lines(&buf);
+        assert_eq!(events[0]["type"], "summary");
+        assert_eq!(events[0]["commands_run"], 3);
+        assert_eq!(events[0]["elapsed_secs"], 1.5);
+    }
+
+    #[test]
+    fn noop_writer_is_disabled() {
+        let mut w = EventWriter::noop();
+        assert!(!w.is_enabled());
+        w.emit(&BatchEvent::Thinking { iteration: 0 });
+    }
+}
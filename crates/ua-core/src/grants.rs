@@ -0,0 +1,240 @@
+//! Session grants: "always allow" answers remembered for one REPL session.
+//!
+//! When the user answers `[a] always` at the approval prompt, each approved
+//! command becomes a grant scoped to the shell's host and current directory. Later
+//! proposals that match a grant skip the judge and the prompt.
+//!
+//! Low-risk commands (read-only, build/test, write) with a subcommand are
+//! granted by prefix, so approving `cargo test -p foo` also covers
+//! `cargo test -p bar`. Everything else is granted as the exact command.
+//! Privileged and denied commands are never granted.
+
+use crate::policy::{subcommand_prefix, RiskLevel};
+
+/// What a grant matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GrantPattern {
+    /// The exact command string (trimmed).
+    Exact(String),
+    /// Any simple command with this binary and subcommand.
+    Prefix { binary: String, subcommand: String },
+}
+
+/// A remembered approval, valid only in the directory it was given in, on
+/// the host the shell reported then (empty if it didn't).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionGrant {
+    pub pattern: GrantPattern,
+    pub host: String,
+    pub cwd: String,
+}
+
+impl SessionGrant {
+    fn matches(&self, cmd: &str, risk: RiskLevel, host: &str, cwd: &str) -> bool {
+        if self.host != host || self.cwd != cwd {
+            return false;
+        }
+        match &self.pattern {
+            GrantPattern::Exact(exact) => exact == cmd.trim(),
+            GrantPattern::Prefix { binary, subcommand } => {
+                risk <= RiskLevel::Write
+                    && subcommand_prefix(cmd).is_some_and(|(b, s)| &b == binary && &s == subcommand)
+            }
+        }
+    }
+
+    /// Short description for display, e.g. `cargo test *`.
+    pub fn describe(&self) -> String {
+        match &self.pattern {
+            GrantPattern::Exact(exact) => exact.clone(),
+            GrantPattern::Prefix { binary, subcommand } => format!("{binary} {subcommand} *"),
+        }
+    }
+}
+
+/// Grants accumulated during a session.
+#[derive(Debug, Default)]
+pub struct SessionGrants {
+    grants: Vec<SessionGrant>,
+}
+
+impl SessionGrants {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.grants.is_empty()
+    }
+
+    /// Record a grant for `cmd` in `cwd` on `host`. Returns the new grant, or
+    /// `None` if the command can't be granted or is already covered.
+    pub fn grant(
+        &mut self,
+        cmd: &str,
+        risk: RiskLevel,
+        host: &str,
+        cwd: &str,
+    ) -> Option<SessionGrant> {
+        if risk >= RiskLevel::Privileged || self.covers(cmd, risk, host, cwd) {
+            return None;
+        }
+        let pattern = match subcommand_prefix(cmd) {
+            Some((binary, subcommand)) if risk <= RiskLevel::Write => {
+                GrantPattern::Prefix { binary, subcommand }
+            }
+            _ => GrantPattern::Exact(cmd.trim().to_string()),
+        };
+        let grant = SessionGrant {
+            pattern,
+            host: host.to_string(),
+            cwd: cwd.to_string(),
+        };
+        self.grants.push(grant.clone());
+        Some(grant)
+    }
+
+    /// Whether a grant covers `cmd` (classified as `risk`) in `cwd` on `host`.
+    pub fn covers(&self, cmd: &str, risk: RiskLevel, host: &str, cwd: &str) -> bool {
+        risk < RiskLevel::Privileged && self.grants.iter().any(|g| g.matches(cmd, risk, host, cwd))
+    }
+
+    /// Whether every command in a proposal is covered.
+    pub fn covers_all(
+        &self,
+        commands: &[String],
+        risk_levels: &[RiskLevel],
+        host: &str,
+        cwd: &str,
+    ) -> bool {
+        !commands.is_empty()
+            && commands
+                .iter()
+                .zip(risk_levels)
+                .all(|(cmd, risk)| self.covers(cmd, *risk, host, cwd))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::analyze_pipe_chain;
+
+    fn grant(grants: &mut SessionGrants, cmd: &str, cwd: &str) -> Option<SessionGrant> {
+        grants.grant(cmd, analyze_pipe_chain(cmd), "", cwd)
+    }
+
+    fn covers(grants: &SessionGrants, cmd: &str, cwd: &str) -> bool {
+        grants.covers(cmd, analyze_pipe_chain(cmd), "", cwd)
+    }
+
+    #[test]
+    fn runner_commands_granted_exactly() {
+        let mut grants = SessionGrants::new();
+        let g = grant(&mut grants, "npm run build", "/work").unwrap();
+        assert_eq!(g.describe(), "npm run build");
+        assert!(covers(&grants, "npm run build", "/work"));
+        assert!(!covers(&grants, "npm run deploy", "/work"));
+        assert!(!covers(&grants, "npm run build -- --prod", "/work"));
+
+        grant(&mut grants, "make build", "/work").unwrap();
+        assert!(!covers(&grants, "make deploy", "/work"));
+    }
+
+    #[test]
+    fn build_command_granted_by_prefix() {
+        let mut grants = SessionGrants::new();
+        let g = grant(&mut grants, "cargo test -p foo", "/work").unwrap();
+        assert_eq!(g.describe(), "cargo test *");
+        assert!(covers(&grants, "cargo test -p foo", "/work"));
+        assert!(covers(&grants, "cargo test -p bar", "/work"));
+        assert!(!covers(&grants, "cargo publish", "/work"));
+    }
+
+    #[test]
+    fn file_commands_granted_exactly() {
+        for (cmd, other) in [
+            ("cp notes backup", "cp notes /etc/passwd"),
+            ("mv notes backup", "mv notes ~/.bashrc"),
+            ("ln notes backup", "ln -sf notes ~/.profile"),
+            ("tee notes", "tee ~/.bashrc"),
+            ("install notes backup", "install notes /usr/local/bin/x"),
+        ] {
+            let mut grants = SessionGrants::new();
+            let g = grant(&mut grants, cmd, "/work").unwrap();
+            assert_eq!(g.describe(), cmd);
+            assert!(covers(&grants, cmd, "/work"));
+            assert!(!covers(&grants, other, "/work"), "{other}");
+        }
+    }
+
+    #[test]
+    fn grants_are_scoped_to_cwd() {
+        let mut grants = SessionGrants::new();
+        grant(&mut grants, "cargo test", "/work");
+        assert!(!covers(&grants, "cargo test", "/elsewhere"));
+    }
+
+    #[test]
+    fn grants_are_scoped_to_host() {
+        let mut grants = SessionGrants::new();
+        grants.grant("make clean", RiskLevel::Destructive, "laptop", "/srv");
+        assert!(grants.covers("make clean", RiskLevel::Destructive, "laptop", "/srv"));
+        // The same path after `ssh web-1` is another directory.
+        assert!(!grants.covers("make clean", RiskLevel::Destructive, "web-1", "/srv"));
+        assert!(!grants.covers("make clean", RiskLevel::Destructive, "", "/srv"));
+    }
+
+    #[test]
+    fn prefix_grant_does_not_cover_compound_commands() {
+        let mut grants = SessionGrants::new();
+        grant(&mut grants, "cargo test", "/work");
+        assert!(!covers(&grants, "cargo test && rm -rf build", "/work"));
+        assert!(!covers(&grants, "cargo test > /etc/motd", "/work"));
+    }
+
+    #[test]
+    fn destructive_command_granted_exactly() {
+        let mut grants = SessionGrants::new();
+        let g = grant(&mut grants, "git clean -fdx", "/work").unwrap();
+        assert_eq!(g.pattern, GrantPattern::Exact("git clean -fdx".to_string()));
+        assert!(covers(&grants, "git clean -fdx", "/work"));
+        assert!(!covers(&grants, "git clean -fd", "/work"));
+    }
+
+    #[test]
+    fn prefix_grant_ignores_riskier_variants() {
+        let mut grants = SessionGrants::new();
+        grant(&mut grants, "git status", "/work");
+        assert!(covers(&grants, "git status --short", "/work"));
+        // Same binary + subcommand but classified destructive
+        assert!(!covers(&grants, "git status --force", "/work"));
+    }
+
+    #[test]
+    fn privileged_never_granted() {
+        let mut grants = SessionGrants::new();
+        assert!(grant(&mut grants, "sudo apt update", "/work").is_none());
+        assert!(grants.is_empty());
+        assert!(!covers(&grants, "sudo apt update", "/work"));
+    }
+
+    #[test]
+    fn duplicate_grant_is_not_recorded() {
+        let mut grants = SessionGrants::new();
+        assert!(grant(&mut grants, "cargo test -p foo", "/work").is_some());
+        assert!(grant(&mut grants, "cargo test -p bar", "/work").is_none());
+    }
+
+    #[test]
+    fn covers_all_requires_every_command() {
+        let mut grants = SessionGrants::new();
+        grant(&mut grants, "cargo build", "/work");
+        let cmds = vec!["cargo build".to_string(), "cargo test".to_string()];
+        let risks: Vec<RiskLevel> = cmds.iter().map(|c| analyze_pipe_chain(c)).collect();
+        assert!(!grants.covers_all(&cmds, &risks, "", "/work"));
+        grant(&mut grants, "cargo test", "/work");
+        assert!(grants.covers_all(&cmds, &risks, "", "/work"));
+        assert!(!grants.covers_all(&[], &[], "", "/work"));
+    }
+}
@@ -0,0 +1,640 @@
+//! Pre-exec hooks: user-supplied executables that gate proposed commands.
+//!
+//! Every executable in the hooks directory whose name starts with `pre-exec`
+//! (e.g. `pre-exec.sh`, `pre-exec-prod.py`) runs once per proposed command,
+//! in lexical order, after the built-in deny list. Each hook receives the
+//! proposed action as JSON on stdin:
+//!
+//! ```json
+//! {"action": "shell", "command": "kubectl delete pod web-1", "risk": "destructive",
+//!  "cwd": "/home/me/infra", "depth": 0, "instruction": "restart the web pod"}
+//! ```
+//!
+//! and answers with one JSON object on stdout:
+//!
+//! ```json
+//! {"decision": "allow"}
+//! {"decision": "deny", "reason": "production contexts are off limits"}
+//! {"decision": "modify", "command": "kubectl --context staging delete pod web-1"}
+//! ```
+//!
+//! A `deny` stops the chain. A `modify` replaces the command for the hooks
+//! that follow and for everything after the hook stage (approval, judge,
+//! execution); the rewritten command is re-classified, so a hook cannot
+//! smuggle a deny-listed command past the policy. Hooks fail closed: a
+//! non-zero exit, a timeout, or unparseable output counts as a deny.
+//!
+//! Hooks run outside the sandbox. When sandboxed commands could write the
+//! hooks directory or a hook in it, no hooks are run.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use ua_sandbox::explain::{explain, Access};
+use ua_sandbox::SandboxPolicy;
+
+use crate::audit::AuditLogger;
+use crate::config::HooksConfig;
+use crate::policy::{analyze_pipe_chain, RiskLevel};
+
+/// Filename prefix that marks an executable as a pre-exec hook.
+const PRE_EXEC_PREFIX: &str = "pre-exec";
+
+/// Interval between exit checks while a hook is running.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Context shared by all commands of one agent turn.
+#[derive(Debug, Clone, Default)]
+pub struct HookContext {
+    /// Host the shell reported; empty for the local shell or when unknown.
+    pub host: String,
+    pub cwd: String,
+    pub depth: u32,
+    pub instruction: String,
+}
+
+/// JSON payload written to a hook's stdin.
+#[derive(Debug, Serialize)]
+struct HookInput<'a> {
+    action: &'static str,
+    command: &'a str,
+    risk: &'a str,
+    host: &'a str,
+    cwd: &'a str,
+    depth: u32,
+    instruction: &'a str,
+}
+
+/// A hook's answer.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum HookDecision {
+    Allow,
+    Deny {
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    Modify {
+        command: String,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+}
+
+/// A command rejected by a hook (or by the deny list after a rewrite).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookDenial {
+    /// Index of the rejected command in the proposed batch.
+    pub index: usize,
+    pub command: String,
+    pub hook: String,
+    pub reason: String,
+}
+
+/// A command rewritten by a hook's `modify` decision.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookRewrite {
+    /// Index of the rewritten command in the proposed batch.
+    pub index: usize,
+    pub hook: String,
+    pub command: String,
+}
+
+/// Discovered pre-exec hooks plus their timeout.
+#[derive(Debug, Clone, Default)]
+pub struct HookRunner {
+    hooks: Vec<PathBuf>,
+    timeout: Duration,
+}
+
+impl HookRunner {
+    /// Load hooks from the configured directory. Returns an empty runner when
+    /// hooks are disabled or the directory does not exist.
+    pub fn from_config(config: &HooksConfig) -> Self {
+        let timeout = Duration::from_secs(config.timeout_secs);
+        if !config.enabled {
+            return Self {
+                hooks: Vec::new(),
+                timeout,
+            };
+        }
+        Self {
+            hooks: discover_hooks(&config.resolve_dir()),
+            timeout,
+        }
+    }
+
+    /// A runner with no hooks.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Whether any hooks are configured.
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Run every hook for each proposed command, applying rewrites in place.
+    ///
+    /// `commands` and `risk_levels` are updated for `modify` decisions. Every
+    /// decision is written to the audit log. Returns the rewrites applied, or
+    /// the first denial.
+    pub fn check_commands(
+        &self,
+        commands: &mut [String],
+        risk_levels: &mut [RiskLevel],
+        ctx: &HookContext,
+        audit: &mut AuditLogger,
+    ) -> Result<Vec<HookRewrite>, HookDenial> {
+        let mut rewrites = Vec::new();
+        for index in 0..commands.len() {
+            for hook in &self.hooks {
+                let name = hook_name(hook);
+                let command = commands[index].clone();
+                let decision = self.run_hook(hook, &command, risk_levels[index], ctx);
+
+                match decision {
+                    HookDecision::Allow => {
+                        audit.log_hook(&command, &name, "allow", "", None);
+                    }
+                    HookDecision::Deny { reason } => {
+                        let reason = reason.unwrap_or_else(|| format!("denied by hook {name}"));
+                        audit.log_hook(&command, &name, "deny", &reason, None);
+                        return Err(HookDenial {
+                            index,
+                            command,
+                            hook: name,
+                            reason,
+                        });
+                    }
+                    HookDecision::Modify {
+                        command: rewritten,
+                        reason,
+                    } => {
+                        let reason = reason.unwrap_or_default();
+                        audit.log_hook(&command, &name, "modify", &reason, Some(&rewritten));
+                        let risk = analyze_pipe_chain(&rewritten);
+                        if risk == RiskLevel::Denied {
+                            audit.log_blocked(&rewritten, risk.as_str(), "denied by policy");
+                            return Err(HookDenial {
+                                index,
+                                command: rewritten,
+                                hook: name,
+                                reason: "rewritten command is on the deny list".to_string(),
+                            });
+                        }
+                        rewrites.push(HookRewrite {
+                            index,
+                            hook: name,
+                            command: rewritten.clone(),
+                        });
+                        commands[index] = rewritten;
+                        risk_levels[index] = risk;
+                    }
+                }
+            }
+        }
+        Ok(rewrites)
+    }
+
+    /// Run one hook for one command. Any failure is reported as a deny.
+    fn run_hook(
+        &self,
+        hook: &Path,
+        command: &str,
+        risk: RiskLevel,
+        ctx: &HookContext,
+    ) -> HookDecision {
+        let input = HookInput {
+            action: "shell",
+            command,
+            risk: risk.as_str(),
+            host: &ctx.host,
+            cwd: &ctx.cwd,
+            depth: ctx.depth,
+            instruction: &ctx.instruction,
+        };
+        let payload = serde_json::to_string(&input).unwrap_or_default();
+
+        match run_with_timeout(hook, &payload, self.timeout) {
+            Ok(stdout) => parse_decision(&stdout).unwrap_or_else(|e| HookDecision::Deny {
+                reason: Some(format!(
+                    "hook {} returned invalid output: {e}",
+                    hook_name(hook)
+                )),
+            }),
+            Err(e) => HookDecision::Deny {
+                reason: Some(format!("hook {} failed: {e}", hook_name(hook))),
+            },
+        }
+    }
+}
+
+/// List executable `pre-exec*` files in `dir`, sorted by name.
+/// Whether commands running under `policy` could add a hook to the
+/// configured directory or change one there. Hooks run outside the
+/// sandbox, so such a hook would let them out of it.
+pub fn sandbox_can_plant(policy: &SandboxPolicy, config: &HooksConfig) -> bool {
+    let writable =
+        |path: &Path| explain(policy, &path.to_string_lossy()).access == Access::ReadWrite;
+    let dir = config.resolve_dir();
+    config.enabled
+        && (writable(&dir)
+            || discover_hooks(&dir)
+                .iter()
+                .any(|hook| writable(&hook.canonicalize().unwrap_or_else(|_| hook.clone()))))
+}
+
+fn discover_hooks(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut hooks: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(PRE_EXEC_PREFIX))
+        })
+        .filter(|p| is_executable(p))
+        .collect();
+    hooks.sort();
+    hooks
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+fn hook_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Spawn `hook`, feed `input` on stdin, and collect stdout. The hook is
+/// killed if it runs longer than `timeout`.
+fn run_with_timeout(hook: &Path, input: &str, timeout: Duration) -> Result<String, String> {
+    let mut child = Command::new(hook)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    // Write on a thread: a hook that never reads would block the write once
+    // the pipe is full, and the timeout would never start. A hook that
+    // ignores stdin may close it early; that's fine.
+    if let Some(mut stdin) = child.stdin.take() {
+        let input = input.to_string();
+        thread::spawn(move || {
+            let _ = stdin.write_all(input.as_bytes());
+        });
+    }
+
+    // Drain stdout on a thread so a chatty hook can't block on a full pipe.
+    let mut stdout = child.stdout.take().ok_or("no stdout")?;
+    let reader = thread::spawn(move || {
+        let mut out = String::new();
+        let _ = stdout.read_to_string(&mut out);
+        out
+    });
+
+    let start = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if start.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("timed out after {}s", timeout.as_secs()));
+            }
+            Ok(None) => thread::sleep(POLL_INTERVAL),
+            Err(e) => return Err(e.to_string()),
+        }
+    };
+
+    let out = reader.join().unwrap_or_default();
+    if !status.success() {
+        return Err(match status.code() {
+            Some(code) => format!("exited with status {code}"),
+            None => "killed by signal".to_string(),
+        });
+    }
+    Ok(out)
+}
+
+fn parse_decision(stdout: &str) -> Result<HookDecision, serde_json::Error> {
+    serde_json::from_str(stdout.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write an executable hook script into `dir`.
+    fn write_hook(dir: &Path, name: &str, body: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn runner(dir: &Path, timeout_secs: u64) -> HookRunner {
+        HookRunner::from_config(&HooksConfig {
+            enabled: true,
+            dir: Some(dir.to_string_lossy().into_owned()),
+            timeout_secs,
+        })
+    }
+
+    fn ctx() -> HookContext {
+        HookContext {
+            host: String::new(),
+            cwd: "/tmp".to_string(),
+            depth: 0,
+            instruction: "do things".to_string(),
+        }
+    }
+
+    fn check(
+        runner: &HookRunner,
+        commands: &[&str],
+        audit: &mut AuditLogger,
+    ) -> (
+        Vec<String>,
+        Vec<RiskLevel>,
+        Result<Vec<HookRewrite>, HookDenial>,
+    ) {
+        let mut commands: Vec<String> = commands.iter().map(|c| c.to_string()).collect();
+        let mut risks: Vec<RiskLevel> = commands.iter().map(|c| analyze_pipe_chain(c)).collect();
+        let result = runner.check_commands(&mut commands, &mut risks, &ctx(), audit);
+        (commands, risks, result)
+    }
+
+    #[test]
+    fn parse_allow_deny_modify() {
+        assert_eq!(
+            parse_decision(r#"{"decision": "allow"}"#).unwrap(),
+            HookDecision::Allow
+        );
+        assert_eq!(
+            parse_decision(r#"{"decision": "deny", "reason": "no"}"#).unwrap(),
+            HookDecision::Deny {
+                reason: Some("no".to_string())
+            }
+        );
+        assert_eq!(
+            parse_decision("{\"decision\": \"modify\", \"command\": \"ls -a\"}\n").unwrap(),
+            HookDecision::Modify {
+                command: "ls -a".to_string(),
+                reason: None
+            }
+        );
+        assert!(parse_decision(r#"{"decision": "maybe"}"#).is_err());
+        assert!(parse_decision("").is_err());
+    }
+
+    #[test]
+    fn discover_only_executable_pre_exec_files() {
+        let dir = tempfile::tempdir().unwrap();
+        write_hook(dir.path(), "pre-exec-b.sh", "true");
+        write_hook(dir.path(), "pre-exec-a.sh", "true");
+        write_hook(dir.path(), "post-exec.sh", "true");
+        std::fs::write(dir.path().join("pre-exec-noexec.sh"), "true").unwrap();
+
+        let hooks = discover_hooks(dir.path());
+        let names: Vec<String> = hooks.iter().map(|h| hook_name(h)).collect();
+        assert_eq!(names, vec!["pre-exec-a.sh", "pre-exec-b.sh"]);
+    }
+
+    #[test]
+    fn sandbox_can_plant_in_writable_hooks() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path().canonicalize().unwrap();
+        let hooks_dir = root.join("hooks");
+        let work = root.join("work");
+        std::fs::create_dir_all(&hooks_dir).unwrap();
+        std::fs::create_dir_all(&work).unwrap();
+        let config = HooksConfig {
+            enabled: true,
+            dir: Some(hooks_dir.to_string_lossy().into_owned()),
+            timeout_secs: 5,
+        };
+        let writable = |path: &Path| {
+            SandboxPolicy::from_config(&[path.to_string_lossy().into_owned()], &[], &[])
+        };
+
+        assert!(!sandbox_can_plant(&writable(&work), &config));
+        assert!(sandbox_can_plant(&writable(&root), &config));
+
+        // A hook that links to a file commands can write.
+        let target = write_hook(&work, "check.sh", "true");
+        std::os::unix::fs::symlink(&target, hooks_dir.join("pre-exec.sh")).unwrap();
+        assert!(sandbox_can_plant(&writable(&work), &config));
+
+        // Disabled hooks never run.
+        let disabled = HooksConfig {
+            enabled: false,
+            ..config
+        };
+        assert!(!sandbox_can_plant(&writable(&root), &disabled));
+    }
+
+    #[test]
+    fn missing_dir_and_disabled_have_no_hooks() {
+        assert!(discover_hooks(Path::new("/nonexistent/hooks")).is_empty());
+
+        let dir = tempfile::tempdir().unwrap();
+        write_hook(dir.path(), "pre-exec.sh", "true");
+        let disabled = HookRunner::from_config(&HooksConfig {
+            enabled: false,
+            dir: Some(dir.path().to_string_lossy().into_owned()),
+            timeout_secs: 5,
+        });
+        assert!(disabled.is_empty());
+    }
+
+    #[test]
+    fn hook_receives_json_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let capture = dir.path().join("input.json");
+        write_hook(
+            dir.path(),
+            "pre-exec.sh",
+            &format!(
+                "cat > {}\necho '{{\"decision\": \"allow\"}}'",
+                capture.display()
+            ),
+        );
+        let mut audit = AuditLogger::noop();
+        let (_, _, result) = check(&runner(dir.path(), 5), &["rm build"], &mut audit);
+        assert!(result.is_ok());
+
+        let input: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(capture).unwrap()).unwrap();
+        assert_eq!(input["action"], "shell");
+        assert_eq!(input["command"], "rm build");
+        assert_eq!(input["risk"], "destructive");
+        assert_eq!(input["host"], "");
+        assert_eq!(input["cwd"], "/tmp");
+        assert_eq!(input["depth"], 0);
+        assert_eq!(input["instruction"], "do things");
+    }
+
+    #[test]
+    fn deny_stops_and_is_audited() {
+        let dir = tempfile::tempdir().unwrap();
+        write_hook(
+            dir.path(),
+            "pre-exec.sh",
+            r#"if grep -q production; then echo '{"decision": "deny", "reason": "no prod"}'; else echo '{"decision": "allow"}'; fi"#,
+        );
+        let audit_path = dir.path().join("audit.jsonl");
+        let mut audit = AuditLogger::new(&audit_path).unwrap();
+
+        let (_, _, result) = check(
+            &runner(dir.path(), 5),
+            &["ls", "kubectl --context production get pods"],
+            &mut audit,
+        );
+        let denial = result.unwrap_err();
+        assert_eq!(denial.index, 1);
+        assert_eq!(denial.hook, "pre-exec.sh");
+        assert_eq!(denial.reason, "no prod");
+
+        let log = std::fs::read_to_string(&audit_path).unwrap();
+        let lines: Vec<serde_json::Value> = log
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["type"], "hook");
+        assert_eq!(lines[0]["decision"], "allow");
+        assert_eq!(lines[1]["decision"], "deny");
+        assert_eq!(lines[1]["reason"], "no prod");
+    }
+
+    #[test]
+    fn modify_rewrites_and_reclassifies() {
+        let dir = tempfile::tempdir().unwrap();
+        write_hook(
+            dir.path(),
+            "pre-exec.sh",
+            r#"cat >/dev/null; echo '{"decision": "modify", "command": "ls -la"}'"#,
+        );
+        let mut audit = AuditLogger::noop();
+        let (commands, risks, result) = check(&runner(dir.path(), 5), &["rm build"], &mut audit);
+        let rewrites = result.unwrap();
+        assert_eq!(rewrites.len(), 1);
+        assert_eq!(rewrites[0].hook, "pre-exec.sh");
+        assert_eq!(rewrites[0].command, "ls -la");
+        assert_eq!(commands, vec!["ls -la"]);
+        assert_eq!(risks, vec![RiskLevel::ReadOnly]);
+    }
+
+    #[test]
+    fn modify_chains_to_next_hook() {
+        let dir = tempfile::tempdir().unwrap();
+        write_hook(
+            dir.path(),
+            "pre-exec-1.sh",
+            r#"cat >/dev/null; echo '{"decision": "modify", "command": "echo rewritten"}'"#,
+        );
+        let capture = dir.path().join("seen.json");
+        write_hook(
+            dir.path(),
+            "pre-exec-2.sh",
+            &format!(
+                "cat > {}\necho '{{\"decision\": \"allow\"}}'",
+                capture.display()
+            ),
+        );
+        let mut audit = AuditLogger::noop();
+        let (commands, _, result) = check(&runner(dir.path(), 5), &["ls"], &mut audit);
+        assert!(result.is_ok());
+        assert_eq!(commands, vec!["echo rewritten"]);
+        let seen = std::fs::read_to_string(capture).unwrap();
+        assert!(seen.contains("echo rewritten"));
+    }
+
+    #[test]
+    fn modify_to_denied_command_is_blocked() {
+        let dir = tempfile::tempdir().unwrap();
+        write_hook(
+            dir.path(),
+            "pre-exec.sh",
+            r#"cat >/dev/null; echo '{"decision": "modify", "command": "rm -rf /"}'"#,
+        );
+        let mut audit = AuditLogger::noop();
+        let (_, _, result) = check(&runner(dir.path(), 5), &["ls"], &mut audit);
+        let denial = result.unwrap_err();
+        assert_eq!(denial.command, "rm -rf /");
+        assert!(denial.reason.contains("deny list"));
+    }
+
+    #[test]
+    fn failing_hook_denies() {
+        let dir = tempfile::tempdir().unwrap();
+        write_hook(dir.path(), "pre-exec.sh", "exit 3");
+        let mut audit = AuditLogger::noop();
+        let (_, _, result) = check(&runner(dir.path(), 5), &["ls"], &mut audit);
+        let denial = result.unwrap_err();
+        assert!(denial.reason.contains("exited with status 3"), "{denial:?}");
+    }
+
+    #[test]
+    fn invalid_output_denies() {
+        let dir = tempfile::tempdir().unwrap();
+        write_hook(dir.path(), "pre-exec.sh", "echo sure");
+        let mut audit = AuditLogger::noop();
+        let (_, _, result) = check(&runner(dir.path(), 5), &["ls"], &mut audit);
+        assert!(result.unwrap_err().reason.contains("invalid output"));
+    }
+
+    #[test]
+    fn slow_hook_times_out() {
+        let dir = tempfile::tempdir().unwrap();
+        write_hook(dir.path(), "pre-exec.sh", "exec sleep 10");
+        let mut audit = AuditLogger::noop();
+        let start = Instant::now();
+        let (_, _, result) = check(&runner(dir.path(), 1), &["ls"], &mut audit);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(result.unwrap_err().reason.contains("timed out after 1s"));
+    }
+
+    #[test]
+    fn hook_ignoring_large_input_times_out() {
+        let dir = tempfile::tempdir().unwrap();
+        write_hook(dir.path(), "pre-exec.sh", "exec sleep 10");
+        let mut audit = AuditLogger::noop();
+        let start = Instant::now();
+        let command = format!("echo {}", "x".repeat(1 << 20));
+        let (_, _, result) = check(&runner(dir.path(), 1), &[&command], &mut audit);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(result.unwrap_err().reason.contains("timed out after 1s"));
+    }
+
+    #[test]
+    fn no_hooks_allows_everything() {
+        let mut audit = AuditLogger::noop();
+        let (commands, _, result) = check(&HookRunner::none(), &["rm build"], &mut audit);
+        assert!(result.is_ok());
+        assert_eq!(commands, vec!["rm build"]);
+    }
+}
@@ -0,0 +1,302 @@
+//! Multi-line `#` instructions.
+//!
+//! A `#` line continues onto the next one when it ends with `,` or `\`, or
+//! when it is entered with Alt+Enter. A pasted block of `#` lines — or a
+//! multi-line paste onto a line that already starts with `#` — becomes a
+//! single instruction. A blank line ends the instruction.
+//!
+//! `#!` lines that name a meta-command are handled by the REPL itself
+//! rather than sent to the agent.
+
+use std::time::{Duration, Instant};
+
+/// Bracketed paste start marker (sent by the terminal when mode 2004 is on).
+pub const PASTE_START: &[u8] = b"\x1b[200~";
+/// Bracketed paste end marker.
+pub const PASTE_END: &[u8] = b"\x1b[201~";
+
+/// Whether a `#` line asks for another line (trailing `,` or `\`).
+pub fn continues(line: &str) -> bool {
+    let line = line.trim_end();
+    line.ends_with(',') || line.ends_with('\\')
+}
+
+/// Instruction text of one line: without the `#` prefix or a trailing `\`.
+pub fn instruction_line(line: &str) -> String {
+    let text = line.trim();
+    let text = text.strip_prefix('#').unwrap_or(text).trim();
+    text.strip_suffix('\\')
+        .unwrap_or(text)
+        .trim_end()
+        .to_string()
+}
+
+/// Join instruction lines into one instruction.
+pub fn join(lines: &[String]) -> String {
+    lines.join("\n").trim().to_string()
+}
+
+/// Split a complete bracketed paste into its body and any bytes after the
+/// end marker. `None` if `buf` is not a paste or the end hasn't arrived yet.
+pub fn bracketed_paste(buf: &[u8]) -> Option<(&[u8], &[u8])> {
+    let body = buf.strip_prefix(PASTE_START)?;
+    let end = body.windows(PASTE_END.len()).position(|w| w == PASTE_END)?;
+    Some((&body[..end], &body[end + PASTE_END.len()..]))
+}
+
+/// Most bytes held back while waiting for a paste's end marker. Longer
+/// pastes go to the shell as they arrive.
+pub const MAX_PASTE: usize = 1 << 20;
+
+/// How long a paste may stall before its end marker is given up on.
+pub const PASTE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A bracketed paste being collected across reads.
+pub struct PasteBuffer {
+    buf: Vec<u8>,
+    last_read: Instant,
+}
+
+/// What to do after a read was added to a [`PasteBuffer`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum PasteStep {
+    /// The end marker hasn't arrived yet.
+    Pending,
+    /// The paste is complete: its body and the bytes read after it.
+    Complete { body: Vec<u8>, rest: Vec<u8> },
+    /// Too long to hold back; these bytes go to the shell unchanged.
+    Oversized(Vec<u8>),
+}
+
+impl PasteBuffer {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            buf: Vec::new(),
+            last_read: now,
+        }
+    }
+
+    /// Add a read.
+    pub fn push(&mut self, data: &[u8], now: Instant) -> PasteStep {
+        self.buf.extend_from_slice(data);
+        self.last_read = now;
+        if let Some((body, rest)) = bracketed_paste(&self.buf) {
+            return PasteStep::Complete {
+                body: body.to_vec(),
+                rest: rest.to_vec(),
+            };
+        }
+        if self.buf.len() > MAX_PASTE {
+            return PasteStep::Oversized(std::mem::take(&mut self.buf));
+        }
+        PasteStep::Pending
+    }
+
+    /// Whether nothing has arrived for [`PASTE_TIMEOUT`]: the end marker
+    /// was lost, and input after it must not be swallowed.
+    pub fn stalled(&self, now: Instant) -> bool {
+        now >= self.deadline()
+    }
+
+    /// When the paste counts as stalled if nothing more arrives.
+    pub fn deadline(&self) -> Instant {
+        self.last_read + PASTE_TIMEOUT
+    }
+
+    /// What was pasted so far, without the start marker, for a paste given
+    /// up on. The shell would wait for the end marker too.
+    pub fn into_body(self) -> Vec<u8> {
+        match self.buf.strip_prefix(PASTE_START) {
+            Some(body) => body.to_vec(),
+            None => self.buf,
+        }
+    }
+}
+
+/// A `#!` line the REPL handles itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetaCommand<'a> {
+    /// `#!integrate [shell]`: load shell integration into the shell at the
+    /// prompt, which was started inside the session (`ssh`, `sudo -i`).
+    Integrate { shell: Option<&'a str> },
+}
+
+/// The meta-command on a `#!` line, if it names one. Other `#!` lines are
+/// ordinary instructions.
+pub fn meta_command(line: &str) -> Option<MetaCommand<'_>> {
+    let rest = line.trim().strip_prefix("#!")?;
+    let mut words = rest.split_whitespace();
+    match words.next()? {
+        "integrate" => {
+            let shell = words.next();
+            words
+                .next()
+                .is_none()
+                .then_some(MetaCommand::Integrate { shell })
+        }
+        _ => None,
+    }
+}
+
+/// Instruction lines for text pasted at the prompt, where `typed` is what is
+/// already on the command line. Returns `None` unless the paste spans
+/// several lines and is an instruction: either every non-blank line starts
+/// with `#`, or `typed` itself starts with `#`.
+pub fn pasted_instruction(typed: &str, pasted: &str) -> Option<Vec<String>> {
+    let pasted = pasted.replace("\r\n", "\n").replace('\r', "\n");
+    let raw: Vec<&str> = pasted.split('\n').collect();
+    if raw.len() < 2 {
+        return None;
+    }
+
+    let typed = typed.trim_start();
+    let mut lines: Vec<String> = if typed.starts_with('#') {
+        let mut lines = vec![instruction_line(&format!("{typed}{}", raw[0]))];
+        lines.extend(raw[1..].iter().map(|l| instruction_line(l)));
+        lines
+    } else if typed.is_empty()
+        && raw
+            .iter()
+            .filter(|l| !l.trim().is_empty())
+            .all(|l| l.trim_start().starts_with('#'))
+    {
+        raw.iter().map(|l| instruction_line(l)).collect()
+    } else {
+        return None;
+    };
+
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    while lines.first().is_some_and(|l| l.is_empty()) {
+        lines.remove(0);
+    }
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn integrate_meta_command() {
+        assert_eq!(
+            meta_command("#!integrate"),
+            Some(MetaCommand::Integrate { shell: None })
+        );
+        assert_eq!(
+            meta_command("  #!integrate  zsh "),
+            Some(MetaCommand::Integrate { shell: Some("zsh") })
+        );
+        assert_eq!(meta_command("#!integrate zsh now"), None);
+        assert_eq!(meta_command("# integrate"), None);
+        assert_eq!(meta_command("#!/bin/sh is a shebang"), None);
+    }
+
+    #[test]
+    fn trailing_comma_or_backslash_continues() {
+        assert!(continues("# deploy the app to staging,"));
+        assert!(continues("# deploy the app \\"));
+        assert!(continues("# run the tests,  "));
+        assert!(!continues("# and rollback if any test fails"));
+    }
+
+    #[test]
+    fn instruction_line_strips_markers() {
+        assert_eq!(instruction_line("# deploy the app,"), "deploy the app,");
+        assert_eq!(instruction_line("#  deploy \\"), "deploy");
+        assert_eq!(instruction_line("plain text"), "plain text");
+        assert_eq!(instruction_line("# "), "");
+    }
+
+    #[test]
+    fn join_keeps_paragraphs() {
+        let lines = strings(&["first,", "second", "", "new paragraph"]);
+        assert_eq!(join(&lines), "first,\nsecond\n\nnew paragraph");
+    }
+
+    #[test]
+    fn bracketed_paste_needs_both_markers() {
+        assert_eq!(
+            bracketed_paste(b"\x1b[200~# a\r# b\x1b[201~x"),
+            Some((&b"# a\r# b"[..], &b"x"[..]))
+        );
+        assert_eq!(bracketed_paste(b"\x1b[200~# a\r# b"), None);
+        assert_eq!(bracketed_paste(b"# a\x1b[201~"), None);
+    }
+
+    #[test]
+    fn paste_buffer_collects_until_the_end_marker() {
+        let t = Instant::now();
+        let mut paste = PasteBuffer::new(t);
+        assert_eq!(paste.push(b"\x1b[200~# one\r", t), PasteStep::Pending);
+        assert_eq!(
+            paste.push(b"# two\x1b[201~ls\r", t),
+            PasteStep::Complete {
+                body: b"# one\r# two".to_vec(),
+                rest: b"ls\r".to_vec(),
+            }
+        );
+    }
+
+    #[test]
+    fn paste_buffer_gives_up_on_a_lost_end_marker() {
+        let t = Instant::now();
+        let mut paste = PasteBuffer::new(t);
+        assert_eq!(paste.push(b"\x1b[200~# one\r", t), PasteStep::Pending);
+        assert!(!paste.stalled(t + PASTE_TIMEOUT / 2));
+        assert!(paste.stalled(t + PASTE_TIMEOUT * 2));
+        assert_eq!(paste.into_body(), b"# one\r");
+
+        let mut paste = PasteBuffer::new(t);
+        let big = [PASTE_START, &[b'x'; MAX_PASTE]].concat();
+        assert_eq!(paste.push(&big, t), PasteStep::Oversized(big.clone()));
+    }
+
+    #[test]
+    fn pasted_hash_lines_become_one_instruction() {
+        let lines = pasted_instruction(
+            "",
+            "# deploy the app to staging,\r# run the test suite,\r# and rollback if any test fails\r",
+        )
+        .unwrap();
+        assert_eq!(
+            lines,
+            strings(&[
+                "deploy the app to staging,",
+                "run the test suite,",
+                "and rollback if any test fails"
+            ])
+        );
+    }
+
+    #[test]
+    fn multi_paragraph_paste_onto_hash_line() {
+        let lines =
+            pasted_instruction("# ", "Fix the flaky test.\n\nIt fails on CI only.\n").unwrap();
+        assert_eq!(
+            lines,
+            strings(&["Fix the flaky test.", "", "It fails on CI only."])
+        );
+    }
+
+    #[test]
+    fn non_instruction_pastes_are_left_to_the_shell() {
+        // Shell commands
+        assert_eq!(pasted_instruction("", "ls -la\ncd /tmp\n"), None);
+        // Mixed
+        assert_eq!(pasted_instruction("", "# note\nls\n"), None);
+        // Single line
+        assert_eq!(pasted_instruction("", "# just one line"), None);
+        // Pasting onto a shell command
+        assert_eq!(pasted_instruction("echo ", "a\nb"), None);
+    }
+}
@@ -1093,7 +1093,7 @@ mod tests {
 
     #[test]
     fn trajectory_split_by_system_prompt() {
-        let entries = vec![
+        let entries = [
             JournalEntry::Instruction {
                 ts: 1,
                 text: "what files?".to_string(),
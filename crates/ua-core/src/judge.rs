@@ -6,7 +6,7 @@
 //! terminal output, conversation history, or environment variables.
 
 use serde::Deserialize;
-use ua_backend::AnthropicClient;
+use ua_backend::Backend;
 
 /// Verdict from the security judge.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -31,7 +31,7 @@ struct JudgeResponse {
 /// working directory. It never sees terminal output, conversation history,
 /// or environment variables.
 pub async fn evaluate_commands(
-    client: &AnthropicClient,
+    backend: &dyn Backend,
     commands: &[String],
     instruction: &str,
     cwd: &str,
@@ -40,7 +40,7 @@ pub async fn evaluate_commands(
     let system = judge_system_prompt(computer_use);
     let user_msg = judge_user_message(commands, instruction, cwd);
 
-    match client.send_non_streaming(&system, &user_msg).await {
+    match backend.send_non_streaming(&system, &user_msg).await {
         Ok(text) => parse_judge_response(&text),
         Err(e) => JudgeVerdict::Error(format!("judge API call failed: {e}")),
     }
@@ -2,16 +2,26 @@ pub mod agents;
 pub mod attachment;
 pub mod audit;
 pub mod batch;
+pub mod capture;
 pub mod config;
 pub mod context;
 pub mod display;
+pub mod events;
+pub mod grants;
+pub mod hooks;
+pub mod instruction;
 pub mod journal;
 pub mod judge;
+pub mod line_editor;
 pub mod osc;
 pub mod policy;
 pub mod process;
+pub mod prompt_detect;
 pub mod pty;
 pub mod renderer;
 pub mod repl;
+pub mod screen;
 pub mod shell_scripts;
 pub mod style;
+pub mod subcommands;
+pub mod trust;
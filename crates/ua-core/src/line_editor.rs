@@ -0,0 +1,352 @@
+//! Minimal inline line editor for editing proposed commands at the approval
+//! prompt.
+//!
+//! Fed raw terminal bytes; supports cursor movement (arrows, Ctrl-A/E/B/F,
+//! Alt-B/F), deletion (Backspace, Delete, Ctrl-D/W/U/K), and Up/Down to swap
+//! between the original command and the user's edit. Alt+Enter is reported
+//! separately from Enter for multi-line input.
+
+/// Result of feeding input to the editor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditEvent {
+    /// Keep editing.
+    Continue,
+    /// Enter pressed — the edited line.
+    Submit(String),
+    /// Alt+Enter pressed — the line so far, with a request for another line.
+    Newline(String),
+    /// Ctrl-C or a lone Escape — abandon the edit.
+    Cancel,
+}
+
+/// Escape-sequence parser state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Escape {
+    None,
+    /// Saw ESC.
+    Start,
+    /// Saw ESC [ or ESC O, collecting parameter bytes.
+    Csi,
+}
+
+/// Single-line editor state.
+#[derive(Debug)]
+pub struct LineEditor {
+    buf: Vec<char>,
+    /// Cursor position as a char index into `buf`.
+    cursor: usize,
+    original: Vec<char>,
+    /// The user's edit, saved while the original is recalled with Up.
+    stashed: Option<Vec<char>>,
+    escape: Escape,
+    csi_params: String,
+    /// Bytes of an incomplete UTF-8 character.
+    utf8_pending: Vec<u8>,
+}
+
+impl LineEditor {
+    /// Start editing `initial`, with the cursor at the end.
+    pub fn new(initial: &str) -> Self {
+        let buf: Vec<char> = initial.chars().collect();
+        Self {
+            cursor: buf.len(),
+            original: buf.clone(),
+            buf,
+            stashed: None,
+            escape: Escape::None,
+            csi_params: String::new(),
+            utf8_pending: Vec::new(),
+        }
+    }
+
+    /// Current line contents.
+    pub fn text(&self) -> String {
+        self.buf.iter().collect()
+    }
+
+    /// Cursor position in chars from the start of the line.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Chars between the cursor and the end of the line.
+    pub fn chars_after_cursor(&self) -> usize {
+        self.buf.len() - self.cursor
+    }
+
+    /// Process a chunk of terminal input.
+    pub fn feed(&mut self, data: &[u8]) -> EditEvent {
+        for &b in data {
+            match self.escape {
+                Escape::Start => {
+                    self.escape = Escape::None;
+                    match b {
+                        b'[' | b'O' => {
+                            self.escape = Escape::Csi;
+                            self.csi_params.clear();
+                        }
+                        b'b' | b'B' => self.cursor = self.word_start(),
+                        b'f' | b'F' => self.cursor = self.word_end(),
+                        0x7f => self.delete_word_back(),
+                        b'\r' | b'\n' => return EditEvent::Newline(self.text()),
+                        // ESC ESC — treat as a lone Escape
+                        0x1b => return EditEvent::Cancel,
+                        _ => {}
+                    }
+                    continue;
+                }
+                Escape::Csi => {
+                    if b.is_ascii_digit() || b == b';' {
+                        self.csi_params.push(b as char);
+                    } else {
+                        self.escape = Escape::None;
+                        self.handle_csi(b);
+                    }
+                    continue;
+                }
+                Escape::None => {}
+            }
+
+            match b {
+                b'\r' | b'\n' => return EditEvent::Submit(self.text()),
+                0x03 => return EditEvent::Cancel,
+                0x1b => self.escape = Escape::Start,
+                0x01 => self.cursor = 0,
+                0x05 => self.cursor = self.buf.len(),
+                0x02 => self.cursor = self.cursor.saturating_sub(1),
+                0x06 => self.cursor = (self.cursor + 1).min(self.buf.len()),
+                0x7f | 0x08 => {
+                    if self.cursor > 0 {
+                        self.cursor -= 1;
+                        self.buf.remove(self.cursor);
+                    }
+                }
+                0x04 => self.delete_forward(),
+                0x17 => self.delete_word_back(),
+                0x15 => {
+                    self.buf.drain(..self.cursor);
+                    self.cursor = 0;
+                }
+                0x0b => self.buf.truncate(self.cursor),
+                b if b < 0x20 => {}
+                b => self.push_byte(b),
+            }
+        }
+
+        // A lone ESC at the end of a chunk is the Escape key, not the start
+        // of a sequence — terminals send sequences in a single write.
+        if self.escape == Escape::Start {
+            self.escape = Escape::None;
+            return EditEvent::Cancel;
+        }
+        EditEvent::Continue
+    }
+
+    fn handle_csi(&mut self, final_byte: u8) {
+        match (final_byte, self.csi_params.as_str()) {
+            (b'A', _) => self.recall_original(),
+            (b'B', _) => self.restore_edit(),
+            (b'C', _) => self.cursor = (self.cursor + 1).min(self.buf.len()),
+            (b'D', _) => self.cursor = self.cursor.saturating_sub(1),
+            (b'H', _) | (b'~', "1") | (b'~', "7") => self.cursor = 0,
+            (b'F', _) | (b'~', "4") | (b'~', "8") => self.cursor = self.buf.len(),
+            (b'~', "3") => self.delete_forward(),
+            _ => {}
+        }
+    }
+
+    fn push_byte(&mut self, b: u8) {
+        self.utf8_pending.push(b);
+        match std::str::from_utf8(&self.utf8_pending) {
+            Ok(s) => {
+                let chars: Vec<char> = s.chars().collect();
+                self.utf8_pending.clear();
+                for c in chars {
+                    self.buf.insert(self.cursor, c);
+                    self.cursor += 1;
+                }
+            }
+            // Incomplete sequence — wait for more bytes
+            Err(e) if e.error_len().is_none() => {}
+            Err(_) => self.utf8_pending.clear(),
+        }
+    }
+
+    /// Up: show the original command, keeping the current edit.
+    fn recall_original(&mut self) {
+        if self.stashed.is_none() {
+            self.stashed = Some(std::mem::replace(&mut self.buf, self.original.clone()));
+            self.cursor = self.buf.len();
+        }
+    }
+
+    /// Down: return to the edit saved by `recall_original`.
+    fn restore_edit(&mut self) {
+        if let Some(edit) = self.stashed.take() {
+            self.buf = edit;
+            self.cursor = self.buf.len();
+        }
+    }
+
+    /// Start of the word before the cursor (skipping whitespace first).
+    fn word_start(&self) -> usize {
+        let mut i = self.cursor;
+        while i > 0 && self.buf[i - 1].is_whitespace() {
+            i -= 1;
+        }
+        while i > 0 && !self.buf[i - 1].is_whitespace() {
+            i -= 1;
+        }
+        i
+    }
+
+    /// End of the word after the cursor (skipping whitespace first).
+    fn word_end(&self) -> usize {
+        let mut i = self.cursor;
+        while i < self.buf.len() && self.buf[i].is_whitespace() {
+            i += 1;
+        }
+        while i < self.buf.len() && !self.buf[i].is_whitespace() {
+            i += 1;
+        }
+        i
+    }
+
+    fn delete_forward(&mut self) {
+        if self.cursor < self.buf.len() {
+            self.buf.remove(self.cursor);
+        }
+    }
+
+    fn delete_word_back(&mut self) {
+        let start = self.word_start();
+        self.buf.drain(start..self.cursor);
+        self.cursor = start;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_with_cursor_at_end() {
+        let ed = LineEditor::new("ls -la");
+        assert_eq!(ed.text(), "ls -la");
+        assert_eq!(ed.cursor(), 6);
+        assert_eq!(ed.chars_after_cursor(), 0);
+    }
+
+    #[test]
+    fn typing_appends_and_enter_submits() {
+        let mut ed = LineEditor::new("ls");
+        assert_eq!(ed.feed(b" -la"), EditEvent::Continue);
+        assert_eq!(ed.feed(b"\r"), EditEvent::Submit("ls -la".to_string()));
+    }
+
+    #[test]
+    fn backspace_and_delete() {
+        let mut ed = LineEditor::new("rm -rf build");
+        ed.feed(&[0x7f, 0x7f]);
+        assert_eq!(ed.text(), "rm -rf bui");
+        ed.feed(&[0x01]); // Ctrl-A
+        ed.feed(b"\x1b[3~"); // Delete
+        assert_eq!(ed.text(), "m -rf bui");
+        ed.feed(&[0x04]); // Ctrl-D
+        assert_eq!(ed.text(), " -rf bui");
+    }
+
+    #[test]
+    fn arrow_keys_move_cursor() {
+        let mut ed = LineEditor::new("rm -rf x");
+        ed.feed(b"\x1b[D\x1b[D");
+        assert_eq!(ed.chars_after_cursor(), 2);
+        ed.feed(b"\x1b[C");
+        assert_eq!(ed.chars_after_cursor(), 1);
+        ed.feed(b"\x1b[H");
+        assert_eq!(ed.cursor(), 0);
+        ed.feed(b"\x1b[F");
+        assert_eq!(ed.chars_after_cursor(), 0);
+    }
+
+    #[test]
+    fn insert_in_middle() {
+        let mut ed = LineEditor::new("rm build");
+        ed.feed(&[0x01]); // Ctrl-A
+        ed.feed(&[0x06, 0x06]); // Ctrl-F x2
+        ed.feed(b" -i");
+        assert_eq!(ed.text(), "rm -i build");
+    }
+
+    #[test]
+    fn word_delete_and_kill() {
+        let mut ed = LineEditor::new("git push --force origin");
+        ed.feed(&[0x17]); // Ctrl-W
+        assert_eq!(ed.text(), "git push --force ");
+        ed.feed(&[0x17]);
+        assert_eq!(ed.text(), "git push ");
+
+        ed.feed(b"\x1bb"); // Alt-B → start of "push"
+        ed.feed(&[0x0b]); // Ctrl-K
+        assert_eq!(ed.text(), "git ");
+
+        ed.feed(&[0x15]); // Ctrl-U
+        assert_eq!(ed.text(), "");
+    }
+
+    #[test]
+    fn alt_word_movement() {
+        let mut ed = LineEditor::new("cargo test -p foo");
+        ed.feed(b"\x1bb\x1bb");
+        assert_eq!(ed.cursor(), 11); // start of "-p"
+        ed.feed(b"\x1bf");
+        assert_eq!(ed.cursor(), 13); // end of "-p"
+    }
+
+    #[test]
+    fn up_recalls_original_down_restores_edit() {
+        let mut ed = LineEditor::new("rm -rf build");
+        ed.feed(&[0x15]);
+        ed.feed(b"rm -ri build");
+        ed.feed(b"\x1b[A");
+        assert_eq!(ed.text(), "rm -rf build");
+        ed.feed(b"\x1b[B");
+        assert_eq!(ed.text(), "rm -ri build");
+    }
+
+    #[test]
+    fn ctrl_c_and_lone_escape_cancel() {
+        let mut ed = LineEditor::new("ls");
+        assert_eq!(ed.feed(&[0x03]), EditEvent::Cancel);
+        let mut ed = LineEditor::new("ls");
+        assert_eq!(ed.feed(&[0x1b]), EditEvent::Cancel);
+    }
+
+    #[test]
+    fn alt_enter_requests_newline() {
+        let mut ed = LineEditor::new("# deploy");
+        assert_eq!(
+            ed.feed(b"\x1b\r"),
+            EditEvent::Newline("# deploy".to_string())
+        );
+    }
+
+    #[test]
+    fn escape_sequence_split_across_chunks_is_not_cancel() {
+        let mut ed = LineEditor::new("ab");
+        assert_eq!(ed.feed(b"\x1b["), EditEvent::Continue);
+        assert_eq!(ed.feed(b"D"), EditEvent::Continue);
+        assert_eq!(ed.chars_after_cursor(), 1);
+    }
+
+    #[test]
+    fn utf8_input_split_across_chunks() {
+        let mut ed = LineEditor::new("echo ");
+        let bytes = "é".as_bytes();
+        ed.feed(&bytes[..1]);
+        ed.feed(&bytes[1..]);
+        assert_eq!(ed.text(), "echo é");
+        ed.feed(&[0x7f]);
+        assert_eq!(ed.text(), "echo ");
+    }
+}
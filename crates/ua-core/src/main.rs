@@ -1,10 +1,12 @@
 use std::io::{self, IsTerminal, Read};
 use std::path::Path;
+use std::sync::Arc;
 
 use crossterm::terminal;
+use ua_backend::{Backend, LazyBackend};
 use ua_core::attachment::load_attachment;
 use ua_core::batch::run_batch;
-use ua_core::config::Config;
+use ua_core::config::{Config, Override};
 use ua_core::process;
 use ua_core::repl::run_repl;
 use ua_core::shell_scripts::{detect_shell, ShellKind};
@@ -31,6 +33,29 @@ impl Drop for TerminalGuard {
     }
 }
 
+/// Apply the configured sandbox to this process. Returns whether it took.
+fn sandbox_agent_process(config: &Config) -> bool {
+    let mut policy = config.sandbox.to_policy(&config.hooks);
+    // Batch commands re-enter through `--sandbox-exec`, so this binary
+    // must stay executable, and /proc writable for setting up their
+    // namespaces (uid_map). The children re-apply the configured policy,
+    // so the commands themselves don't see /proc.
+    if let Ok(exe) = std::env::current_exe() {
+        policy.readable.push(exe);
+    }
+    policy.writable.push("/proc".into());
+    match ua_sandbox::apply(&policy) {
+        Ok(()) => {
+            eprintln!("[ua:sandbox] active");
+            true
+        }
+        Err(e) => {
+            eprintln!("[ua:sandbox] warning: failed to apply: {e}");
+            false
+        }
+    }
+}
+
 fn print_help() {
     println!("unixagent — AI-powered Unix shell agent");
     println!();
@@ -40,10 +65,23 @@ fn print_help() {
     println!("  echo \"instruction\" | unixagent  Batch mode via stdin pipe");
     println!("  unixagent -p \"prompt\" --attachments img.png  Multimodal batch mode");
     println!();
+    println!("Commands:");
+    println!("  unixagent config check      Validate the config files that apply here");
+    println!("  unixagent config show [--effective]");
+    println!(
+        "                              List the config files in use, or print the merged config"
+    );
+    println!("  unixagent config trust      Trust the project .unixagent.toml files here");
+    println!("  unixagent sandbox show      Print the resolved sandbox policy");
+    println!("  unixagent sandbox explain <path> [--write]");
+    println!("                              Show whether the sandbox allows access to a path");
+    println!();
     println!("Options:");
     println!("  -p, --prompt <text>          Instruction text for batch mode");
     println!("  --attachments <files...>     Image files to attach (png, jpg, gif, webp)");
     println!("  --system-prompt-file <path>   Prepend file contents to system prompt (batch mode)");
+    println!("  --json                       Emit JSONL events on stdout (batch mode)");
+    println!("  --set <section.field=value>  Override a config field (repeatable)");
     println!("  --debug-osc                  Print OSC 133 events to stderr");
     println!("  --no-integration             Disable shell integration (OSC 133 injection)");
     println!("  --version                    Print version");
@@ -53,6 +91,8 @@ fn print_help() {
     println!(
         "  UNIXAGENT_COMPUTER_USE=macos  Enable computer-use mode (forces judge in Block mode)"
     );
+    println!("  UNIXAGENT_<SECTION>__<FIELD>  Override a config field, e.g.");
+    println!("                                UNIXAGENT_BACKEND__ANTHROPIC__MODEL=claude-opus-4-20250514");
     println!();
     println!("Internal:");
     println!("  --sandbox-exec <cmd> [args...]  Apply sandbox and exec (used by agent)");
@@ -62,9 +102,12 @@ fn print_help() {
 struct CliArgs {
     debug_osc: bool,
     no_integration: bool,
+    json: bool,
     prompt: Option<String>,
     system_prompt_file: Option<String>,
     attachment_paths: Vec<String>,
+    /// `--set section.field=value` config overrides, in order.
+    overrides: Vec<Override>,
     positional: Vec<String>,
 }
 
@@ -72,9 +115,11 @@ fn parse_args(args: &[String]) -> CliArgs {
     let mut result = CliArgs {
         debug_osc: false,
         no_integration: false,
+        json: false,
         prompt: None,
         system_prompt_file: None,
         attachment_paths: Vec::new(),
+        overrides: Vec::new(),
         positional: Vec::new(),
     };
 
@@ -84,6 +129,7 @@ fn parse_args(args: &[String]) -> CliArgs {
         match arg.as_str() {
             "--debug-osc" => result.debug_osc = true,
             "--no-integration" => result.no_integration = true,
+            "--json" => result.json = true,
             "-p" | "--prompt" => {
                 i += 1;
                 if i < args.len() {
@@ -102,6 +148,20 @@ fn parse_args(args: &[String]) -> CliArgs {
                     std::process::exit(1);
                 }
             }
+            "--set" => {
+                i += 1;
+                let Some(setting) = args.get(i) else {
+                    eprintln!("error: --set requires section.field=value");
+                    std::process::exit(1);
+                };
+                match Override::from_set(setting) {
+                    Ok(o) => result.overrides.push(o),
+                    Err(e) => {
+                        eprintln!("error: {e}");
+                        std::process::exit(1);
+                    }
+                }
+            }
             "--attachments" => {
                 // Consume all subsequent non-flag args as attachment paths
                 i += 1;
@@ -157,7 +217,22 @@ fn main() {
 
     let cli = parse_args(&args);
 
-    let mut config = Config::load_or_default();
+    // Before loading the config, which would ask about the same files.
+    if args.first().map(String::as_str) == Some("config") {
+        std::process::exit(ua_core::subcommands::config(&args[1..]));
+    }
+
+    let loaded = Config::load(&cli.overrides);
+    ua_core::config::export_overrides(&cli.overrides);
+    for issue in &loaded.issues {
+        eprintln!("{issue}");
+    }
+    let config_invalid = loaded.has_errors();
+    let mut config = loaded.config;
+
+    if args.first().map(String::as_str) == Some("sandbox") {
+        std::process::exit(ua_core::subcommands::sandbox(&config, &args[1..]));
+    }
 
     // Detect computer-use mode
     let computer_use = std::env::var("UNIXAGENT_COMPUTER_USE").is_ok();
@@ -166,6 +241,15 @@ fn main() {
         config.security.judge_mode = Some(ua_core::config::JudgeMode::Block);
     }
 
+    // Install user policy rules before anything classifies a command
+    match config.policy.to_rules() {
+        Ok(rules) => ua_core::policy::install_rules(rules),
+        Err(e) => {
+            eprintln!("error: [policy]: {e}");
+            std::process::exit(1);
+        }
+    }
+
     // Read --system-prompt-file contents if provided
     let system_prompt_file: Option<String> = cli.system_prompt_file.as_ref().map(|path| {
         std::fs::read_to_string(path).unwrap_or_else(|e| {
@@ -174,25 +258,6 @@ fn main() {
         })
     });
 
-    // Apply sandbox to agent process (children inherit).
-    // Must happen AFTER config load (needs to read ~/.config/unixagent/config.toml)
-    // and BEFORE any LLM-driven execution.
-    let sandbox_active = if config.sandbox.enabled {
-        let policy = config.sandbox.to_policy();
-        match ua_sandbox::apply(&policy) {
-            Ok(()) => {
-                eprintln!("[ua:sandbox] active");
-                true
-            }
-            Err(e) => {
-                eprintln!("[ua:sandbox] warning: failed to apply: {e}");
-                false
-            }
-        }
-    } else {
-        false
-    };
-
     // Determine instruction: -p flag, positional arg, or stdin pipe
     let stdin_is_pipe = !io::stdin().is_terminal();
 
@@ -224,6 +289,17 @@ fn main() {
 
     // Batch mode
     if let Some(instruction) = instruction {
+        // Nobody is watching to notice settings that were dropped.
+        if config_invalid {
+            eprintln!("error: invalid config; fix it (see `unixagent config check`) before running in batch mode");
+            std::process::exit(1);
+        }
+
+        // Sandbox the agent process itself (children inherit).
+        // Must happen AFTER config load (needs to read ~/.config/unixagent/config.toml)
+        // and BEFORE any LLM-driven execution.
+        let sandbox_active = config.sandbox.enabled && sandbox_agent_process(&config);
+
         // Load attachments
         let attachments: Vec<ua_protocol::Attachment> = cli
             .attachment_paths
@@ -255,19 +331,34 @@ fn main() {
             }
         };
 
+        let backend = match config.backend.create_backend() {
+            Ok(b) => b,
+            Err(e) => {
+                eprintln!("[ua:batch] error: {e}");
+                std::process::exit(1);
+            }
+        };
+
         let code = runtime.block_on(run_batch(
             &config,
+            backend.as_ref(),
             &instruction,
             depth,
             sandbox_active,
             attachments,
             system_prompt_file.as_deref(),
             computer_use,
+            cli.json,
         ));
         std::process::exit(code);
     }
 
     // REPL mode
+    if cli.json {
+        eprintln!("error: --json is only supported in batch mode (provide an instruction)");
+        std::process::exit(1);
+    }
+
     if !cli.attachment_paths.is_empty() {
         eprintln!("error: --attachments is only supported in batch mode (provide an instruction)");
         std::process::exit(1);
@@ -286,11 +377,21 @@ fn main() {
                 "warning: unknown shell '{}', shell integration disabled",
                 shell_cmd
             );
-            eprintln!("hint: use bash, zsh, or fish for full integration");
+            eprintln!("hint: use bash, zsh, fish, nu, xonsh, pwsh or a POSIX sh for integration");
+            if config.shell.prompt_detection {
+                eprintln!("note: prompts will be guessed from the output (shell.prompt_patterns)");
+            }
             config.shell.integration = false;
         }
     }
 
+    // Built at the first agent request, so the shell starts without an API
+    // key and a missing one is reported where it's needed.
+    let backend_config = config.backend.clone();
+    let backend: Arc<dyn Backend> = Arc::new(LazyBackend::new(move || {
+        backend_config.create_backend().map_err(|e| e.to_string())
+    }));
+
     // Create tokio runtime for async operations
     let runtime = match tokio::runtime::Runtime::new() {
         Ok(rt) => rt,
@@ -308,7 +409,16 @@ fn main() {
         }
     };
 
-    let result = run_repl(&config, cli.debug_osc, runtime.handle(), sandbox_active);
+    // The REPL process stays unsandboxed — it owns the user's shell. With
+    // `[sandbox] repl = true`, agent commands run through `--sandbox-exec`.
+    let sandbox_active = config.sandbox.enabled && config.sandbox.repl;
+    let result = run_repl(
+        &config,
+        backend,
+        cli.debug_osc,
+        runtime.handle(),
+        sandbox_active,
+    );
 
     drop(guard);
 
@@ -1,9 +1,27 @@
+use ua_protocol::ShellLevel;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum OscEvent {
     Osc133A,
     Osc133B,
     Osc133C,
-    Osc133D { exit_code: Option<i32> },
+    Osc133D {
+        exit_code: Option<i32>,
+    },
+    /// OSC 7: the shell's working directory, from a `file://host/path` URL.
+    Osc7 {
+        host: String,
+        path: String,
+    },
+    /// OSC 633 E: the command line the shell is about to run.
+    Osc633E {
+        command: String,
+    },
+    /// OSC 633 P: a `key=value` property, e.g. `Cwd=/home/me`.
+    Osc633P {
+        key: String,
+        value: String,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -20,10 +38,18 @@ enum ParseState {
     Escape,
     OscStart,
     OscParam,
+    /// Inside an OSC we don't handle, or one that grew too long: skip to
+    /// its terminator.
+    OscIgnore,
 }
 
+/// Longest OSC payload we keep; long enough for a typical command line.
+const MAX_PARAM_LEN: usize = 4096;
+
 pub struct OscParser {
     state: ParseState,
+    /// The OSC number (7, 133 or 633) while in `OscParam`.
+    code: u16,
     param_buf: Vec<u8>,
     pub terminal_state: TerminalState,
 }
@@ -38,6 +64,7 @@ impl OscParser {
     pub fn new() -> Self {
         Self {
             state: ParseState::Ground,
+            code: 0,
             param_buf: Vec::with_capacity(64),
             terminal_state: TerminalState::Idle,
         }
@@ -63,12 +90,21 @@ impl OscParser {
                 None
             }
             ParseState::OscStart => {
-                // Accumulate until we see if it starts with "133;"
-                if byte == b';' && self.param_buf == b"133" {
+                // Accumulate the OSC number up to the first ';'
+                if byte == b';' {
+                    let code = std::str::from_utf8(&self.param_buf)
+                        .ok()
+                        .and_then(|s| s.parse::<u16>().ok());
                     self.param_buf.clear();
-                    self.state = ParseState::OscParam;
+                    self.state = match code {
+                        Some(code @ (7 | 133 | 633)) => {
+                            self.code = code;
+                            ParseState::OscParam
+                        }
+                        _ => ParseState::OscIgnore,
+                    };
                 } else if byte == 0x07 || byte == 0x1b {
-                    // BEL or ESC (start of ST) — not a 133 sequence
+                    // BEL or ESC (start of ST) — an OSC without parameters
                     self.state = if byte == 0x1b {
                         ParseState::Escape
                     } else {
@@ -77,31 +113,25 @@ impl OscParser {
                     self.param_buf.clear();
                 } else {
                     self.param_buf.push(byte);
-                    // If we have more than 3 chars and haven't matched "133", abort
                     if self.param_buf.len() > 3 {
-                        self.state = ParseState::OscParam;
-                        // Continue collecting until terminator, but it won't match 133
-                        // Actually, switch to a non-133 OSC collection mode
-                        // Simplify: just keep collecting until terminator
+                        // Longer than any number we handle
+                        self.state = ParseState::OscIgnore;
+                        self.param_buf.clear();
                     }
                 }
                 None
             }
             ParseState::OscParam => {
                 // BEL (0x07) terminates, or ESC \ (ST) terminates
-                if byte == 0x07 {
-                    let event = self.parse_133_param();
-                    self.state = ParseState::Ground;
-                    self.param_buf.clear();
-                    if let Some(ref evt) = event {
-                        self.update_terminal_state(evt);
-                    }
-                    event
-                } else if byte == 0x1b {
+                if byte == 0x07 || byte == 0x1b {
                     // Could be start of ST (ESC \) — but we'd need to see the next byte.
                     // For simplicity, treat ESC as terminator (the \ that follows is harmless).
-                    let event = self.parse_133_param();
-                    self.state = ParseState::Escape;
+                    let event = self.parse_param();
+                    self.state = if byte == 0x1b {
+                        ParseState::Escape
+                    } else {
+                        ParseState::Ground
+                    };
                     self.param_buf.clear();
                     if let Some(ref evt) = event {
                         self.update_terminal_state(evt);
@@ -109,14 +139,22 @@ impl OscParser {
                     event
                 } else {
                     self.param_buf.push(byte);
-                    if self.param_buf.len() > 256 {
+                    if self.param_buf.len() > MAX_PARAM_LEN {
                         // Prevent unbounded growth on malformed sequences
-                        self.state = ParseState::Ground;
+                        self.state = ParseState::OscIgnore;
                         self.param_buf.clear();
                     }
                     None
                 }
             }
+            ParseState::OscIgnore => {
+                match byte {
+                    0x07 => self.state = ParseState::Ground,
+                    0x1b => self.state = ParseState::Escape,
+                    _ => {}
+                }
+                None
+            }
         }
     }
 
@@ -131,16 +169,18 @@ impl OscParser {
         events
     }
 
-    fn parse_133_param(&self) -> Option<OscEvent> {
+    fn parse_param(&self) -> Option<OscEvent> {
         let param = &self.param_buf;
         if param.is_empty() {
             return None;
         }
-        match param[0] {
-            b'A' => Some(OscEvent::Osc133A),
-            b'B' => Some(OscEvent::Osc133B),
-            b'C' => Some(OscEvent::Osc133C),
-            b'D' => {
+        match (self.code, param[0]) {
+            (7, _) => parse_osc7(&String::from_utf8_lossy(param)),
+            // VS Code's 633 A-D mean the same as 133 A-D.
+            (133 | 633, b'A') => Some(OscEvent::Osc133A),
+            (133 | 633, b'B') => Some(OscEvent::Osc133B),
+            (133 | 633, b'C') => Some(OscEvent::Osc133C),
+            (133 | 633, b'D') => {
                 let exit_code = if param.len() > 1 && param[1] == b';' {
                     std::str::from_utf8(&param[2..])
                         .ok()
@@ -150,20 +190,244 @@ impl OscParser {
                 };
                 Some(OscEvent::Osc133D { exit_code })
             }
+            (633, b'E') => {
+                let rest = String::from_utf8_lossy(param.get(2..)?);
+                // An optional `;<nonce>` follows; escaped `;` never appear raw.
+                let command = rest.split(';').next().unwrap_or_default();
+                Some(OscEvent::Osc633E {
+                    command: unescape_633(command),
+                })
+            }
+            (633, b'P') => {
+                let rest = String::from_utf8_lossy(param.get(2..)?);
+                let (key, value) = rest.split_once('=')?;
+                Some(OscEvent::Osc633P {
+                    key: key.to_string(),
+                    value: unescape_633(value),
+                })
+            }
             _ => None,
         }
     }
 
-    fn update_terminal_state(&mut self, event: &OscEvent) {
+    /// Move the terminal state as `event` would. Also used for events
+    /// synthesized outside the parser.
+    pub fn update_terminal_state(&mut self, event: &OscEvent) {
         self.terminal_state = match event {
             OscEvent::Osc133A => TerminalState::Prompt,
             OscEvent::Osc133B => TerminalState::Input,
             OscEvent::Osc133C => TerminalState::Executing,
             OscEvent::Osc133D { .. } => TerminalState::Idle,
+            _ => return,
         };
     }
 }
 
+/// Parse an OSC 7 `file://host/path` URL, percent-decoding the path.
+fn parse_osc7(url: &str) -> Option<OscEvent> {
+    let rest = url.strip_prefix("file://")?;
+    let slash = rest.find('/')?;
+    let (host, path) = rest.split_at(slash);
+    Some(OscEvent::Osc7 {
+        host: host.to_string(),
+        path: percent_decode(path),
+    })
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Undo OSC 633 escaping: `\\` is a backslash and `\xAB` a byte (used for
+/// `;`, control characters and the OSC terminators).
+fn unescape_633(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            if bytes.get(i + 1) == Some(&b'\\') {
+                out.push(b'\\');
+                i += 2;
+                continue;
+            }
+            let hex = bytes
+                .get(i + 2..i + 4)
+                .filter(|_| bytes.get(i + 1) == Some(&b'x'))
+                .and_then(|h| std::str::from_utf8(h).ok())
+                .and_then(|h| u8::from_str_radix(h, 16).ok());
+            if let Some(b) = hex {
+                out.push(b);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// What the shell reports about itself through OSC 7 and OSC 633, which
+/// stays right where asking the OS about the shell process doesn't: over
+/// SSH, inside containers, or in a nested shell.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShellReports {
+    /// Working directory from the last OSC 7 or `633;P;Cwd=` sent with a
+    /// prompt.
+    pub cwd: Option<String>,
+    /// Command line from the last `633;E`, until taken.
+    pub command: Option<String>,
+    /// Whether the shell sends `633;E` at all. If it does, its reports
+    /// replace the command text reconstructed from keystrokes.
+    pub reports_commands: bool,
+    /// The local shell, then each shell integrated inside it with
+    /// `#!integrate`; the last one is the one reporting.
+    pub levels: Vec<ShellLevel>,
+    /// Whether any shell has reported its depth (`633;P;UaDepth=`).
+    depth_reported: bool,
+    /// Shell the next new level runs, named when its bootstrap is typed.
+    pending_shell: Option<String>,
+    /// `$0` from the `#!integrate` probe (`633;P;UaShell=`), until taken.
+    pub probed_shell: Option<String>,
+    /// Between a `133;D` and the next `133;A`, where the shell's prompt
+    /// code sends its reports. Anywhere else they may be a command's
+    /// output, e.g. a file being printed.
+    in_prompt: bool,
+}
+
+impl ShellReports {
+    /// Reports for a session whose local shell is `shell`.
+    pub fn new(shell: &str) -> Self {
+        Self {
+            levels: vec![ShellLevel {
+                shell: shell.to_string(),
+                ..ShellLevel::default()
+            }],
+            ..Self::default()
+        }
+    }
+
+    pub fn observe(&mut self, event: &OscEvent) {
+        match event {
+            OscEvent::Osc133D { .. } => self.in_prompt = true,
+            OscEvent::Osc133A | OscEvent::Osc133B | OscEvent::Osc133C => self.in_prompt = false,
+            OscEvent::Osc7 { host, path } if self.in_prompt => {
+                self.cwd = Some(path.clone());
+                if let Some(level) = self.current_level() {
+                    level.host = host.clone();
+                    level.cwd = path.clone();
+                }
+            }
+            OscEvent::Osc633P { key, value } if key == "Cwd" && self.in_prompt => {
+                self.cwd = Some(value.clone());
+                if let Some(level) = self.current_level() {
+                    level.cwd = value.clone();
+                }
+            }
+            OscEvent::Osc633P { key, value } if key == "UaDepth" && self.in_prompt => {
+                if let Ok(depth) = value.parse() {
+                    self.set_depth(depth);
+                }
+            }
+            // The probe is answered by a command, not at the prompt.
+            OscEvent::Osc633P { key, value } if key == "UaShell" => {
+                self.probed_shell = Some(value.clone());
+            }
+            OscEvent::Osc633E { command } => {
+                self.command = Some(command.clone());
+                self.reports_commands = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// Host the reporting shell runs on; empty until it says.
+    pub fn host(&self) -> &str {
+        self.levels.last().map_or("", |level| level.host.as_str())
+    }
+
+    /// Depth of the shell reporting now; 0 is the local shell.
+    pub fn depth(&self) -> usize {
+        self.levels.len().saturating_sub(1)
+    }
+
+    /// Depth for a shell about to be integrated with `#!integrate`: one
+    /// below the reporting shell, or 0 if no shell reports yet (the local
+    /// shell runs without integration).
+    pub fn next_depth(&self) -> usize {
+        if self.depth_reported {
+            self.levels.len()
+        } else {
+            0
+        }
+    }
+
+    /// Name the shell that will report the next new level.
+    pub fn expect_level(&mut self, shell: &str) {
+        self.pending_shell = Some(shell.to_string());
+    }
+
+    /// The levels, if the session is nested at all.
+    pub fn nested_levels(&self) -> Vec<ShellLevel> {
+        if self.depth() > 0 {
+            self.levels.clone()
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn current_level(&mut self) -> Option<&mut ShellLevel> {
+        self.levels.last_mut()
+    }
+
+    /// A shell reported its depth: deeper levels have exited, or a new one
+    /// was entered.
+    fn set_depth(&mut self, depth: usize) {
+        self.depth_reported = true;
+        let len = depth + 1;
+        if len == self.levels.len() {
+            return;
+        }
+        if len < self.levels.len() {
+            self.levels.truncate(len);
+            // The outer shell's cwd arrives with its next report.
+            self.cwd = None;
+        } else {
+            let shell = self
+                .pending_shell
+                .take()
+                .unwrap_or_else(|| "sh".to_string());
+            self.levels.resize_with(len, || ShellLevel {
+                shell: shell.clone(),
+                ..ShellLevel::default()
+            });
+        }
+        // Each shell says for itself whether it reports command lines.
+        self.reports_commands = false;
+        self.command = None;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -280,15 +544,185 @@ mod tests {
         assert_eq!(parser.terminal_state, TerminalState::Prompt);
     }
 
+    fn osc(code: &str, param: &str) -> Vec<u8> {
+        let mut v = vec![0x1b, b']'];
+        v.extend_from_slice(format!("{code};{param}").as_bytes());
+        v.push(0x07);
+        v
+    }
+
     #[test]
-    fn ignores_non_133_osc() {
+    fn ignores_unhandled_osc() {
         let mut parser = OscParser::new();
-        // OSC 7 (cwd notification)
-        let mut data = vec![0x1b, b']'];
-        data.extend_from_slice(b"7;file:///tmp");
-        data.push(0x07);
+        // OSC 0 (window title) and OSC 1337 (iTerm2), then a real event
+        let mut data = osc("0", "A title");
+        data.extend_from_slice(&osc("1337", "A"));
+        data.extend_from_slice(&osc_bytes("B"));
         let events = parser.feed_bytes(&data);
-        assert!(events.is_empty());
+        assert_eq!(events, vec![OscEvent::Osc133B]);
+    }
+
+    #[test]
+    fn parse_osc7_cwd() {
+        let mut parser = OscParser::new();
+        let events = parser.feed_bytes(&osc("7", "file://box/home/me/My%20Docs"));
+        assert_eq!(
+            events,
+            vec![OscEvent::Osc7 {
+                host: "box".to_string(),
+                path: "/home/me/My Docs".to_string(),
+            }]
+        );
+        // Reporting the directory doesn't change the prompt state.
+        assert_eq!(parser.terminal_state, TerminalState::Idle);
+        assert!(parser.feed_bytes(&osc("7", "/not/a/url")).is_empty());
+    }
+
+    #[test]
+    fn parse_633_command_line_and_properties() {
+        let mut parser = OscParser::new();
+        let mut data = osc("633", r"E;echo a\x3b b\\c\x0ad;nonce123");
+        data.extend_from_slice(&osc("633", "P;Cwd=/srv/app"));
+        data.extend_from_slice(&osc("633", "C"));
+        let events = parser.feed_bytes(&data);
+        assert_eq!(
+            events,
+            vec![
+                OscEvent::Osc633E {
+                    command: "echo a; b\\c\nd".to_string()
+                },
+                OscEvent::Osc633P {
+                    key: "Cwd".to_string(),
+                    value: "/srv/app".to_string()
+                },
+                OscEvent::Osc133C,
+            ]
+        );
+        assert_eq!(parser.terminal_state, TerminalState::Executing);
+    }
+
+    #[test]
+    fn long_command_line_is_kept() {
+        let mut parser = OscParser::new();
+        let command = "x".repeat(1000);
+        let events = parser.feed_bytes(&osc("633", &format!("E;{command}")));
+        assert_eq!(events, vec![OscEvent::Osc633E { command }]);
+    }
+
+    #[test]
+    fn shell_reports_track_cwd_and_command() {
+        let mut reports = ShellReports::default();
+        reports.observe(&OscEvent::Osc133D { exit_code: Some(0) });
+        reports.observe(&OscEvent::Osc7 {
+            host: "box".to_string(),
+            path: "/tmp".to_string(),
+        });
+        assert_eq!(reports.cwd.as_deref(), Some("/tmp"));
+        reports.observe(&OscEvent::Osc633P {
+            key: "Cwd".to_string(),
+            value: "/srv".to_string(),
+        });
+        assert_eq!(reports.cwd.as_deref(), Some("/srv"));
+        reports.observe(&OscEvent::Osc133A);
+        reports.observe(&OscEvent::Osc633E {
+            command: "make".to_string(),
+        });
+        assert_eq!(reports.command.take().as_deref(), Some("make"));
+        assert!(reports.reports_commands);
+    }
+
+    #[test]
+    fn shell_reports_track_nested_levels() {
+        let p = |key: &str, value: &str| OscEvent::Osc633P {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        let cwd = |host: &str, path: &str| OscEvent::Osc7 {
+            host: host.to_string(),
+            path: path.to_string(),
+        };
+        let done = OscEvent::Osc133D { exit_code: Some(0) };
+        let mut reports = ShellReports::new("/bin/bash");
+        reports.observe(&done);
+        reports.observe(&p("UaDepth", "0"));
+        reports.observe(&cwd("laptop", "/home/me"));
+        reports.observe(&OscEvent::Osc133A);
+        assert_eq!(reports.next_depth(), 1);
+        assert_eq!(reports.host(), "laptop");
+        assert!(reports.nested_levels().is_empty());
+
+        // `ssh web-1`, then `#!integrate`.
+        reports.observe(&p("UaShell", "-bash"));
+        assert_eq!(reports.probed_shell.take().as_deref(), Some("-bash"));
+        reports.expect_level("bash");
+        reports.observe(&OscEvent::Osc633E {
+            command: "ssh web-1".to_string(),
+        });
+        reports.observe(&done);
+        reports.observe(&p("UaDepth", "1"));
+        reports.observe(&cwd("web-1", "/srv"));
+        assert_eq!(reports.depth(), 1);
+        assert_eq!(reports.host(), "web-1");
+        assert!(!reports.reports_commands);
+        let levels = reports.nested_levels();
+        assert_eq!(levels.len(), 2);
+        assert_eq!(levels[0].host, "laptop");
+        assert_eq!(levels[0].cwd, "/home/me");
+        assert_eq!(levels[1].shell, "bash");
+        assert_eq!(levels[1].host, "web-1");
+        assert_eq!(reports.cwd.as_deref(), Some("/srv"));
+
+        // Back in the local shell.
+        reports.observe(&done);
+        reports.observe(&p("UaDepth", "0"));
+        assert_eq!(reports.depth(), 0);
+        assert_eq!(reports.cwd, None);
+        reports.observe(&cwd("laptop", "/home/me"));
+        assert_eq!(reports.cwd.as_deref(), Some("/home/me"));
+        assert_eq!(reports.host(), "laptop");
+    }
+
+    #[test]
+    fn shell_reports_ignore_reports_outside_the_prompt() {
+        let mut reports = ShellReports::new("/bin/bash");
+        let spoofed = [
+            OscEvent::Osc7 {
+                host: "prod".to_string(),
+                path: "/etc".to_string(),
+            },
+            OscEvent::Osc633P {
+                key: "Cwd".to_string(),
+                value: "/etc".to_string(),
+            },
+            OscEvent::Osc633P {
+                key: "UaDepth".to_string(),
+                value: "3".to_string(),
+            },
+        ];
+        // Before the first prompt, and in a command's output (`cat notes`).
+        for phase in [None, Some(OscEvent::Osc133C)] {
+            if let Some(event) = phase {
+                reports.observe(&event);
+            }
+            for event in &spoofed {
+                reports.observe(event);
+            }
+            assert_eq!(reports.cwd, None);
+            assert_eq!(reports.host(), "");
+            assert_eq!(reports.depth(), 0);
+        }
+
+        // The same sequences from the shell's prompt code count.
+        reports.observe(&OscEvent::Osc133D { exit_code: Some(0) });
+        reports.observe(&spoofed[0]);
+        assert_eq!(reports.cwd.as_deref(), Some("/etc"));
+        assert_eq!(reports.host(), "prod");
+    }
+
+    #[test]
+    fn next_depth_is_zero_until_a_shell_reports() {
+        let reports = ShellReports::new("/bin/tcsh");
+        assert_eq!(reports.next_depth(), 0);
     }
 
     #[test]
@@ -11,32 +11,33 @@
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 
-/// Count how many ancestors of `my_pid` have an executable matching `my_exe`.
+/// PIDs of the ancestors of `my_pid` whose executable matches `my_exe`,
+/// nearest first.
 ///
 /// `info_fn(pid)` returns `(parent_pid, exe_path)` for the given process,
 /// or `None` if the process doesn't exist / can't be inspected.
 ///
 /// This is the testable core — tests provide a fake `info_fn`, production
 /// provides the real OS-level one.
-fn count_matching_ancestors(
+fn matching_ancestors(
     my_exe: &Path,
     my_pid: u32,
     info_fn: &dyn Fn(u32) -> Option<(u32, PathBuf)>,
-) -> u32 {
-    let mut depth = 0;
+) -> Vec<u32> {
+    let mut ancestors = Vec::new();
     let mut seen = HashSet::new();
     seen.insert(my_pid);
 
     // Get our parent
     let mut current = match info_fn(my_pid) {
         Some((ppid, _)) => ppid,
-        None => return 0,
+        None => return ancestors,
     };
 
     while current > 1 && seen.insert(current) {
         if let Some((next_ppid, exe)) = info_fn(current) {
             if exe == my_exe {
-                depth += 1;
+                ancestors.push(current);
             }
             current = next_ppid;
         } else {
@@ -44,7 +45,16 @@ fn count_matching_ancestors(
         }
     }
 
-    depth
+    ancestors
+}
+
+/// Count how many ancestors of `my_pid` have an executable matching `my_exe`.
+fn count_matching_ancestors(
+    my_exe: &Path,
+    my_pid: u32,
+    info_fn: &dyn Fn(u32) -> Option<(u32, PathBuf)>,
+) -> u32 {
+    matching_ancestors(my_exe, my_pid, info_fn).len() as u32
 }
 
 /// Count how many ancestor processes are the same binary as the current process.
@@ -60,6 +70,31 @@ pub fn count_ancestor_depth() -> u32 {
     count_matching_ancestors(&my_exe, std::process::id(), &platform::process_info)
 }
 
+/// PID of the nearest ancestor running the same binary: the agent whose
+/// shell started this one. `None` at depth 0 or if the tree can't be walked.
+pub fn parent_agent() -> Option<u32> {
+    let my_exe = std::env::current_exe()
+        .and_then(|p| p.canonicalize())
+        .ok()?;
+    matching_ancestors(&my_exe, std::process::id(), &platform::process_info)
+        .first()
+        .copied()
+}
+
+/// The arguments and environment (`NAME=value`) a process was started
+/// with. Later changes to its environment don't show, and neither can its
+/// descendants change them. `None` if the process can't be inspected.
+pub fn launch_info(pid: u32) -> Option<(Vec<String>, Vec<String>)> {
+    platform::launch_info(pid)
+}
+
+/// Split a block of NUL-terminated strings.
+#[cfg_attr(not(any(target_os = "linux", target_os = "macos")), allow(dead_code))]
+fn nul_separated<'a>(buf: &'a [u8]) -> impl Iterator<Item = String> + 'a {
+    buf.split(|&b| b == 0)
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+}
+
 /// Check depth against the limit. Returns `Ok(depth)` if under the limit,
 /// `Err(depth)` if at or over.
 pub fn check_depth(max: u32) -> Result<u32, u32> {
@@ -202,6 +237,50 @@ mod platform {
         Some((ppid, exe))
     }
 
+    pub fn launch_info(pid: u32) -> Option<(Vec<String>, Vec<String>)> {
+        let mut mib = [libc::CTL_KERN, libc::KERN_PROCARGS2, pid as libc::c_int];
+        let mut size: libc::size_t = 0;
+        let ret = unsafe {
+            libc::sysctl(
+                mib.as_mut_ptr(),
+                3,
+                std::ptr::null_mut(),
+                &mut size,
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        if ret != 0 {
+            return None;
+        }
+        let mut buf = vec![0u8; size];
+        let ret = unsafe {
+            libc::sysctl(
+                mib.as_mut_ptr(),
+                3,
+                buf.as_mut_ptr() as *mut libc::c_void,
+                &mut size,
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        if ret != 0 || size < 4 {
+            return None;
+        }
+        buf.truncate(size);
+
+        // argc, the executable path and NUL padding, then argv and the
+        // environment, which ends at an empty string.
+        let argc = i32::from_ne_bytes(buf[..4].try_into().ok()?) as usize;
+        let rest = &buf[4..];
+        let exe_end = rest.iter().position(|&b| b == 0)?;
+        let start = rest[exe_end..].iter().position(|&b| b != 0)? + exe_end;
+        let mut strings = super::nul_separated(&rest[start..]);
+        let args: Vec<String> = strings.by_ref().take(argc).collect();
+        let env = strings.take_while(|s| !s.is_empty()).collect();
+        Some((args, env))
+    }
+
     pub fn cwd_of(pid: u32) -> Option<String> {
         let mut info: ProcVnodePathInfo = unsafe { std::mem::zeroed() };
         let size = std::mem::size_of::<ProcVnodePathInfo>() as libc::c_int;
@@ -305,6 +384,21 @@ mod platform {
         Some(link.to_string_lossy().to_string())
     }
 
+    pub fn launch_info(pid: u32) -> Option<(Vec<String>, Vec<String>)> {
+        let args = std::fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+        let env = std::fs::read(format!("/proc/{pid}/environ")).ok()?;
+        // Both end with a NUL, which leaves an empty last string.
+        let strings = |buf: &[u8]| -> Vec<String> {
+            let buf = buf.strip_suffix(&[0]).unwrap_or(buf);
+            if buf.is_empty() {
+                Vec::new()
+            } else {
+                super::nul_separated(buf).collect()
+            }
+        };
+        Some((strings(&args), strings(&env)))
+    }
+
     fn exe_of(pid: u32) -> Option<PathBuf> {
         let link = std::fs::read_link(format!("/proc/{pid}/exe")).ok()?;
         Some(link.canonicalize().unwrap_or(link))
@@ -343,6 +437,10 @@ mod platform {
         None // Can't introspect process CWD on this platform
     }
 
+    pub fn launch_info(_pid: u32) -> Option<(Vec<String>, Vec<String>)> {
+        None
+    }
+
     pub fn list_all_pids() -> Vec<u32> {
         Vec::new()
     }
@@ -403,6 +501,31 @@ mod tests {
         assert_eq!(depth, 2);
     }
 
+    #[test]
+    fn matching_ancestors_are_nearest_first() {
+        // unixagent(100) -> sh(101) -> unixagent(102) -> sh(103) -> unixagent(104)
+        let table: HashMap<u32, (u32, PathBuf)> = [
+            (104, (103, PathBuf::from("/usr/bin/unixagent"))),
+            (103, (102, PathBuf::from("/bin/sh"))),
+            (102, (101, PathBuf::from("/usr/bin/unixagent"))),
+            (101, (100, PathBuf::from("/bin/sh"))),
+            (100, (1, PathBuf::from("/usr/bin/unixagent"))),
+        ]
+        .into();
+
+        let ancestors =
+            matching_ancestors(Path::new("/usr/bin/unixagent"), 104, &fake_info(&table));
+        assert_eq!(ancestors, vec![102, 100]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn launch_info_of_self() {
+        let (args, env) = launch_info(std::process::id()).unwrap();
+        assert_eq!(args.len(), std::env::args().count());
+        assert!(env.iter().all(|var| var.contains('=')));
+    }
+
     #[test]
     fn skips_non_matching_ancestors() {
         // bash(50) -> vim(60) -> sh(70) -> unixagent(80) -> sh(90) -> unixagent(100)
@@ -0,0 +1,340 @@
+//! Prompt detection for shells that don't report their prompt.
+//!
+//! Without OSC 133 — `--no-integration`, an unknown shell, or a shell
+//! reached through `ssh`, `docker exec` or a serial console — the REPL
+//! can't tell a prompt from output. The detector guesses: when the output
+//! goes quiet with the cursor at the end of a line that matches a prompt
+//! pattern, it synthesizes `133;A` and `133;B` (preceded by `133;D` if it
+//! saw a command entered at its previous prompt). A line ending at that
+//! prompt — the echo of Enter — synthesizes `133;C`.
+//!
+//! Real markers always win: any OSC 133 event from the shell resets the
+//! detector, and it only looks for prompts while the terminal state says a
+//! command is running (or, without integration, while nothing has said
+//! anything yet). Once the shell has sent markers, a running command is
+//! only watched if the user started it — it may be `ssh` or a nested shell
+//! without integration. Agent commands started at a real prompt are not:
+//! a `100%` from `curl` is not a prompt to type the next command at.
+
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+
+use crate::config::ShellConfig;
+use crate::context::OutputHistory;
+use crate::osc::{OscEvent, TerminalState};
+
+pub struct PromptDetector {
+    patterns: Vec<Regex>,
+    idle: Duration,
+    /// Whether the shell's own integration is on, so the initial `Idle`
+    /// state is its to leave.
+    integration: bool,
+    /// Output as it would show on screen; only the current line matters.
+    screen: OutputHistory,
+    last_output: Instant,
+    /// We synthesized A/B and no line has been entered since.
+    at_prompt: bool,
+    /// A line was entered at our prompt; the next one gets a D first.
+    submitted: bool,
+    /// The shell has sent OSC 133 markers of its own.
+    integrated: bool,
+    /// The running command was started by the user at a real prompt, so
+    /// the prompts it shows may be a nested shell's.
+    nested: bool,
+}
+
+impl PromptDetector {
+    /// A detector for the configured patterns, or `None` if detection is
+    /// off. Invalid patterns are skipped (`config check` reports them).
+    pub fn from_config(config: &ShellConfig) -> Option<Self> {
+        if !config.prompt_detection {
+            return None;
+        }
+        let patterns = config
+            .prompt_patterns
+            .iter()
+            .filter_map(|p| Regex::new(p).ok())
+            .collect();
+        Some(Self::new(
+            patterns,
+            Duration::from_millis(config.prompt_idle_ms),
+            config.integration,
+        ))
+    }
+
+    pub fn new(patterns: Vec<Regex>, idle: Duration, integration: bool) -> Self {
+        Self {
+            patterns,
+            idle,
+            integration,
+            screen: OutputHistory::with_cr_reset(1),
+            last_output: Instant::now(),
+            at_prompt: false,
+            submitted: false,
+            integrated: false,
+            nested: false,
+        }
+    }
+
+    /// Feed PTY output and the events the parser found in it. Returns a
+    /// synthesized `133;C` when a line is entered at a detected prompt.
+    /// `agent_running` says the agent's commands are executing.
+    pub fn observe(
+        &mut self,
+        data: &[u8],
+        events: &[OscEvent],
+        agent_running: bool,
+        now: Instant,
+    ) -> Vec<OscEvent> {
+        if !data.is_empty() {
+            self.screen.feed(data);
+            self.last_output = now;
+        }
+        if let Some(last) = events.iter().rfind(|e| is_prompt_marker(e)) {
+            self.at_prompt = false;
+            self.submitted = false;
+            self.integrated = true;
+            self.nested = *last == OscEvent::Osc133C && !agent_running;
+            return Vec::new();
+        }
+        if self.at_prompt && data.contains(&b'\n') {
+            self.at_prompt = false;
+            self.submitted = true;
+            return vec![OscEvent::Osc133C];
+        }
+        Vec::new()
+    }
+
+    /// Synthesize a prompt if one has been sitting quietly on screen for
+    /// the idle time.
+    pub fn poll(&mut self, state: TerminalState, now: Instant) -> Vec<OscEvent> {
+        if self.next_check(state, now) != Some(Duration::ZERO) {
+            return Vec::new();
+        }
+        let mut events = Vec::new();
+        if std::mem::take(&mut self.submitted) {
+            events.push(OscEvent::Osc133D { exit_code: None });
+        }
+        events.extend([OscEvent::Osc133A, OscEvent::Osc133B]);
+        self.at_prompt = true;
+        events
+    }
+
+    /// Whether the current prompt is one the detector synthesized.
+    pub fn at_prompt(&self) -> bool {
+        self.at_prompt
+    }
+
+    /// How long until [`PromptDetector::poll`] could synthesize a prompt,
+    /// or `None` if nothing on screen looks like one.
+    pub fn next_check(&self, state: TerminalState, now: Instant) -> Option<Duration> {
+        let armed = match state {
+            TerminalState::Executing => !self.integrated || self.nested,
+            TerminalState::Idle => !self.integration,
+            TerminalState::Prompt | TerminalState::Input => false,
+        };
+        if !armed || self.at_prompt || !self.looks_like_prompt() {
+            return None;
+        }
+        Some(
+            (self.last_output + self.idle)
+                .checked_duration_since(now)
+                .unwrap_or(Duration::ZERO),
+        )
+    }
+
+    fn looks_like_prompt(&self) -> bool {
+        let line = self.screen.current_line();
+        !line.trim().is_empty() && self.patterns.iter().any(|p| p.is_match(line))
+    }
+}
+
+fn is_prompt_marker(event: &OscEvent) -> bool {
+    matches!(
+        event,
+        OscEvent::Osc133A | OscEvent::Osc133B | OscEvent::Osc133C | OscEvent::Osc133D { .. }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ShellConfig;
+
+    fn detector(integration: bool) -> PromptDetector {
+        let config = ShellConfig {
+            integration,
+            ..ShellConfig::default()
+        };
+        PromptDetector::from_config(&config).unwrap()
+    }
+
+    fn later(start: Instant, ms: u64) -> Instant {
+        start + Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn quiet_prompt_becomes_a_b() {
+        let mut d = detector(false);
+        let t = Instant::now();
+        assert!(d
+            .observe(b"Welcome\r\nme@box:~$ ", &[], false, t)
+            .is_empty());
+        // Not quiet long enough yet.
+        assert!(d.poll(TerminalState::Idle, later(t, 50)).is_empty());
+        assert_eq!(
+            d.next_check(TerminalState::Idle, later(t, 50)),
+            Some(Duration::from_millis(150))
+        );
+        assert_eq!(
+            d.poll(TerminalState::Idle, later(t, 250)),
+            vec![OscEvent::Osc133A, OscEvent::Osc133B]
+        );
+        // Only once per prompt.
+        assert!(d.poll(TerminalState::Input, later(t, 500)).is_empty());
+    }
+
+    #[test]
+    fn entered_line_is_c_and_next_prompt_gets_d() {
+        let mut d = detector(false);
+        let t = Instant::now();
+        d.observe(b"$ ", &[], false, t);
+        d.poll(TerminalState::Idle, later(t, 300));
+
+        // Typing echoes without a newline; Enter ends the line.
+        assert!(d.observe(b"ls", &[], false, later(t, 400)).is_empty());
+        assert_eq!(
+            d.observe(b"\r\n", &[], false, later(t, 500)),
+            vec![OscEvent::Osc133C]
+        );
+        d.observe(b"a.txt  b.txt\r\n/ # ", &[], false, later(t, 600));
+        assert_eq!(
+            d.poll(TerminalState::Executing, later(t, 900)),
+            vec![
+                OscEvent::Osc133D { exit_code: None },
+                OscEvent::Osc133A,
+                OscEvent::Osc133B
+            ]
+        );
+    }
+
+    #[test]
+    fn output_that_is_not_a_prompt_is_ignored() {
+        let mut d = detector(false);
+        let t = Instant::now();
+        d.observe(b"Downloading... 45%", &[], false, t);
+        assert_eq!(d.next_check(TerminalState::Idle, t), None);
+        d.observe(b"\r\nDone.\r\n", &[], false, t);
+        assert_eq!(d.next_check(TerminalState::Idle, t), None);
+        // Python's prompt is not a shell prompt.
+        d.observe(b">>> ", &[], false, t);
+        assert!(d.poll(TerminalState::Executing, later(t, 1000)).is_empty());
+    }
+
+    #[test]
+    fn redrawn_prompt_line_still_matches() {
+        let mut d = detector(false);
+        let t = Instant::now();
+        d.observe(
+            b"\x1b[1;32mroot@web-1\x1b[0m:/srv# typo\r\x1b[K",
+            &[],
+            false,
+            t,
+        );
+        d.observe(b"root@web-1:/srv# ", &[], false, t);
+        assert_eq!(d.poll(TerminalState::Idle, later(t, 300)).len(), 2);
+    }
+
+    #[test]
+    fn with_integration_only_running_commands_are_watched() {
+        let mut d = detector(true);
+        let t = Instant::now();
+        // The login prompt before the integration script has loaded.
+        d.observe(b"$ ", &[], false, t);
+        assert!(d.poll(TerminalState::Idle, later(t, 300)).is_empty());
+        assert!(d.poll(TerminalState::Input, later(t, 300)).is_empty());
+
+        // `ssh host` is running (the local shell sent 133;C); the remote
+        // prompt shows up. No D: the local command hasn't finished.
+        d.observe(b"\r\n", &[OscEvent::Osc133C], false, later(t, 400));
+        d.observe(b"admin@remote:~$ ", &[], false, later(t, 500));
+        assert_eq!(
+            d.poll(TerminalState::Executing, later(t, 800)),
+            vec![OscEvent::Osc133A, OscEvent::Osc133B]
+        );
+    }
+
+    #[test]
+    fn agent_commands_at_a_real_prompt_are_not_watched() {
+        let mut d = detector(true);
+        let t = Instant::now();
+        let prompt = [OscEvent::Osc133A, OscEvent::Osc133B];
+        d.observe(b"me@laptop:~$ ", &prompt, false, t);
+
+        // The agent's command prints something prompt-like and goes quiet.
+        d.observe(b"\r\n", &[OscEvent::Osc133C], true, later(t, 100));
+        d.observe(b"admin@remote:~$ ", &[], true, later(t, 200));
+        assert_eq!(d.next_check(TerminalState::Executing, later(t, 200)), None);
+        assert!(d.poll(TerminalState::Executing, later(t, 900)).is_empty());
+
+        // The same from a command the user started may be a nested shell.
+        d.observe(b"", &prompt, false, later(t, 1000));
+        d.observe(b"\r\n", &[OscEvent::Osc133C], false, later(t, 1100));
+        d.observe(b"admin@remote:~$ ", &[], false, later(t, 1200));
+        assert_eq!(d.poll(TerminalState::Executing, later(t, 1500)).len(), 2);
+    }
+
+    #[test]
+    fn default_patterns_need_prompt_like_text() {
+        let mut d = detector(false);
+        let t = Instant::now();
+        for line in ["100%", "100% ", "#", "# 3", "$5", "total: 42 $ "] {
+            d.observe(format!("\r\n{line}").as_bytes(), &[], false, t);
+            assert_eq!(d.next_check(TerminalState::Idle, t), None, "{line:?}");
+        }
+        for line in [
+            "$ ",
+            "# ",
+            "bash-5.2# ",
+            "sh-5.1$ ",
+            "/ # ",
+            "~/app % ",
+            "[root@box ~]# ",
+            "me@box:~/src$ ",
+            "PS /home/me> ",
+        ] {
+            d.observe(format!("\r\n{line}").as_bytes(), &[], false, t);
+            assert!(d.next_check(TerminalState::Idle, t).is_some(), "{line:?}");
+        }
+    }
+
+    #[test]
+    fn real_markers_reset_the_detector() {
+        let mut d = detector(true);
+        let t = Instant::now();
+        d.observe(b"admin@remote:~$ ", &[], false, t);
+        d.poll(TerminalState::Executing, later(t, 300));
+        d.observe(b"exit\r\n", &[], false, later(t, 400));
+
+        // Back in the local shell, which reports its own prompt.
+        let real = [
+            OscEvent::Osc133D { exit_code: Some(0) },
+            OscEvent::Osc133A,
+            OscEvent::Osc133B,
+        ];
+        assert!(d
+            .observe(b"me@laptop:~$ ", &real, false, later(t, 500))
+            .is_empty());
+        assert!(d.poll(TerminalState::Input, later(t, 900)).is_empty());
+    }
+
+    #[test]
+    fn disabled_by_config() {
+        let config = ShellConfig {
+            prompt_detection: false,
+            ..ShellConfig::default()
+        };
+        assert!(PromptDetector::from_config(&config).is_none());
+    }
+}
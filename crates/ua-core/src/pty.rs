@@ -1,8 +1,63 @@
 use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
 use std::io::{self, Read, Write};
-use ua_sandbox::SandboxPolicy;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use ua_sandbox::{ReportPipe, SandboxPolicy};
 
-use crate::shell_scripts::{detect_shell, integration_script};
+use crate::shell_scripts::{detect_shell, integration_script, script_suffix, source_command};
+
+/// The fd the shell holds a [`ReportFifo`] on, for `--sandbox-exec`
+/// commands it starts to report to. A single digit for `/bin/sh`.
+pub const SHELL_REPORT_FD: i32 = 9;
+
+/// How long to wait for the shell to open a [`ReportFifo`].
+const FIFO_OPEN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A FIFO in a private directory that a shell spawned with it inherits as
+/// [`SHELL_REPORT_FD`], so `--sandbox-exec` commands typed into the shell
+/// can report to the agent without going through the terminal.
+pub struct ReportFifo {
+    dir: tempfile::TempDir,
+    read: File,
+}
+
+impl ReportFifo {
+    /// Create the FIFO and open its reading end.
+    pub fn new() -> io::Result<Self> {
+        let dir = tempfile::Builder::new().prefix("ua-reports").tempdir()?;
+        let path = dir.path().join("fifo");
+        let c_path = CString::new(path.as_os_str().as_bytes()).map_err(io::Error::other)?;
+        if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Non-blocking, or opening would wait for the shell.
+        let read = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&path)?;
+        Ok(Self { dir, read })
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir.path().join("fifo")
+    }
+
+    /// Wait for the shell to open the FIFO, then remove it so nothing else
+    /// can. The pipe carries nothing if the shell never opened it.
+    pub fn connect(mut self) -> ReportPipe {
+        let deadline = Instant::now() + FIFO_OPEN_TIMEOUT;
+        // Without a writer a read is EOF; with one and no data, EAGAIN.
+        while matches!(self.read.read(&mut [0u8; 1]), Ok(0)) && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.dir.close();
+        ReportPipe::from_reader(self.read)
+    }
+}
 
 pub struct PtySession {
     master: Box<dyn MasterPty + Send>,
@@ -22,6 +77,19 @@ impl PtySession {
         shell_cmd: &str,
         integration: bool,
         sandbox_policy: Option<&SandboxPolicy>,
+    ) -> io::Result<(Self, Box<dyn Read + Send>)> {
+        Self::spawn_with_env(shell_cmd, integration, sandbox_policy, &[], None)
+    }
+
+    /// Like [`PtySession::spawn`], with extra environment variables for the
+    /// shell. With `report_fifo`, the shell holds the FIFO open for writing
+    /// on [`SHELL_REPORT_FD`].
+    pub fn spawn_with_env(
+        shell_cmd: &str,
+        integration: bool,
+        sandbox_policy: Option<&SandboxPolicy>,
+        env: &[(&str, String)],
+        report_fifo: Option<&ReportFifo>,
     ) -> io::Result<(Self, Box<dyn Read + Send>)> {
         let (cols, rows) = crossterm::terminal::size().unwrap_or((80, 24));
 
@@ -41,7 +109,9 @@ impl PtySession {
         // The script ends with `clear` to wipe any startup artifacts.
         let integration_file = if integration {
             if let Some(script) = integration_script(kind) {
-                let mut tmpfile = tempfile::NamedTempFile::new()?;
+                let mut tmpfile = tempfile::Builder::new()
+                    .suffix(script_suffix(kind))
+                    .tempfile()?;
                 tmpfile.write_all(script.as_bytes())?;
                 tmpfile.flush()?;
                 Some(tmpfile)
@@ -52,21 +122,31 @@ impl PtySession {
             None
         };
 
-        let mut cmd = if let Some(policy) = sandbox_policy {
+        let mut argv: Vec<std::ffi::OsString> = if sandbox_policy.is_some() {
             // Sandbox mode: run via `unixagent --sandbox-exec <shell> -l`
             let exe = std::env::current_exe().map_err(io::Error::other)?;
-            let mut c = CommandBuilder::new(exe);
-            c.arg("--sandbox-exec");
-            c.arg(shell_cmd);
-            c.arg("-l");
-            c.env(ua_sandbox::policy::SANDBOX_ENV_VAR, policy.to_json());
-            c
+            vec![exe.into(), "--sandbox-exec".into(), shell_cmd.into()]
         } else {
-            let mut c = CommandBuilder::new(shell_cmd);
-            // Pass -l for login shell behavior (profile sourcing).
-            c.arg("-l");
-            c
+            vec![shell_cmd.into()]
         };
+        // Pass -l for login shell behavior (profile sourcing).
+        argv.push("-l".into());
+        if let Some(fifo) = report_fifo {
+            // portable-pty closes inherited fds, so the shell opens the
+            // FIFO itself before becoming the real shell.
+            let mut trampoline: Vec<std::ffi::OsString> = vec![
+                "/bin/sh".into(),
+                "-c".into(),
+                format!("command exec {SHELL_REPORT_FD}>\"$0\"; exec \"$@\"").into(),
+                fifo.path().into_os_string(),
+            ];
+            trampoline.append(&mut argv);
+            argv = trampoline;
+        }
+        let mut cmd = CommandBuilder::from_argv(argv);
+        if let Some(policy) = sandbox_policy {
+            cmd.env(ua_sandbox::policy::SANDBOX_ENV_VAR, policy.to_json());
+        }
         // Start in the user's current directory.
         if let Ok(cwd) = std::env::current_dir() {
             cmd.cwd(cwd);
@@ -75,6 +155,9 @@ impl PtySession {
             "TERM",
             std::env::var("TERM").unwrap_or_else(|_| "xterm-256color".into()),
         );
+        for (key, value) in env {
+            cmd.env(key, value);
+        }
 
         let child = pair.slave.spawn_command(cmd).map_err(io::Error::other)?;
 
@@ -83,13 +166,10 @@ impl PtySession {
         let mut writer = pair.master.take_writer().map_err(io::Error::other)?;
 
         // Source the integration script by writing a short command to the PTY.
-        // Leading space prevents it from being added to shell history.
         // The script itself ends with `clear`, which wipes this source line
         // from the visible terminal.
         if let Some(ref tmpfile) = integration_file {
-            let path = tmpfile.path().to_string_lossy();
-            // `source` works in bash, zsh, and fish.
-            let source_cmd = format!(" source {path}\n");
+            let source_cmd = source_command(kind, &tmpfile.path().to_string_lossy());
             writer.write_all(source_cmd.as_bytes())?;
             writer.flush()?;
         }
@@ -189,4 +269,38 @@ mod tests {
         }
         assert!(exited, "child should have exited");
     }
+
+    #[test]
+    fn shell_holds_report_fifo() {
+        let fifo = ReportFifo::new().unwrap();
+        let path = fifo.path();
+        let (mut session, mut reader) =
+            PtySession::spawn_with_env("/bin/sh", false, None, &[], Some(&fifo))
+                .expect("spawn failed");
+        let mut reports = fifo.connect();
+        assert!(!path.exists(), "FIFO should be removed once open");
+
+        session
+            .write_all(b"echo '[ua:sandbox] from the shell' >&9; echo fifo_done\n")
+            .expect("write failed");
+        let mut output = Vec::new();
+        let mut buf = [0u8; 1024];
+        for _ in 0..20 {
+            thread::sleep(Duration::from_millis(100));
+            match reader.read(&mut buf) {
+                Ok(n) if n > 0 => output.extend_from_slice(&buf[..n]),
+                _ => {}
+            }
+            // The echoed input holds it once; the output twice.
+            if String::from_utf8_lossy(&output)
+                .matches("fifo_done")
+                .count()
+                >= 2
+            {
+                break;
+            }
+        }
+        assert_eq!(reports.take().messages, vec!["[ua:sandbox] from the shell"]);
+        session.write_all(b"exit\n").expect("write exit failed");
+    }
 }
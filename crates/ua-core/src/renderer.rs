@@ -5,10 +5,10 @@
 //! method clears the spinner first, preventing spinner bleed into PTY output.
 
 use std::io::Write;
-use std::path::PathBuf;
 
 use crate::policy::RiskLevel;
 use crate::style::{format_tokens, Style};
+use ua_sandbox::SandboxPolicy;
 
 /// Braille spinner frames.
 const SPINNER_FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
@@ -122,6 +122,33 @@ impl<W: Write> ReplRenderer<W> {
         );
     }
 
+    /// Show a command auto-approved by a session grant: `  ❯ cmd  ▐ granted`
+    pub fn emit_command_granted(&mut self, cmd: &str) {
+        self.clear_spinner();
+        let safe = cmd.replace('\n', "\r\n");
+        let _ = writeln!(
+            self.writer,
+            "\r  ❯ {safe}  {}▐ granted{}",
+            self.style.dim_start(),
+            self.style.reset(),
+        );
+    }
+
+    /// Show the grants recorded by `[a] always`: `[ua] allowed for this session: ...`
+    pub fn emit_grants_recorded(&mut self, grants: &[String]) {
+        self.clear_spinner();
+        if grants.is_empty() {
+            return;
+        }
+        let _ = writeln!(
+            self.writer,
+            "\r\n{}[ua] allowed for this session: {}{}\r",
+            self.style.dim_start(),
+            grants.join(", "),
+            self.style.reset(),
+        );
+    }
+
     /// Show a command with its risk label: `  ❯ cmd  ▐ label`
     pub fn emit_command_risk(&mut self, cmd: &str, risk: &RiskLevel) {
         self.clear_spinner();
@@ -147,6 +174,30 @@ impl<W: Write> ReplRenderer<W> {
         );
     }
 
+    /// Show a command rejected by a pre-exec hook: `  ❯ cmd  ▐ DENIED hook: reason`
+    pub fn emit_hook_denied(&mut self, cmd: &str, hook: &str, reason: &str) {
+        self.clear_spinner();
+        let safe = cmd.replace('\n', "\r\n");
+        let _ = writeln!(
+            self.writer,
+            "\r  ❯ {safe}  {}▐ DENIED{} {hook}: {reason}",
+            self.style.red_start(),
+            self.style.reset(),
+        );
+    }
+
+    /// Show a command rewritten by a pre-exec hook: `  ↻ hook: new command`
+    pub fn emit_hook_modified(&mut self, hook: &str, new_cmd: &str) {
+        self.clear_spinner();
+        let safe = new_cmd.replace('\n', "\r\n");
+        let _ = writeln!(
+            self.writer,
+            "\r  {}↻ {hook}:{} {safe}",
+            self.style.yellow_start(),
+            self.style.reset(),
+        );
+    }
+
     /// Show an argument safety warning: `  ⚠ reason`
     pub fn emit_arg_warning(&mut self, reason: &str) {
         self.clear_spinner();
@@ -192,17 +243,63 @@ impl<W: Write> ReplRenderer<W> {
         );
     }
 
-    /// Show the approval prompt: `[y] run  [n] skip  [e] edit` or `Type 'yes' to approve: `
-    pub fn emit_approval_prompt(&mut self, privileged: bool) {
+    /// Show a warning `--sandbox-exec` reported about the sandbox itself.
+    pub fn emit_sandbox_report(&mut self, msg: &str) {
+        self.clear_spinner();
+        let _ = writeln!(
+            self.writer,
+            "\r\x1b[K{}{}{}",
+            self.style.dim_start(),
+            msg,
+            self.style.reset()
+        );
+    }
+
+    /// Show the approval prompt: `[y] run  [a] always  [n] skip  [e] edit` or `Type 'yes' to approve: `.
+    /// `step` adds `[s] step` for multi-command batches.
+    pub fn emit_approval_prompt(&mut self, privileged: bool, step: bool) {
         self.clear_spinner();
         if privileged {
             let _ = write!(self.writer, "\rType 'yes' to approve: ");
+        } else if step {
+            let _ = write!(
+                self.writer,
+                "\r[y] run  [a] always  [s] step  [n] skip  [e] edit "
+            );
         } else {
-            let _ = write!(self.writer, "\r[y] run  [n] skip  [e] edit ");
+            let _ = write!(self.writer, "\r[y] run  [a] always  [n] skip  [e] edit ");
         }
         let _ = self.writer.flush();
     }
 
+    /// Show one step of step-through mode: `[2/3] ❯ cmd  ▐ risk` and its prompt.
+    pub fn emit_step_prompt(&mut self, step: usize, total: usize, cmd: &str, risk: &RiskLevel) {
+        self.clear_spinner();
+        let safe = cmd.replace('\n', "\r\n");
+        let color = risk_color(risk, &self.style);
+        let label = risk.label();
+        let _ = write!(
+            self.writer,
+            "\r\x1b[K{}[{step}/{total}]{} ❯ {safe}  {color}▐ {label}{}\r\n\
+             [y] run  [n] skip  [e] edit  [q] stop ",
+            self.style.dim_start(),
+            self.style.reset(),
+            self.style.reset(),
+        );
+        let _ = self.writer.flush();
+    }
+
+    /// Show a skipped step: `  ↷ skipped`
+    pub fn emit_step_skipped(&mut self) {
+        self.clear_spinner();
+        let _ = writeln!(
+            self.writer,
+            "\r\x1b[K  {}↷ skipped{}",
+            self.style.dim_start(),
+            self.style.reset(),
+        );
+    }
+
     /// Show the footer stats line: `1.2k↑ 500↓  2 cmds  3s`
     pub fn emit_footer(&mut self, input_tokens: u32, output_tokens: u32, cmds: u32, secs: u64) {
         self.clear_spinner();
@@ -271,18 +368,27 @@ impl<W: Write> ReplRenderer<W> {
         let _ = self.writer.flush();
     }
 
-    /// Show sandbox warning at startup with writable paths.
-    pub fn emit_sandbox_warning(&mut self, writable_paths: &[PathBuf]) {
+    /// Show sandbox warning at startup: writable paths, network mode,
+    /// syscall filter and timeout.
+    pub fn emit_sandbox_warning(&mut self, policy: &SandboxPolicy) {
         self.clear_spinner();
-        let paths: Vec<String> = writable_paths
+        let paths: Vec<String> = policy
+            .writable
             .iter()
             .map(|p| p.to_string_lossy().into_owned())
             .collect();
+        let timeout = match policy.limits.timeout_secs {
+            0 => "none".to_string(),
+            secs => format!("{secs}s"),
+        };
         let _ = writeln!(
             self.writer,
-            "\r{}sandbox: r/w access limited to {}{}",
+            "\r{}sandbox: r/w access limited to {} \u{b7} network {} \u{b7} seccomp {} \u{b7} timeout {}{}",
             self.style.dim_start(),
             paths.join(", "),
+            policy.network.mode.as_str(),
+            policy.seccomp.as_str(),
+            timeout,
             self.style.reset(),
         );
     }
@@ -308,10 +414,59 @@ impl<W: Write> ReplRenderer<W> {
         );
     }
 
-    /// Show "edit not yet implemented" message.
-    pub fn emit_edit_not_implemented(&mut self) {
+    /// Redraw the inline command editor: `  ✎ cmd`, with the terminal cursor
+    /// moved back `after_cursor` chars from the end of the line.
+    pub fn emit_edit_line(&mut self, text: &str, after_cursor: usize) {
+        self.draw_input_line("  ✎ ", text, after_cursor);
+    }
+
+    /// Redraw the mid-plan `# instruction` input line.
+    pub fn emit_steer_line(&mut self, text: &str, after_cursor: usize) {
+        self.draw_input_line("", text, after_cursor);
+    }
+
+    fn draw_input_line(&mut self, marker: &str, text: &str, after_cursor: usize) {
+        self.clear_spinner();
+        let _ = write!(self.writer, "\r\x1b[K{marker}{text}");
+        if after_cursor > 0 {
+            let _ = write!(self.writer, "\x1b[{after_cursor}D");
+        }
+        let _ = self.writer.flush();
+    }
+
+    /// End the current input line, keeping it on screen.
+    pub fn emit_line_break(&mut self) {
+        self.clear_spinner();
+        let _ = write!(self.writer, "\r\n");
+        let _ = self.writer.flush();
+    }
+
+    /// Show one line of a pasted multi-line instruction: `# text`
+    pub fn emit_instruction_line(&mut self, line: &str) {
+        self.clear_spinner();
+        let _ = writeln!(self.writer, "\r\x1b[K# {line}\r");
+    }
+
+    /// Show a mid-plan redirect: `[ua] redirected: instruction`
+    pub fn emit_redirected(&mut self, instruction: &str) {
+        self.clear_spinner();
+        let _ = writeln!(
+            self.writer,
+            "\r\x1b[K{}[ua] redirected: {instruction}{}\r",
+            self.style.dim_start(),
+            self.style.reset(),
+        );
+    }
+
+    /// Show the outcome of `#!integrate`: `[ua] integrate: msg`
+    pub fn emit_integrate(&mut self, msg: &str) {
         self.clear_spinner();
-        let _ = writeln!(self.writer, "\r\n  (edit not yet implemented)\r");
+        let _ = writeln!(
+            self.writer,
+            "\r\n{}[ua] integrate: {msg}{}\r",
+            self.style.dim_start(),
+            self.style.reset(),
+        );
     }
 
     /// Write a single character (for yes-buffer echo).
@@ -344,6 +499,7 @@ pub fn risk_color<'a>(risk: &RiskLevel, style: &'a Style) -> &'a str {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::PathBuf;
 
     fn make_renderer(style: Style) -> ReplRenderer<Vec<u8>> {
         ReplRenderer::new(Vec::new(), style)
@@ -446,19 +602,54 @@ mod tests {
         r.emit_spinner_initial();
         r.emit_stream_end();
         r.emit_command_risk("rm -rf build/", &RiskLevel::Destructive);
-        r.emit_approval_prompt(false);
+        r.emit_approval_prompt(false, false);
 
         let s = output_str(&r);
         assert!(s.contains("❯ rm -rf build/"), "should have command");
         assert!(s.contains("destructive"), "should have risk label");
         assert!(s.contains("[y] run"), "should have approval prompt");
+        assert!(s.contains("[a] always"), "should offer session grant");
+    }
+
+    #[test]
+    fn approval_prompt_offers_step_for_batches() {
+        let mut r = make_renderer(Style::disabled());
+        r.emit_approval_prompt(false, true);
+        assert!(output_str(&r).contains("[s] step"));
+
+        let mut r = make_renderer(Style::disabled());
+        r.emit_approval_prompt(false, false);
+        assert!(!output_str(&r).contains("[s] step"));
+    }
+
+    #[test]
+    fn step_prompt_and_skip() {
+        let mut r = make_renderer(Style::disabled());
+        r.emit_step_prompt(2, 3, "rm -rf build", &RiskLevel::Destructive);
+        r.emit_step_skipped();
+
+        let s = output_str(&r);
+        assert!(s.contains("[2/3] ❯ rm -rf build  ▐ destructive"));
+        assert!(s.contains("[y] run  [n] skip  [e] edit  [q] stop"));
+        assert!(s.contains("↷ skipped"));
+    }
+
+    #[test]
+    fn granted_command_and_grant_summary() {
+        let mut r = make_renderer(Style::disabled());
+        r.emit_command_granted("cargo test -p foo");
+        r.emit_grants_recorded(&["cargo test *".to_string(), "git clean -fdx".to_string()]);
+
+        let s = output_str(&r);
+        assert!(s.contains("❯ cargo test -p foo  ▐ granted"));
+        assert!(s.contains("allowed for this session: cargo test *, git clean -fdx"));
     }
 
     #[test]
     fn approval_required_with_ansi() {
         let mut r = make_renderer(Style::force_enabled());
         r.emit_command_risk("rm -rf build/", &RiskLevel::Destructive);
-        r.emit_approval_prompt(false);
+        r.emit_approval_prompt(false, false);
 
         let s = output_str(&r);
         assert!(s.contains("\x1b[31m"), "destructive should be red");
@@ -474,7 +665,7 @@ mod tests {
             "This command requires root privileges",
             &["It will modify system packages"],
         );
-        r.emit_approval_prompt(true);
+        r.emit_approval_prompt(true, false);
 
         let s = output_str(&r);
         assert!(s.contains("⚠"), "should have warning symbol");
@@ -490,7 +681,7 @@ mod tests {
         let mut r = make_renderer(Style::force_enabled());
         r.emit_command_risk("sudo apt install", &RiskLevel::Privileged);
         r.emit_judge_warning("dangerous", &["details"]);
-        r.emit_approval_prompt(true);
+        r.emit_approval_prompt(true, false);
 
         let s = output_str(&r);
         assert!(s.contains("\x1b[33m"), "warning should be yellow");
@@ -624,6 +815,27 @@ mod tests {
         assert!(s.contains("\x1b[31m"), "DENIED should be red");
     }
 
+    #[test]
+    fn hook_denied_shows_hook_and_reason() {
+        let mut r = make_renderer(Style::disabled());
+        r.emit_hook_denied("kubectl delete pod web", "pre-exec.sh", "no prod");
+
+        let s = output_str(&r);
+        assert!(s.contains("DENIED"));
+        assert!(s.contains("kubectl delete pod web"));
+        assert!(s.contains("pre-exec.sh: no prod"));
+    }
+
+    #[test]
+    fn hook_modified_shows_new_command() {
+        let mut r = make_renderer(Style::disabled());
+        r.emit_hook_modified("pre-exec.sh", "rm -i build");
+
+        let s = output_str(&r);
+        assert!(s.contains("pre-exec.sh:"));
+        assert!(s.contains("rm -i build"));
+    }
+
     // ── Scenario 8: Cancelled ───────────────────────────────────────────
 
     #[test]
@@ -678,6 +890,30 @@ mod tests {
         assert!(s.contains("type 'yes' to approve"));
     }
 
+    #[test]
+    fn emit_edit_line_positions_cursor() {
+        let mut r = make_renderer(Style::disabled());
+        r.emit_edit_line("rm -ri build", 5);
+        let s = output_str(&r);
+        assert!(s.starts_with("\r\x1b[K"));
+        assert!(s.contains("✎ rm -ri build"));
+        assert!(s.ends_with("\x1b[5D"));
+
+        let mut r = make_renderer(Style::disabled());
+        r.emit_edit_line("ls", 0);
+        assert!(!output_str(&r).contains("D"));
+    }
+
+    #[test]
+    fn emit_steer_line_and_redirected() {
+        let mut r = make_renderer(Style::disabled());
+        r.emit_steer_line("# keep the cache", 0);
+        r.emit_redirected("keep the cache");
+        let s = output_str(&r);
+        assert!(s.starts_with("\r\x1b[K# keep the cache"));
+        assert!(s.contains("[ua] redirected: keep the cache"));
+    }
+
     #[test]
     fn emit_skipped_without_reason() {
         let mut r = make_renderer(Style::disabled());
@@ -737,7 +973,11 @@ mod tests {
     #[test]
     fn emit_sandbox_warning_no_ansi() {
         let mut r = make_renderer(Style::disabled());
-        r.emit_sandbox_warning(&[PathBuf::from("/home/user/project"), PathBuf::from("/tmp")]);
+        let policy = SandboxPolicy {
+            writable: vec![PathBuf::from("/home/user/project"), PathBuf::from("/tmp")],
+            ..Default::default()
+        };
+        r.emit_sandbox_warning(&policy);
         let s = output_str(&r);
         assert!(s.contains("sandbox:"), "should say sandbox:");
         assert!(
@@ -745,12 +985,18 @@ mod tests {
             "should list writable path"
         );
         assert!(s.contains("/tmp"), "should list /tmp");
+        assert!(s.contains("network allow"), "should show network mode: {s}");
+        assert!(s.contains("timeout 300s"), "should show timeout: {s}");
     }
 
     #[test]
     fn emit_sandbox_warning_with_ansi() {
         let mut r = make_renderer(Style::force_enabled());
-        r.emit_sandbox_warning(&[PathBuf::from("/tmp")]);
+        let policy = SandboxPolicy {
+            writable: vec![PathBuf::from("/tmp")],
+            ..Default::default()
+        };
+        r.emit_sandbox_warning(&policy);
         let s = output_str(&r);
         assert!(s.contains("\x1b[2m"), "should have dim codes");
         assert!(s.contains("sandbox:"));
@@ -1,33 +1,42 @@
 use std::collections::{HashSet, VecDeque};
 use std::io::{self, Read, Write};
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc};
 use std::thread::{self, JoinHandle};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use futures::StreamExt;
 use tokio::runtime::Handle;
 use tokio::sync::oneshot;
 use ua_backend::anthropic::build_system_prompt;
-use ua_backend::AnthropicClient;
+use ua_backend::Backend;
 use ua_protocol::{StreamEvent, ToolResultRecord, ToolUseRecord};
 
 use crate::agents;
 use crate::audit::AuditLogger;
 use crate::config::Config;
 use crate::context::{
-    build_agent_request, build_shell_context, scrub_injection_markers, OutputHistory,
+    build_agent_request, build_shell_context, scrub_injection_markers, shell_cwd, OutputHistory,
     TOOL_RESULT_PREFIX,
 };
 use crate::display::PlanDisplay;
+use crate::grants::SessionGrants;
+use crate::hooks::{self, HookContext, HookDenial, HookRunner};
+use crate::instruction;
 use crate::journal::{
     build_conversation_from_journal, epoch_secs, generate_session_id, message_tokens, JournalEntry,
     SessionJournal,
 };
 use crate::judge::{self, JudgeVerdict};
-use crate::osc::{OscEvent, OscParser, TerminalState};
+use crate::line_editor::{EditEvent, LineEditor};
+use crate::osc::{OscEvent, OscParser, ShellReports, TerminalState};
 use crate::policy::{analyze_pipe_chain, validate_arguments, ArgumentSafety, RiskLevel};
-use crate::pty::PtySession;
+use crate::prompt_detect::PromptDetector;
+use crate::pty::{self, PtySession, ReportFifo};
 use crate::renderer::ReplRenderer;
+use crate::screen::Screen;
+use crate::shell_scripts::{
+    detect_shell, nested_bootstrap, quote, shell_name, ShellKind, SHELL_PROBE,
+};
 use crate::style::Style;
 
 enum Event {
@@ -45,6 +54,27 @@ enum Event {
     SpinnerTick,
     /// Periodic poll for child agent processes.
     ChildPoll,
+    /// The prompt detector's idle time may have passed.
+    PromptCheck,
+    /// No input came while a paste was pending; it may have stalled.
+    PasteCheck,
+}
+
+/// The next event, or [`Event::PasteCheck`] once a pending paste has gone
+/// without input for [`instruction::PASTE_TIMEOUT`]. `None` when all
+/// senders are gone.
+fn next_event(
+    rx: &mpsc::Receiver<Event>,
+    paste: Option<&instruction::PasteBuffer>,
+) -> Option<Event> {
+    let Some(paste) = paste else {
+        return rx.recv().ok();
+    };
+    match rx.recv_timeout(paste.deadline().saturating_duration_since(Instant::now())) {
+        Ok(event) => Some(event),
+        Err(mpsc::RecvTimeoutError::Timeout) => Some(Event::PasteCheck),
+        Err(mpsc::RecvTimeoutError::Disconnected) => None,
+    }
 }
 
 /// Agent state machine — drives the main event loop.
@@ -108,6 +138,46 @@ enum AgentState {
         /// Whether to use CR-reset mode for output capture.
         use_cr_reset: bool,
     },
+    /// Editing proposed commands inline, one at a time (`[e]` at the prompt).
+    Editing {
+        /// Commands as proposed by the LLM.
+        commands: Vec<String>,
+        /// Finished edits, one per command before `editor`'s.
+        edited: Vec<String>,
+        /// Editor for `commands[edited.len()]`.
+        editor: LineEditor,
+        /// Current agentic loop iteration.
+        iteration: usize,
+        /// Tool use IDs for building tool_result messages.
+        tool_use_ids: Vec<String>,
+        /// Whether any command in the batch is Privileged.
+        has_privileged: bool,
+        /// Whether to use CR-reset mode for output capture.
+        use_cr_reset: bool,
+    },
+    /// Step-through mode: waiting for a decision on the next command.
+    Stepping {
+        /// Steps of the batch and their results so far.
+        steps: StepThrough,
+        /// Inline editor for the current step, while `[e]` is active.
+        editor: Option<LineEditor>,
+        /// Current agentic loop iteration.
+        iteration: usize,
+    },
+    /// Composing a multi-line `#` instruction (continuation line or paste).
+    Composing {
+        /// Instruction lines so far, without the `#` prefix.
+        lines: Vec<String>,
+        /// Input for the next line, starting with `# `.
+        editor: LineEditor,
+    },
+    /// Typing a `# instruction` at a paused approval or step prompt.
+    Steering {
+        /// Input line, starting with `# `.
+        editor: LineEditor,
+        /// The Approving or Stepping state to return to on cancel.
+        paused: Box<AgentState>,
+    },
     /// Commands are being executed in the PTY.
     Executing {
         /// Current agentic loop iteration.
@@ -116,6 +186,10 @@ enum AgentState {
         capture: OutputHistory,
         /// Tool use IDs for building tool_result messages.
         tool_use_ids: Vec<String>,
+        /// Notes prepended to the tool result (e.g. user edits).
+        notes: Vec<String>,
+        /// Step-through bookkeeping when running one step of a batch.
+        step: Option<StepThrough>,
     },
     // Note: The cr_resets mode is baked into the OutputHistory `capture` buffer
     // at construction time — `OutputHistory::new(200)` for "full" mode,
@@ -149,6 +223,49 @@ struct CommandQueue {
     executing: bool,
     /// Exit code from the most recent 133;D event.
     last_exit_code: Option<i32>,
+    /// Set when agent commands run under `--sandbox-exec`.
+    sandbox: Option<SandboxWrap>,
+    /// Where `--sandbox-exec` reports about the commands.
+    reports: Option<ua_sandbox::ReportPipe>,
+}
+
+/// The policy agent commands run under in the REPL: the configured one
+/// without the wall-clock timeout. The user is there to interrupt a command,
+/// and a timeout would only kill the interactive ones (editors, pagers,
+/// prompts) while the user is still using them.
+fn repl_sandbox_policy(config: &Config) -> ua_sandbox::SandboxPolicy {
+    let policy = config.sandbox.to_policy(&config.hooks);
+    let limits = ua_sandbox::ResourceLimits {
+        timeout_secs: 0,
+        ..policy.limits.clone()
+    };
+    policy.with_limits(limits)
+}
+
+/// How to wrap a command so it runs under `unixagent --sandbox-exec`.
+struct SandboxWrap {
+    /// Path to the `unixagent` binary.
+    exe: String,
+    /// The interactive shell, which parses the wrapped command line.
+    kind: ShellKind,
+}
+
+impl SandboxWrap {
+    fn wrap(&self, command: &str) -> String {
+        let line = format!(
+            "{} --sandbox-exec sh -c {}",
+            quote(self.kind, &self.exe),
+            quote(self.kind, command)
+        );
+        // A quoted word at the start of a line is a string, not a command,
+        // in these shells unless it's marked as one.
+        match self.kind {
+            ShellKind::PowerShell => format!("& {line}"),
+            ShellKind::Nushell => format!("^{line}"),
+            ShellKind::Xonsh => format!("![{line}]"),
+            _ => line,
+        }
+    }
 }
 
 impl CommandQueue {
@@ -158,12 +275,42 @@ impl CommandQueue {
             awaiting_ready: false,
             executing: false,
             last_exit_code: None,
+            sandbox: None,
+            reports: None,
+        }
+    }
+
+    /// Queue that runs every command under `--sandbox-exec`.
+    fn sandboxed(wrap: SandboxWrap) -> Self {
+        Self {
+            sandbox: Some(wrap),
+            ..Self::new()
         }
     }
 
+    /// Read `--sandbox-exec` reports from `reports`.
+    fn with_reports(mut self, reports: Option<ua_sandbox::ReportPipe>) -> Self {
+        self.reports = reports;
+        self
+    }
+
+    /// Reports about the commands run since they were queued.
+    fn take_reports(&mut self) -> Option<ua_sandbox::SandboxReports> {
+        self.reports.as_mut().map(ua_sandbox::ReportPipe::take)
+    }
+
     /// Queue commands for execution and mark as executing.
     fn enqueue(&mut self, commands: impl IntoIterator<Item = String>) {
-        self.commands.extend(commands);
+        if !self.executing {
+            // From commands the user ran by hand since.
+            self.take_reports();
+        }
+        let sandbox = &self.sandbox;
+        self.commands
+            .extend(commands.into_iter().map(|cmd| match sandbox {
+                Some(wrap) => wrap.wrap(&cmd),
+                None => cmd,
+            }));
         if !self.commands.is_empty() {
             self.executing = true;
         }
@@ -240,19 +387,494 @@ impl CommandQueue {
     }
 }
 
+/// Tool result for a step the user skipped in step-through mode.
+const STEP_SKIPPED_MESSAGE: &str = "The user skipped this command in step-through mode. \
+    It was not executed.";
+
+/// Tool result for steps left unrun when the user stops the plan.
+const STEP_STOPPED_MESSAGE: &str = "The user stopped the plan before this command. \
+    It was not executed.";
+
+/// Step-through mode (`[s]` at the approval prompt): each command of a batch
+/// is approved, skipped or edited when its turn comes, and gets its own tool
+/// result so the model sees exactly which steps ran.
+#[derive(Debug)]
+struct StepThrough {
+    /// Steps not yet reached, as (tool use ID, command).
+    pending: VecDeque<(String, String)>,
+    /// The step awaiting a decision or executing.
+    current: (String, String),
+    /// Results of steps that ran.
+    ran: Vec<ToolResultRecord>,
+    /// Results of steps that were skipped or blocked.
+    blocked: Vec<ToolResultRecord>,
+    /// Whether to use CR-reset mode for output capture.
+    use_cr_reset: bool,
+}
+
+impl StepThrough {
+    /// Pair commands with their tool use IDs. `None` for an empty batch.
+    fn new(commands: Vec<String>, tool_use_ids: Vec<String>, use_cr_reset: bool) -> Option<Self> {
+        let mut pending: VecDeque<(String, String)> =
+            tool_use_ids.into_iter().zip(commands).collect();
+        let current = pending.pop_front()?;
+        Some(Self {
+            pending,
+            current,
+            ran: Vec::new(),
+            blocked: Vec::new(),
+            use_cr_reset,
+        })
+    }
+
+    fn command(&self) -> &str {
+        &self.current.1
+    }
+
+    fn tool_use_id(&self) -> &str {
+        &self.current.0
+    }
+
+    /// 1-based position of the current step and the batch size.
+    fn position(&self) -> (usize, usize) {
+        let done = self.ran.len() + self.blocked.len();
+        (done + 1, done + 1 + self.pending.len())
+    }
+
+    /// Record the current step's output.
+    fn record(&mut self, observation: String) {
+        self.ran
+            .push(ToolResultRecord::text(self.current.0.clone(), observation));
+    }
+
+    /// Record the current step as not run.
+    fn block(&mut self, message: &str) {
+        self.blocked.push(ToolResultRecord::text(
+            self.current.0.clone(),
+            message.to_string(),
+        ));
+    }
+
+    /// Record the current step and every remaining one as not run.
+    fn block_rest(&mut self, message: &str) {
+        self.block(message);
+        while let Some(step) = self.pending.pop_front() {
+            self.current = step;
+            self.block(message);
+        }
+    }
+
+    /// Move to the next step. Returns false when the batch is finished.
+    fn advance(&mut self) -> bool {
+        match self.pending.pop_front() {
+            Some(step) => {
+                self.current = step;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Journal the results — steps that ran as `ToolResult`, the rest as
+    /// `Blocked` — and return them all for the conversation, plus whether
+    /// any step ran.
+    fn finish(self, journal: &mut Option<SessionJournal>) -> (Vec<ToolResultRecord>, bool) {
+        let any_ran = !self.ran.is_empty();
+        if let Some(ref mut j) = journal {
+            if !self.ran.is_empty() {
+                j.append(&JournalEntry::ToolResult {
+                    ts: epoch_secs(),
+                    results: self.ran.clone(),
+                });
+            }
+            if !self.blocked.is_empty() {
+                j.append(&JournalEntry::Blocked {
+                    ts: epoch_secs(),
+                    results: self.blocked.clone(),
+                });
+            }
+        }
+        let mut results = self.ran;
+        results.extend(self.blocked);
+        (results, any_ran)
+    }
+}
+
+/// What to do after input in step-through mode.
+#[derive(Debug)]
+enum StepAction {
+    /// Keep waiting for input (possibly on the next step).
+    Wait,
+    /// Run the current step (possibly edited). `notes` go in its tool result.
+    Run { command: String, notes: Vec<String> },
+    /// The last step was skipped or blocked — the batch is finished.
+    Done,
+    /// The user pressed `#` to redirect the plan.
+    Steer,
+    /// The user stopped the plan; remaining steps are recorded as not run.
+    Stop,
+    /// Ctrl-C — abandon the turn.
+    Cancel,
+}
+
+/// Show the prompt for the current step.
+fn show_step<W: Write>(steps: &StepThrough, renderer: &mut ReplRenderer<W>) {
+    let (step, total) = steps.position();
+    let cmd = steps.command();
+    renderer.emit_step_prompt(step, total, cmd, &analyze_pipe_chain(cmd));
+}
+
+/// Run the prompt detector over a chunk of PTY output (empty for an idle
+/// check), moving the terminal state for each event it synthesizes.
+/// Returns those events and how long until it should check again.
+fn detect_prompt(
+    detector: &mut PromptDetector,
+    parser: &mut OscParser,
+    data: &[u8],
+    events: &[OscEvent],
+    agent_running: bool,
+) -> (Vec<OscEvent>, Option<Duration>) {
+    let now = Instant::now();
+    let mut synthesized = detector.observe(data, events, agent_running, now);
+    for evt in &synthesized {
+        parser.update_terminal_state(evt);
+    }
+    for evt in detector.poll(parser.terminal_state, now) {
+        parser.update_terminal_state(&evt);
+        synthesized.push(evt);
+    }
+    let next_check = detector.next_check(parser.terminal_state, now);
+    (synthesized, next_check)
+}
+
+/// Handle `#!integrate [shell]`. `reporting` says the prompt came from the
+/// shell's own markers, so there is nothing to do. Without a shell name,
+/// types a probe for `$0` when the next prompt can be detected; returns
+/// whether it did.
+fn start_integrate<W: Write>(
+    shell: Option<&str>,
+    reporting: bool,
+    can_probe: bool,
+    reports: &mut ShellReports,
+    session: &mut PtySession,
+    renderer: &mut ReplRenderer<W>,
+) -> bool {
+    if reporting {
+        renderer.emit_integrate("this shell already reports its prompt");
+        return false;
+    }
+    match shell {
+        Some(shell) => {
+            type_bootstrap(shell, reports, session, renderer);
+            false
+        }
+        None if can_probe => session.write_all(SHELL_PROBE.as_bytes()).is_ok(),
+        None => {
+            renderer.emit_integrate("name the shell, e.g. #!integrate bash");
+            false
+        }
+    }
+}
+
+/// Type the integration bootstrap for `shell` (a name or `$0`) into the
+/// shell at the prompt, one level below the one reporting now.
+fn type_bootstrap<W: Write>(
+    shell: &str,
+    reports: &mut ShellReports,
+    session: &mut PtySession,
+    renderer: &mut ReplRenderer<W>,
+) {
+    match nested_bootstrap(detect_shell(shell), reports.next_depth()) {
+        Some(line) => {
+            reports.expect_level(shell_name(shell));
+            if let Err(e) = session.write_all(line.as_bytes()) {
+                renderer.emit_pty_error(&e.to_string());
+            }
+        }
+        None => renderer.emit_integrate(&format!(
+            "no nested integration for {}; bash, zsh, fish and POSIX sh are supported",
+            shell_name(shell)
+        )),
+    }
+}
+
+/// Start composing a multi-line `#` instruction. `echo` shows `lines` first
+/// (for pastes, which the shell never saw).
+fn start_composing<W: Write>(
+    lines: Vec<String>,
+    echo: bool,
+    renderer: &mut ReplRenderer<W>,
+) -> AgentState {
+    renderer.emit_line_break();
+    if echo {
+        for line in &lines {
+            renderer.emit_instruction_line(line);
+        }
+    }
+    let editor = LineEditor::new("# ");
+    renderer.emit_steer_line(&editor.text(), editor.chars_after_cursor());
+    AgentState::Composing { lines, editor }
+}
+
+/// Hand a paste whose end marker never came to the shell as typed text.
+fn flush_stalled_paste(
+    paste: instruction::PasteBuffer,
+    line_buf: &mut String,
+    session: &mut PtySession,
+) -> io::Result<()> {
+    let body = paste.into_body();
+    track_paste(line_buf, &String::from_utf8_lossy(&body));
+    session.write_all(&body)
+}
+
+/// Keep the `#` line buffer in step with a paste handed to the shell.
+fn track_paste(line_buf: &mut String, pasted: &str) {
+    if pasted.contains(['\r', '\n']) {
+        line_buf.clear();
+    } else {
+        line_buf.push_str(pasted);
+    }
+}
+
+/// Tool result for pending tool uses when the user redirects mid-plan.
+fn redirect_message(instruction: &str) -> String {
+    format!("user redirected: {instruction}")
+}
+
+/// Start typing a `# instruction` over a paused Approving or Stepping state.
+fn start_steering<W: Write>(paused: AgentState, renderer: &mut ReplRenderer<W>) -> AgentState {
+    let editor = LineEditor::new("# ");
+    renderer.emit_steer_line(&editor.text(), editor.chars_after_cursor());
+    AgentState::Steering {
+        editor,
+        paused: Box::new(paused),
+    }
+}
+
+/// Re-show the prompt of a paused approval or step.
+fn show_paused_prompt<W: Write>(
+    state: &AgentState,
+    config: &Config,
+    renderer: &mut ReplRenderer<W>,
+) {
+    renderer.emit_clear_line();
+    match state {
+        AgentState::Approving {
+            commands,
+            has_privileged,
+            ..
+        } => {
+            let risk_levels: Vec<RiskLevel> =
+                commands.iter().map(|cmd| analyze_pipe_chain(cmd)).collect();
+            show_approval_ui(commands, &risk_levels, *has_privileged, config, renderer);
+        }
+        AgentState::Stepping { steps, .. } => show_step(steps, renderer),
+        _ => {}
+    }
+}
+
+/// Abandon a paused plan for a mid-plan `#` instruction: answer its pending
+/// tool uses with "user redirected: ..." and journal the instruction after
+/// them. Steps that already ran keep their output. Returns the iteration of
+/// the abandoned plan.
+fn journal_redirect(
+    paused: AgentState,
+    instruction: &str,
+    journal: &mut Option<SessionJournal>,
+) -> usize {
+    let message = redirect_message(instruction);
+    let iteration = match paused {
+        AgentState::Approving {
+            iteration,
+            tool_use_ids,
+            ..
+        } => {
+            let results: Vec<ToolResultRecord> = tool_use_ids
+                .iter()
+                .map(|id| ToolResultRecord::text(id.clone(), message.clone()))
+                .collect();
+            if let Some(ref mut j) = journal {
+                if !results.is_empty() {
+                    j.append(&JournalEntry::ToolResult {
+                        ts: epoch_secs(),
+                        results,
+                    });
+                }
+            }
+            iteration
+        }
+        AgentState::Stepping {
+            mut steps,
+            iteration,
+            ..
+        } => {
+            steps.block_rest(&message);
+            steps.finish(journal);
+            iteration
+        }
+        _ => 0,
+    };
+    if let Some(ref mut j) = journal {
+        j.append(&JournalEntry::Instruction {
+            ts: epoch_secs(),
+            text: instruction.to_string(),
+            attachments: vec![],
+        });
+    }
+    iteration
+}
+
+/// Prompt for the next step, or report that the batch is finished.
+fn next_step<W: Write>(steps: &mut StepThrough, renderer: &mut ReplRenderer<W>) -> StepAction {
+    if steps.advance() {
+        show_step(steps, renderer);
+        StepAction::Wait
+    } else {
+        StepAction::Done
+    }
+}
+
+/// Handle input for the current step: `[y]` run, `[n]` skip, `[e]` edit
+/// (re-checked like approval-prompt edits), `[q]` stop the plan, `#` redirect.
+#[allow(clippy::too_many_arguments)]
+fn handle_step_input<W: Write>(
+    data: &[u8],
+    steps: &mut StepThrough,
+    editor: &mut Option<LineEditor>,
+    iteration: usize,
+    hooks: &HookRunner,
+    hook_ctx: &HookContext,
+    audit: &mut AuditLogger,
+    renderer: &mut ReplRenderer<W>,
+) -> StepAction {
+    if let Some(ed) = editor {
+        return match ed.feed(data) {
+            EditEvent::Continue => {
+                renderer.emit_edit_line(&ed.text(), ed.chars_after_cursor());
+                StepAction::Wait
+            }
+            EditEvent::Cancel => {
+                *editor = None;
+                show_step(steps, renderer);
+                StepAction::Wait
+            }
+            EditEvent::Submit(line) | EditEvent::Newline(line) => {
+                *editor = None;
+                renderer.emit_clear_line();
+                let original = [steps.command().to_string()];
+                match review_edits(
+                    &original,
+                    vec![line],
+                    iteration,
+                    hooks,
+                    hook_ctx,
+                    audit,
+                    renderer,
+                ) {
+                    EditOutcome::Run {
+                        mut commands,
+                        notes,
+                    } => StepAction::Run {
+                        command: commands.remove(0),
+                        notes,
+                    },
+                    EditOutcome::Blocked { message } => {
+                        steps.block(&message);
+                        next_step(steps, renderer)
+                    }
+                    EditOutcome::Empty => {
+                        steps.block(STEP_SKIPPED_MESSAGE);
+                        renderer.emit_step_skipped();
+                        next_step(steps, renderer)
+                    }
+                }
+            }
+        };
+    }
+
+    let (step, total) = steps.position();
+    for &b in data {
+        match b {
+            b'y' | b'Y' | b'\r' | b'\n' => {
+                audit.log_approved(
+                    iteration,
+                    "step",
+                    &format!("user approved step {step}/{total}"),
+                );
+                renderer.emit_clear_line();
+                return StepAction::Run {
+                    command: steps.command().to_string(),
+                    notes: Vec::new(),
+                };
+            }
+            b'n' | b'N' => {
+                audit.log_denied(
+                    iteration,
+                    "step",
+                    &format!("user skipped step {step}/{total}"),
+                );
+                steps.block(STEP_SKIPPED_MESSAGE);
+                renderer.emit_step_skipped();
+                return next_step(steps, renderer);
+            }
+            b'e' | b'E' => {
+                let ed = LineEditor::new(steps.command());
+                renderer.emit_edit_line(&ed.text(), ed.chars_after_cursor());
+                *editor = Some(ed);
+                return StepAction::Wait;
+            }
+            b'#' => return StepAction::Steer,
+            b'q' | b'Q' => {
+                audit.log_denied(
+                    iteration,
+                    "step",
+                    &format!("user stopped the plan at step {step}/{total}"),
+                );
+                steps.block_rest(STEP_STOPPED_MESSAGE);
+                return StepAction::Stop;
+            }
+            0x03 => {
+                audit.log_denied(iteration, "ctrl_c", "user cancelled");
+                return StepAction::Cancel;
+            }
+            _ => {}
+        }
+    }
+    StepAction::Wait
+}
+
+/// Tool result sent to the LLM when a command is on the deny list.
+const POLICY_DENIAL_MESSAGE: &str = "Command was blocked by the security policy. \
+    The command is on the deny list and cannot be executed. \
+    Please suggest a safer alternative.";
+
+/// Tool result sent to the LLM when a pre-exec hook denies a command.
+fn hook_denial_message(denial: &HookDenial) -> String {
+    format!(
+        "Command `{}` was blocked by the pre-exec hook {}: {}. \
+         Please suggest a safer alternative.",
+        denial.command, denial.hook, denial.reason
+    )
+}
+
 /// What to do after classifying proposed commands.
 #[derive(Debug)]
 enum CommandAction {
     /// No commands in the response — return to Idle.
     NoCommands,
-    /// At least one command was denied by policy — return to Idle.
-    Blocked { tool_use_ids: Vec<String> },
+    /// At least one command was denied by policy or a pre-exec hook — return
+    /// to Idle. `message` is sent back to the LLM as the tool result.
+    Blocked {
+        tool_use_ids: Vec<String>,
+        message: String,
+    },
     /// All commands are read-only and auto-approve is on.
     AutoApprove {
         commands: Vec<String>,
         tool_use_ids: Vec<String>,
         iteration: usize,
         use_cr_reset: bool,
+        hook_notes: Vec<HookNote>,
     },
     /// Judge is enabled — transition to Judging.
     Judge {
@@ -262,6 +884,7 @@ enum CommandAction {
         has_privileged: bool,
         iteration: usize,
         use_cr_reset: bool,
+        hook_notes: Vec<HookNote>,
     },
     /// Go directly to approval UI (judge disabled or not applicable).
     Approve {
@@ -271,17 +894,76 @@ enum CommandAction {
         has_privileged: bool,
         iteration: usize,
         use_cr_reset: bool,
+        hook_notes: Vec<HookNote>,
     },
 }
 
+/// A tool result note saying a pre-exec hook rewrote a command, so the
+/// model knows what actually ran.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HookNote {
+    /// Tool use whose command was rewritten.
+    tool_use_id: String,
+    note: String,
+}
+
+fn hook_note(hook: &str, from: &str, to: &str) -> String {
+    format!("pre-exec hook {hook} changed command from `{from}` to `{to}`")
+}
+
+/// The notes for the tool use `id`, or for the whole batch if `None`.
+fn hook_notes_for(notes: &[HookNote], id: Option<&str>) -> Vec<String> {
+    notes
+        .iter()
+        .filter(|n| match id {
+            Some(id) => n.tool_use_id == id,
+            None => true,
+        })
+        .map(|n| n.note.clone())
+        .collect()
+}
+
+/// Run changed commands through the pre-exec hooks. Returns the hook notes,
+/// or the tool result message if a hook denied one.
+#[allow(clippy::too_many_arguments)]
+fn run_hooks<W: Write>(
+    commands: &mut [String],
+    risk_levels: &mut [RiskLevel],
+    tool_use_ids: &[String],
+    hooks: &HookRunner,
+    hook_ctx: &HookContext,
+    audit: &mut AuditLogger,
+    renderer: &mut ReplRenderer<W>,
+) -> Result<Vec<HookNote>, String> {
+    let mut previous = commands.to_vec();
+    match hooks.check_commands(commands, risk_levels, hook_ctx, audit) {
+        Ok(rewrites) => Ok(rewrites
+            .into_iter()
+            .map(|rewrite| {
+                renderer.emit_hook_modified(&rewrite.hook, &rewrite.command);
+                let from = std::mem::replace(&mut previous[rewrite.index], rewrite.command);
+                HookNote {
+                    tool_use_id: tool_use_ids.get(rewrite.index).cloned().unwrap_or_default(),
+                    note: hook_note(&rewrite.hook, &from, &previous[rewrite.index]),
+                }
+            })
+            .collect()),
+        Err(denial) => {
+            renderer.emit_hook_denied(&denial.command, &denial.hook, &denial.reason);
+            Err(hook_denial_message(&denial))
+        }
+    }
+}
+
 /// Classify proposed commands and decide the next action.
 ///
 /// This is the pure decision logic extracted from the BackendDone handler.
-/// It performs risk classification, deny checks, argument warnings, and
-/// decides whether to auto-approve, send to the judge, or go to approval UI.
+/// It performs risk classification, deny checks, pre-exec hooks, argument
+/// warnings, and decides whether to auto-approve, send to the judge, or go to
+/// approval UI. Session grants are matched against `hook_ctx.cwd`.
 #[allow(clippy::too_many_arguments)]
 fn classify_and_gate<W: Write>(
-    commands: Vec<String>,
+    mut commands: Vec<String>,
     tool_use_ids: Vec<String>,
     iteration: usize,
     use_cr_reset: bool,
@@ -289,16 +971,20 @@ fn classify_and_gate<W: Write>(
     audit: &mut AuditLogger,
     renderer: &mut ReplRenderer<W>,
     sandbox_active: bool,
+    hooks: &HookRunner,
+    hook_ctx: &HookContext,
+    grants: &SessionGrants,
 ) -> CommandAction {
     if commands.is_empty() {
         return CommandAction::NoCommands;
     }
 
     // Classify each command
-    let risk_levels: Vec<RiskLevel> = commands.iter().map(|cmd| analyze_pipe_chain(cmd)).collect();
-    let risk_labels: Vec<&str> = risk_levels.iter().map(|r| r.as_str()).collect();
+    let mut risk_levels: Vec<RiskLevel> =
+        commands.iter().map(|cmd| analyze_pipe_chain(cmd)).collect();
 
     // Log proposed commands
+    let risk_labels: Vec<&str> = risk_levels.iter().map(|r| r.as_str()).collect();
     audit.log_proposed(iteration, &commands, &risk_labels, "llm");
 
     // Check for denied commands — block them
@@ -312,7 +998,34 @@ fn classify_and_gate<W: Write>(
     }
 
     if blocked {
-        return CommandAction::Blocked { tool_use_ids };
+        return CommandAction::Blocked {
+            tool_use_ids,
+            message: POLICY_DENIAL_MESSAGE.to_string(),
+        };
+    }
+
+    // Run pre-exec hooks — they may rewrite or deny commands
+    let hook_notes = match run_hooks(
+        &mut commands,
+        &mut risk_levels,
+        &tool_use_ids,
+        hooks,
+        hook_ctx,
+        audit,
+        renderer,
+    ) {
+        Ok(notes) => notes,
+        Err(message) => {
+            return CommandAction::Blocked {
+                tool_use_ids,
+                message,
+            };
+        }
+    };
+    if !hook_notes.is_empty() {
+        // What will be gated and run, with the risks of the rewrites.
+        let risk_labels: Vec<&str> = risk_levels.iter().map(|r| r.as_str()).collect();
+        audit.log_proposed(iteration, &commands, &risk_labels, "hook");
     }
 
     // Check for dangerous arguments
@@ -345,6 +1058,7 @@ fn classify_and_gate<W: Write>(
             tool_use_ids,
             iteration,
             use_cr_reset,
+            hook_notes,
         }
     } else if all_read_only && config.security.auto_approve_read_only {
         audit.log_approved(iteration, "auto", "all commands read-only");
@@ -356,6 +1070,19 @@ fn classify_and_gate<W: Write>(
             tool_use_ids,
             iteration,
             use_cr_reset,
+            hook_notes,
+        }
+    } else if grants.covers_all(&commands, &risk_levels, &hook_ctx.host, &hook_ctx.cwd) {
+        audit.log_approved(iteration, "session_grant", "matched session grant");
+        for cmd in &commands {
+            renderer.emit_command_granted(cmd);
+        }
+        CommandAction::AutoApprove {
+            commands,
+            tool_use_ids,
+            iteration,
+            use_cr_reset,
+            hook_notes,
         }
     } else if config.security.judge_enabled {
         CommandAction::Judge {
@@ -365,6 +1092,7 @@ fn classify_and_gate<W: Write>(
             has_privileged,
             iteration,
             use_cr_reset,
+            hook_notes,
         }
     } else {
         CommandAction::Approve {
@@ -374,6 +1102,7 @@ fn classify_and_gate<W: Write>(
             has_privileged,
             iteration,
             use_cr_reset,
+            hook_notes,
         }
     }
 }
@@ -405,30 +1134,190 @@ fn handle_judge_verdict<W: Write>(
     }
 }
 
+/// Outcome of re-checking commands the user edited at the approval prompt.
+#[derive(Debug)]
+enum EditOutcome {
+    /// Run the commands. `notes` describe each change and are prepended to
+    /// the tool result.
+    Run {
+        commands: Vec<String>,
+        notes: Vec<String>,
+    },
+    /// An edited command is denied by policy. `message` is sent back to the
+    /// LLM as the tool result.
+    Blocked { message: String },
+    /// The user cleared every command.
+    Empty,
+}
+
+/// Tool result note recording a user edit, so the model learns the correction.
+fn edit_note(from: &str, to: &str) -> String {
+    if to.is_empty() {
+        format!("user removed command `{from}`")
+    } else {
+        format!("user edited command from `{from}` to `{to}`")
+    }
+}
+
+/// Prepend user-action notes (one per line) to a tool result observation.
+fn with_notes(notes: &[String], observation: String) -> String {
+    if notes.is_empty() {
+        observation
+    } else {
+        format!("{}\n{observation}", notes.join("\n"))
+    }
+}
+
+/// Re-classify commands after inline editing.
+///
+/// Unchanged commands pass through as approved. Changed commands are run
+/// through the deny list and argument checks again and shown with their new
+/// risk level; a denied edit blocks the batch. Submitting the edit counts as
+/// approval, since the user typed the command themselves.
+fn review_edits<W: Write>(
+    originals: &[String],
+    edited: Vec<String>,
+    iteration: usize,
+    hooks: &HookRunner,
+    hook_ctx: &HookContext,
+    audit: &mut AuditLogger,
+    renderer: &mut ReplRenderer<W>,
+) -> EditOutcome {
+    let mut commands = Vec::new();
+    let mut notes = Vec::new();
+
+    for (from, to) in originals.iter().zip(edited) {
+        let to = to.trim().to_string();
+        if to == from.trim() {
+            commands.push(from.clone());
+            continue;
+        }
+        let note = edit_note(from, &to);
+        if to.is_empty() {
+            notes.push(note);
+            continue;
+        }
+
+        let risk = analyze_pipe_chain(&to);
+        if risk == RiskLevel::Denied {
+            renderer.emit_denied(&to);
+            audit.log_blocked(&to, risk.as_str(), "edited command denied by policy");
+            return EditOutcome::Blocked {
+                message: format!("{note}. {POLICY_DENIAL_MESSAGE}"),
+            };
+        }
+        notes.push(note);
+        let (mut edit, mut risks) = ([to], [risk]);
+        match run_hooks(&mut edit, &mut risks, &[], hooks, hook_ctx, audit, renderer) {
+            Ok(hook_notes) => notes.extend(hook_notes.into_iter().map(|n| n.note)),
+            Err(message) => {
+                return EditOutcome::Blocked {
+                    message: format!("{}. {message}", notes.pop().unwrap_or_default()),
+                };
+            }
+        }
+        let ([to], [risk]) = (edit, risks);
+        renderer.emit_command_risk(&to, &risk);
+        if let ArgumentSafety::Dangerous(reason) = validate_arguments(&to) {
+            renderer.emit_arg_warning(&reason);
+        }
+        commands.push(to);
+    }
+
+    if commands.is_empty() {
+        audit.log_denied(iteration, "edit", "user removed all commands");
+        return EditOutcome::Empty;
+    }
+
+    if notes.is_empty() {
+        audit.log_approved(iteration, "edit", "user pressed e (no changes)");
+    } else {
+        let risk_labels: Vec<&str> = commands
+            .iter()
+            .map(|cmd| analyze_pipe_chain(cmd).as_str())
+            .collect();
+        audit.log_proposed(iteration, &commands, &risk_labels, "user_edit");
+        audit.log_approved(iteration, "edit", &notes.join("; "));
+    }
+    EditOutcome::Run { commands, notes }
+}
+
 pub fn run_repl(
     config: &Config,
+    backend: Arc<dyn Backend>,
     debug_osc: bool,
     rt_handle: &Handle,
     sandbox_active: bool,
 ) -> io::Result<()> {
     let shell_cmd = config.shell_command();
-    // REPL passes None for sandbox — human approval is the defense here.
-    let (mut session, pty_reader) = PtySession::spawn(&shell_cmd, config.shell.integration, None)?;
+    // The shell itself stays unsandboxed — it belongs to the human. With
+    // the sandbox active, agent commands are wrapped in `--sandbox-exec`,
+    // which reads the policy from the shell's environment.
+    // They report blocked syscalls through a pipe the shell holds, not the
+    // terminal they write to.
+    let report_fifo = if sandbox_active {
+        ReportFifo::new()
+            .map_err(|e| eprintln!("[ua] warning: no sandbox report pipe: {e}"))
+            .ok()
+    } else {
+        None
+    };
+    let sandbox_policy = sandbox_active.then(|| {
+        repl_sandbox_policy(config)
+            .with_report_fd(report_fifo.as_ref().map(|_| pty::SHELL_REPORT_FD))
+    });
+    let sandbox_env: Vec<(&str, String)> = sandbox_policy
+        .iter()
+        .map(|policy| (ua_sandbox::policy::SANDBOX_ENV_VAR, policy.to_json()))
+        .collect();
+    let (mut session, pty_reader) = PtySession::spawn_with_env(
+        &shell_cmd,
+        config.shell.integration,
+        None,
+        &sandbox_env,
+        report_fifo.as_ref(),
+    )?;
+    let sandbox_reports = report_fifo.map(ReportFifo::connect);
     let mut parser = OscParser::new();
+    // Guesses prompts where the shell doesn't mark them (no integration,
+    // or a shell inside `ssh`/`docker exec`).
+    let mut prompt_detector = PromptDetector::from_config(&config.shell);
+    let mut prompt_check_pending = false;
     let mut line_buf = String::new();
+    // Bracketed paste collected across reads while at the prompt.
+    let mut paste_buf: Option<instruction::PasteBuffer> = None;
+    // Input read after a paste that became an instruction; handled before
+    // the next event.
+    let mut stdin_rest: Option<Vec<u8>> = None;
     let mut output_history = OutputHistory::new(config.context.max_terminal_lines);
     let mut terminal_size = crossterm::terminal::size().unwrap_or((80, 24));
-    let mut command_queue = CommandQueue::new();
+    let mut screen = Screen::new(terminal_size.0, terminal_size.1);
+    let mut command_queue = if sandbox_active {
+        CommandQueue::sandboxed(SandboxWrap {
+            exe: std::env::current_exe()?.to_string_lossy().into_owned(),
+            kind: detect_shell(&shell_cmd),
+        })
+        .with_reports(sandbox_reports)
+    } else {
+        CommandQueue::new()
+    };
     let mut state = AgentState::Idle;
     // Instruction text saved across the state transition (Idle → Streaming).
     let mut pending_instruction: Option<String> = None;
+    // Hook context and hook rewrite notes of the batch being gated, kept
+    // for edits and the tool result.
+    let mut hook_ctx = HookContext::default();
+    let mut hook_notes: Vec<HookNote> = Vec::new();
     // Child shell PID for CWD resolution.
     let child_pid = session.child_pid();
+    let mut shell_reports = ShellReports::new(shell_name(&shell_cmd));
+    // `#!integrate` typed its probe; the answer comes before the next prompt.
+    let mut integrate_probe = false;
     // User command text captured on Enter, awaiting exit code from 133;D.
     let mut pending_user_command: Option<String> = None;
     // Captures terminal output between 133;C and 133;D for user commands.
     let mut user_cmd_capture: Option<OutputHistory> = None;
-    // Buffer PTY output during Approving/Judging to prevent interleaving.
+    // Buffer PTY output during Approving/Editing/Stepping/Steering/Composing/Judging to prevent interleaving.
     let mut pty_buffer: Vec<u8> = Vec::new();
 
     // Initialize session journal
@@ -448,8 +1337,22 @@ pub fn run_repl(
         }
     };
 
-    // Initialize audit logger
-    let mut audit = if config.security.audit_enabled {
+    // Discover pre-exec hooks once per session
+    let hooks = match &sandbox_policy {
+        Some(policy) if hooks::sandbox_can_plant(policy, &config.hooks) => {
+            eprintln!(
+                "[ua] warning: not running hooks from {}: sandboxed commands can write there",
+                config.hooks.resolve_dir().display()
+            );
+            HookRunner::none()
+        }
+        _ => HookRunner::from_config(&config.hooks),
+    };
+    // "Always allow" answers, remembered until the REPL exits
+    let mut session_grants = SessionGrants::new();
+
+    // Initialize audit logger
+    let mut audit = if config.security.audit_enabled {
         let path = config.security.resolve_audit_path();
         match AuditLogger::new(&path) {
             Ok(logger) => logger,
@@ -567,12 +1470,27 @@ pub fn run_repl(
     let mut stdout = io::stdout().lock();
     let mut renderer = ReplRenderer::new(io::stderr(), style);
 
-    if sandbox_active {
-        renderer.emit_sandbox_warning(&config.sandbox.to_policy().writable);
+    if let Some(policy) = &sandbox_policy {
+        renderer.emit_sandbox_warning(policy);
     }
 
-    while let Ok(event) = rx.recv() {
+    while let Some(event) = stdin_rest
+        .take()
+        .map(Event::Stdin)
+        .or_else(|| next_event(&rx, paste_buf.as_ref()))
+    {
         match event {
+            Event::PasteCheck => {
+                let now = Instant::now();
+                if let Some(stalled) = paste_buf.take_if(|paste| paste.stalled(now)) {
+                    if let Err(e) = flush_stalled_paste(stalled, &mut line_buf, &mut session) {
+                        if debug_osc {
+                            renderer.emit_pty_error(&e.to_string());
+                        }
+                        break;
+                    }
+                }
+            }
             Event::Stdin(data) => {
                 match state {
                     AgentState::Idle => {
@@ -582,17 +1500,126 @@ pub fn run_repl(
                         // - Prompt = after 133;A (prompt start)
                         // - Input  = after 133;B (prompt rendered, ZLE/readline ready)
                         // - Idle   = after 133;D but before 133;A (brief window)
-                        if matches!(
+                        let at_prompt = matches!(
                             parser.terminal_state,
                             TerminalState::Prompt | TerminalState::Input | TerminalState::Idle
-                        ) {
-                            for &b in &data {
+                        );
+
+                        // A paste whose end marker never came goes to the
+                        // shell as typed text; this read is handled on its own.
+                        let now = Instant::now();
+                        if let Some(stalled) = paste_buf.take_if(|paste| paste.stalled(now)) {
+                            if let Err(e) =
+                                flush_stalled_paste(stalled, &mut line_buf, &mut session)
+                            {
+                                if debug_osc {
+                                    renderer.emit_pty_error(&e.to_string());
+                                }
+                                break;
+                            }
+                        }
+
+                        // A multi-line paste of `#` lines becomes one instruction.
+                        // Bracketed pastes are collected until the end marker.
+                        if at_prompt
+                            && (paste_buf.is_some() || data.starts_with(instruction::PASTE_START))
+                        {
+                            let step = paste_buf
+                                .get_or_insert_with(|| instruction::PasteBuffer::new(now))
+                                .push(&data, now);
+                            let raw = match step {
+                                instruction::PasteStep::Pending => continue,
+                                instruction::PasteStep::Oversized(raw) => raw,
+                                instruction::PasteStep::Complete { body, rest } => {
+                                    // Keystrokes after the paste are handled as usual.
+                                    if !rest.is_empty() {
+                                        stdin_rest = Some(rest);
+                                    }
+                                    let pasted = String::from_utf8_lossy(&body);
+                                    if let Some(lines) =
+                                        instruction::pasted_instruction(&line_buf, &pasted)
+                                    {
+                                        paste_buf = None;
+                                        state = start_composing(lines, true, &mut renderer);
+                                        line_buf.clear();
+                                        continue;
+                                    }
+                                    // Not an instruction — hand the paste to the shell
+                                    track_paste(&mut line_buf, &pasted);
+                                    [instruction::PASTE_START, &body, instruction::PASTE_END]
+                                        .concat()
+                                }
+                            };
+                            paste_buf = None;
+                            if let Err(e) = session.write_all(&raw) {
+                                if debug_osc {
+                                    renderer.emit_pty_error(&e.to_string());
+                                }
+                                break;
+                            }
+                            continue;
+                        }
+                        if at_prompt && line_buf.is_empty() {
+                            // Unbracketed paste: several lines arriving in one read
+                            let pasted = String::from_utf8_lossy(&data);
+                            let multi_line =
+                                pasted.trim_end_matches(['\r', '\n']).contains(['\r', '\n']);
+                            if let Some(lines) = multi_line
+                                .then(|| instruction::pasted_instruction("", &pasted))
+                                .flatten()
+                            {
+                                state = start_composing(lines, true, &mut renderer);
+                                continue;
+                            }
+                        }
+
+                        if at_prompt {
+                            for (i, &b) in data.iter().enumerate() {
                                 match b {
                                     b'\r' | b'\n' => {
                                         let trimmed = line_buf.trim();
-                                        if let Some(instruction) = trimmed.strip_prefix('#') {
+                                        let alt_enter = i > 0 && data[i - 1] == 0x1b;
+                                        if let Some(instruction::MetaCommand::Integrate { shell }) =
+                                            instruction::meta_command(trimmed)
+                                        {
+                                            handled_instruction = true;
+                                            // Clear shell readline (removes the #! text)
+                                            let _ = session.write_all(b"\x15");
+                                            let reporting = matches!(
+                                                parser.terminal_state,
+                                                TerminalState::Prompt | TerminalState::Input
+                                            ) && !prompt_detector
+                                                .as_ref()
+                                                .is_some_and(PromptDetector::at_prompt);
+                                            integrate_probe = start_integrate(
+                                                shell,
+                                                reporting,
+                                                prompt_detector.is_some(),
+                                                &mut shell_reports,
+                                                &mut session,
+                                                &mut renderer,
+                                            );
+                                            line_buf.clear();
+                                            break;
+                                        } else if let Some(instruction) = trimmed.strip_prefix('#')
+                                        {
                                             let instruction = instruction.trim();
-                                            if !instruction.is_empty() {
+                                            if !instruction.is_empty()
+                                                && (alt_enter
+                                                    || instruction::continues(instruction))
+                                            {
+                                                // Instruction continues on the next line
+                                                handled_instruction = true;
+                                                state = start_composing(
+                                                    vec![instruction::instruction_line(
+                                                        instruction,
+                                                    )],
+                                                    false,
+                                                    &mut renderer,
+                                                );
+                                                line_buf.clear();
+                                                break;
+                                            } else if !instruction.is_empty() {
                                                 handled_instruction = true;
                                                 pending_instruction = Some(instruction.to_string());
 
@@ -615,19 +1642,25 @@ pub fn run_repl(
                                                 state = start_streaming(
                                                     rt_handle,
                                                     config,
+                                                    &backend,
                                                     &mut journal,
                                                     &output_history,
+                                                    &screen,
                                                     terminal_size,
                                                     0,
                                                     &tx_for_streaming,
                                                     &mut renderer,
                                                     child_pid,
+                                                    Some(&shell_reports),
                                                     &mut cached_conversation,
                                                     &mut conversation_tokens,
                                                 );
                                             }
-                                        } else if !trimmed.is_empty() {
-                                            // Capture user shell command (non-# input).
+                                        } else if !trimmed.is_empty()
+                                            && !shell_reports.reports_commands
+                                        {
+                                            // Capture user shell command (non-# input),
+                                            // unless the shell reports it (633;E).
                                             // Flush any previous pending command with unknown exit.
                                             if let Some(old_cmd) = pending_user_command.take() {
                                                 let old_output =
@@ -790,6 +1823,11 @@ pub fn run_repl(
                                                             iteration,
                                                             capture,
                                                             tool_use_ids,
+                                                            notes: hook_notes_for(
+                                                                &hook_notes,
+                                                                None,
+                                                            ),
+                                                            step: None,
                                                         };
                                                     }
                                                 }
@@ -822,7 +1860,8 @@ pub fn run_repl(
                                     }
                                     0x7f | 0x08 => {
                                         // Backspace
-                                        if yes_buffer.pop().is_some() {
+                                        let erased = yes_buffer.pop();
+                                        if erased.is_some() {
                                             renderer.emit_backspace();
                                         }
                                     }
@@ -851,6 +1890,12 @@ pub fn run_repl(
                                         let _ = session.write_all(b"\n");
                                         break;
                                     }
+                                    b'#' if yes_buffer.is_empty() => {
+                                        let paused =
+                                            std::mem::replace(&mut state, AgentState::Idle);
+                                        state = start_steering(paused, &mut renderer);
+                                        break;
+                                    }
                                     b if b >= 0x20 => {
                                         yes_buffer.push(b as char);
                                         renderer.emit_char(b as char);
@@ -862,7 +1907,8 @@ pub fn run_repl(
                             // Normal: single keystroke approval
                             for &b in &data {
                                 match b {
-                                    b'y' | b'Y' | b'\r' | b'\n' => {
+                                    b'y' | b'Y' | b'a' | b'A' | b'\r' | b'\n' => {
+                                        let always = matches!(b, b'a' | b'A');
                                         if let AgentState::Approving {
                                             commands,
                                             iteration,
@@ -877,11 +1923,36 @@ pub fn run_repl(
                                                 stdout.flush()?;
                                                 pty_buffer.clear();
                                             }
-                                            audit.log_approved(
-                                                iteration,
-                                                "keystroke",
-                                                "user pressed y",
-                                            );
+                                            if always {
+                                                let cwd = shell_cwd(
+                                                    child_pid,
+                                                    shell_reports.cwd.as_deref(),
+                                                );
+                                                let recorded: Vec<String> = commands
+                                                    .iter()
+                                                    .filter_map(|cmd| {
+                                                        session_grants.grant(
+                                                            cmd,
+                                                            analyze_pipe_chain(cmd),
+                                                            shell_reports.host(),
+                                                            &cwd,
+                                                        )
+                                                    })
+                                                    .map(|g| g.describe())
+                                                    .collect();
+                                                renderer.emit_grants_recorded(&recorded);
+                                                audit.log_approved(
+                                                    iteration,
+                                                    "keystroke",
+                                                    "user pressed a (always for this session)",
+                                                );
+                                            } else {
+                                                audit.log_approved(
+                                                    iteration,
+                                                    "keystroke",
+                                                    "user pressed y",
+                                                );
+                                            }
 
                                             total_commands += commands.len() as u32;
                                             command_queue.enqueue(commands);
@@ -900,6 +1971,8 @@ pub fn run_repl(
                                                         iteration,
                                                         capture,
                                                         tool_use_ids,
+                                                        notes: hook_notes_for(&hook_notes, None),
+                                                        step: None,
                                                     };
                                                 }
                                             }
@@ -930,8 +2003,63 @@ pub fn run_repl(
                                         let _ = session.write_all(b"\n");
                                         break;
                                     }
+                                    b'#' => {
+                                        let paused =
+                                            std::mem::replace(&mut state, AgentState::Idle);
+                                        state = start_steering(paused, &mut renderer);
+                                        break;
+                                    }
+                                    b's' | b'S' => {
+                                        if let AgentState::Approving {
+                                            commands,
+                                            iteration,
+                                            tool_use_ids,
+                                            use_cr_reset,
+                                            ..
+                                        } = std::mem::replace(&mut state, AgentState::Idle)
+                                        {
+                                            if let Some(steps) = StepThrough::new(
+                                                commands,
+                                                tool_use_ids,
+                                                use_cr_reset,
+                                            ) {
+                                                renderer.emit_stream_end();
+                                                show_step(&steps, &mut renderer);
+                                                state = AgentState::Stepping {
+                                                    steps,
+                                                    editor: None,
+                                                    iteration,
+                                                };
+                                            }
+                                        }
+                                        break;
+                                    }
                                     b'e' | b'E' => {
-                                        renderer.emit_edit_not_implemented();
+                                        if let AgentState::Approving {
+                                            commands,
+                                            iteration,
+                                            tool_use_ids,
+                                            has_privileged,
+                                            use_cr_reset,
+                                            ..
+                                        } = std::mem::replace(&mut state, AgentState::Idle)
+                                        {
+                                            let editor = LineEditor::new(&commands[0]);
+                                            renderer.emit_edit_line(
+                                                &editor.text(),
+                                                editor.chars_after_cursor(),
+                                            );
+                                            state = AgentState::Editing {
+                                                commands,
+                                                edited: Vec::new(),
+                                                editor,
+                                                iteration,
+                                                tool_use_ids,
+                                                has_privileged,
+                                                use_cr_reset,
+                                            };
+                                        }
+                                        break;
                                     }
                                     _ => {
                                         // Ignore other keys
@@ -940,6 +2068,433 @@ pub fn run_repl(
                             }
                         }
                     }
+                    AgentState::Editing { ref mut editor, .. } => match editor.feed(&data) {
+                        EditEvent::Continue => {
+                            renderer.emit_edit_line(&editor.text(), editor.chars_after_cursor());
+                        }
+                        EditEvent::Cancel => {
+                            // Back to the approval prompt with the original commands
+                            if let AgentState::Editing {
+                                commands,
+                                iteration,
+                                tool_use_ids,
+                                has_privileged,
+                                use_cr_reset,
+                                ..
+                            } = std::mem::replace(&mut state, AgentState::Idle)
+                            {
+                                renderer.emit_clear_line();
+                                let risk_levels: Vec<RiskLevel> =
+                                    commands.iter().map(|cmd| analyze_pipe_chain(cmd)).collect();
+                                show_approval_ui(
+                                    &commands,
+                                    &risk_levels,
+                                    has_privileged,
+                                    config,
+                                    &mut renderer,
+                                );
+                                state = AgentState::Approving {
+                                    commands,
+                                    iteration,
+                                    tool_use_ids,
+                                    has_privileged,
+                                    yes_buffer: String::new(),
+                                    use_cr_reset,
+                                };
+                            }
+                        }
+                        EditEvent::Submit(line) | EditEvent::Newline(line) => {
+                            if let AgentState::Editing {
+                                commands,
+                                mut edited,
+                                iteration,
+                                tool_use_ids,
+                                has_privileged,
+                                use_cr_reset,
+                                ..
+                            } = std::mem::replace(&mut state, AgentState::Idle)
+                            {
+                                renderer.emit_clear_line();
+                                edited.push(line);
+                                if edited.len() < commands.len() {
+                                    // Move on to the next proposed command
+                                    let editor = LineEditor::new(&commands[edited.len()]);
+                                    renderer.emit_edit_line(
+                                        &editor.text(),
+                                        editor.chars_after_cursor(),
+                                    );
+                                    state = AgentState::Editing {
+                                        commands,
+                                        edited,
+                                        editor,
+                                        iteration,
+                                        tool_use_ids,
+                                        has_privileged,
+                                        use_cr_reset,
+                                    };
+                                    continue;
+                                }
+
+                                // Flush buffered PTY output before leaving Editing
+                                if !pty_buffer.is_empty() {
+                                    stdout.write_all(&pty_buffer)?;
+                                    stdout.flush()?;
+                                    pty_buffer.clear();
+                                }
+                                match review_edits(
+                                    &commands,
+                                    edited,
+                                    iteration,
+                                    &hooks,
+                                    &hook_ctx,
+                                    &mut audit,
+                                    &mut renderer,
+                                ) {
+                                    EditOutcome::Run {
+                                        commands,
+                                        notes: edit_notes,
+                                    } => {
+                                        let mut notes = hook_notes_for(&hook_notes, None);
+                                        notes.extend(edit_notes);
+                                        total_commands += commands.len() as u32;
+                                        command_queue.enqueue(commands);
+                                        if let Some(cmd) = command_queue.pop_immediate() {
+                                            let cmd = format!("{cmd}\n");
+                                            if let Err(e) = session.write_all(cmd.as_bytes()) {
+                                                renderer.emit_pty_error(&e.to_string());
+                                                command_queue.clear();
+                                            } else {
+                                                let capture = if use_cr_reset {
+                                                    OutputHistory::with_cr_reset(200)
+                                                } else {
+                                                    OutputHistory::new(200)
+                                                };
+                                                state = AgentState::Executing {
+                                                    iteration,
+                                                    capture,
+                                                    tool_use_ids,
+                                                    notes,
+                                                    step: None,
+                                                };
+                                            }
+                                        }
+                                    }
+                                    EditOutcome::Blocked { message } => {
+                                        let tool_results: Vec<ToolResultRecord> = tool_use_ids
+                                            .iter()
+                                            .map(|id| {
+                                                ToolResultRecord::text(id.clone(), message.clone())
+                                            })
+                                            .collect();
+                                        if let Some(ref mut j) = journal {
+                                            j.append(&JournalEntry::Blocked {
+                                                ts: epoch_secs(),
+                                                results: tool_results,
+                                            });
+                                        }
+                                        renderer.emit_blocked();
+                                        total_input_tokens = 0;
+                                        total_output_tokens = 0;
+                                        total_commands = 0;
+                                        turn_start = None;
+                                        cached_conversation = None;
+                                        conversation_tokens = 0;
+                                        // Nudge shell to redisplay prompt below agent output
+                                        let _ = session.write_all(b"\n");
+                                    }
+                                    EditOutcome::Empty => {
+                                        renderer.emit_skipped(Some("all commands removed"));
+                                        total_input_tokens = 0;
+                                        total_output_tokens = 0;
+                                        total_commands = 0;
+                                        turn_start = None;
+                                        // Nudge shell to redisplay prompt below agent output
+                                        let _ = session.write_all(b"\n");
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    AgentState::Stepping {
+                        ref mut steps,
+                        ref mut editor,
+                        iteration,
+                    } => match handle_step_input(
+                        &data,
+                        steps,
+                        editor,
+                        iteration,
+                        &hooks,
+                        &hook_ctx,
+                        &mut audit,
+                        &mut renderer,
+                    ) {
+                        StepAction::Wait => {}
+                        StepAction::Steer => {
+                            let paused = std::mem::replace(&mut state, AgentState::Idle);
+                            state = start_steering(paused, &mut renderer);
+                        }
+                        StepAction::Run { command, notes } => {
+                            if let AgentState::Stepping { steps, .. } =
+                                std::mem::replace(&mut state, AgentState::Idle)
+                            {
+                                let mut notes = notes;
+                                let step_notes =
+                                    hook_notes_for(&hook_notes, Some(steps.tool_use_id()));
+                                notes.splice(0..0, step_notes);
+                                // Flush buffered PTY output before leaving Stepping
+                                if !pty_buffer.is_empty() {
+                                    stdout.write_all(&pty_buffer)?;
+                                    stdout.flush()?;
+                                    pty_buffer.clear();
+                                }
+                                total_commands += 1;
+                                command_queue.enqueue([command]);
+                                if let Some(cmd) = command_queue.pop_immediate() {
+                                    let cmd = format!("{cmd}\n");
+                                    if let Err(e) = session.write_all(cmd.as_bytes()) {
+                                        renderer.emit_pty_error(&e.to_string());
+                                        command_queue.clear();
+                                    } else {
+                                        let capture = if steps.use_cr_reset {
+                                            OutputHistory::with_cr_reset(200)
+                                        } else {
+                                            OutputHistory::new(200)
+                                        };
+                                        state = AgentState::Executing {
+                                            iteration,
+                                            capture,
+                                            tool_use_ids: vec![steps.tool_use_id().to_string()],
+                                            notes,
+                                            step: Some(steps),
+                                        };
+                                    }
+                                }
+                            }
+                        }
+                        action => {
+                            if let AgentState::Stepping { steps, .. } =
+                                std::mem::replace(&mut state, AgentState::Idle)
+                            {
+                                // Flush buffered PTY output before leaving Stepping
+                                if !pty_buffer.is_empty() {
+                                    stdout.write_all(&pty_buffer)?;
+                                    stdout.flush()?;
+                                    pty_buffer.clear();
+                                }
+                                let mut resume = None;
+                                match action {
+                                    StepAction::Cancel => renderer.emit_cancelled(),
+                                    StepAction::Stop => {
+                                        steps.finish(&mut journal);
+                                        renderer.emit_skipped(Some("plan stopped"));
+                                    }
+                                    _ => {
+                                        let (results, any_ran) = steps.finish(&mut journal);
+                                        if any_ran {
+                                            resume = Some(results);
+                                        } else {
+                                            renderer.emit_skipped(None);
+                                        }
+                                    }
+                                }
+
+                                if let Some(tool_results) = resume {
+                                    // Last step skipped after others ran — send
+                                    // every result back and continue the loop.
+                                    let result_msg =
+                                        ua_protocol::ConversationMessage::tool_result(tool_results);
+                                    conversation_tokens += message_tokens(&result_msg);
+                                    if let Some(ref mut conv) = cached_conversation {
+                                        conv.push(result_msg);
+                                    }
+                                    if conversation_tokens > config.journal.conversation_budget {
+                                        cached_conversation = None;
+                                        conversation_tokens = 0;
+                                    }
+                                    let _ = session.write_all(b"\x15");
+                                    state = start_streaming(
+                                        rt_handle,
+                                        config,
+                                        &backend,
+                                        &mut journal,
+                                        &output_history,
+                                        &screen,
+                                        terminal_size,
+                                        iteration + 1,
+                                        &tx_for_streaming,
+                                        &mut renderer,
+                                        child_pid,
+                                        Some(&shell_reports),
+                                        &mut cached_conversation,
+                                        &mut conversation_tokens,
+                                    );
+                                } else {
+                                    total_input_tokens = 0;
+                                    total_output_tokens = 0;
+                                    total_commands = 0;
+                                    turn_start = None;
+                                    // Next # instruction rebuilds from journal.
+                                    cached_conversation = None;
+                                    conversation_tokens = 0;
+                                    // Nudge shell to redisplay prompt below agent output
+                                    let _ = session.write_all(b"\n");
+                                }
+                            }
+                        }
+                    },
+                    AgentState::Composing {
+                        ref mut lines,
+                        ref mut editor,
+                    } => {
+                        let (line, more) = match editor.feed(&data) {
+                            EditEvent::Continue => {
+                                renderer
+                                    .emit_steer_line(&editor.text(), editor.chars_after_cursor());
+                                continue;
+                            }
+                            EditEvent::Cancel => (None, false),
+                            EditEvent::Newline(line) => (Some(line), true),
+                            EditEvent::Submit(line) => {
+                                let more = instruction::continues(&line);
+                                (Some(line), more)
+                            }
+                        };
+                        let cancelled = line.is_none();
+                        if let Some(line) = line {
+                            let text = instruction::instruction_line(&line);
+                            if more {
+                                lines.push(text);
+                                renderer.emit_line_break();
+                                *editor = LineEditor::new("# ");
+                                renderer
+                                    .emit_steer_line(&editor.text(), editor.chars_after_cursor());
+                                continue;
+                            }
+                            // A blank line ends the instruction
+                            if !text.is_empty() {
+                                lines.push(text);
+                            }
+                        }
+
+                        let AgentState::Composing { lines, .. } =
+                            std::mem::replace(&mut state, AgentState::Idle)
+                        else {
+                            continue;
+                        };
+                        // Flush buffered PTY output before leaving Composing
+                        if !pty_buffer.is_empty() {
+                            stdout.write_all(&pty_buffer)?;
+                            stdout.flush()?;
+                            pty_buffer.clear();
+                        }
+                        renderer.emit_line_break();
+                        // Clear shell readline (removes a typed first # line)
+                        let _ = session.write_all(b"\x15");
+
+                        let text = instruction::join(&lines);
+                        if cancelled || text.is_empty() {
+                            if cancelled {
+                                renderer.emit_cancelled();
+                            }
+                            // Nudge shell to redisplay prompt below agent output
+                            let _ = session.write_all(b"\n");
+                            continue;
+                        }
+
+                        pending_instruction = Some(text.clone());
+                        if let Some(ref mut j) = journal {
+                            j.append(&JournalEntry::Instruction {
+                                ts: epoch_secs(),
+                                text,
+                                attachments: vec![],
+                            });
+                        }
+                        // Fresh instruction: rebuild from journal.
+                        cached_conversation = None;
+                        conversation_tokens = 0;
+                        state = start_streaming(
+                            rt_handle,
+                            config,
+                            &backend,
+                            &mut journal,
+                            &output_history,
+                            &screen,
+                            terminal_size,
+                            0,
+                            &tx_for_streaming,
+                            &mut renderer,
+                            child_pid,
+                            Some(&shell_reports),
+                            &mut cached_conversation,
+                            &mut conversation_tokens,
+                        );
+                    }
+                    AgentState::Steering { ref mut editor, .. } => {
+                        let instruction = match editor.feed(&data) {
+                            EditEvent::Continue => {
+                                renderer
+                                    .emit_steer_line(&editor.text(), editor.chars_after_cursor());
+                                continue;
+                            }
+                            EditEvent::Cancel => None,
+                            EditEvent::Submit(line) | EditEvent::Newline(line) => line
+                                .trim()
+                                .strip_prefix('#')
+                                .map(|text| text.trim().to_string())
+                                .filter(|text| !text.is_empty()),
+                        };
+                        let AgentState::Steering { paused, .. } =
+                            std::mem::replace(&mut state, AgentState::Idle)
+                        else {
+                            continue;
+                        };
+                        match instruction {
+                            None => {
+                                // Back to the paused prompt
+                                show_paused_prompt(&paused, config, &mut renderer);
+                                state = *paused;
+                            }
+                            Some(instruction) => {
+                                // Flush buffered PTY output before leaving the prompt
+                                if !pty_buffer.is_empty() {
+                                    stdout.write_all(&pty_buffer)?;
+                                    stdout.flush()?;
+                                    pty_buffer.clear();
+                                }
+                                renderer.emit_redirected(&instruction);
+                                let iteration =
+                                    journal_redirect(*paused, &instruction, &mut journal);
+                                audit.log_denied(
+                                    iteration,
+                                    "redirect",
+                                    &redirect_message(&instruction),
+                                );
+                                pending_instruction = Some(instruction);
+
+                                // Revised plan: rebuild from journal, which now
+                                // holds the redirect results and the instruction.
+                                cached_conversation = None;
+                                conversation_tokens = 0;
+                                state = start_streaming(
+                                    rt_handle,
+                                    config,
+                                    &backend,
+                                    &mut journal,
+                                    &output_history,
+                                    &screen,
+                                    terminal_size,
+                                    iteration + 1,
+                                    &tx_for_streaming,
+                                    &mut renderer,
+                                    child_pid,
+                                    Some(&shell_reports),
+                                    &mut cached_conversation,
+                                    &mut conversation_tokens,
+                                );
+                            }
+                        }
+                    }
                     AgentState::Executing { .. } => {
                         // Check for Ctrl+C — forward to PTY and abort agent loop
                         if data.contains(&0x03) {
@@ -1029,10 +2584,8 @@ pub fn run_repl(
                             }
                         }
                         StreamEvent::Usage { .. } => {}
-                        StreamEvent::Error(_) => {
-                            if display.streaming_text.is_empty() {
-                                renderer.emit_clear_line();
-                            }
+                        StreamEvent::Error(_) if display.streaming_text.is_empty() => {
+                            renderer.emit_clear_line();
                         }
                         _ => {}
                     }
@@ -1107,6 +2660,12 @@ pub fn run_repl(
                             let tool_use_ids: Vec<String> =
                                 tool_uses.iter().map(|t| t.id.clone()).collect();
 
+                            hook_ctx = HookContext {
+                                host: shell_reports.host().to_string(),
+                                cwd: shell_cwd(child_pid, shell_reports.cwd.as_deref()),
+                                depth: 0,
+                                instruction: pending_instruction.clone().unwrap_or_default(),
+                            };
                             let action = classify_and_gate(
                                 commands,
                                 tool_use_ids,
@@ -1116,6 +2675,9 @@ pub fn run_repl(
                                 &mut audit,
                                 &mut renderer,
                                 sandbox_active,
+                                &hooks,
+                                &hook_ctx,
+                                &session_grants,
                             );
 
                             match action {
@@ -1139,18 +2701,15 @@ pub fn run_repl(
                                     // Nudge shell to redisplay prompt below agent output
                                     let _ = session.write_all(b"\n");
                                 }
-                                CommandAction::Blocked { tool_use_ids: ids } => {
+                                CommandAction::Blocked {
+                                    tool_use_ids: ids,
+                                    message,
+                                } => {
                                     if !ids.is_empty() {
-                                        let denial_msg = "Command was blocked by the security policy. \
-                                            The command is on the deny list and cannot be executed. \
-                                            Please suggest a safer alternative.";
                                         let tool_results: Vec<ToolResultRecord> = ids
                                             .iter()
                                             .map(|id| {
-                                                ToolResultRecord::text(
-                                                    id.clone(),
-                                                    denial_msg.to_string(),
-                                                )
+                                                ToolResultRecord::text(id.clone(), message.clone())
                                             })
                                             .collect();
                                         if let Some(ref mut j) = journal {
@@ -1178,7 +2737,9 @@ pub fn run_repl(
                                     tool_use_ids,
                                     iteration,
                                     use_cr_reset,
+                                    hook_notes: notes,
                                 } => {
+                                    hook_notes = notes;
                                     total_commands += commands.len() as u32;
                                     command_queue.enqueue(commands);
                                     if let Some(cmd) = command_queue.pop_immediate() {
@@ -1196,6 +2757,8 @@ pub fn run_repl(
                                                 iteration,
                                                 capture,
                                                 tool_use_ids,
+                                                notes: hook_notes_for(&hook_notes, None),
+                                                step: None,
                                             };
                                         }
                                     }
@@ -1207,13 +2770,21 @@ pub fn run_repl(
                                     has_privileged,
                                     iteration,
                                     use_cr_reset,
+                                    hook_notes: notes,
                                 } => {
+                                    hook_notes = notes;
                                     state = start_judging(
                                         rt_handle,
-                                        config,
+                                        &backend,
                                         &commands,
                                         pending_instruction.as_deref().unwrap_or(""),
-                                        &build_shell_context(config, terminal_size, child_pid).cwd,
+                                        &build_shell_context(
+                                            config,
+                                            terminal_size,
+                                            child_pid,
+                                            Some(&shell_reports),
+                                        )
+                                        .cwd,
                                         iteration,
                                         tool_use_ids,
                                         risk_levels,
@@ -1230,7 +2801,9 @@ pub fn run_repl(
                                     has_privileged,
                                     iteration,
                                     use_cr_reset,
+                                    hook_notes: notes,
                                 } => {
+                                    hook_notes = notes;
                                     show_approval_ui(
                                         &commands,
                                         &risk_levels,
@@ -1289,12 +2862,27 @@ pub fn run_repl(
                     };
                 }
             }
-            Event::PtyOutput(data) => {
-                // Buffer PTY output during Approving/Judging to prevent
+            event @ (Event::PtyOutput(_) | Event::PromptCheck) => {
+                // A prompt check runs the output path with no new bytes, so
+                // the events the detector synthesizes are handled like the
+                // shell's own.
+                let data = match event {
+                    Event::PtyOutput(data) => data,
+                    _ => {
+                        prompt_check_pending = false;
+                        Vec::new()
+                    }
+                };
+                // Buffer PTY output during Approving/Editing/Stepping/Steering/Composing/Judging to prevent
                 // zsh job notifications from corrupting the approval UI.
                 if matches!(
                     state,
-                    AgentState::Approving { .. } | AgentState::Judging { .. }
+                    AgentState::Approving { .. }
+                        | AgentState::Editing { .. }
+                        | AgentState::Stepping { .. }
+                        | AgentState::Steering { .. }
+                        | AgentState::Composing { .. }
+                        | AgentState::Judging { .. }
                 ) {
                     pty_buffer.extend_from_slice(&data);
                 } else {
@@ -1306,8 +2894,9 @@ pub fn run_repl(
                     stdout.flush()?;
                 }
 
-                // Feed to output history
+                // Feed to output history and the screen
                 output_history.feed(&data);
+                screen.feed(&data);
 
                 // Feed to user command capture if active (Idle state, between 133;C and 133;D)
                 if matches!(state, AgentState::Idle) {
@@ -1324,7 +2913,7 @@ pub fn run_repl(
                     capture.feed(&data);
                 }
 
-                let events = parser.feed_bytes(&data);
+                let mut events = parser.feed_bytes(&data);
                 if debug_osc {
                     for evt in &events {
                         renderer.emit_debug(&format!(
@@ -1334,10 +2923,53 @@ pub fn run_repl(
                     }
                 }
 
-                for evt in &events {
-                    if *evt == OscEvent::Osc133A {
-                        line_buf.clear();
-                    }
+                let real_events = events.len();
+                if let Some(detector) = prompt_detector.as_mut() {
+                    let agent_running = matches!(state, AgentState::Executing { .. });
+                    let (synthesized, next_check) =
+                        detect_prompt(detector, &mut parser, &data, &events, agent_running);
+                    if debug_osc {
+                        for evt in &synthesized {
+                            renderer.emit_debug(&format!("[ua:prompt] synthesized {evt:?}"));
+                        }
+                    }
+                    events.extend(synthesized);
+                    if let Some(wait) = next_check.filter(|_| !prompt_check_pending) {
+                        prompt_check_pending = true;
+                        let tx_check = tx_for_streaming.clone();
+                        thread::spawn(move || {
+                            thread::sleep(wait);
+                            let _ = tx_check.send(Event::PromptCheck);
+                        });
+                    }
+                }
+
+                // A guessed prompt only moves the queue on when it ends a
+                // command entered at a guessed prompt (it comes with a D);
+                // otherwise it may be output of the command still running.
+                let guessed_done = events[real_events..]
+                    .iter()
+                    .any(|evt| matches!(evt, OscEvent::Osc133D { .. }));
+                for (i, evt) in events.iter().enumerate() {
+                    shell_reports.observe(evt);
+                    if *evt == OscEvent::Osc133A {
+                        line_buf.clear();
+                    }
+
+                    // `#!integrate` probe answered (or not) by the next prompt.
+                    if *evt == OscEvent::Osc133B && std::mem::take(&mut integrate_probe) {
+                        match shell_reports.probed_shell.take() {
+                            Some(shell) => type_bootstrap(
+                                &shell,
+                                &mut shell_reports,
+                                &mut session,
+                                &mut renderer,
+                            ),
+                            None => renderer.emit_integrate(
+                                "couldn't tell which shell this is; name it, e.g. #!integrate bash",
+                            ),
+                        }
+                    }
 
                     // Start capturing user command output on 133;C (command started).
                     if *evt == OscEvent::Osc133C && matches!(state, AgentState::Idle) {
@@ -1346,8 +2978,13 @@ pub fn run_repl(
 
                     // Capture user command exit code on 133;D (idle, non-agent).
                     if let OscEvent::Osc133D { exit_code } = evt {
+                        // The command line the shell reported (633;E) is
+                        // exact; the keystrokes we saw miss history recall
+                        // and completion.
+                        let reported_command = shell_reports.command.take();
                         if matches!(state, AgentState::Idle) {
-                            if let Some(cmd) = pending_user_command.take() {
+                            let typed = pending_user_command.take();
+                            if let Some(cmd) = reported_command.or(typed) {
                                 let captured_output = user_cmd_capture.take().and_then(|cap| {
                                     let lines = cap.lines();
                                     if lines.is_empty() {
@@ -1372,6 +3009,9 @@ pub fn run_repl(
                     }
 
                     // OSC 133 sequencing: dispatch next command on 133;B
+                    if i >= real_events && !guessed_done {
+                        continue;
+                    }
                     match command_queue.handle_osc_event(evt) {
                         QueueEvent::Dispatch(cmd) => {
                             let cmd = format!("{cmd}\n");
@@ -1391,31 +3031,67 @@ pub fn run_repl(
                                 iteration,
                                 capture,
                                 tool_use_ids,
-                                ..
+                                notes,
+                                step,
                             } = std::mem::replace(&mut state, AgentState::Idle)
                             {
                                 let captured_lines = capture.lines();
-                                if !captured_lines.is_empty() {
-                                    // Build observation with scrubbing
-                                    let raw_output = captured_lines.join("\n");
-                                    let scrubbed = scrub_injection_markers(&raw_output);
-                                    let observation =
-                                        format!("{}{}\n", TOOL_RESULT_PREFIX, scrubbed);
-
-                                    // Write tool result to journal
-                                    let tool_results: Vec<ToolResultRecord> = tool_use_ids
-                                        .iter()
-                                        .map(|id| {
-                                            ToolResultRecord::text(id.clone(), observation.clone())
-                                        })
-                                        .collect();
-                                    if let Some(ref mut j) = journal {
-                                        j.append(&JournalEntry::ToolResult {
-                                            ts: epoch_secs(),
-                                            results: tool_results.clone(),
-                                        });
+                                // Build observation with scrubbing
+                                let raw_output = captured_lines.join("\n");
+                                let mut notes = notes;
+                                // Sandbox reports become notes, as in batch mode.
+                                if let Some(reports) = command_queue.take_reports() {
+                                    for msg in &reports.messages {
+                                        renderer.emit_sandbox_report(msg);
+                                    }
+                                    notes.extend(reports.notes());
+                                }
+                                let scrubbed = scrub_injection_markers(&raw_output);
+                                let observation = with_notes(
+                                    &notes,
+                                    format!("{}{}\n", TOOL_RESULT_PREFIX, scrubbed),
+                                );
+
+                                let tool_results = match step {
+                                    // Step-through: record this step's result,
+                                    // then prompt for the next one
+                                    Some(mut steps) => {
+                                        steps.record(observation);
+                                        if steps.advance() {
+                                            show_step(&steps, &mut renderer);
+                                            state = AgentState::Stepping {
+                                                steps,
+                                                editor: None,
+                                                iteration,
+                                            };
+                                            continue;
+                                        }
+                                        Some(steps.finish(&mut journal).0)
                                     }
+                                    None if !captured_lines.is_empty() || !notes.is_empty() => {
+                                        // Write tool result to journal
+                                        let tool_results: Vec<ToolResultRecord> = tool_use_ids
+                                            .iter()
+                                            .map(|id| {
+                                                ToolResultRecord::text(
+                                                    id.clone(),
+                                                    observation.clone(),
+                                                )
+                                            })
+                                            .collect();
+                                        if let Some(ref mut j) = journal {
+                                            j.append(&JournalEntry::ToolResult {
+                                                ts: epoch_secs(),
+                                                results: tool_results.clone(),
+                                            });
+                                        }
+                                        Some(tool_results)
+                                    }
+                                    // No output — stay Idle
+                                    None => None,
+                                };
 
+                                if let Some(tool_results) = tool_results {
                                     // Append tool_result to in-memory conversation
                                     let result_msg =
                                         ua_protocol::ConversationMessage::tool_result(tool_results);
@@ -1438,18 +3114,20 @@ pub fn run_repl(
                                     state = start_streaming(
                                         rt_handle,
                                         config,
+                                        &backend,
                                         &mut journal,
                                         &output_history,
+                                        &screen,
                                         terminal_size,
                                         next_iteration,
                                         &tx_for_streaming,
                                         &mut renderer,
                                         child_pid,
+                                        Some(&shell_reports),
                                         &mut cached_conversation,
                                         &mut conversation_tokens,
                                     );
                                 }
-                                // else: no output — stay Idle
                             }
                         }
                         QueueEvent::Failed(code) => {
@@ -1474,6 +3152,7 @@ pub fn run_repl(
             }
             Event::Resize(cols, rows) => {
                 terminal_size = (cols, rows);
+                screen.resize(cols, rows);
                 if let Err(e) = session.resize(cols, rows) {
                     if debug_osc {
                         renderer.emit_debug(&format!("[ua] resize error: {e}"));
@@ -1506,11 +3185,16 @@ pub fn run_repl(
                         .into_iter()
                         .collect();
 
-                // Suppress status emissions during Approving/Judging to prevent
+                // Suppress status emissions during Approving/Editing/Stepping/Steering/Composing/Judging to prevent
                 // child status lines from corrupting the approval UI.
                 let suppress_emissions = matches!(
                     state,
-                    AgentState::Approving { .. } | AgentState::Judging { .. }
+                    AgentState::Approving { .. }
+                        | AgentState::Editing { .. }
+                        | AgentState::Stepping { .. }
+                        | AgentState::Steering { .. }
+                        | AgentState::Composing { .. }
+                        | AgentState::Judging { .. }
                 );
 
                 // New children: appeared since last poll
@@ -1558,25 +3242,19 @@ pub fn run_repl(
 fn start_streaming<W: Write>(
     rt_handle: &Handle,
     config: &Config,
+    backend: &Arc<dyn Backend>,
     journal: &mut Option<SessionJournal>,
     history: &OutputHistory,
+    screen: &Screen,
     terminal_size: (u16, u16),
     iteration: usize,
     tx: &mpsc::Sender<Event>,
     renderer: &mut ReplRenderer<W>,
     child_pid: Option<u32>,
+    shell_reports: Option<&ShellReports>,
     cached_conversation: &mut Option<Vec<ua_protocol::ConversationMessage>>,
     conversation_tokens: &mut usize,
 ) -> AgentState {
-    // Resolve API key
-    let api_key = match config.backend.anthropic.resolve_api_key() {
-        Ok(key) => key,
-        Err(e) => {
-            renderer.emit_error(&e.to_string());
-            return AgentState::Idle;
-        }
-    };
-
     // Use cached conversation or rebuild from journal
     let rebuilt_from_journal = cached_conversation.is_none();
     let conversation = if let Some(conv) = cached_conversation.take() {
@@ -1601,7 +3279,15 @@ fn start_streaming<W: Write>(
         conversation.clone(),
         terminal_size,
         child_pid,
+        shell_reports,
     );
+    // A full-screen program draws with cursor moves; the history only has
+    // fragments of its redraws.
+    let request = if screen.alternate_screen() {
+        request.with_screen(screen.screen_snapshot())
+    } else {
+        request
+    };
 
     // Log system prompt to journal only when we rebuilt from journal.
     if rebuilt_from_journal {
@@ -1617,9 +3303,7 @@ fn start_streaming<W: Write>(
     // Store conversation back in cache for the caller
     *cached_conversation = Some(conversation);
 
-    // Create client and stream
-    let client = AnthropicClient::with_model(&api_key, &config.backend.anthropic.model);
-    let stream = client.send(&request);
+    let stream = backend.send(&request);
 
     // Show initial spinner
     renderer.emit_spinner_initial();
@@ -1683,7 +3367,7 @@ fn show_approval_ui<W: Write>(
     }
 
     let privileged = has_privileged && config.security.require_yes_for_privileged;
-    renderer.emit_approval_prompt(privileged);
+    renderer.emit_approval_prompt(privileged, commands.len() > 1);
 }
 
 /// Spawn a tokio task to run the LLM security judge, forwarding the result through the mpsc channel.
@@ -1691,7 +3375,7 @@ fn show_approval_ui<W: Write>(
 #[allow(clippy::too_many_arguments)]
 fn start_judging<W: Write>(
     rt_handle: &Handle,
-    config: &Config,
+    backend: &Arc<dyn Backend>,
     commands: &[String],
     instruction: &str,
     cwd: &str,
@@ -1703,27 +3387,9 @@ fn start_judging<W: Write>(
     tx: &mpsc::Sender<Event>,
     renderer: &mut ReplRenderer<W>,
 ) -> AgentState {
-    // Resolve API key
-    let api_key = match config.backend.anthropic.resolve_api_key() {
-        Ok(key) => key,
-        Err(e) => {
-            renderer.emit_judge_error(&e.to_string());
-            // Fall through to approval UI without judge
-            show_approval_ui(commands, &risk_levels, has_privileged, config, renderer);
-            return AgentState::Approving {
-                commands: commands.to_vec(),
-                iteration,
-                tool_use_ids,
-                has_privileged,
-                yes_buffer: String::new(),
-                use_cr_reset,
-            };
-        }
-    };
-
     renderer.emit_judging();
 
-    let client = AnthropicClient::with_model(&api_key, &config.backend.anthropic.model);
+    let backend = Arc::clone(backend);
     let commands_owned: Vec<String> = commands.to_vec();
     let instruction_owned = instruction.to_string();
     let cwd_owned = cwd.to_string();
@@ -1733,7 +3399,7 @@ fn start_judging<W: Write>(
     let tx_clone = tx.clone();
     rt_handle.spawn(async move {
         let verdict = tokio::select! {
-            v = judge::evaluate_commands(&client, &commands_owned, &instruction_owned, &cwd_owned, false) => v,
+            v = judge::evaluate_commands(backend.as_ref(), &commands_owned, &instruction_owned, &cwd_owned, false) => v,
             _ = cancel_rx => {
                 return; // Cancelled — don't send result
             }
@@ -1812,6 +3478,40 @@ mod tests {
         assert!(!queue.is_empty()); // "pwd" still queued
     }
 
+    #[test]
+    fn command_queue_wraps_commands_in_sandbox_exec() {
+        let mut queue = CommandQueue::sandboxed(SandboxWrap {
+            exe: "/opt/ua/unixagent".to_string(),
+            kind: ShellKind::Bash,
+        });
+        queue.enqueue(vec!["echo 'hi' > out.txt".to_string()]);
+        assert_eq!(
+            queue.pop_immediate().unwrap(),
+            r"'/opt/ua/unixagent' --sandbox-exec sh -c 'echo '\''hi'\'' > out.txt'"
+        );
+    }
+
+    #[test]
+    fn repl_sandbox_policy_has_no_timeout() {
+        let mut config = Config::default();
+        config.sandbox.limits.memory_mb = 512;
+        let policy = repl_sandbox_policy(&config);
+        assert_eq!(policy.limits.timeout_secs, 0);
+        assert_eq!(policy.limits.memory_mb, 512);
+    }
+
+    #[test]
+    fn sandbox_wrap_calls_quoted_executable_in_powershell() {
+        let wrap = SandboxWrap {
+            exe: "/opt/ua/unixagent".to_string(),
+            kind: ShellKind::PowerShell,
+        };
+        assert_eq!(
+            wrap.wrap("echo 'hi'"),
+            "& '/opt/ua/unixagent' --sandbox-exec sh -c 'echo ''hi'''"
+        );
+    }
+
     #[test]
     fn command_queue_does_not_dispatch_on_133a() {
         let mut queue = CommandQueue::new();
@@ -2226,7 +3926,7 @@ mod tests {
 
     #[test]
     fn tool_result_built_from_ids() {
-        let tool_use_ids = vec!["toolu_a".to_string(), "toolu_b".to_string()];
+        let tool_use_ids = ["toolu_a".to_string(), "toolu_b".to_string()];
         let observation = "file1.txt\nfile2.txt".to_string();
 
         let tool_results: Vec<ToolResultRecord> = tool_use_ids
@@ -2312,6 +4012,9 @@ mod tests {
             &mut audit,
             &mut renderer,
             false,
+            &HookRunner::none(),
+            &HookContext::default(),
+            &SessionGrants::new(),
         );
 
         assert!(matches!(action, CommandAction::AutoApprove { .. }));
@@ -2353,6 +4056,9 @@ mod tests {
             &mut audit,
             &mut renderer,
             false,
+            &HookRunner::none(),
+            &HookContext::default(),
+            &SessionGrants::new(),
         );
 
         assert!(
@@ -2389,6 +4095,9 @@ mod tests {
             &mut audit,
             &mut renderer,
             false,
+            &HookRunner::none(),
+            &HookContext::default(),
+            &SessionGrants::new(),
         );
 
         assert!(
@@ -2415,6 +4124,9 @@ mod tests {
             &mut audit,
             &mut renderer,
             false,
+            &HookRunner::none(),
+            &HookContext::default(),
+            &SessionGrants::new(),
         );
 
         assert!(matches!(action, CommandAction::Blocked { .. }));
@@ -2429,6 +4141,736 @@ mod tests {
         assert!(output.contains("DENIED"));
     }
 
+    /// Hook runner backed by a single `pre-exec.sh` script in `dir`.
+    fn hook_runner(dir: &std::path::Path, body: &str) -> HookRunner {
+        use std::os::unix::fs::PermissionsExt;
+        let hooks_dir = dir.join("hooks");
+        std::fs::create_dir_all(&hooks_dir).unwrap();
+        let script = hooks_dir.join("pre-exec.sh");
+        std::fs::write(&script, format!("#!/bin/sh\ncat >/dev/null\n{body}\n")).unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        HookRunner::from_config(&crate::config::HooksConfig {
+            enabled: true,
+            dir: Some(hooks_dir.to_string_lossy().into_owned()),
+            timeout_secs: 5,
+        })
+    }
+
+    #[test]
+    fn hook_deny_blocks_with_reason() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let mut audit = AuditLogger::new(&path).unwrap();
+        let config = gate_config(true, true);
+        let mut renderer = ReplRenderer::new(Vec::new(), Style::disabled());
+        let hooks = hook_runner(
+            dir.path(),
+            r#"echo '{"decision": "deny", "reason": "not on fridays"}'"#,
+        );
+
+        let action = classify_and_gate(
+            vec!["ls".to_string()],
+            vec!["toolu_1".to_string()],
+            0,
+            false,
+            &config,
+            &mut audit,
+            &mut renderer,
+            false,
+            &hooks,
+            &HookContext::default(),
+            &SessionGrants::new(),
+        );
+
+        match action {
+            CommandAction::Blocked {
+                tool_use_ids,
+                message,
+            } => {
+                assert_eq!(tool_use_ids, vec!["toolu_1"]);
+                assert!(message.contains("not on fridays"), "{message}");
+            }
+            other => panic!("expected Blocked, got: {other:?}"),
+        }
+        let lines = read_audit_lines(&path);
+        assert!(lines
+            .iter()
+            .any(|l| l["type"] == "hook" && l["decision"] == "deny"));
+        let output = String::from_utf8_lossy(&renderer.writer);
+        assert!(output.contains("not on fridays"));
+    }
+
+    #[test]
+    fn hook_modify_rewrites_before_approval() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let mut audit = AuditLogger::new(&path).unwrap();
+        let config = gate_config(true, false);
+        let mut renderer = ReplRenderer::new(Vec::new(), Style::disabled());
+        let hooks = hook_runner(
+            dir.path(),
+            r#"echo '{"decision": "modify", "command": "ls -la"}'"#,
+        );
+
+        // `rm build` would need approval; the hook rewrites it to a read-only command.
+        let action = classify_and_gate(
+            vec!["rm build".to_string()],
+            vec!["toolu_1".to_string()],
+            0,
+            false,
+            &config,
+            &mut audit,
+            &mut renderer,
+            false,
+            &hooks,
+            &HookContext::default(),
+            &SessionGrants::new(),
+        );
+
+        match action {
+            CommandAction::AutoApprove {
+                commands,
+                hook_notes,
+                ..
+            } => {
+                assert_eq!(commands, vec!["ls -la"]);
+                assert_eq!(
+                    hook_notes,
+                    vec![HookNote {
+                        tool_use_id: "toolu_1".to_string(),
+                        note:
+                            "pre-exec hook pre-exec.sh changed command from `rm build` to `ls -la`"
+                                .to_string(),
+                    }]
+                );
+            }
+            other => panic!("expected AutoApprove, got: {other:?}"),
+        }
+        // The rewritten batch is logged with its own risk labels.
+        let lines = read_audit_lines(&path);
+        let rewritten = lines
+            .iter()
+            .find(|l| l["type"] == "proposed" && l["source"] == "hook")
+            .expect("hook proposal logged");
+        assert_eq!(rewritten["commands"][0], "ls -la");
+        assert_eq!(rewritten["risk_levels"][0], "read_only");
+    }
+
+    #[test]
+    fn review_edits_runs_hooks_on_changed_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut audit = AuditLogger::noop();
+        let mut renderer = ReplRenderer::new(Vec::new(), Style::disabled());
+        let hooks = hook_runner(
+            dir.path(),
+            r#"echo '{"decision": "deny", "reason": "no deletes today"}'"#,
+        );
+
+        let outcome = review_edits(
+            &strings(&["ls"]),
+            strings(&["rm -ri build"]),
+            0,
+            &hooks,
+            &HookContext::default(),
+            &mut audit,
+            &mut renderer,
+        );
+        match outcome {
+            EditOutcome::Blocked { message } => {
+                assert!(message.starts_with("user edited command from `ls`"));
+                assert!(message.contains("no deletes today"), "{message}");
+            }
+            other => panic!("expected Blocked, got: {other:?}"),
+        }
+
+        let hooks = hook_runner(
+            dir.path(),
+            r#"echo '{"decision": "modify", "command": "rm -ri ./build"}'"#,
+        );
+        let outcome = review_edits(
+            &strings(&["ls"]),
+            strings(&["rm -ri build"]),
+            0,
+            &hooks,
+            &HookContext::default(),
+            &mut audit,
+            &mut renderer,
+        );
+        match outcome {
+            EditOutcome::Run { commands, notes } => {
+                assert_eq!(commands, strings(&["rm -ri ./build"]));
+                assert_eq!(notes.len(), 2);
+                assert!(notes[1].starts_with("pre-exec hook"), "{notes:?}");
+            }
+            other => panic!("expected Run, got: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn session_grant_auto_approves_and_skips_judge() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let mut audit = AuditLogger::new(&path).unwrap();
+        let config = gate_config(true, true); // judge enabled
+        let mut renderer = ReplRenderer::new(Vec::new(), Style::disabled());
+        let ctx = HookContext {
+            cwd: "/work".to_string(),
+            ..Default::default()
+        };
+        let mut grants = SessionGrants::new();
+        grants.grant("cargo test -p foo", RiskLevel::BuildTest, "", "/work");
+
+        let action = classify_and_gate(
+            vec!["cargo test -p bar".to_string()],
+            vec!["toolu_1".to_string()],
+            0,
+            false,
+            &config,
+            &mut audit,
+            &mut renderer,
+            false,
+            &HookRunner::none(),
+            &ctx,
+            &grants,
+        );
+
+        assert!(
+            matches!(action, CommandAction::AutoApprove { .. }),
+            "expected AutoApprove, got: {action:?}"
+        );
+        let lines = read_audit_lines(&path);
+        assert!(lines
+            .iter()
+            .any(|l| l["type"] == "approved" && l["method"] == "session_grant"));
+        let output = String::from_utf8_lossy(&renderer.writer);
+        assert!(output.contains("granted"));
+    }
+
+    // --- Inline edit tests ---
+
+    fn strings(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn review_edits_records_change_and_approves() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let mut audit = AuditLogger::new(&path).unwrap();
+        let mut renderer = ReplRenderer::new(Vec::new(), Style::disabled());
+
+        let outcome = review_edits(
+            &strings(&["ls", "rm -rf build"]),
+            strings(&["ls", "rm -ri build"]),
+            2,
+            &HookRunner::default(),
+            &HookContext::default(),
+            &mut audit,
+            &mut renderer,
+        );
+
+        match outcome {
+            EditOutcome::Run { commands, notes } => {
+                assert_eq!(commands, strings(&["ls", "rm -ri build"]));
+                assert_eq!(
+                    notes,
+                    strings(&["user edited command from `rm -rf build` to `rm -ri build`"])
+                );
+            }
+            other => panic!("expected Run, got: {other:?}"),
+        }
+        let lines = read_audit_lines(&path);
+        assert!(lines
+            .iter()
+            .any(|l| l["type"] == "proposed" && l["source"] == "user_edit"));
+        assert!(lines
+            .iter()
+            .any(|l| l["type"] == "approved" && l["method"] == "edit"));
+        // Only the changed command is re-shown with its risk
+        let output = String::from_utf8_lossy(&renderer.writer);
+        assert!(output.contains("rm -ri build"));
+    }
+
+    #[test]
+    fn review_edits_unchanged_has_no_notes() {
+        let mut audit = AuditLogger::noop();
+        let mut renderer = ReplRenderer::new(Vec::new(), Style::disabled());
+        let outcome = review_edits(
+            &strings(&["cargo test"]),
+            strings(&["cargo test "]),
+            0,
+            &HookRunner::default(),
+            &HookContext::default(),
+            &mut audit,
+            &mut renderer,
+        );
+        assert!(
+            matches!(&outcome, EditOutcome::Run { notes, .. } if notes.is_empty()),
+            "got: {outcome:?}"
+        );
+    }
+
+    #[test]
+    fn review_edits_blocks_denied_edit() {
+        let mut audit = AuditLogger::noop();
+        let mut renderer = ReplRenderer::new(Vec::new(), Style::disabled());
+        let outcome = review_edits(
+            &strings(&["rm -rf build"]),
+            strings(&["rm -rf /"]),
+            0,
+            &HookRunner::default(),
+            &HookContext::default(),
+            &mut audit,
+            &mut renderer,
+        );
+        match outcome {
+            EditOutcome::Blocked { message } => {
+                assert!(message.starts_with("user edited command from `rm -rf build`"));
+                assert!(message.contains("blocked by the security policy"));
+            }
+            other => panic!("expected Blocked, got: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn review_edits_removed_commands() {
+        let mut audit = AuditLogger::noop();
+        let mut renderer = ReplRenderer::new(Vec::new(), Style::disabled());
+        let outcome = review_edits(
+            &strings(&["ls", "make clean"]),
+            strings(&["ls", ""]),
+            0,
+            &HookRunner::default(),
+            &HookContext::default(),
+            &mut audit,
+            &mut renderer,
+        );
+        match outcome {
+            EditOutcome::Run { commands, notes } => {
+                assert_eq!(commands, strings(&["ls"]));
+                assert_eq!(notes, strings(&["user removed command `make clean`"]));
+            }
+            other => panic!("expected Run, got: {other:?}"),
+        }
+
+        let outcome = review_edits(
+            &strings(&["make clean"]),
+            strings(&[""]),
+            0,
+            &HookRunner::default(),
+            &HookContext::default(),
+            &mut audit,
+            &mut renderer,
+        );
+        assert!(matches!(outcome, EditOutcome::Empty));
+    }
+
+    #[test]
+    fn with_notes_prepends_lines() {
+        assert_eq!(with_notes(&[], "out\n".to_string()), "out\n");
+        let notes = strings(&["user edited command from `a` to `b`"]);
+        assert_eq!(
+            with_notes(&notes, "out\n".to_string()),
+            "user edited command from `a` to `b`\nout\n"
+        );
+    }
+
+    // --- Step-through tests ---
+
+    fn three_steps() -> StepThrough {
+        StepThrough::new(
+            strings(&["ls", "rm -rf build", "make"]),
+            strings(&["toolu_1", "toolu_2", "toolu_3"]),
+            false,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn step_through_pairs_commands_with_tool_uses() {
+        let mut steps = three_steps();
+        assert_eq!(steps.position(), (1, 3));
+        assert_eq!(steps.command(), "ls");
+        assert_eq!(steps.tool_use_id(), "toolu_1");
+        steps.record("out".to_string());
+        assert!(steps.advance());
+        assert_eq!(steps.position(), (2, 3));
+        assert_eq!(steps.tool_use_id(), "toolu_2");
+        assert!(StepThrough::new(Vec::new(), Vec::new(), false).is_none());
+    }
+
+    #[test]
+    fn step_through_results_are_per_tool_use() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut journal = Some(SessionJournal::new(dir.path().join("j.jsonl")).unwrap());
+        let mut steps = three_steps();
+        steps.record("listing".to_string());
+        steps.advance();
+        steps.block(STEP_SKIPPED_MESSAGE);
+        steps.advance();
+        steps.record("built".to_string());
+        assert!(!steps.advance());
+
+        let (results, any_ran) = steps.finish(&mut journal);
+        assert!(any_ran);
+        let ids: Vec<&str> = results.iter().map(|r| r.tool_use_id.as_str()).collect();
+        assert_eq!(ids, vec!["toolu_1", "toolu_3", "toolu_2"]);
+
+        let entries = journal.unwrap().read_all();
+        assert!(
+            matches!(&entries[0], JournalEntry::ToolResult { results, .. } if results.len() == 2)
+        );
+        assert!(
+            matches!(&entries[1], JournalEntry::Blocked { results, .. } if results.len() == 1 && results[0].tool_use_id == "toolu_2")
+        );
+    }
+
+    #[test]
+    fn step_input_skip_advances_and_last_skip_finishes() {
+        let mut audit = AuditLogger::noop();
+        let mut renderer = ReplRenderer::new(Vec::new(), Style::disabled());
+        let mut steps = StepThrough::new(
+            strings(&["rm -rf build", "make"]),
+            strings(&["toolu_1", "toolu_2"]),
+            false,
+        )
+        .unwrap();
+        let mut editor = None;
+
+        let action = handle_step_input(
+            b"n",
+            &mut steps,
+            &mut editor,
+            0,
+            &HookRunner::default(),
+            &HookContext::default(),
+            &mut audit,
+            &mut renderer,
+        );
+        assert!(matches!(action, StepAction::Wait));
+        assert_eq!(steps.command(), "make");
+        let output = String::from_utf8_lossy(&renderer.writer);
+        assert!(output.contains("skipped"));
+        assert!(output.contains("[2/2] ❯ make"));
+
+        let action = handle_step_input(
+            b"n",
+            &mut steps,
+            &mut editor,
+            0,
+            &HookRunner::default(),
+            &HookContext::default(),
+            &mut audit,
+            &mut renderer,
+        );
+        assert!(matches!(action, StepAction::Done));
+        let (results, any_ran) = steps.finish(&mut None);
+        assert!(!any_ran);
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn step_input_approve_runs_current_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let mut audit = AuditLogger::new(&path).unwrap();
+        let mut renderer = ReplRenderer::new(Vec::new(), Style::disabled());
+        let mut steps = three_steps();
+        let action = handle_step_input(
+            b"y",
+            &mut steps,
+            &mut None,
+            0,
+            &HookRunner::default(),
+            &HookContext::default(),
+            &mut audit,
+            &mut renderer,
+        );
+        assert!(
+            matches!(&action, StepAction::Run { command, notes } if command == "ls" && notes.is_empty()),
+            "got: {action:?}"
+        );
+        let lines = read_audit_lines(&path);
+        assert!(lines
+            .iter()
+            .any(|l| l["type"] == "approved" && l["reason"] == "user approved step 1/3"));
+    }
+
+    #[test]
+    fn step_input_edit_reclassifies_and_runs() {
+        let mut audit = AuditLogger::noop();
+        let mut renderer = ReplRenderer::new(Vec::new(), Style::disabled());
+        let mut steps = three_steps();
+        steps.advance(); // rm -rf build
+        let mut editor = None;
+
+        let action = handle_step_input(
+            b"e",
+            &mut steps,
+            &mut editor,
+            0,
+            &HookRunner::default(),
+            &HookContext::default(),
+            &mut audit,
+            &mut renderer,
+        );
+        assert!(matches!(action, StepAction::Wait));
+        assert!(editor.is_some());
+
+        // Ctrl-U, retype, Enter
+        let action = handle_step_input(
+            b"\x15rm -ri build\r",
+            &mut steps,
+            &mut editor,
+            0,
+            &HookRunner::default(),
+            &HookContext::default(),
+            &mut audit,
+            &mut renderer,
+        );
+        match action {
+            StepAction::Run { command, notes } => {
+                assert_eq!(command, "rm -ri build");
+                assert_eq!(
+                    notes,
+                    strings(&["user edited command from `rm -rf build` to `rm -ri build`"])
+                );
+            }
+            other => panic!("expected Run, got: {other:?}"),
+        }
+        assert!(editor.is_none());
+    }
+
+    #[test]
+    fn step_input_denied_edit_blocks_only_that_step() {
+        let mut audit = AuditLogger::noop();
+        let mut renderer = ReplRenderer::new(Vec::new(), Style::disabled());
+        let mut steps = three_steps();
+        steps.advance();
+        let mut editor = Some(LineEditor::new(""));
+        let action = handle_step_input(
+            b"rm -rf /\r",
+            &mut steps,
+            &mut editor,
+            0,
+            &HookRunner::default(),
+            &HookContext::default(),
+            &mut audit,
+            &mut renderer,
+        );
+        assert!(matches!(action, StepAction::Wait));
+        assert_eq!(steps.command(), "make");
+        let (results, _) = steps.finish(&mut None);
+        let blocked = results.iter().find(|r| r.tool_use_id == "toolu_2").unwrap();
+        assert!(blocked.content.contains("blocked by the security policy"));
+    }
+
+    #[test]
+    fn step_input_stop_blocks_remaining() {
+        let mut audit = AuditLogger::noop();
+        let mut renderer = ReplRenderer::new(Vec::new(), Style::disabled());
+        let mut steps = three_steps();
+        steps.record("listing".to_string());
+        steps.advance();
+        let action = handle_step_input(
+            b"q",
+            &mut steps,
+            &mut None,
+            0,
+            &HookRunner::default(),
+            &HookContext::default(),
+            &mut audit,
+            &mut renderer,
+        );
+        assert!(matches!(action, StepAction::Stop));
+        let (results, any_ran) = steps.finish(&mut None);
+        assert!(any_ran);
+        assert_eq!(results.len(), 3);
+        assert!(results[2].content.contains("stopped the plan"));
+    }
+
+    // --- Mid-plan steering tests ---
+
+    #[test]
+    fn redirect_at_approval_answers_pending_tool_uses() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut journal = Some(SessionJournal::new(dir.path().join("j.jsonl")).unwrap());
+        let paused = AgentState::Approving {
+            commands: strings(&["rm -rf /var/cache/nginx", "df -h"]),
+            iteration: 2,
+            tool_use_ids: strings(&["toolu_1", "toolu_2"]),
+            has_privileged: false,
+            yes_buffer: String::new(),
+            use_cr_reset: false,
+        };
+
+        let iteration = journal_redirect(paused, "keep the nginx cache", &mut journal);
+        assert_eq!(iteration, 2);
+
+        let entries = journal.unwrap().read_all();
+        match &entries[0] {
+            JournalEntry::ToolResult { results, .. } => {
+                assert_eq!(results.len(), 2);
+                assert!(results
+                    .iter()
+                    .all(|r| r.content == "user redirected: keep the nginx cache"));
+            }
+            other => panic!("expected ToolResult, got: {other:?}"),
+        }
+        assert!(
+            matches!(&entries[1], JournalEntry::Instruction { text, .. } if text == "keep the nginx cache")
+        );
+
+        // Rebuilt conversation: one user turn with the results and the instruction
+        let messages = crate::journal::convert_entries_to_messages(&entries);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].tool_results.len(), 2);
+        assert_eq!(messages[0].content, "keep the nginx cache");
+    }
+
+    #[test]
+    fn redirect_mid_step_keeps_output_of_steps_that_ran() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut journal = Some(SessionJournal::new(dir.path().join("j.jsonl")).unwrap());
+        let mut steps = three_steps();
+        steps.record("listing".to_string());
+        steps.advance();
+        let paused = AgentState::Stepping {
+            steps,
+            editor: None,
+            iteration: 0,
+        };
+
+        journal_redirect(paused, "use make clean instead", &mut journal);
+
+        let entries = journal.unwrap().read_all();
+        assert!(
+            matches!(&entries[0], JournalEntry::ToolResult { results, .. } if results.len() == 1 && results[0].content == "listing")
+        );
+        match &entries[1] {
+            JournalEntry::Blocked { results, .. } => {
+                let ids: Vec<&str> = results.iter().map(|r| r.tool_use_id.as_str()).collect();
+                assert_eq!(ids, vec!["toolu_2", "toolu_3"]);
+                assert!(results[0].content.starts_with("user redirected:"));
+            }
+            other => panic!("expected Blocked, got: {other:?}"),
+        }
+        assert!(matches!(&entries[2], JournalEntry::Instruction { .. }));
+    }
+
+    #[test]
+    fn hash_at_step_prompt_starts_steering() {
+        let mut audit = AuditLogger::noop();
+        let mut renderer = ReplRenderer::new(Vec::new(), Style::disabled());
+        let mut steps = three_steps();
+        let action = handle_step_input(
+            b"#",
+            &mut steps,
+            &mut None,
+            0,
+            &HookRunner::default(),
+            &HookContext::default(),
+            &mut audit,
+            &mut renderer,
+        );
+        assert!(matches!(action, StepAction::Steer));
+
+        let paused = AgentState::Stepping {
+            steps,
+            editor: None,
+            iteration: 0,
+        };
+        let state = start_steering(paused, &mut renderer);
+        match state {
+            AgentState::Steering { editor, paused } => {
+                assert_eq!(editor.text(), "# ");
+                assert!(matches!(*paused, AgentState::Stepping { .. }));
+            }
+            _ => panic!("expected Steering"),
+        }
+    }
+
+    // --- Multi-line instruction tests ---
+
+    #[test]
+    fn start_composing_echoes_pasted_lines_only() {
+        let mut renderer = ReplRenderer::new(Vec::new(), Style::disabled());
+        let state = start_composing(strings(&["deploy,", "and test"]), true, &mut renderer);
+        let output = String::from_utf8_lossy(&renderer.writer).to_string();
+        assert!(output.contains("# deploy,"));
+        assert!(output.contains("# and test"));
+        assert!(matches!(state, AgentState::Composing { ref lines, .. } if lines.len() == 2));
+
+        // Typed continuation: the first line is already on the shell's line
+        let mut renderer = ReplRenderer::new(Vec::new(), Style::disabled());
+        start_composing(strings(&["deploy,"]), false, &mut renderer);
+        let output = String::from_utf8_lossy(&renderer.writer).to_string();
+        assert!(!output.contains("deploy"));
+    }
+
+    #[test]
+    fn pending_paste_is_checked_without_more_input() {
+        let (tx, rx) = mpsc::channel();
+        let started = Instant::now() - instruction::PASTE_TIMEOUT;
+        let paste = instruction::PasteBuffer::new(started);
+
+        // Input that is already waiting comes first.
+        tx.send(Event::Stdin(b"x".to_vec())).unwrap();
+        assert!(matches!(
+            next_event(&rx, Some(&paste)),
+            Some(Event::Stdin(_))
+        ));
+        // Then the loop wakes up for the paste instead of blocking.
+        assert!(matches!(
+            next_event(&rx, Some(&paste)),
+            Some(Event::PasteCheck)
+        ));
+
+        drop(tx);
+        assert!(next_event(&rx, None).is_none());
+    }
+
+    #[test]
+    fn track_paste_updates_line_buffer() {
+        let mut line_buf = "echo ".to_string();
+        track_paste(&mut line_buf, "hello");
+        assert_eq!(line_buf, "echo hello");
+        track_paste(&mut line_buf, "a\nb");
+        assert!(line_buf.is_empty());
+    }
+
+    #[test]
+    fn session_grant_in_other_cwd_still_prompts() {
+        let mut audit = AuditLogger::noop();
+        let config = gate_config(true, false);
+        let mut renderer = ReplRenderer::new(Vec::new(), Style::disabled());
+        let ctx = HookContext {
+            cwd: "/elsewhere".to_string(),
+            ..Default::default()
+        };
+        let mut grants = SessionGrants::new();
+        grants.grant("cargo test", RiskLevel::BuildTest, "", "/work");
+
+        let action = classify_and_gate(
+            vec!["cargo test".to_string()],
+            vec!["toolu_1".to_string()],
+            0,
+            false,
+            &config,
+            &mut audit,
+            &mut renderer,
+            false,
+            &HookRunner::none(),
+            &ctx,
+            &grants,
+        );
+
+        assert!(
+            matches!(action, CommandAction::Approve { .. }),
+            "expected Approve, got: {action:?}"
+        );
+    }
+
     #[test]
     fn judge_gate_no_commands_returns_idle() {
         let mut audit = AuditLogger::noop();
@@ -2444,6 +4886,9 @@ mod tests {
             &mut audit,
             &mut renderer,
             false,
+            &HookRunner::none(),
+            &HookContext::default(),
+            &SessionGrants::new(),
         );
 
         assert!(matches!(action, CommandAction::NoCommands));
@@ -2466,6 +4911,9 @@ mod tests {
             &mut audit,
             &mut renderer,
             false,
+            &HookRunner::none(),
+            &HookContext::default(),
+            &SessionGrants::new(),
         );
 
         assert!(matches!(action, CommandAction::Judge { .. }));
@@ -2570,6 +5018,9 @@ mod tests {
             &mut audit,
             &mut renderer,
             false,
+            &HookRunner::none(),
+            &HookContext::default(),
+            &SessionGrants::new(),
         );
         assert!(matches!(action, CommandAction::Judge { .. }));
 
@@ -2605,6 +5056,9 @@ mod tests {
             &mut audit,
             &mut renderer,
             false,
+            &HookRunner::none(),
+            &HookContext::default(),
+            &SessionGrants::new(),
         );
         assert!(
             matches!(action, CommandAction::Judge { .. }),
@@ -2849,6 +5303,9 @@ mod tests {
             &mut audit,
             &mut renderer,
             true, // sandbox_active
+            &HookRunner::none(),
+            &HookContext::default(),
+            &SessionGrants::new(),
         );
 
         assert!(
@@ -2880,6 +5337,9 @@ mod tests {
             &mut audit,
             &mut renderer,
             true, // sandbox_active
+            &HookRunner::none(),
+            &HookContext::default(),
+            &SessionGrants::new(),
         );
 
         assert!(
@@ -2906,6 +5366,9 @@ mod tests {
             &mut audit,
             &mut renderer,
             true, // sandbox_active
+            &HookRunner::none(),
+            &HookContext::default(),
+            &SessionGrants::new(),
         );
 
         assert!(
@@ -2932,6 +5395,9 @@ mod tests {
             &mut audit,
             &mut renderer,
             false, // sandbox_active = false
+            &HookRunner::none(),
+            &HookContext::default(),
+            &SessionGrants::new(),
         );
 
         assert!(
@@ -2958,6 +5424,9 @@ mod tests {
             &mut audit,
             &mut renderer,
             true, // sandbox_active
+            &HookRunner::none(),
+            &HookContext::default(),
+            &SessionGrants::new(),
         );
 
         assert!(
@@ -0,0 +1,680 @@
+//! Virtual terminal screen.
+//!
+//! `OutputHistory` keeps lines of text, which suits a shell session but not
+//! full-screen programs (`htop`, `vim`, `less`, TUI installers): they move
+//! the cursor around and redraw parts of the screen, so their output comes
+//! out as fragments. `Screen` runs the PTY output through a VT100/xterm
+//! state machine onto a grid of cells, so what the user sees can be read
+//! back with [`Screen::screen_snapshot`].
+//!
+//! Only what changes the text is tracked: cursor movement, erasing,
+//! insertion and deletion, scroll regions and the alternate screen. Colors
+//! and other attributes are dropped, and every character takes one cell.
+
+/// Tab stops are fixed every 8 columns.
+const TAB_WIDTH: usize = 8;
+
+/// Largest CSI parameter; bigger ones are clamped, so cursor arithmetic
+/// can't overflow.
+const MAX_PARAM: usize = u16::MAX as usize;
+
+type Grid = Vec<Vec<char>>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Cursor {
+    row: usize,
+    col: usize,
+    /// A character was written in the last column; the next one wraps.
+    wrap_pending: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    /// `ESC (`, `ESC #` and the like: one more byte, then done.
+    EscapeIntermediate,
+    Csi,
+    /// OSC, DCS, SOS, PM and APC strings: skipped up to BEL or ST.
+    Skip,
+    SkipEscape,
+}
+
+pub struct Screen {
+    cols: usize,
+    rows: usize,
+    primary: Grid,
+    alternate: Grid,
+    alternate_active: bool,
+    cursor: Cursor,
+    /// Saved by `ESC 7` / `CSI s`.
+    saved: Cursor,
+    /// Saved on entering the alternate screen with mode 1049.
+    saved_primary: Cursor,
+    /// Scroll region, first and last row inclusive.
+    scroll_top: usize,
+    scroll_bottom: usize,
+    autowrap: bool,
+    state: State,
+    /// Parameter and intermediate bytes of the CSI sequence being read.
+    csi: Vec<u8>,
+    /// Bytes of a UTF-8 character split across reads.
+    utf8: Vec<u8>,
+    /// Last character printed, for `CSI b` (repeat).
+    last_char: char,
+}
+
+impl Screen {
+    pub fn new(cols: u16, rows: u16) -> Self {
+        let cols = usize::from(cols).max(1);
+        let rows = usize::from(rows).max(1);
+        Self {
+            cols,
+            rows,
+            primary: blank_grid(cols, rows),
+            alternate: blank_grid(cols, rows),
+            alternate_active: false,
+            cursor: Cursor::default(),
+            saved: Cursor::default(),
+            saved_primary: Cursor::default(),
+            scroll_top: 0,
+            scroll_bottom: rows - 1,
+            autowrap: true,
+            state: State::Ground,
+            csi: Vec::new(),
+            utf8: Vec::new(),
+            last_char: ' ',
+        }
+    }
+
+    /// Whether a full-screen program has switched to the alternate screen.
+    pub fn alternate_screen(&self) -> bool {
+        self.alternate_active
+    }
+
+    /// The visible screen as text: one line per row, trailing blanks and
+    /// trailing empty rows dropped.
+    pub fn screen_snapshot(&self) -> String {
+        let mut lines: Vec<String> = self
+            .grid()
+            .iter()
+            .map(|row| row.iter().collect::<String>().trim_end().to_string())
+            .collect();
+        while lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+        lines.join("\n")
+    }
+
+    /// Follow a terminal resize. Rows cut off at the bottom of the primary
+    /// screen go from the top instead if the cursor is below them.
+    pub fn resize(&mut self, cols: u16, rows: u16) {
+        let cols = usize::from(cols).max(1);
+        let rows = usize::from(rows).max(1);
+        if self.cursor.row >= rows && !self.alternate_active {
+            self.primary.drain(..=self.cursor.row - rows);
+            self.cursor.row = rows - 1;
+        }
+        for grid in [&mut self.primary, &mut self.alternate] {
+            grid.resize_with(rows, || vec![' '; cols]);
+            for row in grid.iter_mut() {
+                row.resize(cols, ' ');
+            }
+        }
+        self.cols = cols;
+        self.rows = rows;
+        self.scroll_top = 0;
+        self.scroll_bottom = rows - 1;
+        for cursor in [&mut self.cursor, &mut self.saved, &mut self.saved_primary] {
+            cursor.row = cursor.row.min(rows - 1);
+            cursor.col = cursor.col.min(cols - 1);
+            cursor.wrap_pending = false;
+        }
+    }
+
+    /// Feed raw bytes from terminal output.
+    pub fn feed(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.feed_byte(byte);
+        }
+    }
+
+    fn feed_byte(&mut self, byte: u8) {
+        match self.state {
+            State::Ground => self.ground(byte),
+            State::Escape => self.escape(byte),
+            State::EscapeIntermediate => self.state = State::Ground,
+            State::Csi => match byte {
+                0x1b => self.state = State::Escape,
+                0x20..=0x3f => self.csi.push(byte),
+                0x40..=0x7e => {
+                    self.state = State::Ground;
+                    self.csi_dispatch(byte);
+                }
+                // Control characters take effect in the middle of a sequence.
+                0x00..=0x1f => self.control(byte),
+                _ => {}
+            },
+            State::Skip => match byte {
+                0x07 => self.state = State::Ground,
+                0x1b => self.state = State::SkipEscape,
+                _ => {}
+            },
+            State::SkipEscape => match byte {
+                b'\\' => self.state = State::Ground,
+                // Not ST: the string was cut short by a new sequence.
+                _ => self.escape(byte),
+            },
+        }
+    }
+
+    fn ground(&mut self, byte: u8) {
+        if !self.utf8.is_empty() || byte >= 0x80 {
+            self.utf8_byte(byte);
+            return;
+        }
+        match byte {
+            0x1b => self.state = State::Escape,
+            0x20..=0x7e => self.print(byte as char),
+            0x00..=0x1f => self.control(byte),
+            _ => {}
+        }
+    }
+
+    fn utf8_byte(&mut self, byte: u8) {
+        if byte & 0xc0 != 0x80 && !self.utf8.is_empty() {
+            // A new character before the last one was complete.
+            self.utf8.clear();
+            self.print(char::REPLACEMENT_CHARACTER);
+            self.ground(byte);
+            return;
+        }
+        self.utf8.push(byte);
+        let len = match self.utf8[0] {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 1,
+        };
+        if self.utf8.len() < len {
+            return;
+        }
+        let c = std::str::from_utf8(&self.utf8)
+            .ok()
+            .and_then(|s| s.chars().next())
+            .unwrap_or(char::REPLACEMENT_CHARACTER);
+        self.utf8.clear();
+        self.print(c);
+    }
+
+    fn control(&mut self, byte: u8) {
+        match byte {
+            0x08 => {
+                self.cursor.col = self.cursor.col.saturating_sub(1);
+                self.cursor.wrap_pending = false;
+            }
+            b'\t' => {
+                let next = (self.cursor.col / TAB_WIDTH + 1) * TAB_WIDTH;
+                self.cursor.col = next.min(self.cols - 1);
+                self.cursor.wrap_pending = false;
+            }
+            // LF, VT and FF
+            0x0a..=0x0c => self.linefeed(),
+            b'\r' => {
+                self.cursor.col = 0;
+                self.cursor.wrap_pending = false;
+            }
+            _ => {}
+        }
+    }
+
+    fn escape(&mut self, byte: u8) {
+        self.state = State::Ground;
+        match byte {
+            b'[' => {
+                self.csi.clear();
+                self.state = State::Csi;
+            }
+            b']' | b'P' | b'X' | b'^' | b'_' => self.state = State::Skip,
+            0x20..=0x2f => self.state = State::EscapeIntermediate,
+            b'7' => self.saved = self.cursor,
+            b'8' => self.cursor = self.saved,
+            b'D' => self.linefeed(),
+            b'E' => {
+                self.linefeed();
+                self.cursor.col = 0;
+            }
+            b'M' => self.reverse_index(),
+            b'c' => *self = Self::new(self.cols as u16, self.rows as u16),
+            _ => {}
+        }
+    }
+
+    fn csi_dispatch(&mut self, action: u8) {
+        let raw = std::mem::take(&mut self.csi);
+        // Sequences with intermediates (`CSI SP q`, `CSI ! p`) don't touch
+        // the text.
+        if raw.iter().any(|b| (0x20..=0x2f).contains(b)) {
+            return;
+        }
+        let (private, raw) = match raw.first() {
+            Some(&p @ (b'?' | b'>' | b'<' | b'=')) => (Some(p), &raw[1..]),
+            _ => (None, &raw[..]),
+        };
+        let params: Vec<usize> = raw
+            .split(|&b| b == b';' || b == b':')
+            .map(|p| {
+                p.iter()
+                    .filter(|b| b.is_ascii_digit())
+                    .fold(0usize, |acc, &b| {
+                        (acc * 10 + usize::from(b - b'0')).min(MAX_PARAM)
+                    })
+            })
+            .collect();
+        // Most parameters count something; 0 or missing means 1.
+        let n = |i: usize| params.get(i).copied().filter(|&p| p > 0).unwrap_or(1);
+        let mode = params.first().copied().unwrap_or(0);
+
+        match (private, action) {
+            (Some(b'?'), b'h') => params.iter().for_each(|&m| self.set_mode(m, true)),
+            (Some(b'?'), b'l') => params.iter().for_each(|&m| self.set_mode(m, false)),
+            (Some(b'?'), b'J') | (None, b'J') => self.erase_display(mode),
+            (Some(b'?'), b'K') | (None, b'K') => self.erase_line(mode),
+            (Some(_), _) => {}
+            (None, b'@') => self.insert_chars(n(0)),
+            (None, b'A') => self.cursor_up(n(0)),
+            (None, b'B' | b'e') => self.cursor_down(n(0)),
+            (None, b'C' | b'a') => {
+                self.move_to(self.cursor.row, self.cursor.col.saturating_add(n(0)))
+            }
+            (None, b'D') => self.move_to(self.cursor.row, self.cursor.col.saturating_sub(n(0))),
+            (None, b'E') => {
+                self.cursor_down(n(0));
+                self.cursor.col = 0;
+            }
+            (None, b'F') => {
+                self.cursor_up(n(0));
+                self.cursor.col = 0;
+            }
+            (None, b'G' | b'`') => self.move_to(self.cursor.row, n(0) - 1),
+            (None, b'H' | b'f') => self.move_to(n(0) - 1, n(1) - 1),
+            (None, b'd') => self.move_to(n(0) - 1, self.cursor.col),
+            (None, b'L') => self.insert_lines(n(0)),
+            (None, b'M') => self.delete_lines(n(0)),
+            (None, b'P') => self.delete_chars(n(0)),
+            (None, b'X') => {
+                let (row, col) = (self.cursor.row, self.cursor.col);
+                let end = col.saturating_add(n(0)).min(self.cols);
+                self.grid_mut()[row][col..end].fill(' ');
+            }
+            (None, b'S') => self.scroll_up(n(0)),
+            // With more parameters, `CSI T` is mouse tracking.
+            (None, b'T') if params.len() <= 1 => self.scroll_down(n(0)),
+            (None, b'b') => {
+                let c = self.last_char;
+                for _ in 0..n(0).min(self.cols * self.rows) {
+                    self.print(c);
+                }
+            }
+            (None, b'r') => {
+                let top = n(0) - 1;
+                let bottom = params
+                    .get(1)
+                    .copied()
+                    .filter(|&p| p > 0)
+                    .unwrap_or(self.rows)
+                    .min(self.rows)
+                    - 1;
+                if top < bottom {
+                    self.scroll_top = top;
+                    self.scroll_bottom = bottom;
+                    self.move_to(0, 0);
+                }
+            }
+            (None, b's') => self.saved = self.cursor,
+            (None, b'u') => self.cursor = self.saved,
+            _ => {}
+        }
+    }
+
+    fn set_mode(&mut self, mode: usize, on: bool) {
+        match mode {
+            7 => self.autowrap = on,
+            // 1049 saves the cursor and clears the alternate screen on the
+            // way in; 1047 clears it on the way out; 47 does neither.
+            47 | 1047 | 1049 => {
+                if on == self.alternate_active {
+                    return;
+                }
+                if on {
+                    if mode == 1049 {
+                        self.saved_primary = self.cursor;
+                        self.alternate = blank_grid(self.cols, self.rows);
+                    }
+                } else {
+                    if mode == 1047 {
+                        self.alternate = blank_grid(self.cols, self.rows);
+                    }
+                    if mode == 1049 {
+                        self.cursor = self.saved_primary;
+                    }
+                }
+                self.alternate_active = on;
+            }
+            _ => {}
+        }
+    }
+
+    fn grid(&self) -> &Grid {
+        if self.alternate_active {
+            &self.alternate
+        } else {
+            &self.primary
+        }
+    }
+
+    fn grid_mut(&mut self) -> &mut Grid {
+        if self.alternate_active {
+            &mut self.alternate
+        } else {
+            &mut self.primary
+        }
+    }
+
+    fn print(&mut self, c: char) {
+        if self.cursor.wrap_pending {
+            self.cursor.col = 0;
+            self.linefeed();
+        }
+        let (row, col) = (self.cursor.row, self.cursor.col);
+        self.grid_mut()[row][col] = c;
+        if col + 1 < self.cols {
+            self.cursor.col += 1;
+        } else {
+            // Without autowrap, later characters overwrite the last column.
+            self.cursor.wrap_pending = self.autowrap;
+        }
+        self.last_char = c;
+    }
+
+    fn move_to(&mut self, row: usize, col: usize) {
+        self.cursor = Cursor {
+            row: row.min(self.rows - 1),
+            col: col.min(self.cols - 1),
+            wrap_pending: false,
+        };
+    }
+
+    /// Up, stopping at the top of the scroll region if the cursor is in it.
+    fn cursor_up(&mut self, n: usize) {
+        let top = if self.cursor.row >= self.scroll_top {
+            self.scroll_top
+        } else {
+            0
+        };
+        self.move_to(self.cursor.row.saturating_sub(n).max(top), self.cursor.col);
+    }
+
+    /// Down, stopping at the bottom of the scroll region if the cursor is
+    /// in it.
+    fn cursor_down(&mut self, n: usize) {
+        let bottom = if self.cursor.row <= self.scroll_bottom {
+            self.scroll_bottom
+        } else {
+            self.rows - 1
+        };
+        self.move_to(
+            self.cursor.row.saturating_add(n).min(bottom),
+            self.cursor.col,
+        );
+    }
+
+    fn linefeed(&mut self) {
+        self.cursor.wrap_pending = false;
+        if self.cursor.row == self.scroll_bottom {
+            self.scroll_up(1);
+        } else if self.cursor.row + 1 < self.rows {
+            self.cursor.row += 1;
+        }
+    }
+
+    fn reverse_index(&mut self) {
+        self.cursor.wrap_pending = false;
+        if self.cursor.row == self.scroll_top {
+            self.scroll_down(1);
+        } else {
+            self.cursor.row = self.cursor.row.saturating_sub(1);
+        }
+    }
+
+    /// Scroll the region up: rows leave at the top, blank ones enter at the
+    /// bottom.
+    fn scroll_up(&mut self, n: usize) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        self.shift_rows(top, bottom, n, true);
+    }
+
+    fn scroll_down(&mut self, n: usize) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        self.shift_rows(top, bottom, n, false);
+    }
+
+    fn insert_lines(&mut self, n: usize) {
+        let row = self.cursor.row;
+        if (self.scroll_top..=self.scroll_bottom).contains(&row) {
+            let bottom = self.scroll_bottom;
+            self.shift_rows(row, bottom, n, false);
+            self.move_to(row, 0);
+        }
+    }
+
+    fn delete_lines(&mut self, n: usize) {
+        let row = self.cursor.row;
+        if (self.scroll_top..=self.scroll_bottom).contains(&row) {
+            let bottom = self.scroll_bottom;
+            self.shift_rows(row, bottom, n, true);
+            self.move_to(row, 0);
+        }
+    }
+
+    /// Move rows `top..=bottom` up (or down) by `n`, filling with blanks.
+    fn shift_rows(&mut self, top: usize, bottom: usize, n: usize, up: bool) {
+        let region = &mut self.grid_mut()[top..=bottom];
+        let n = n.min(region.len());
+        if up {
+            region.rotate_left(n);
+            let len = region.len();
+            region[len - n..].iter_mut().for_each(|r| r.fill(' '));
+        } else {
+            region.rotate_right(n);
+            region[..n].iter_mut().for_each(|r| r.fill(' '));
+        }
+    }
+
+    fn insert_chars(&mut self, n: usize) {
+        let (row, col) = (self.cursor.row, self.cursor.col);
+        let line = &mut self.grid_mut()[row][col..];
+        let n = n.min(line.len());
+        line.rotate_right(n);
+        line[..n].fill(' ');
+        self.cursor.wrap_pending = false;
+    }
+
+    fn delete_chars(&mut self, n: usize) {
+        let (row, col) = (self.cursor.row, self.cursor.col);
+        let line = &mut self.grid_mut()[row][col..];
+        let n = n.min(line.len());
+        line.rotate_left(n);
+        let len = line.len();
+        line[len - n..].fill(' ');
+        self.cursor.wrap_pending = false;
+    }
+
+    fn erase_display(&mut self, mode: usize) {
+        let (row, col) = (self.cursor.row, self.cursor.col);
+        let grid = self.grid_mut();
+        match mode {
+            0 => {
+                grid[row][col..].fill(' ');
+                grid[row + 1..].iter_mut().for_each(|r| r.fill(' '));
+            }
+            1 => {
+                grid[..row].iter_mut().for_each(|r| r.fill(' '));
+                grid[row][..=col].fill(' ');
+            }
+            2 | 3 => grid.iter_mut().for_each(|r| r.fill(' ')),
+            _ => {}
+        }
+    }
+
+    fn erase_line(&mut self, mode: usize) {
+        let (row, col) = (self.cursor.row, self.cursor.col);
+        let line = &mut self.grid_mut()[row];
+        match mode {
+            0 => line[col..].fill(' '),
+            1 => line[..=col].fill(' '),
+            2 => line.fill(' '),
+            _ => {}
+        }
+    }
+}
+
+fn blank_grid(cols: usize, rows: usize) -> Grid {
+    vec![vec![' '; cols]; rows]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen(data: &[u8]) -> Screen {
+        let mut s = Screen::new(20, 5);
+        s.feed(data);
+        s
+    }
+
+    #[test]
+    fn text_lines_and_carriage_return() {
+        let s = screen(b"hello\r\nworld\r\n50%\r100%");
+        assert_eq!(s.screen_snapshot(), "hello\nworld\n100%");
+        assert!(!s.alternate_screen());
+    }
+
+    #[test]
+    fn cursor_positioning_draws_in_place() {
+        // What htop does: jump around, color everything.
+        let s = screen(b"\x1b[2J\x1b[3;5H\x1b[1;31mCPU\x1b[0m\x1b[1;1Hup 3 days\x1b[2;10Hx");
+        assert_eq!(s.screen_snapshot(), "up 3 days\n         x\n    CPU");
+    }
+
+    #[test]
+    fn erase_line_and_display() {
+        let mut s = screen(b"aaaa\r\nbbbb\r\ncccc");
+        s.feed(b"\x1b[2;3H\x1b[K");
+        assert_eq!(s.screen_snapshot(), "aaaa\nbb\ncccc");
+        s.feed(b"\x1b[1K");
+        assert_eq!(s.screen_snapshot(), "aaaa\n\ncccc");
+        s.feed(b"\x1b[J");
+        assert_eq!(s.screen_snapshot(), "aaaa");
+        s.feed(b"\x1b[2J");
+        assert_eq!(s.screen_snapshot(), "");
+    }
+
+    #[test]
+    fn alternate_screen_keeps_the_shell_screen() {
+        let mut s = screen(b"$ vim notes.txt\r\n");
+        s.feed(b"\x1b[?1049h\x1b[H\x1b[2Jfirst line\r\n~\r\n~");
+        assert!(s.alternate_screen());
+        assert_eq!(s.screen_snapshot(), "first line\n~\n~");
+
+        s.feed(b"\x1b[?1049l$ ");
+        assert!(!s.alternate_screen());
+        assert_eq!(s.screen_snapshot(), "$ vim notes.txt\n$");
+    }
+
+    #[test]
+    fn wraps_at_the_last_column() {
+        let mut s = Screen::new(5, 3);
+        s.feed(b"abcde");
+        // Exactly full: the wrap waits for the next character.
+        s.feed(b"\r\nfghijkl");
+        assert_eq!(s.screen_snapshot(), "abcde\nfghij\nkl");
+
+        s.feed(b"\x1b[?7l\x1b[1;1H123456");
+        assert_eq!(s.screen_snapshot(), "12346\nfghij\nkl");
+    }
+
+    #[test]
+    fn scrolling_drops_the_top_row() {
+        let s = screen(b"1\r\n2\r\n3\r\n4\r\n5\r\n6");
+        assert_eq!(s.screen_snapshot(), "2\n3\n4\n5\n6");
+    }
+
+    #[test]
+    fn scroll_region_leaves_status_lines_alone() {
+        // A pager: header on row 1, status on row 5, text scrolls between.
+        let mut s = screen(b"\x1b[1;1Hheader\x1b[5;1Hstatus\x1b[2;4r");
+        s.feed(b"\x1b[2;1Ha\r\nb\r\nc\r\nd");
+        assert_eq!(s.screen_snapshot(), "header\nb\nc\nd\nstatus");
+        s.feed(b"\x1b[2;1H\x1bM");
+        assert_eq!(s.screen_snapshot(), "header\n\nb\nc\nstatus");
+    }
+
+    #[test]
+    fn insert_and_delete() {
+        let mut s = screen(b"abcdef\r\nline2\r\nline3");
+        s.feed(b"\x1b[1;3H\x1b[2P");
+        assert_eq!(s.screen_snapshot(), "abef\nline2\nline3");
+        s.feed(b"\x1b[2@XY");
+        assert_eq!(s.screen_snapshot(), "abXYef\nline2\nline3");
+        s.feed(b"\x1b[2;1H\x1b[L");
+        assert_eq!(s.screen_snapshot(), "abXYef\n\nline2\nline3");
+        s.feed(b"\x1b[M\x1b[M");
+        assert_eq!(s.screen_snapshot(), "abXYef\nline3");
+    }
+
+    #[test]
+    fn utf8_split_across_reads() {
+        let mut s = Screen::new(20, 3);
+        let text = "│ é ─".as_bytes();
+        s.feed(&text[..2]);
+        s.feed(&text[2..]);
+        assert_eq!(s.screen_snapshot(), "│ é ─");
+    }
+
+    #[test]
+    fn strings_and_modes_leave_no_text() {
+        let s = screen(
+            b"\x1b]0;title\x07\x1b]133;A\x1b\\\x1bP+q544e\x1b\\\x1b(B\x1b[?25l\x1b[2 q\x1b[>4;1mok",
+        );
+        assert_eq!(s.screen_snapshot(), "ok");
+    }
+
+    #[test]
+    fn huge_parameters_are_clamped() {
+        for action in "@ABeCaDEFG`HfdLMPXSTbrsuJK".chars() {
+            let mut s = screen(b"abc\r\ndef\r\nghi\x1b[2;2H");
+            for params in [
+                "18446744073709551615",
+                "99999999999999999999999",
+                "18446744073709551615;18446744073709551615",
+            ] {
+                s.feed(format!("\x1b[{params}{action}x").as_bytes());
+                s.feed(format!("\x1b[?{params}{action}").as_bytes());
+            }
+            assert!(s.screen_snapshot().lines().count() <= 5, "CSI {action}");
+        }
+    }
+
+    #[test]
+    fn resize_keeps_the_cursor_row() {
+        let mut s = screen(b"1\r\n2\r\n3\r\n4\r\n5");
+        s.resize(10, 3);
+        assert_eq!(s.screen_snapshot(), "3\n4\n5");
+        s.feed(b"!");
+        assert_eq!(s.screen_snapshot(), "3\n4\n5!");
+        s.resize(10, 4);
+        s.feed(b"\r\nmore");
+        assert_eq!(s.screen_snapshot(), "3\n4\n5!\nmore");
+    }
+}
@@ -1,20 +1,36 @@
+use base64::Engine;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ShellKind {
     Bash,
     Zsh,
     Fish,
+    Nushell,
+    Xonsh,
+    PowerShell,
+    /// `sh`, `dash`, `ksh` and `mksh`: prompt markers only, from `PS1`.
+    Posix,
     Unknown,
 }
 
-pub fn detect_shell(cmd: &str) -> ShellKind {
+/// The shell's name from its command path or `$0`: `/bin/bash` and `-bash`
+/// are both `bash`.
+pub fn shell_name(cmd: &str) -> &str {
     // Extract the basename from the command path
     let basename = cmd.rsplit('/').next().unwrap_or(cmd);
     // Strip leading dash (login shell convention)
-    let name = basename.strip_prefix('-').unwrap_or(basename);
-    match name {
+    basename.strip_prefix('-').unwrap_or(basename)
+}
+
+pub fn detect_shell(cmd: &str) -> ShellKind {
+    match shell_name(cmd) {
         "bash" => ShellKind::Bash,
         "zsh" => ShellKind::Zsh,
         "fish" => ShellKind::Fish,
+        "nu" => ShellKind::Nushell,
+        "xonsh" => ShellKind::Xonsh,
+        "pwsh" => ShellKind::PowerShell,
+        "sh" | "dash" | "ksh" | "mksh" => ShellKind::Posix,
         _ => ShellKind::Unknown,
     }
 }
@@ -28,10 +44,93 @@ pub fn integration_script(kind: ShellKind) -> Option<&'static str> {
         ShellKind::Bash => Some(BASH_INTEGRATION),
         ShellKind::Zsh => Some(ZSH_INTEGRATION),
         ShellKind::Fish => Some(FISH_INTEGRATION),
+        ShellKind::Nushell => Some(NUSHELL_INTEGRATION),
+        ShellKind::Xonsh => Some(XONSH_INTEGRATION),
+        ShellKind::PowerShell => Some(POWERSHELL_INTEGRATION),
+        ShellKind::Posix => Some(POSIX_INTEGRATION),
         ShellKind::Unknown => None,
     }
 }
 
+/// File name suffix for the integration script. PowerShell only
+/// dot-sources `.ps1` files.
+pub fn script_suffix(kind: ShellKind) -> &'static str {
+    match kind {
+        ShellKind::Nushell => ".nu",
+        ShellKind::Xonsh => ".xsh",
+        ShellKind::PowerShell => ".ps1",
+        _ => ".sh",
+    }
+}
+
+/// The command line that sources the integration script at `path`.
+///
+/// The leading space keeps it out of history in shells that honor that.
+pub fn source_command(kind: ShellKind, path: &str) -> String {
+    let path = quote(kind, path);
+    match kind {
+        ShellKind::PowerShell | ShellKind::Posix => format!(" . {path}\n"),
+        _ => format!(" source {path}\n"),
+    }
+}
+
+/// The command line that loads the integration into a shell nested in the
+/// session (`ssh`, `sudo -i`, `docker exec`), where the script file doesn't
+/// exist: the script travels base64-encoded on one line, and `depth` tells
+/// it which level of nesting to report. `None` for shells that can't load
+/// it from a pipe.
+pub fn nested_bootstrap(kind: ShellKind, depth: usize) -> Option<String> {
+    let script = integration_script(kind)?;
+    // Leave the screen alone: the user is in the middle of a session.
+    let script = script.trim_end().strip_suffix("clear").unwrap_or(script);
+    let encoded = base64::engine::general_purpose::STANDARD.encode(script);
+    match kind {
+        ShellKind::Bash | ShellKind::Zsh | ShellKind::Posix => Some(format!(
+            " __ua_depth={depth}; eval \"$(printf %s '{encoded}' | base64 -d)\"\n"
+        )),
+        ShellKind::Fish => Some(format!(
+            " set -g __ua_depth {depth}; printf %s '{encoded}' | base64 -d | source\n"
+        )),
+        _ => None,
+    }
+}
+
+/// Asks the shell at the prompt for its `$0` (`633;P;UaShell=`), so
+/// `#!integrate` knows which bootstrap to type.
+pub const SHELL_PROBE: &str = " printf '\\033]633;P;UaShell=%s\\007' \"$0\"\n";
+
+/// Quote `s` as a single word for the given shell.
+///
+/// POSIX shells get `'...'` with embedded quotes spliced as `'\''`. Fish
+/// allows `\'` and `\\` escapes inside single quotes instead; xonsh strings
+/// are Python literals, so newlines are escaped too. PowerShell doubles
+/// quotes (including the typographic ones it also accepts), and nushell
+/// gets a double-quoted string.
+pub fn quote(kind: ShellKind, s: &str) -> String {
+    match kind {
+        ShellKind::Fish => format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'")),
+        ShellKind::Xonsh => format!(
+            "'{}'",
+            s.replace('\\', "\\\\")
+                .replace('\'', "\\'")
+                .replace('\n', "\\n")
+        ),
+        ShellKind::PowerShell => {
+            let mut out = String::from("'");
+            for c in s.chars() {
+                if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}') {
+                    out.push(c);
+                }
+                out.push(c);
+            }
+            out.push('\'');
+            out
+        }
+        ShellKind::Nushell => format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")),
+        _ => format!("'{}'", s.replace('\'', "'\\''")),
+    }
+}
+
 // OSC 133 markers:
 //   A = prompt start (fresh prompt ready for input)
 //   B = command start (user pressed Enter, about to execute)
@@ -39,17 +138,51 @@ pub fn integration_script(kind: ShellKind) -> Option<&'static str> {
 //   D;N = command finished with exit code N
 //
 // Sequence: D;$? → A → [user types] → B → C → [output] → D;$? → A → ...
+//
+// Alongside them:
+//   OSC 7;file://host/path = working directory, sent with every prompt
+//   OSC 633;E;<command> = the command line about to run, sent before C.
+//     `\` is sent as `\\`, and `;` and control characters as `\xAB`.
+//   OSC 633;P;UaDepth=<n> = nesting level, sent with every prompt: 0 for
+//     the session's own shell, set by `nested_bootstrap` in nested ones.
 
 // These scripts are sourced from a temp file, so they can use normal
 // multi-line shell syntax — no need for eval or quoting gymnastics.
 
 const BASH_INTEGRATION: &str = r#"
+__ua_report_cwd() {
+    local p=${PWD//\%/%25}
+    printf '\x1b]7;file://%s%s\x07' "$HOSTNAME" "${p// /%20}"
+}
+__ua_report_command() {
+    # The history entry has the whole line; $BASH_COMMAND only the first
+    # simple command. Lines kept out of history (ignorespace) leave the
+    # entry number unchanged.
+    local cmd=$BASH_COMMAND line re='^ *([0-9]+)\*? +(.*)$'
+    line=$(HISTTIMEFORMAT= builtin history 1)
+    if [[ $line =~ $re && ${BASH_REMATCH[1]} != "$__ua_histnum" ]]; then
+        __ua_histnum=${BASH_REMATCH[1]}
+        cmd=${BASH_REMATCH[2]}
+    fi
+    cmd=${cmd//\\/\\\\}
+    cmd=${cmd//;/\\x3b}
+    cmd=${cmd//$'\n'/\\x0a}
+    cmd=${cmd//$'\a'/\\x07}
+    cmd=${cmd//$'\e'/\\x1b}
+    printf '\x1b]633;E;%s\x07' "$cmd"
+}
 __ua_prompt_command() {
     local exit_code=$?
     printf '\x1b]133;D;%d\x07' "$exit_code"
+    printf '\x1b]633;P;UaDepth=%s\x07' "${__ua_depth:-0}"
+    __ua_report_cwd
     printf '\x1b]133;A\x07'
 }
-[[ "${PROMPT_COMMAND[*]}" =~ __ua_prompt_command ]] || PROMPT_COMMAND=("__ua_prompt_command" "${PROMPT_COMMAND[@]}")
+# Last in PROMPT_COMMAND: the next command the DEBUG trap sees is the user's.
+__ua_prompt_done() {
+    __ua_at_prompt=1
+}
+[[ "${PROMPT_COMMAND[*]}" =~ __ua_prompt_command ]] || PROMPT_COMMAND=("__ua_prompt_command" "${PROMPT_COMMAND[@]}" "__ua_prompt_done")
 
 PS1="\[\e[38;5;242m\]◇\[\e[0m\] ${PS1}"
 
@@ -60,20 +193,38 @@ case "$PS1" in
     *) PS1="${PS1}\[\e]133;B\a\]" ;;
 esac
 
+# Fires before every simple command; report once per command line.
 __ua_debug_trap() {
-    [[ "$BASH_COMMAND" != "__ua_prompt_command" ]] && printf '\x1b]133;C\x07'
+    [[ -n "$__ua_at_prompt" ]] || return
+    __ua_at_prompt=
+    [[ "$BASH_COMMAND" != "__ua_prompt_command" ]] || return
+    __ua_report_command
+    printf '\x1b]133;C\x07'
 }
 trap '__ua_debug_trap' DEBUG
 clear
 "#;
 
 const ZSH_INTEGRATION: &str = r#"
+__ua_report_cwd() {
+    local p=${PWD//\%/%25}
+    printf '\x1b]7;file://%s%s\x07' "$HOST" "${p// /%20}"
+}
 __ua_precmd() {
     local exit_code=$?
     printf '\x1b]133;D;%d\x07' "$exit_code"
+    printf '\x1b]633;P;UaDepth=%s\x07' "${__ua_depth:-0}"
+    __ua_report_cwd
     printf '\x1b]133;A\x07'
 }
 __ua_preexec() {
+    local cmd=$1
+    cmd=${cmd//\\/\\\\}
+    cmd=${cmd//;/\\x3b}
+    cmd=${cmd//$'\n'/\\x0a}
+    cmd=${cmd//$'\a'/\\x07}
+    cmd=${cmd//$'\e'/\\x1b}
+    printf '\x1b]633;E;%s\x07' "$cmd"
     printf '\x1b]133;C\x07'
 }
 # Emit 133;B after prompt is rendered and ZLE is initialized.
@@ -90,12 +241,25 @@ clear
 "#;
 
 const FISH_INTEGRATION: &str = r#"
+function __ua_report_cwd
+    set -l p (string replace -a % %25 -- $PWD | string replace -a ' ' %20)
+    printf \x1b]7\;file://%s%s\x07 $hostname "$p"
+end
 function __ua_fish_prompt --on-event fish_prompt
     set -l exit_code $status
     printf \x1b]133\;D\;%d\x07 $exit_code
+    set -q __ua_depth; or set -g __ua_depth 0
+    printf \x1b]633\;P\;UaDepth=%s\x07 $__ua_depth
+    __ua_report_cwd
     printf \x1b]133\;A\x07
 end
 function __ua_fish_preexec --on-event fish_preexec
+    set -l cmd (string replace -a -- '\\' '\\\\' $argv[1] \
+        | string replace -a -- ';' '\\x3b' \
+        | string replace -a -- \a '\\x07' \
+        | string replace -a -- \e '\\x1b' \
+        | string join '\\x0a')
+    printf \x1b]633\;E\;%s\x07 "$cmd"
     printf \x1b]133\;B\x07
     printf \x1b]133\;C\x07
 end
@@ -109,6 +273,113 @@ end
 clear
 "#;
 
+// Nushell emits OSC 133 and OSC 7 itself once `shell_integration` asks for
+// them; only the command line needs a hook.
+const NUSHELL_INTEGRATION: &str = r#"
+$env.config.shell_integration.osc133 = true
+$env.config.shell_integration.osc7 = true
+$env.config.hooks.pre_prompt = ($env.config.hooks.pre_prompt? | default [] | append {||
+    print -n "\e]633;P;UaDepth=0\a"
+})
+$env.config.hooks.pre_execution = ($env.config.hooks.pre_execution? | default [] | append {||
+    let cmd = (commandline
+        | str replace -a '\' '\\'
+        | str replace -a ';' '\x3b'
+        | str replace -a "\n" '\x0a'
+        | str replace -a "\a" '\x07'
+        | str replace -a "\e" '\x1b')
+    print -n $"\e]633;E;($cmd)\a"
+})
+let __ua_prompt = $env.PROMPT_COMMAND?
+$env.PROMPT_COMMAND = {||
+    let prompt = if ($__ua_prompt | describe) == "closure" {
+        do $__ua_prompt
+    } else {
+        $__ua_prompt | default ""
+    }
+    $"\e[38;5;242m◇\e[0m ($prompt)"
+}
+clear
+"#;
+
+// prompt_toolkit escapes control characters in $PROMPT, so xonsh marks the
+// prompt from events: B goes out with A, just before the prompt is drawn.
+const XONSH_INTEGRATION: &str = r#"
+import socket as _ua_socket
+import sys as _ua_sys
+
+_ua_state = {'rtn': 0, 'host': _ua_socket.gethostname()}
+
+def _ua_write(s):
+    _ua_sys.stdout.write(s)
+    _ua_sys.stdout.flush()
+
+@events.on_postcommand
+def _ua_postcommand(cmd, rtn, **kwargs):
+    _ua_state['rtn'] = rtn or 0
+
+@events.on_pre_prompt
+def _ua_pre_prompt(**kwargs):
+    p = $PWD.replace('%', '%25').replace(' ', '%20')
+    _ua_write('\x1b]133;D;%d\x07' % _ua_state['rtn'])
+    _ua_write('\x1b]633;P;UaDepth=0\x07')
+    _ua_write('\x1b]7;file://%s%s\x07' % (_ua_state['host'], p))
+    _ua_write('\x1b]133;A\x07\x1b]133;B\x07')
+
+@events.on_precommand
+def _ua_precommand(cmd, **kwargs):
+    cmd = (cmd.rstrip('\n')
+        .replace('\\', '\\\\')
+        .replace(';', '\\x3b')
+        .replace('\n', '\\x0a')
+        .replace('\a', '\\x07')
+        .replace('\x1b', '\\x1b'))
+    _ua_write('\x1b]633;E;%s\x07\x1b]133;C\x07' % cmd)
+
+_ua_prompt = $PROMPT
+if callable(_ua_prompt):
+    $PROMPT = lambda: '{INTENSE_BLACK}◇{RESET} ' + _ua_prompt()
+else:
+    $PROMPT = '{INTENSE_BLACK}◇{RESET} ' + _ua_prompt
+clear
+"#;
+
+// The prompt function prints D, the cwd and A ahead of the user's prompt and
+// B after it. PSReadLine hands back the accepted line, which gives E and C.
+const POWERSHELL_INTEGRATION: &str = r#"
+$global:__UaPrompt = $function:prompt
+function global:prompt {
+    $code = if ($global:?) { 0 } elseif ($global:LASTEXITCODE) { $global:LASTEXITCODE } else { 1 }
+    $p = $PWD.ProviderPath -replace '%', '%25' -replace ' ', '%20'
+    "`e]133;D;$code`a`e]633;P;UaDepth=0`a`e]7;file://$([Environment]::MachineName)$p`a`e]133;A`a" +
+        "`e[38;5;242m◇`e[0m " + (& $global:__UaPrompt) + "`e]133;B`a"
+}
+if (Test-Path function:PSConsoleHostReadLine) {
+    $global:__UaReadLine = $function:PSConsoleHostReadLine
+    function global:PSConsoleHostReadLine {
+        $line = & $global:__UaReadLine
+        $cmd = $line -replace '\\', '\\' -replace ';', '\x3b' -replace "`n", '\x0a' `
+            -replace "`a", '\x07' -replace "`e", '\x1b'
+        [Console]::Write("`e]633;E;$cmd`a`e]133;C`a")
+        $line
+    }
+}
+clear
+"#;
+
+// POSIX shells have no pre-exec hook (`trap DEBUG` is a bash/ksh extension),
+// so everything comes from PS1 expansion: D with `$?`, the cwd and A before
+// the user's prompt, B after it. There is no C or E — the REPL falls back
+// to the keystrokes it saw for the command line. The cwd is sent without
+// percent-encoding, since expanding PS1 can't run commands in every shell.
+const POSIX_INTEGRATION: &str = r#"
+__ua_host=$(uname -n 2>/dev/null)
+__ua_esc=$(printf '\033')
+__ua_bel=$(printf '\007')
+PS1='${__ua_esc}]133;D;$?${__ua_bel}${__ua_esc}]633;P;UaDepth=${__ua_depth:-0}${__ua_bel}${__ua_esc}]7;file://${__ua_host}${PWD}${__ua_bel}${__ua_esc}]133;A${__ua_bel}'"${__ua_esc}[38;5;242m◇${__ua_esc}[0m ${PS1:-\$ }${__ua_esc}]133;B${__ua_bel}"
+clear
+"#;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -118,9 +389,61 @@ mod tests {
         assert_eq!(detect_shell("/bin/bash"), ShellKind::Bash);
         assert_eq!(detect_shell("/usr/bin/zsh"), ShellKind::Zsh);
         assert_eq!(detect_shell("/usr/local/bin/fish"), ShellKind::Fish);
-        assert_eq!(detect_shell("/bin/sh"), ShellKind::Unknown);
+        assert_eq!(detect_shell("/bin/sh"), ShellKind::Posix);
+        assert_eq!(detect_shell("-dash"), ShellKind::Posix);
+        assert_eq!(detect_shell("/usr/bin/nu"), ShellKind::Nushell);
+        assert_eq!(detect_shell("xonsh"), ShellKind::Xonsh);
+        assert_eq!(
+            detect_shell("/opt/microsoft/powershell/7/pwsh"),
+            ShellKind::PowerShell
+        );
+        assert_eq!(detect_shell("/bin/tcsh"), ShellKind::Unknown);
         assert_eq!(detect_shell("bash"), ShellKind::Bash);
         assert_eq!(detect_shell("-zsh"), ShellKind::Zsh);
+        assert_eq!(shell_name("-bash"), "bash");
+        assert_eq!(shell_name("/usr/bin/fish"), "fish");
+    }
+
+    #[test]
+    fn quote_posix_and_fish() {
+        assert_eq!(quote(ShellKind::Bash, "ls"), "'ls'");
+        assert_eq!(quote(ShellKind::Bash, "echo 'hi'"), r"'echo '\''hi'\'''");
+        assert_eq!(quote(ShellKind::Unknown, r"a\b"), r"'a\b'");
+        assert_eq!(quote(ShellKind::Fish, r"echo 'a\b'"), r"'echo \'a\\b\''");
+    }
+
+    #[test]
+    fn quote_other_shells() {
+        assert_eq!(quote(ShellKind::PowerShell, "it's"), "'it''s'");
+        assert_eq!(
+            quote(ShellKind::PowerShell, "it\u{2019}s"),
+            "'it\u{2019}\u{2019}s'"
+        );
+        assert_eq!(
+            quote(ShellKind::Nushell, r#"say "a\b""#),
+            r#""say \"a\\b\"""#
+        );
+        assert_eq!(quote(ShellKind::Xonsh, "a'b\nc"), r"'a\'b\nc'");
+    }
+
+    #[test]
+    fn source_commands() {
+        assert_eq!(
+            source_command(ShellKind::Bash, "/tmp/ua.sh"),
+            " source '/tmp/ua.sh'\n"
+        );
+        assert_eq!(
+            source_command(ShellKind::Posix, "/tmp/ua.sh"),
+            " . '/tmp/ua.sh'\n"
+        );
+        assert_eq!(
+            source_command(ShellKind::PowerShell, "/tmp/ua.ps1"),
+            " . '/tmp/ua.ps1'\n"
+        );
+        assert_eq!(
+            source_command(ShellKind::Nushell, "/tmp/ua.nu"),
+            " source \"/tmp/ua.nu\"\n"
+        );
     }
 
     #[test]
@@ -128,12 +451,22 @@ mod tests {
         assert!(integration_script(ShellKind::Bash).is_some());
         assert!(integration_script(ShellKind::Zsh).is_some());
         assert!(integration_script(ShellKind::Fish).is_some());
+        assert!(integration_script(ShellKind::Nushell).is_some());
+        assert!(integration_script(ShellKind::Xonsh).is_some());
+        assert!(integration_script(ShellKind::PowerShell).is_some());
+        assert!(integration_script(ShellKind::Posix).is_some());
         assert!(integration_script(ShellKind::Unknown).is_none());
     }
 
     #[test]
     fn scripts_contain_osc_markers() {
-        for kind in [ShellKind::Bash, ShellKind::Zsh, ShellKind::Fish] {
+        for kind in [
+            ShellKind::Bash,
+            ShellKind::Zsh,
+            ShellKind::Fish,
+            ShellKind::Xonsh,
+            ShellKind::PowerShell,
+        ] {
             let script = integration_script(kind).unwrap();
             // Fish uses escaped semicolons (133\;A), others use 133;A
             let has_marker =
@@ -142,12 +475,65 @@ mod tests {
             assert!(has_marker("133;B"), "{kind:?} missing 133;B");
             assert!(has_marker("133;D"), "{kind:?} missing 133;D");
             assert!(has_marker("133;C"), "{kind:?} missing 133;C");
+            assert!(has_marker("]7;file://"), "{kind:?} missing OSC 7");
+            assert!(has_marker("633;E;"), "{kind:?} missing 633;E");
+            assert!(has_marker("633;P;UaDepth="), "{kind:?} missing depth");
         }
     }
 
+    #[test]
+    fn nested_bootstrap_carries_script_and_depth() {
+        let line = nested_bootstrap(ShellKind::Bash, 2).unwrap();
+        assert!(line.starts_with(" __ua_depth=2; eval "));
+        assert!(line.ends_with("| base64 -d)\"\n"));
+        assert_eq!(line.lines().count(), 1);
+        let encoded = line.split('\'').nth(1).unwrap();
+        let script = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .unwrap();
+        let script = String::from_utf8(script).unwrap();
+        assert!(script.contains("133;A"));
+        assert!(!script.trim_end().ends_with("clear"));
+
+        let fish = nested_bootstrap(ShellKind::Fish, 1).unwrap();
+        assert!(fish.starts_with(" set -g __ua_depth 1; "));
+        assert!(fish.ends_with("| base64 -d | source\n"));
+        assert!(nested_bootstrap(ShellKind::Posix, 1).is_some());
+        assert!(nested_bootstrap(ShellKind::Nushell, 1).is_none());
+        assert!(nested_bootstrap(ShellKind::Unknown, 1).is_none());
+    }
+
+    #[test]
+    fn nushell_script_enables_builtin_markers() {
+        let script = integration_script(ShellKind::Nushell).unwrap();
+        assert!(script.contains("shell_integration.osc133 = true"));
+        assert!(script.contains("shell_integration.osc7 = true"));
+        assert!(script.contains("633;P;UaDepth=0"));
+        assert!(script.contains("633;E;"));
+    }
+
+    #[test]
+    fn posix_script_marks_prompt_without_debug_trap() {
+        let script = integration_script(ShellKind::Posix).unwrap();
+        assert!(script.contains("133;D;$?"));
+        assert!(script.contains("UaDepth=${__ua_depth:-0}"));
+        assert!(script.contains("133;A"));
+        assert!(script.contains("133;B"));
+        assert!(script.contains("]7;file://"));
+        assert!(!script.contains("trap"));
+    }
+
     #[test]
     fn scripts_end_with_clear() {
-        for kind in [ShellKind::Bash, ShellKind::Zsh, ShellKind::Fish] {
+        for kind in [
+            ShellKind::Bash,
+            ShellKind::Zsh,
+            ShellKind::Fish,
+            ShellKind::Nushell,
+            ShellKind::Xonsh,
+            ShellKind::PowerShell,
+            ShellKind::Posix,
+        ] {
             let script = integration_script(kind).unwrap();
             assert!(
                 script.trim().ends_with("clear"),
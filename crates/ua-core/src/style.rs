@@ -148,6 +148,20 @@ pub fn format_tokens(n: u32) -> String {
     }
 }
 
+/// Format a byte count for display: `512 B`, `12.3 KB`, `4.1 MB`.
+pub fn format_bytes(n: usize) -> String {
+    const KB: f64 = 1024.0;
+    const MB: f64 = KB * 1024.0;
+    let n = n as f64;
+    if n >= MB {
+        format!("{:.1} MB", n / MB)
+    } else if n >= KB {
+        format!("{:.1} KB", n / KB)
+    } else {
+        format!("{n} B")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -206,4 +220,11 @@ mod tests {
         assert_eq!(format_tokens(1000), "1.0k");
         assert_eq!(format_tokens(15600), "15.6k");
     }
+
+    #[test]
+    fn format_bytes_units() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(12_600), "12.3 KB");
+        assert_eq!(format_bytes(4_300_000), "4.1 MB");
+    }
 }
@@ -0,0 +1,413 @@
+//! `unixagent <command> ...` subcommands that inspect or manage the
+//! configuration instead of running an agent.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use ua_sandbox::explain::{explain, Access, Explanation, RuleKind};
+use ua_sandbox::{SandboxBackend, SandboxPolicy};
+
+use crate::config::{
+    discover_layers, env_overrides, load_layers, subagent_overrides, Config, LayerKind, Severity,
+};
+use crate::trust::{self, TrustStore};
+
+const CONFIG_USAGE: &str = "usage: unixagent config check\n       unixagent config show [--effective]\n       unixagent config trust";
+
+const SANDBOX_USAGE: &str =
+    "usage: unixagent sandbox show\n       unixagent sandbox explain <path> [--write]";
+
+/// `unixagent sandbox show | explain <path> [--write]`. Returns the exit
+/// code: for `explain`, 0 when the access is allowed and 1 when it isn't.
+pub fn sandbox(config: &Config, args: &[String]) -> i32 {
+    let policy = config.sandbox.to_policy(&config.hooks);
+    match args.first().map(String::as_str) {
+        Some("show") if args.len() == 1 => {
+            if !config.sandbox.enabled {
+                println!("sandbox disabled ([sandbox] enabled = false); policy if enabled:");
+            }
+            print!("{}", render_policy(&policy));
+            0
+        }
+        Some("explain") => {
+            let write = args.iter().any(|a| a == "--write");
+            let paths: Vec<&String> = args[1..].iter().filter(|a| *a != "--write").collect();
+            let [path] = paths[..] else {
+                eprintln!("{SANDBOX_USAGE}");
+                return 2;
+            };
+            let explanation = explain(&policy, path);
+            print!("{}", render_explanation(&explanation, write));
+            let allowed = match explanation.access {
+                Access::ReadWrite => true,
+                Access::ReadOnly => !write,
+                Access::Denied => false,
+            };
+            i32::from(!allowed)
+        }
+        _ => {
+            eprintln!("{SANDBOX_USAGE}");
+            2
+        }
+    }
+}
+
+/// `unixagent config check | show [--effective] | trust`. Returns the exit
+/// code: for `check`, 1 when any file has errors.
+pub fn config(args: &[String]) -> i32 {
+    let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args[..] {
+        ["check"] => check_config(&cwd),
+        ["show"] => show_config(&cwd, false),
+        ["show", "--effective"] => show_config(&cwd, true),
+        ["trust"] => trust_projects(&cwd),
+        _ => {
+            eprintln!("{CONFIG_USAGE}");
+            2
+        }
+    }
+}
+
+/// Every config file that applies in `cwd`, with its contents, lowest
+/// precedence first. Unreadable files are reported and skipped.
+fn read_layers(cwd: &Path) -> Vec<(LayerKind, PathBuf, String)> {
+    discover_layers(cwd)
+        .into_iter()
+        .filter_map(|(kind, path)| match std::fs::read_to_string(&path) {
+            Ok(contents) => Some((kind, path, contents)),
+            Err(e) => {
+                eprintln!("error: {}: {e}", path.display());
+                None
+            }
+        })
+        .collect()
+}
+
+/// Validate every file that applies, trusted or not, and the
+/// `UNIXAGENT_*` overrides.
+fn check_config(cwd: &Path) -> i32 {
+    let files = read_layers(cwd);
+    let (overrides, ignored) = subagent_overrides(env_overrides());
+    if files.is_empty() && overrides.is_empty() && ignored.is_empty() {
+        println!("no config files; using the defaults");
+        return 0;
+    }
+    let store = TrustStore::load(trust::store_path());
+    for (kind, path, contents) in &files {
+        let untrusted = *kind == LayerKind::Project && !store.is_trusted(path, contents);
+        let note = if untrusted {
+            " (not trusted; run `unixagent config trust` to use it)"
+        } else {
+            ""
+        };
+        println!("{} {}{note}", kind.as_str(), path.display());
+    }
+    for o in &overrides {
+        println!("{} {}", o.kind.as_str(), o.source());
+    }
+
+    let mut loaded = load_layers(files, &overrides);
+    loaded.issues.extend(ignored);
+    for issue in &loaded.issues {
+        println!("{issue}");
+    }
+    let count = |severity| {
+        loaded
+            .issues
+            .iter()
+            .filter(|i| i.severity == severity)
+            .count()
+    };
+    match (count(Severity::Error), count(Severity::Warning)) {
+        (0, 0) => println!("config ok"),
+        (errors, warnings) => println!("{errors} error(s), {warnings} warning(s)"),
+    }
+    i32::from(loaded.has_errors())
+}
+
+/// List the files in use or, with `effective`, print the merged config.
+/// Untrusted project files are left out, as they would be at startup.
+fn show_config(cwd: &Path, effective: bool) -> i32 {
+    let store = TrustStore::load(trust::store_path());
+    let mut files = Vec::new();
+    for (kind, path, contents) in read_layers(cwd) {
+        if kind == LayerKind::Project && !store.is_trusted(&path, &contents) {
+            eprintln!(
+                "note: ignoring {} until it is trusted (`unixagent config trust`)",
+                path.display()
+            );
+            continue;
+        }
+        files.push((kind, path, contents));
+    }
+
+    let (overrides, ignored) = subagent_overrides(env_overrides());
+    let mut loaded = load_layers(files, &overrides);
+    loaded.issues.extend(ignored);
+    for issue in &loaded.issues {
+        eprintln!("{issue}");
+    }
+    if !effective {
+        if loaded.layers.is_empty() {
+            println!("no config files; using the defaults");
+        }
+        for layer in &loaded.layers {
+            println!("{} {}", layer.kind.as_str(), layer.path.display());
+        }
+        return 0;
+    }
+
+    for layer in &loaded.layers {
+        println!("# {} {}", layer.kind.as_str(), layer.path.display());
+    }
+    match render_effective(loaded.config) {
+        Ok(out) => {
+            print!("{out}");
+            0
+        }
+        Err(e) => {
+            eprintln!("error: cannot render config: {e}");
+            1
+        }
+    }
+}
+
+/// The merged config as TOML, with default locations filled in and API
+/// key commands redacted.
+fn render_effective(mut config: Config) -> Result<String, toml::ser::Error> {
+    config.shell.command = Some(config.shell_command());
+    let audit = config.security.resolve_audit_path();
+    config.security.audit_log_path = Some(audit.to_string_lossy().into_owned());
+    let sessions = config.journal.resolve_sessions_dir();
+    config.journal.sessions_dir = Some(sessions.to_string_lossy().into_owned());
+    let hooks = config.hooks.resolve_dir();
+    config.hooks.dir = Some(hooks.to_string_lossy().into_owned());
+
+    for cmd in [
+        &mut config.backend.anthropic.api_key_cmd,
+        &mut config.backend.openai.api_key_cmd,
+    ] {
+        if cmd.is_some() {
+            *cmd = Some("<redacted>".to_string());
+        }
+    }
+    toml::to_string(&config)
+}
+
+/// `unixagent config trust`: accept the project config files that apply
+/// in the current directory, as they are now.
+fn trust_projects(cwd: &Path) -> i32 {
+    let projects: Vec<PathBuf> = discover_layers(cwd)
+        .into_iter()
+        .filter(|(kind, _)| *kind == LayerKind::Project)
+        .map(|(_, path)| path)
+        .collect();
+    if projects.is_empty() {
+        eprintln!(
+            "no {} in {} or its parents",
+            crate::config::PROJECT_CONFIG_NAME,
+            cwd.display()
+        );
+        return 1;
+    }
+
+    let mut store = TrustStore::load(trust::store_path());
+    for path in projects {
+        let result = std::fs::read_to_string(&path).and_then(|c| store.trust(&path, &c));
+        match result {
+            Ok(()) => println!("trusted {}", path.display()),
+            Err(e) => {
+                eprintln!("error: {}: {e}", path.display());
+                return 1;
+            }
+        }
+    }
+    0
+}
+
+fn backend_name(policy: &SandboxPolicy) -> &'static str {
+    if cfg!(target_os = "macos") {
+        "seatbelt"
+    } else {
+        match policy.backend {
+            SandboxBackend::Landlock => "landlock",
+            SandboxBackend::Namespace => "namespace",
+        }
+    }
+}
+
+/// The resolved policy, one section per list, with the settings
+/// `--sandbox-exec` adds on top.
+fn render_policy(policy: &SandboxPolicy) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "backend: {}", backend_name(policy));
+    let lists: [(RuleKind, &[PathBuf]); 4] = [
+        (RuleKind::Writable, &policy.writable),
+        (RuleKind::Readable, &policy.readable),
+        (RuleKind::DenyWrite, &policy.deny_write),
+        (RuleKind::Denied, &policy.denied),
+    ];
+    for (kind, paths) in lists {
+        let _ = writeln!(out, "{}:", kind.as_str());
+        for path in paths {
+            let missing = if path.exists() { "" } else { " (missing)" };
+            let _ = writeln!(out, "  {}{missing}", path.display());
+        }
+    }
+
+    let network = &policy.network;
+    let _ = write!(out, "network: {}", network.mode.as_str());
+    if network.mode == ua_sandbox::NetworkMode::Ports {
+        let ports: Vec<String> = network.allow_ports.iter().map(u16::to_string).collect();
+        let _ = write!(out, " ({})", ports.join(", "));
+    }
+    out.push('\n');
+    let _ = writeln!(out, "seccomp: {}", policy.seccomp.as_str());
+
+    let limits = &policy.limits;
+    let limit = |value: u64, unit: &str| match value {
+        0 => "unlimited".to_string(),
+        n => format!("{n}{unit}"),
+    };
+    let _ = writeln!(
+        out,
+        "limits: timeout {}, cpu {}, memory {}, processes {}, file size {}",
+        limit(limits.timeout_secs, "s"),
+        limit(limits.cpu_secs, "s"),
+        limit(limits.memory_mb, " MB"),
+        limit(limits.max_processes, ""),
+        limit(limits.max_file_size_mb, " MB"),
+    );
+    if policy.debug {
+        let _ = writeln!(out, "debug: denied accesses are logged to the audit log");
+    }
+
+    if !cfg!(target_os = "macos") && policy.backend == SandboxBackend::Landlock {
+        let beneath_writable: Vec<&PathBuf> = policy
+            .denied
+            .iter()
+            .filter(|p| policy.writable.iter().any(|w| p.starts_with(w)))
+            .chain(policy.unenforceable_deny_write())
+            .collect();
+        for path in beneath_writable {
+            let _ = writeln!(
+                out,
+                "note: {} is inside a writable path; Landlock cannot protect it (backend = \"namespace\" can)",
+                path.display()
+            );
+        }
+    }
+    out
+}
+
+/// The verdict for one path: the access, the entry that decides it, and
+/// entries the backend ignores.
+fn render_explanation(e: &Explanation, write: bool) -> String {
+    let mut out = String::new();
+    let path = e.path.display();
+    if write {
+        let verdict = if e.access == Access::ReadWrite {
+            "allowed"
+        } else {
+            "denied"
+        };
+        let _ = writeln!(out, "{path}: write {verdict} ({})", e.access.as_str());
+    } else {
+        let _ = writeln!(out, "{path}: {}", e.access.as_str());
+    }
+    match &e.rule {
+        Some(rule) => {
+            let _ = writeln!(out, "  {}: {}", rule.kind.as_str(), rule.path.display());
+        }
+        None if e.access == Access::Denied => {
+            out.push_str("  no rule matches; paths outside the policy are denied\n");
+        }
+        None => out.push_str("  no rule matches; reads are allowed outside the policy\n"),
+    }
+    for rule in &e.unenforced {
+        let _ = writeln!(
+            out,
+            "  note: {} entry {} is not enforced by Landlock (backend = \"namespace\" can)",
+            rule.kind.as_str(),
+            rule.path.display()
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ua_sandbox::explain::Rule;
+
+    fn policy() -> SandboxPolicy {
+        SandboxPolicy {
+            writable: vec!["/nonexistent-ua/work".into()],
+            readable: vec!["/usr".into()],
+            denied: vec!["/nonexistent-ua/work/.env".into()],
+            deny_write: vec![],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn render_policy_lists_resolved_paths() {
+        let out = render_policy(&policy());
+        assert!(out.contains("writable_paths:\n  /nonexistent-ua/work (missing)\n"));
+        assert!(out.contains("readable_paths:\n  /usr\n"));
+        assert!(out.contains("network: allow\n"));
+        assert!(out.contains("limits: timeout 300s, cpu unlimited"));
+        #[cfg(target_os = "linux")]
+        assert!(
+            out.contains("note: /nonexistent-ua/work/.env is inside a writable path"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn render_explanation_names_the_rule() {
+        let e = Explanation {
+            path: "/usr/bin/ls".into(),
+            access: Access::ReadOnly,
+            rule: Some(Rule {
+                kind: RuleKind::Readable,
+                path: "/usr".into(),
+            }),
+            unenforced: vec![],
+        };
+        assert_eq!(
+            render_explanation(&e, false),
+            "/usr/bin/ls: read-only\n  readable_paths: /usr\n"
+        );
+        assert_eq!(
+            render_explanation(&e, true),
+            "/usr/bin/ls: write denied (read-only)\n  readable_paths: /usr\n"
+        );
+    }
+
+    #[test]
+    fn render_explanation_default_deny() {
+        let e = Explanation {
+            path: "/root/.ssh/id_rsa".into(),
+            access: Access::Denied,
+            rule: None,
+            unenforced: vec![],
+        };
+        assert!(render_explanation(&e, false).contains("no rule matches"));
+    }
+
+    #[test]
+    fn render_effective_redacts_api_key_commands() {
+        let mut config = Config::default();
+        config.backend.anthropic.api_key_cmd = Some("echo sk-secret".to_string());
+        let out = render_effective(config).unwrap();
+        assert!(!out.contains("sk-secret"), "{out}");
+        assert!(out.contains("api_key_cmd = \"<redacted>\""));
+        assert!(out.contains("audit_log_path = \"/"));
+
+        // The output is a config file that loads back.
+        let parsed: Config = toml::from_str(&out).unwrap();
+        assert_eq!(parsed.backend.anthropic.model, "claude-sonnet-4-20250514");
+    }
+}
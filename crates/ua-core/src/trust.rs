@@ -0,0 +1,219 @@
+//! Trust decisions for per-project config files.
+//!
+//! A `.unixagent.toml` arrives with whatever repository was cloned, and it
+//! can widen the sandbox or add to the system prompt. A project file is only
+//! used once the user has accepted its exact contents: the SHA-256 of each
+//! accepted file is kept in the config directory, and any edit asks again.
+//!
+//! The store sits next to `config.toml`, outside the paths sandboxed
+//! commands may write by default, and both are write-protected. Where the
+//! sandbox can't enforce that (Landlock, when the working directory
+//! contains the config directory), a file sandboxed commands could have
+//! written along with its trust record is confirmed again every time.
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use ua_sandbox::explain::{explain, Access};
+use ua_sandbox::SandboxPolicy;
+
+use crate::config::config_dir;
+
+/// Where accepted project config hashes are kept.
+pub fn store_path() -> PathBuf {
+    config_dir().join("trusted_projects.json")
+}
+
+/// Whether commands running under `policy` could write both the project
+/// config at `file` and the trust store, and so make the file trusted.
+pub fn sandbox_can_forge(policy: &SandboxPolicy, file: &Path) -> bool {
+    let writable =
+        |path: &Path| explain(policy, &path.to_string_lossy()).access == Access::ReadWrite;
+    writable(file) && writable(&store_path())
+}
+
+/// Hex SHA-256 of a config file's contents.
+pub fn hash(contents: &str) -> String {
+    Sha256::digest(contents.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Accepted project config files, keyed by path.
+#[derive(Debug)]
+pub struct TrustStore {
+    path: PathBuf,
+    trusted: BTreeMap<PathBuf, String>,
+}
+
+impl TrustStore {
+    /// Load the store at `path`. A missing or unreadable store trusts
+    /// nothing.
+    pub fn load(path: PathBuf) -> Self {
+        let trusted = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Self { path, trusted }
+    }
+
+    /// Whether `file` was accepted with exactly these contents.
+    pub fn is_trusted(&self, file: &Path, contents: &str) -> bool {
+        self.trusted.get(file) == Some(&hash(contents))
+    }
+
+    /// Whether `file` was accepted before, with any contents.
+    pub fn is_known(&self, file: &Path) -> bool {
+        self.trusted.contains_key(file)
+    }
+
+    /// Accept `file` with these contents and save the store.
+    pub fn trust(&mut self, file: &Path, contents: &str) -> io::Result<()> {
+        self.trusted.insert(file.to_path_buf(), hash(contents));
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(&self.trusted).map_err(io::Error::other)?;
+        std::fs::write(&self.path, json)
+    }
+}
+
+/// Whether to use the project config at `path`. Trusted files are used
+/// silently; otherwise the user is asked on the terminal when `interactive`,
+/// and the file is skipped with a warning when not.
+///
+/// `forgeable` says sandboxed commands could have written the file and its
+/// trust record (see [`sandbox_can_forge`]); a stored trust decision then
+/// proves nothing, and the file is confirmed again.
+pub fn confirm(
+    store: &mut TrustStore,
+    path: &Path,
+    contents: &str,
+    interactive: bool,
+    forgeable: bool,
+) -> bool {
+    let trusted = store.is_trusted(path, contents);
+    if trusted && !forgeable {
+        return true;
+    }
+    let what = if trusted {
+        "is writable by sandboxed commands, as is the trust store"
+    } else if store.is_known(path) {
+        "has changed since it was trusted"
+    } else {
+        "is not trusted yet"
+    };
+    if !interactive {
+        eprintln!(
+            "warning: ignoring {}: it {what}; run `unixagent config trust` to use it",
+            path.display()
+        );
+        return false;
+    }
+
+    let mut stderr = io::stderr();
+    let _ = writeln!(stderr, "Project config {} {what}:", path.display());
+    for line in contents.lines() {
+        let _ = writeln!(stderr, "  {line}");
+    }
+    let _ = write!(
+        stderr,
+        "It can change the sandbox and the system prompt. Trust it? [y/N] "
+    );
+    let _ = stderr.flush();
+
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err()
+        || !matches!(answer.trim(), "y" | "Y" | "yes")
+    {
+        eprintln!("ignoring {}", path.display());
+        return false;
+    }
+    if let Err(e) = store.trust(path, contents) {
+        eprintln!("warning: cannot save trust decision: {e}");
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_is_sha256_hex() {
+        assert_eq!(
+            hash("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn store_is_outside_the_default_writable_paths() {
+        let policy = crate::config::SandboxConfig::default().to_policy(&Default::default());
+        assert_ne!(
+            explain(&policy, &store_path().to_string_lossy()).access,
+            Access::ReadWrite
+        );
+    }
+
+    #[test]
+    fn trust_survives_reload_until_contents_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let store_path = dir.path().join("data").join("trusted_projects.json");
+        let file = dir.path().join(".unixagent.toml");
+
+        let mut store = TrustStore::load(store_path.clone());
+        assert!(!store.is_trusted(&file, "a = 1\n"));
+        store.trust(&file, "a = 1\n").unwrap();
+
+        let store = TrustStore::load(store_path);
+        assert!(store.is_trusted(&file, "a = 1\n"));
+        assert!(!store.is_trusted(&file, "a = 2\n"));
+        assert!(store.is_known(&file));
+    }
+
+    #[test]
+    fn untrusted_file_is_skipped_without_a_terminal() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = TrustStore::load(dir.path().join("trusted_projects.json"));
+        let file = dir.path().join(".unixagent.toml");
+        assert!(!confirm(&mut store, &file, "a = 1\n", false, false));
+        store.trust(&file, "a = 1\n").unwrap();
+        assert!(confirm(&mut store, &file, "a = 1\n", false, false));
+    }
+
+    #[test]
+    fn forgeable_trust_is_not_used_without_asking() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = TrustStore::load(dir.path().join("trusted_projects.json"));
+        let file = dir.path().join(".unixagent.toml");
+        store.trust(&file, "a = 1\n").unwrap();
+        assert!(!confirm(&mut store, &file, "a = 1\n", false, true));
+    }
+
+    #[test]
+    fn sandbox_can_forge_needs_both_file_and_store_writable() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join(".unixagent.toml");
+        let project = project.to_string_lossy().into_owned();
+        let cwd = dir.path().to_string_lossy().into_owned();
+        let store = store_path().to_string_lossy().into_owned();
+
+        // Only the project directory is writable: the store is safe.
+        let policy = SandboxPolicy::from_config(std::slice::from_ref(&cwd), &[], &[]);
+        assert!(!sandbox_can_forge(&policy, Path::new(&project)));
+
+        // Everything is writable, and Landlock can't protect the store.
+        let policy = SandboxPolicy::from_config(&["/".to_string()], &[], &[])
+            .with_backend(ua_sandbox::SandboxBackend::Landlock)
+            .with_deny_write(&[store, project.clone()]);
+        assert_eq!(
+            sandbox_can_forge(&policy, Path::new(&project)),
+            !cfg!(target_os = "macos")
+        );
+    }
+}
@@ -10,6 +10,7 @@ use std::time::Duration;
 
 use ua_core::osc::{OscEvent, OscParser};
 use ua_core::pty::PtySession;
+use ua_core::shell_scripts::{nested_bootstrap, ShellKind};
 
 /// Check if a shell is available on the system.
 fn shell_available(name: &str) -> bool {
@@ -43,7 +44,7 @@ fn collect_osc_events(shell: &str, command: &str, timeout_ms: u64) -> Vec<OscEve
             _ => {}
         }
         // Wait until we see the first prompt cycle (133;A + 133;B)
-        if events.iter().any(|e| *e == OscEvent::Osc133B) {
+        if events.contains(&OscEvent::Osc133B) {
             break;
         }
     }
@@ -95,7 +96,7 @@ fn bash_osc133_sequences() {
 
     // Should see: C (preexec), D (command done), A (prompt start), B (prompt ready)
     assert!(
-        events.iter().any(|e| *e == OscEvent::Osc133C),
+        events.contains(&OscEvent::Osc133C),
         "expected 133;C in bash events: {events:?}"
     );
     assert!(
@@ -103,11 +104,11 @@ fn bash_osc133_sequences() {
         "expected 133;D in bash events: {events:?}"
     );
     assert!(
-        events.iter().any(|e| *e == OscEvent::Osc133A),
+        events.contains(&OscEvent::Osc133A),
         "expected 133;A in bash events: {events:?}"
     );
     assert!(
-        events.iter().any(|e| *e == OscEvent::Osc133B),
+        events.contains(&OscEvent::Osc133B),
         "expected 133;B in bash events: {events:?}"
     );
 }
@@ -122,7 +123,7 @@ fn zsh_osc133_sequences() {
     let events = collect_osc_events("zsh", "echo hello_zsh_test", 5000);
 
     assert!(
-        events.iter().any(|e| *e == OscEvent::Osc133C),
+        events.contains(&OscEvent::Osc133C),
         "expected 133;C in zsh events: {events:?}"
     );
     assert!(
@@ -130,11 +131,11 @@ fn zsh_osc133_sequences() {
         "expected 133;D in zsh events: {events:?}"
     );
     assert!(
-        events.iter().any(|e| *e == OscEvent::Osc133A),
+        events.contains(&OscEvent::Osc133A),
         "expected 133;A in zsh events: {events:?}"
     );
     assert!(
-        events.iter().any(|e| *e == OscEvent::Osc133B),
+        events.contains(&OscEvent::Osc133B),
         "expected 133;B in zsh events: {events:?}"
     );
 }
@@ -153,11 +154,11 @@ fn fish_osc133_sequences() {
         "expected 133;D in fish events: {events:?}"
     );
     assert!(
-        events.iter().any(|e| *e == OscEvent::Osc133A),
+        events.contains(&OscEvent::Osc133A),
         "expected 133;A in fish events: {events:?}"
     );
     assert!(
-        events.iter().any(|e| *e == OscEvent::Osc133B),
+        events.contains(&OscEvent::Osc133B),
         "expected 133;B in fish events: {events:?}"
     );
 }
@@ -207,3 +208,163 @@ fn bash_exit_code_zero() {
         "expected 133;D with exit code 0 for `true` command: {events:?}"
     );
 }
+
+/// Assert the prompt cycle every integrated shell reports: D, A and B, plus
+/// C where the shell has a pre-exec hook.
+fn assert_prompt_cycle(shell: &str, events: &[OscEvent], expect_c: bool) {
+    if expect_c {
+        assert!(
+            events.contains(&OscEvent::Osc133C),
+            "expected 133;C in {shell} events: {events:?}"
+        );
+    }
+    assert!(
+        events.iter().any(|e| matches!(e, OscEvent::Osc133D { .. })),
+        "expected 133;D in {shell} events: {events:?}"
+    );
+    assert!(
+        events.contains(&OscEvent::Osc133A),
+        "expected 133;A in {shell} events: {events:?}"
+    );
+    assert!(
+        events.contains(&OscEvent::Osc133B),
+        "expected 133;B in {shell} events: {events:?}"
+    );
+}
+
+#[test]
+fn nushell_osc133_sequences() {
+    if !shell_available("nu") {
+        eprintln!("skipping: nu not available");
+        return;
+    }
+
+    let events = collect_osc_events("nu", "echo hello_nu_test", 5000);
+    assert_prompt_cycle("nu", &events, true);
+    assert!(
+        events.contains(&OscEvent::Osc633E {
+            command: "echo hello_nu_test".to_string()
+        }),
+        "expected 633;E in nu events: {events:?}"
+    );
+}
+
+#[test]
+fn xonsh_osc133_sequences() {
+    if !shell_available("xonsh") {
+        eprintln!("skipping: xonsh not available");
+        return;
+    }
+
+    let events = collect_osc_events("xonsh", "echo hello_xonsh_test", 10000);
+    assert_prompt_cycle("xonsh", &events, true);
+}
+
+#[test]
+fn pwsh_osc133_sequences() {
+    if !shell_available("pwsh") {
+        eprintln!("skipping: pwsh not available");
+        return;
+    }
+
+    let events = collect_osc_events("pwsh", "echo hello_pwsh_test", 10000);
+    assert_prompt_cycle("pwsh", &events, true);
+}
+
+#[test]
+fn dash_osc133_sequences() {
+    if !shell_available("dash") {
+        eprintln!("skipping: dash not available");
+        return;
+    }
+
+    // No pre-exec hook in POSIX shells, so no 133;C.
+    let events = collect_osc_events("dash", "echo hello_dash_test", 5000);
+    assert_prompt_cycle("dash", &events, false);
+    assert!(
+        events.iter().any(|e| matches!(e, OscEvent::Osc7 { .. })),
+        "expected OSC 7 in dash events: {events:?}"
+    );
+}
+
+#[test]
+fn dash_exit_code_in_133d() {
+    if !shell_available("dash") {
+        eprintln!("skipping: dash not available");
+        return;
+    }
+
+    let events = collect_osc_events("dash", "false", 5000);
+    assert!(
+        events.contains(&OscEvent::Osc133D { exit_code: Some(1) }),
+        "expected 133;D with exit code 1 for `false` command: {events:?}"
+    );
+}
+
+#[test]
+fn ksh_osc133_sequences() {
+    if !shell_available("ksh") {
+        eprintln!("skipping: ksh not available");
+        return;
+    }
+
+    let events = collect_osc_events("ksh", "echo hello_ksh_test", 5000);
+    assert_prompt_cycle("ksh", &events, false);
+}
+
+/// A shell started inside the integrated one, then integrated with the
+/// `#!integrate` bootstrap, typed ahead.
+fn collect_nested_events(outer: &str, inner: &str, kind: ShellKind) -> Vec<OscEvent> {
+    let bootstrap = nested_bootstrap(kind, 1).unwrap();
+    collect_osc_events(outer, &format!("{inner}\n{}", bootstrap.trim_end()), 5000)
+}
+
+fn depth_report(depth: &str) -> OscEvent {
+    OscEvent::Osc633P {
+        key: "UaDepth".to_string(),
+        value: depth.to_string(),
+    }
+}
+
+#[test]
+fn bash_reports_depth_zero() {
+    if !shell_available("bash") {
+        eprintln!("skipping: bash not available");
+        return;
+    }
+
+    let events = collect_osc_events("bash", "true", 5000);
+    assert!(
+        events.contains(&depth_report("0")),
+        "expected depth 0 in bash events: {events:?}"
+    );
+}
+
+#[test]
+fn nested_bash_integrates_at_depth_one() {
+    if !shell_available("bash") {
+        eprintln!("skipping: bash not available");
+        return;
+    }
+
+    let events = collect_nested_events("bash", "bash --norc --noprofile -i", ShellKind::Bash);
+    assert!(
+        events.contains(&depth_report("1")),
+        "expected depth 1 from the nested bash: {events:?}"
+    );
+    assert_prompt_cycle("nested bash", &events, false);
+}
+
+#[test]
+fn nested_dash_integrates_at_depth_one() {
+    if !shell_available("bash") || !shell_available("dash") {
+        eprintln!("skipping: bash or dash not available");
+        return;
+    }
+
+    let events = collect_nested_events("bash", "dash -i", ShellKind::Posix);
+    assert!(
+        events.contains(&depth_report("1")),
+        "expected depth 1 from the nested dash: {events:?}"
+    );
+}
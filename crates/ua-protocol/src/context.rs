@@ -11,6 +11,21 @@ pub struct ShellContext {
     pub arch: String,
     pub env_vars: Vec<(String, String)>,
     pub terminal_size: (u16, u16),
+    /// The local shell and the shells nested in it (`ssh`, `sudo -i`,
+    /// `docker exec`), outermost first. Empty unless nested; `cwd` is then
+    /// the innermost one's.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub levels: Vec<ShellLevel>,
+}
+
+/// One shell in a stack of nested shells.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ShellLevel {
+    pub shell: String,
+    /// Host name the shell reported; empty until it does.
+    pub host: String,
+    /// Working directory the shell reported; empty until it does.
+    pub cwd: String,
 }
 
 impl Default for ShellContext {
@@ -22,6 +37,7 @@ impl Default for ShellContext {
             arch: std::env::consts::ARCH.to_string(),
             env_vars: Vec::new(),
             terminal_size: (80, 24),
+            levels: Vec::new(),
         }
     }
 }
@@ -174,6 +190,11 @@ pub struct AgentRequest {
     /// Image attachments to include with the instruction.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub attachments: Vec<Attachment>,
+    /// The visible terminal screen, while a full-screen program (`htop`,
+    /// `vim`, `less`) has the alternate screen up. `terminal_history` only
+    /// has fragments of its redraws.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub screen: Option<String>,
 }
 
 impl AgentRequest {
@@ -185,6 +206,7 @@ impl AgentRequest {
             conversation: Vec::new(),
             system_prompt_extra: None,
             attachments: Vec::new(),
+            screen: None,
         }
     }
 
@@ -202,6 +224,11 @@ impl AgentRequest {
         self.attachments = attachments;
         self
     }
+
+    pub fn with_screen(mut self, screen: String) -> Self {
+        self.screen = Some(screen);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -227,6 +254,11 @@ mod tests {
             arch: "x86_64".to_string(),
             env_vars: vec![("PATH".to_string(), "/usr/bin".to_string())],
             terminal_size: (120, 40),
+            levels: vec![ShellLevel {
+                shell: "bash".to_string(),
+                host: "web-1".to_string(),
+                cwd: "/srv".to_string(),
+            }],
         };
         let json = serde_json::to_string(&ctx).unwrap();
         let ctx2: ShellContext = serde_json::from_str(&json).unwrap();
@@ -439,5 +471,15 @@ mod tests {
         let request = AgentRequest::new("hello", ctx);
         let json = serde_json::to_string(&request).unwrap();
         assert!(!json.contains("attachments"));
+        assert!(!json.contains("screen"));
+    }
+
+    #[test]
+    fn agent_request_with_screen_roundtrip() {
+        let request = AgentRequest::new("what is using the cpu", ShellContext::default())
+            .with_screen("  PID USER      %CPU COMMAND\n 4242 me        99.0 cargo".to_string());
+        let json = serde_json::to_string(&request).unwrap();
+        let request2: AgentRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(request, request2);
     }
 }
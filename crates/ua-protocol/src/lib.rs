@@ -8,6 +8,6 @@ pub mod message;
 
 pub use context::{
     AgentRequest, Attachment, ConversationMessage, MediaRef, ResolvedMedia, Role, ShellContext,
-    TerminalHistory, ToolResultRecord, ToolUseRecord,
+    ShellLevel, TerminalHistory, ToolResultRecord, ToolUseRecord,
 };
 pub use message::StreamEvent;
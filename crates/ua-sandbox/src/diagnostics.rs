@@ -0,0 +1,221 @@
+//! Reports from `--sandbox-exec` about the command it ran.
+//!
+//! Status lines (`[ua:sandbox] active`, warnings, blocked syscalls, a kill
+//! after the timeout) go to a pipe the policy names by fd, not to the
+//! command's stderr, so a command can neither forge a report nor hide its
+//! own output behind the prefix. The fd is close-on-exec in
+//! `--sandbox-exec`, so the command doesn't inherit it, and the processes
+//! that keep it open (the timeout waiter, the seccomp supervisor) are not
+//! dumpable, so it can't be reopened through `/proc`. Without a pipe,
+//! reports go to stderr as they always did, for running `--sandbox-exec`
+//! by hand.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// Report prefix for a syscall the seccomp filter blocked, followed by the
+/// syscall name.
+pub const VIOLATION_PREFIX: &str = "[ua:sandbox] blocked by sandbox: ";
+
+/// Report prefix when `--sandbox-exec` killed the command, followed by the
+/// reason (e.g. "timeout after 300s").
+pub const KILLED_PREFIX: &str = "[ua:sandbox] killed: ";
+
+/// The pipe reports go to, or -1 for stderr.
+static REPORT_FD: AtomicI32 = AtomicI32::new(-1);
+
+/// Send reports to `fd` if it is a pipe, closing it on exec. Anything else
+/// (a stale fd, a file the caller didn't mean) leaves them on stderr.
+pub(crate) fn report_to(fd: RawFd) {
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstat(fd, &mut stat) } != 0 || stat.st_mode & libc::S_IFMT != libc::S_IFIFO {
+        return;
+    }
+    unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    REPORT_FD.store(fd, Ordering::SeqCst);
+}
+
+/// Write one status line to the report pipe, or to stderr without one.
+/// A full pipe drops the line rather than block the command.
+pub(crate) fn report(line: &str) {
+    let fd = REPORT_FD.load(Ordering::SeqCst);
+    if fd < 0 {
+        eprintln!("{line}");
+        return;
+    }
+    let line = format!("{line}\n");
+    unsafe { libc::write(fd, line.as_ptr() as *const libc::c_void, line.len()) };
+}
+
+/// Keep processes that hold the report pipe out of reach of `/proc`.
+pub(crate) fn hide_report_fd() {
+    if REPORT_FD.load(Ordering::SeqCst) >= 0 {
+        #[cfg(target_os = "linux")]
+        unsafe {
+            libc::prctl(libc::PR_SET_DUMPABLE, 0, 0, 0, 0)
+        };
+    }
+}
+
+/// The reading end of a report pipe, for the process that starts
+/// `--sandbox-exec`.
+#[derive(Debug)]
+pub struct ReportPipe {
+    read: File,
+    write: Option<OwnedFd>,
+}
+
+impl ReportPipe {
+    /// A new pipe. Both ends are non-blocking and close-on-exec; see
+    /// [`ReportPipe::share_with`].
+    pub fn new() -> io::Result<Self> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+        for fd in [&read, &write] {
+            unsafe {
+                libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC);
+                libc::fcntl(fd.as_raw_fd(), libc::F_SETFL, libc::O_NONBLOCK);
+            }
+        }
+        Ok(Self {
+            read: File::from(read),
+            write: Some(write),
+        })
+    }
+
+    /// Read the reports from a pipe some other process writes, such as an
+    /// opened FIFO. `read` should be non-blocking.
+    pub fn from_reader(read: File) -> Self {
+        Self { read, write: None }
+    }
+
+    /// The fd `--sandbox-exec` should report to, for
+    /// [`crate::SandboxPolicy::with_report_fd`].
+    pub fn write_fd(&self) -> Option<RawFd> {
+        self.write.as_ref().map(AsRawFd::as_raw_fd)
+    }
+
+    /// Let `command` inherit the writing end, under the same fd number.
+    pub fn share_with(&self, command: &mut Command) {
+        let Some(fd) = self.write_fd() else {
+            return;
+        };
+        unsafe {
+            command.pre_exec(move || {
+                libc::fcntl(fd, libc::F_SETFD, 0);
+                Ok(())
+            })
+        };
+    }
+
+    /// The reports written so far.
+    pub fn take(&mut self) -> SandboxReports {
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 4096];
+        while let Ok(n @ 1..) = self.read.read(&mut chunk) {
+            buf.extend_from_slice(&chunk[..n]);
+        }
+        SandboxReports::parse(&String::from_utf8_lossy(&buf))
+    }
+}
+
+/// What `--sandbox-exec` reported about a command.
+#[derive(Debug, Default, PartialEq)]
+pub struct SandboxReports {
+    /// Names of syscalls the filter blocked.
+    pub blocked: Vec<String>,
+    /// Why the command was killed, if it was (e.g. "timeout after 300s").
+    pub killed: Option<String>,
+    /// Warnings and debug notes about the sandbox itself.
+    pub messages: Vec<String>,
+}
+
+impl SandboxReports {
+    /// Parse report lines.
+    pub fn parse(text: &str) -> Self {
+        let mut out = Self::default();
+        for line in text.lines() {
+            if let Some(name) = line.strip_prefix(VIOLATION_PREFIX) {
+                out.blocked.push(name.to_string());
+            } else if let Some(reason) = line.strip_prefix(KILLED_PREFIX) {
+                out.killed = Some(reason.to_string());
+            } else if !line.is_empty() && line != "[ua:sandbox] active" {
+                out.messages.push(line.to_string());
+            }
+        }
+        out
+    }
+
+    /// Tool-result notes for the reports: `[blocked by sandbox: <name>]`
+    /// per blocked syscall, then `[killed: <reason>]`.
+    pub fn notes(&self) -> Vec<String> {
+        self.blocked
+            .iter()
+            .map(|name| format!("[blocked by sandbox: {name}]"))
+            .chain(
+                self.killed
+                    .iter()
+                    .map(|reason| format!("[killed: {reason}]")),
+            )
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reports() {
+        let reports = SandboxReports::parse(
+            "[ua:sandbox] active\n\
+             [ua:sandbox] warning: Landlock partially enforced\n\
+             [ua:sandbox] blocked by sandbox: ptrace\n\
+             [ua:sandbox] killed: timeout after 300s\n",
+        );
+        assert_eq!(reports.blocked, vec!["ptrace"]);
+        assert_eq!(reports.killed.as_deref(), Some("timeout after 300s"));
+        assert_eq!(
+            reports.messages,
+            vec!["[ua:sandbox] warning: Landlock partially enforced"]
+        );
+        assert_eq!(
+            reports.notes(),
+            vec![
+                "[blocked by sandbox: ptrace]",
+                "[killed: timeout after 300s]"
+            ]
+        );
+    }
+
+    #[test]
+    fn shared_pipe_carries_reports() {
+        let mut pipe = ReportPipe::new().unwrap();
+        let fd = pipe.write_fd().unwrap();
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(format!("echo '{VIOLATION_PREFIX}mount' >&{fd}"));
+        pipe.share_with(&mut command);
+        assert!(command.status().unwrap().success());
+        assert_eq!(pipe.take().blocked, vec!["mount"]);
+        // Nothing more, and no blocking.
+        assert_eq!(pipe.take(), SandboxReports::default());
+
+        // Other children don't get it.
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(format!("echo x >&{fd}"))
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(!status.success());
+    }
+}
@@ -0,0 +1,296 @@
+//! Offline evaluation of a policy for one path.
+//!
+//! Answers "would the sandbox let a command read or write this?" without
+//! applying anything, for `unixagent sandbox explain`. Mirrors how each
+//! backend enforces the policy: Landlock grants the union of every rule
+//! above a path and cannot deny beneath an allowed one; the namespace
+//! backend and Seatbelt honor `denied` and `deny_write` everywhere.
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::policy::{resolve_path, SandboxBackend, SandboxPolicy};
+
+/// What a sandboxed command may do with a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    ReadWrite,
+    ReadOnly,
+    Denied,
+}
+
+impl Access {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Access::ReadWrite => "writable",
+            Access::ReadOnly => "read-only",
+            Access::Denied => "denied",
+        }
+    }
+}
+
+/// The policy list a rule comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleKind {
+    Writable,
+    Readable,
+    Denied,
+    DenyWrite,
+}
+
+impl RuleKind {
+    /// The `[sandbox]` config key for this list.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleKind::Writable => "writable_paths",
+            RuleKind::Readable => "readable_paths",
+            RuleKind::Denied => "denied_paths",
+            RuleKind::DenyWrite => "deny_write_paths",
+        }
+    }
+}
+
+/// One policy entry that applies to a path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub kind: RuleKind,
+    /// The entry as it appears in the resolved policy.
+    pub path: PathBuf,
+}
+
+/// The verdict for one path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    /// The path after placeholder expansion and symlink resolution.
+    pub path: PathBuf,
+    pub access: Access,
+    /// The entry that decides `access`. `None` when no entry covers the
+    /// path and the default applies.
+    pub rule: Option<Rule>,
+    /// Entries covering the path that the backend cannot enforce, e.g. a
+    /// denied path beneath a writable one under Landlock.
+    pub unenforced: Vec<Rule>,
+}
+
+/// Evaluate `policy` for `path`. `$CWD` and `$HOME` placeholders are
+/// expanded, relative paths are taken from the current directory, and
+/// symlinks are resolved as far as the path exists.
+pub fn explain(policy: &SandboxPolicy, path: &str) -> Explanation {
+    let mut resolved = resolve_path(path);
+    if resolved.is_relative() {
+        resolved = env::current_dir()
+            .unwrap_or_else(|_| PathBuf::from("/"))
+            .join(resolved);
+    }
+    let path = canonicalize_lenient(&resolved);
+
+    let writable = deepest(&policy.writable, &path, RuleKind::Writable);
+    let readable = deepest(&policy.readable, &path, RuleKind::Readable);
+    let denied = deepest(&policy.denied, &path, RuleKind::Denied);
+    let deny_write = deepest(&policy.deny_write, &path, RuleKind::DenyWrite);
+
+    let mut unenforced = Vec::new();
+    let (access, rule) = if cfg!(target_os = "macos") {
+        // Reads are allowed everywhere; denies beat allows.
+        match (denied, deny_write, writable) {
+            (Some(d), _, _) => (Access::Denied, Some(d.rule)),
+            (None, Some(dw), Some(_)) => (Access::ReadOnly, Some(dw.rule)),
+            (None, _, Some(w)) => (Access::ReadWrite, Some(w.rule)),
+            (None, _, None) => (Access::ReadOnly, readable.map(|r| r.rule)),
+        }
+    } else {
+        match policy.backend {
+            SandboxBackend::Landlock => {
+                let (access, rule) = match (writable, readable) {
+                    (Some(w), _) => (Access::ReadWrite, Some(w.rule)),
+                    (None, Some(r)) => (Access::ReadOnly, Some(r.rule)),
+                    (None, None) => (Access::Denied, denied.clone().map(|d| d.rule)),
+                };
+                if access != Access::Denied {
+                    unenforced.extend(denied.map(|d| d.rule));
+                }
+                if access == Access::ReadWrite {
+                    unenforced.extend(deny_write.map(|dw| dw.rule));
+                }
+                (access, rule)
+            }
+            SandboxBackend::Namespace => {
+                // Deeper bind mounts cover shallower ones; denied paths and
+                // write protection are mounted last.
+                let bound = match (writable, readable) {
+                    (Some(w), Some(r)) if r.depth > w.depth => Some((Access::ReadOnly, r)),
+                    (Some(w), _) => Some((Access::ReadWrite, w)),
+                    (None, Some(r)) => Some((Access::ReadOnly, r)),
+                    (None, None) => None,
+                };
+                match (denied, deny_write, bound) {
+                    (Some(d), _, _) => (Access::Denied, Some(d.rule)),
+                    (None, Some(dw), Some(_)) => (Access::ReadOnly, Some(dw.rule)),
+                    (None, _, Some((access, m))) => (access, Some(m.rule)),
+                    (None, _, None) => (Access::Denied, None),
+                }
+            }
+        }
+    };
+
+    Explanation {
+        path,
+        access,
+        rule,
+        unenforced,
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Match {
+    rule: Rule,
+    depth: usize,
+}
+
+/// The most specific entry of `entries` that is `path` or one of its
+/// ancestors. On a tie the first entry wins, which is the canonical form
+/// when the policy lists both.
+fn deepest(entries: &[PathBuf], path: &Path, kind: RuleKind) -> Option<Match> {
+    entries
+        .iter()
+        .rev()
+        .filter_map(|entry| {
+            let canonical = canonicalize_lenient(entry);
+            path.starts_with(&canonical).then(|| Match {
+                rule: Rule {
+                    kind,
+                    path: entry.clone(),
+                },
+                depth: canonical.components().count(),
+            })
+        })
+        .max_by_key(|m| m.depth)
+}
+
+/// Canonicalize the longest existing prefix of `path` and append the rest,
+/// so a file that doesn't exist yet still gets its parents' symlinks
+/// resolved.
+fn canonicalize_lenient(path: &Path) -> PathBuf {
+    let mut rest = Vec::new();
+    let mut current = path;
+    loop {
+        if let Ok(mut canonical) = current.canonicalize() {
+            canonical.extend(rest.iter().rev());
+            return canonical;
+        }
+        match (current.parent(), current.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                current = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(backend: SandboxBackend) -> SandboxPolicy {
+        SandboxPolicy {
+            backend,
+            writable: vec!["/nonexistent-ua/work".into()],
+            readable: vec![
+                "/nonexistent-ua/usr".into(),
+                "/nonexistent-ua/work/vendor".into(),
+            ],
+            denied: vec!["/nonexistent-ua/work/.env".into()],
+            deny_write: vec!["/nonexistent-ua/work/.unixagent".into()],
+            ..Default::default()
+        }
+    }
+
+    fn rule(kind: RuleKind, path: &str) -> Option<Rule> {
+        Some(Rule {
+            kind,
+            path: path.into(),
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn landlock_grants_the_union_of_rules() {
+        let p = policy(SandboxBackend::Landlock);
+
+        let e = explain(&p, "/nonexistent-ua/work/src/main.rs");
+        assert_eq!(e.access, Access::ReadWrite);
+        assert_eq!(e.rule, rule(RuleKind::Writable, "/nonexistent-ua/work"));
+        assert!(e.unenforced.is_empty());
+
+        // A readable path beneath a writable one stays writable.
+        let e = explain(&p, "/nonexistent-ua/work/vendor/lib.rs");
+        assert_eq!(e.access, Access::ReadWrite);
+
+        let e = explain(&p, "/nonexistent-ua/work/.env");
+        assert_eq!(e.access, Access::ReadWrite);
+        assert_eq!(
+            e.unenforced,
+            vec![Rule {
+                kind: RuleKind::Denied,
+                path: "/nonexistent-ua/work/.env".into()
+            }]
+        );
+
+        let e = explain(&p, "/nonexistent-ua/usr/bin/ls");
+        assert_eq!(e.access, Access::ReadOnly);
+        assert_eq!(e.rule, rule(RuleKind::Readable, "/nonexistent-ua/usr"));
+
+        let e = explain(&p, "/nonexistent-ua/home");
+        assert_eq!(e.access, Access::Denied);
+        assert_eq!(e.rule, None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn namespace_honors_the_most_specific_rule() {
+        let p = policy(SandboxBackend::Namespace);
+
+        let e = explain(&p, "/nonexistent-ua/work/vendor/lib.rs");
+        assert_eq!(e.access, Access::ReadOnly);
+        assert_eq!(
+            e.rule,
+            rule(RuleKind::Readable, "/nonexistent-ua/work/vendor")
+        );
+
+        let e = explain(&p, "/nonexistent-ua/work/.env");
+        assert_eq!(e.access, Access::Denied);
+        assert_eq!(e.rule, rule(RuleKind::Denied, "/nonexistent-ua/work/.env"));
+
+        let e = explain(&p, "/nonexistent-ua/work/.unixagent/config.toml");
+        assert_eq!(e.access, Access::ReadOnly);
+        assert_eq!(
+            e.rule,
+            rule(RuleKind::DenyWrite, "/nonexistent-ua/work/.unixagent")
+        );
+        assert!(e.unenforced.is_empty());
+    }
+
+    #[test]
+    fn symlinks_resolve_before_matching() {
+        let dir = env::temp_dir().join(format!("ua-explain-{}", std::process::id()));
+        let real = dir.join("real");
+        std::fs::create_dir_all(&real).unwrap();
+        let link = dir.join("link");
+        let _ = std::fs::remove_file(&link);
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        let p = SandboxPolicy {
+            writable: vec![real.canonicalize().unwrap()],
+            readable: vec![],
+            denied: vec![],
+            deny_write: vec![],
+            ..Default::default()
+        };
+        let e = explain(&p, &format!("{}/new-file", link.display()));
+        assert_eq!(e.path, real.canonicalize().unwrap().join("new-file"));
+        assert_eq!(e.access, Access::ReadWrite);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,20 +3,36 @@
 //! Uses the `landlock` crate with ABI V5 and BestEffort compatibility.
 //! Default-deny: only paths listed in the policy are accessible.
 
+use std::io;
+use std::os::fd::{AsRawFd, OwnedFd};
+
+use crate::diagnostics;
 use crate::policy::SandboxPolicy;
 use crate::SandboxError;
 
 use landlock::{
-    Access, AccessFs, PathBeneath, PathFd, Ruleset, RulesetAttr, RulesetCreatedAttr, RulesetStatus,
-    ABI,
+    Access, AccessFs, PathBeneath, PathFd, Ruleset, RulesetAttr, RulesetCreated,
+    RulesetCreatedAttr, RulesetStatus, ABI,
 };
 
+/// `LANDLOCK_CREATE_RULESET_VERSION`: query the kernel's Landlock ABI.
+const CREATE_RULESET_VERSION: libc::c_uint = 1 << 0;
+
+/// `LANDLOCK_RESTRICT_SELF_LOG_NEW_EXEC_ON`: keep logging denials after
+/// exec, so the sandboxed command's are logged and not just ours.
+const RESTRICT_SELF_LOG_NEW_EXEC_ON: libc::c_uint = 1 << 1;
+
+/// First Landlock ABI with audit logging (Linux 6.15).
+const AUDIT_ABI: libc::c_long = 7;
+
 /// Apply Landlock filesystem sandbox to the current process. Irreversible.
 pub fn apply_landlock(policy: &SandboxPolicy) -> Result<(), SandboxError> {
     let abi = ABI::V5;
 
     let mut ruleset = Ruleset::default()
         .handle_access(AccessFs::from_all(abi))
+        .map_err(|e| SandboxError::Platform(format!("Landlock ruleset creation failed: {e}")))?
+        .create()
         .map_err(|e| SandboxError::Platform(format!("Landlock ruleset creation failed: {e}")))?;
 
     let read_access = AccessFs::from_read(abi);
@@ -57,7 +73,28 @@ pub fn apply_landlock(policy: &SandboxPolicy) -> Result<(), SandboxError> {
     //
     // Note: if a denied path is a subdirectory of an allowed writable path,
     // Landlock cannot enforce the deny. This is a known limitation — the
-    // policy should be constructed so denied paths are not under writable paths.
+    // policy should be constructed so denied paths are not under writable paths,
+    // or use the namespace backend, which can.
+    //
+    // Write-protected paths are enforced the same way: they are never granted
+    // write access. One beneath a writable path can't be carved out, so warn.
+    for path in policy.unenforceable_deny_write() {
+        diagnostics::report(&format!(
+            "[ua:sandbox] warning: {} is inside a writable path and cannot be write-protected",
+            path.display()
+        ));
+    }
+
+    if policy.debug {
+        let abi = kernel_abi();
+        if abi >= AUDIT_ABI {
+            return restrict_self_logged(ruleset);
+        }
+        diagnostics::report(&format!(
+            "[ua:sandbox] warning: denied accesses are not logged: Landlock audit \
+             needs ABI v{AUDIT_ABI} (Linux 6.15+), kernel has v{abi}"
+        ));
+    }
 
     let status = ruleset
         .restrict_self()
@@ -66,7 +103,9 @@ pub fn apply_landlock(policy: &SandboxPolicy) -> Result<(), SandboxError> {
     match status.ruleset {
         RulesetStatus::FullyEnforced => Ok(()),
         RulesetStatus::PartiallyEnforced => {
-            eprintln!("[ua:sandbox] warning: Landlock partially enforced (kernel may lack full ABI support)");
+            diagnostics::report(
+                "[ua:sandbox] warning: Landlock partially enforced (kernel may lack full ABI support)",
+            );
             Ok(())
         }
         RulesetStatus::NotEnforced => Err(SandboxError::Platform(
@@ -74,3 +113,50 @@ pub fn apply_landlock(policy: &SandboxPolicy) -> Result<(), SandboxError> {
         )),
     }
 }
+
+/// The running kernel's Landlock ABI version, or a negative value
+/// without Landlock.
+fn kernel_abi() -> libc::c_long {
+    unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<libc::c_void>(),
+            0usize,
+            CREATE_RULESET_VERSION,
+        )
+    }
+}
+
+/// Restrict the current process with `ruleset`, asking the kernel to log
+/// every denied access to the audit log. The `landlock` crate has no way to
+/// pass the flag, so this makes the `landlock_restrict_self` call itself.
+fn restrict_self_logged(ruleset: RulesetCreated) -> Result<(), SandboxError> {
+    let fd: Option<OwnedFd> = ruleset.into();
+    let Some(fd) = fd else {
+        return Err(SandboxError::Platform(
+            "Landlock not enforced (kernel support missing?)".to_string(),
+        ));
+    };
+
+    let err = |what: &str| {
+        SandboxError::Platform(format!("{what} failed: {}", io::Error::last_os_error()))
+    };
+    if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+        return Err(err("PR_SET_NO_NEW_PRIVS"));
+    }
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_restrict_self,
+            fd.as_raw_fd(),
+            RESTRICT_SELF_LOG_NEW_EXEC_ON,
+        )
+    };
+    if ret != 0 {
+        return Err(err("Landlock restrict_self"));
+    }
+    diagnostics::report(
+        "[ua:sandbox] debug: denied accesses are logged to the kernel audit log \
+         (ausearch -m LANDLOCK_ACCESS, or dmesg)",
+    );
+    Ok(())
+}
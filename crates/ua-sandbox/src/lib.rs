@@ -1,7 +1,8 @@
 //! OS-level filesystem sandbox for UnixAgent.
 //!
-//! Provides kernel-enforced filesystem isolation using Landlock (Linux) or
-//! Seatbelt (macOS). The sandbox is applied to the current process and is
+//! Provides kernel-enforced filesystem isolation using Landlock or a mount namespace (Linux) or
+//! Seatbelt (macOS), plus network egress restrictions and a seccomp syscall
+//! filter for `--sandbox-exec` on Linux. The sandbox is applied to the current process and is
 //! irreversible — designed to be used in a child process before exec.
 //!
 //! # Architecture
@@ -9,8 +10,14 @@
 //! The parent process (REPL/batch loop) remains unsandboxed. Child commands
 //! run via `unixagent --sandbox-exec`, which:
 //! 1. Deserializes the policy from `__UA_SANDBOX_POLICY` env var
-//! 2. Applies the OS sandbox (Landlock or Seatbelt)
-//! 3. Execs the requested command
+//! 2. Applies the OS sandbox (Landlock or Seatbelt), network rules, and the
+//!    seccomp filter
+//! 3. Execs the requested command under the configured rlimits, or with a
+//!    timeout, runs it in its own process group and kills the group when
+//!    the timeout expires
+//!
+//! Status reports (blocked syscalls, a timeout kill) go to a pipe named in
+//! the policy rather than the command's stderr; see [`diagnostics`].
 //!
 //! # Usage
 //!
@@ -22,15 +29,31 @@
 //! // Process is now sandboxed — cannot access paths outside the policy
 //! ```
 
+pub mod diagnostics;
+pub mod explain;
+pub mod limits;
 pub mod policy;
 
 #[cfg(target_os = "linux")]
 pub mod landlock;
 
+#[cfg(target_os = "linux")]
+pub mod namespace;
+
+#[cfg(target_os = "linux")]
+pub mod network;
+
+#[cfg(target_os = "linux")]
+pub mod seccomp;
+
 #[cfg(target_os = "macos")]
 pub mod seatbelt;
 
-pub use policy::SandboxPolicy;
+pub use policy::{
+    NetworkMode, NetworkPolicy, ResourceLimits, SandboxBackend, SandboxPolicy, SeccompProfile,
+};
+
+pub use diagnostics::{ReportPipe, SandboxReports, KILLED_PREFIX, VIOLATION_PREFIX};
 
 use std::os::unix::process::CommandExt;
 use std::process::Command;
@@ -60,7 +83,7 @@ impl std::error::Error for SandboxError {}
 /// Apply the filesystem sandbox to the current process. Irreversible.
 ///
 /// On macOS, uses Seatbelt (`sandbox_init`).
-/// On Linux, uses Landlock.
+/// On Linux, uses Landlock or a private mount namespace, per `policy.backend`.
 /// On other platforms, returns an error.
 pub fn apply(policy: &SandboxPolicy) -> Result<(), SandboxError> {
     #[cfg(target_os = "macos")]
@@ -69,7 +92,10 @@ pub fn apply(policy: &SandboxPolicy) -> Result<(), SandboxError> {
     }
     #[cfg(target_os = "linux")]
     {
-        landlock::apply_landlock(policy)
+        match policy.backend {
+            SandboxBackend::Landlock => landlock::apply_landlock(policy),
+            SandboxBackend::Namespace => namespace::apply_namespace(policy),
+        }
     }
     #[cfg(not(any(target_os = "macos", target_os = "linux")))]
     {
@@ -80,6 +106,43 @@ pub fn apply(policy: &SandboxPolicy) -> Result<(), SandboxError> {
     }
 }
 
+/// Apply the network section of the policy to the current process. Irreversible.
+///
+/// Kept separate from [`apply`] because the agent process sandboxes itself
+/// and must still reach the LLM API; only `--sandbox-exec` children get this.
+pub fn apply_network(policy: &NetworkPolicy) -> Result<(), SandboxError> {
+    #[cfg(target_os = "linux")]
+    {
+        network::apply_network(policy)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        if policy.is_restricted() {
+            Err(SandboxError::Platform(
+                "network restrictions are only supported on Linux".to_string(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Apply the seccomp profile to the current process. Irreversible.
+///
+/// Like [`apply_network`], only for `--sandbox-exec` children. A no-op
+/// off Linux.
+pub fn apply_seccomp(profile: SeccompProfile) -> Result<(), SandboxError> {
+    #[cfg(target_os = "linux")]
+    {
+        seccomp::apply_seccomp(profile)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = profile;
+        Ok(())
+    }
+}
+
 /// Deserialize policy from env, apply sandbox, exec command. Does not return on success.
 ///
 /// This is the entry point for `unixagent --sandbox-exec <args...>`.
@@ -94,19 +157,42 @@ pub fn exec_sandboxed(args: &[String]) -> ! {
         }
     };
 
-    // Apply the sandbox (irreversible)
-    if let Err(e) = apply(&policy) {
+    if let Some(fd) = policy.report_fd {
+        diagnostics::report_to(fd);
+    }
+
+    // Network first: entering a namespace needs /proc/self, which the
+    // filesystem sandbox may hide. Both are irreversible.
+    // Seccomp last — it forks the supervisor, which needs no privileges.
+    // Before that, hide the report pipe from /proc: the supervisor and the
+    // timeout waiter keep it open while the command runs.
+    if let Err(e) = apply_network(&policy.network)
+        .and_then(|()| apply(&policy))
+        .and_then(|()| {
+            diagnostics::hide_report_fd();
+            apply_seccomp(policy.seccomp)
+        })
+    {
         eprintln!("[ua:sandbox] error: {e}");
         std::process::exit(126);
     }
 
-    eprintln!("[ua:sandbox] active");
+    diagnostics::report("[ua:sandbox] active");
 
     if args.is_empty() {
         eprintln!("[ua:sandbox] error: no command specified");
         std::process::exit(126);
     }
 
+    // With a timeout, stay behind to enforce it; otherwise exec directly.
+    if policy.limits.timeout_secs > 0 {
+        std::process::exit(limits::run_with_timeout(args, &policy.limits));
+    }
+    if let Err(e) = limits::set_rlimits(&policy.limits) {
+        eprintln!("[ua:sandbox] error: setrlimit: {e}");
+        std::process::exit(126);
+    }
+
     // Exec the requested command — replaces this process
     let err = Command::new(&args[0]).args(&args[1..]).exec();
 
@@ -0,0 +1,230 @@
+//! Resource limits and wall-clock timeout for `--sandbox-exec`.
+//!
+//! rlimits are set in the command's process just before exec. With a
+//! timeout, `--sandbox-exec` stays behind as a waiter: the command runs in
+//! its own process group, which gets SIGTERM when the timeout expires and
+//! SIGKILL if it is still around [`KILL_GRACE`] later.
+//!
+//! When the waiter owns the terminal, the command's group becomes the
+//! terminal's foreground group so it can read from it (a background group
+//! would stop on SIGTTIN), and the waiter takes the terminal back when the
+//! command exits. If the command is stopped (Ctrl-Z), the waiter stops too,
+//! so the shell that started it sees a stopped job, and resumes the
+//! command when it is continued. Time spent stopped doesn't count against
+//! the timeout.
+
+use std::io;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::diagnostics::{self, KILLED_PREFIX};
+use crate::policy::ResourceLimits;
+
+/// How long a timed-out command gets between SIGTERM and SIGKILL.
+pub const KILL_GRACE: Duration = Duration::from_secs(5);
+
+/// Exit code after a timeout, as with `timeout(1)`.
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Last termination signal the waiter received, to pass on to the command.
+static PENDING_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn on_signal(sig: libc::c_int) {
+    PENDING_SIGNAL.store(sig, Ordering::SeqCst);
+}
+
+/// Apply the rlimits in `limits` to the current process. Only lowers
+/// limits: a value above the current hard limit is clamped to it.
+///
+/// Async-signal-safe, so it can run between fork and exec.
+pub fn set_rlimits(limits: &ResourceLimits) -> io::Result<()> {
+    const MIB: u64 = 1024 * 1024;
+    // RLIMIT_CPU gets one second of headroom on the hard limit so the
+    // command sees SIGXCPU before SIGKILL.
+    let table = [
+        (libc::RLIMIT_CPU, limits.cpu_secs, 1),
+        (libc::RLIMIT_AS, limits.memory_mb.saturating_mul(MIB), 0),
+        (libc::RLIMIT_NPROC, limits.max_processes, 0),
+        (
+            libc::RLIMIT_FSIZE,
+            limits.max_file_size_mb.saturating_mul(MIB),
+            0,
+        ),
+    ];
+    for (resource, value, headroom) in table {
+        if value == 0 {
+            continue;
+        }
+        let mut current = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        if unsafe { libc::getrlimit(resource, &mut current) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let max = current.rlim_max;
+        let new = libc::rlimit {
+            rlim_cur: value.min(max),
+            rlim_max: value.saturating_add(headroom).min(max),
+        };
+        if unsafe { libc::setrlimit(resource, &new) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Run `args` under `limits` and wait for it, enforcing
+/// `limits.timeout_secs`. Returns the exit code `--sandbox-exec` should
+/// exit with: the command's own, 128 + signal if it was killed, or
+/// [`TIMEOUT_EXIT_CODE`] after a timeout. A timeout is also reported as
+/// `[ua:sandbox] killed: timeout after <n>s`; the exit code alone is one
+/// the command could pick itself.
+///
+/// SIGINT, SIGTERM and SIGHUP sent to the waiter are passed on to the
+/// command's process group.
+pub fn run_with_timeout(args: &[String], limits: &ResourceLimits) -> i32 {
+    for sig in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+        unsafe { libc::signal(sig, on_signal as *const () as libc::sighandler_t) };
+    }
+
+    let tty = owns_terminal();
+    let rlimits = limits.clone();
+    let mut command = Command::new(&args[0]);
+    command.args(&args[1..]).process_group(0);
+    unsafe {
+        command.pre_exec(move || {
+            // Both sides hand over the terminal, so the command can't read
+            // from it before the waiter gets to it.
+            if tty {
+                set_foreground(libc::getpid());
+            }
+            set_rlimits(&rlimits)
+        })
+    };
+
+    let child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("[ua:sandbox] exec failed: {e}");
+            return 126;
+        }
+    };
+    let pgid = child.id() as libc::pid_t;
+    if tty {
+        set_foreground(pgid);
+    }
+
+    let mut deadline = Instant::now() + Duration::from_secs(limits.timeout_secs);
+    let mut timed_out = false;
+    let status = loop {
+        let mut raw = 0;
+        match unsafe { libc::waitpid(pgid, &mut raw, libc::WNOHANG | libc::WUNTRACED) } {
+            0 => {}
+            -1 => {
+                let e = io::Error::last_os_error();
+                if e.kind() != io::ErrorKind::Interrupted {
+                    eprintln!("[ua:sandbox] error: wait failed: {e}");
+                    kill_group(pgid, libc::SIGKILL);
+                    restore_terminal(pgid, tty);
+                    return 126;
+                }
+            }
+            _ if libc::WIFSTOPPED(raw) => {
+                let stopped = Instant::now();
+                stop_with_command(pgid, tty);
+                deadline += stopped.elapsed();
+            }
+            _ => break ExitStatus::from_raw(raw),
+        }
+
+        let sig = PENDING_SIGNAL.swap(0, Ordering::SeqCst);
+        if sig != 0 {
+            kill_group(pgid, sig);
+        }
+
+        if Instant::now() >= deadline {
+            // SIGTERM first, then SIGKILL every grace period until it's gone.
+            kill_group(
+                pgid,
+                if timed_out {
+                    libc::SIGKILL
+                } else {
+                    libc::SIGTERM
+                },
+            );
+            // A stopped command only acts on SIGTERM once continued.
+            kill_group(pgid, libc::SIGCONT);
+            timed_out = true;
+            deadline = Instant::now() + KILL_GRACE;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+    restore_terminal(pgid, tty);
+
+    if timed_out {
+        // The leader may have exited on SIGTERM while others in the group
+        // ignored it.
+        kill_group(pgid, libc::SIGKILL);
+        diagnostics::report(&format!(
+            "{KILLED_PREFIX}timeout after {}s",
+            limits.timeout_secs
+        ));
+        return TIMEOUT_EXIT_CODE;
+    }
+    status
+        .code()
+        .unwrap_or_else(|| 128 + status.signal().unwrap_or(0))
+}
+
+fn kill_group(pgid: libc::pid_t, sig: libc::c_int) {
+    unsafe { libc::killpg(pgid, sig) };
+}
+
+/// Whether stdin is a terminal whose foreground group is ours.
+fn owns_terminal() -> bool {
+    unsafe {
+        libc::isatty(libc::STDIN_FILENO) == 1
+            && libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp()
+    }
+}
+
+/// Make `pgid` the terminal's foreground group. SIGTTOU is ignored for the
+/// call, as it would stop a caller that is already in the background.
+///
+/// Async-signal-safe, so it can run between fork and exec.
+fn set_foreground(pgid: libc::pid_t) {
+    unsafe {
+        let previous = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+        libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+        libc::signal(libc::SIGTTOU, previous);
+    }
+}
+
+/// Take the terminal back from the command's group, unless it has
+/// already moved on (the job was put in the background).
+fn restore_terminal(pgid: libc::pid_t, tty: bool) {
+    if tty && unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) } == pgid {
+        set_foreground(unsafe { libc::getpgrp() });
+    }
+}
+
+/// The command was stopped: stop the waiter as well, and when it is
+/// continued, continue the command. Without a terminal nobody is there to
+/// resume the waiter, so the command is left for whoever stopped it.
+fn stop_with_command(pgid: libc::pid_t, tty: bool) {
+    if !tty {
+        return;
+    }
+    restore_terminal(pgid, true);
+    unsafe { libc::raise(libc::SIGSTOP) };
+    // `fg` gives the waiter the terminal before continuing it; `bg` doesn't.
+    if owns_terminal() {
+        set_foreground(pgid);
+    }
+    kill_group(pgid, libc::SIGCONT);
+}
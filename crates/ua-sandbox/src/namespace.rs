@@ -0,0 +1,337 @@
+//! Linux mount-namespace sandbox backend.
+//!
+//! Builds a fresh root in a private mount namespace: writable paths are
+//! bind-mounted read-write, readable paths read-only, and nothing else
+//! exists. Unlike Landlock, denied and write-protected paths are honored
+//! even beneath a writable path — denied directories are covered with an
+//! empty read-only tmpfs, denied files with `/dev/null`, and write-protected
+//! paths are re-bound read-only.
+//!
+//! Runs unprivileged: when `CLONE_NEWNS` alone is not permitted, a user
+//! namespace mapping only our own uid/gid is created alongside it.
+
+use std::ffi::CString;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::diagnostics;
+use crate::policy::SandboxPolicy;
+use crate::SandboxError;
+
+/// Where the host root is reachable while the new root is assembled.
+const OLD_ROOT: &str = "/oldroot";
+/// Where the new root is assembled.
+const NEW_ROOT: &str = "/newroot";
+
+/// `unshare(flags)`, adding a user namespace that maps only our own uid/gid
+/// if `flags` alone needs privileges we don't have. Must run before any
+/// threads are spawned.
+pub(crate) fn unshare(flags: libc::c_int) -> io::Result<()> {
+    if unsafe { libc::unshare(flags) } == 0 {
+        return Ok(());
+    }
+    let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+    if unsafe { libc::unshare(libc::CLONE_NEWUSER | flags) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let write = |file: &str, contents: String| {
+        std::fs::write(file, contents).map_err(|e| io::Error::new(e.kind(), format!("{file}: {e}")))
+    };
+    write("/proc/self/setgroups", "deny".to_string())?;
+    write("/proc/self/uid_map", format!("{uid} {uid} 1"))?;
+    write("/proc/self/gid_map", format!("{gid} {gid} 1"))
+}
+
+/// How a policy path appears in the new root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Read,
+    Write,
+}
+
+/// Apply the mount-namespace sandbox to the current process. Irreversible.
+pub fn apply_namespace(policy: &SandboxPolicy) -> Result<(), SandboxError> {
+    let cwd = std::env::current_dir().ok();
+    let err = |what: &str, e: io::Error| {
+        SandboxError::Platform(format!("mount namespace sandbox: {what}: {e}"))
+    };
+
+    if policy.debug {
+        diagnostics::report(
+            "[ua:sandbox] warning: denied accesses are only logged with the Landlock backend",
+        );
+    }
+
+    unshare(libc::CLONE_NEWNS).map_err(|e| err("unshare", e))?;
+    // Keep our mounts from propagating back to the host.
+    mount(None, "/", None, libc::MS_REC | libc::MS_PRIVATE, None)
+        .map_err(|e| err("make / private", e))?;
+
+    // Step onto an empty tmpfs, with the host root reachable at OLD_ROOT.
+    mount(
+        Some("tmpfs"),
+        "/tmp",
+        Some("tmpfs"),
+        libc::MS_NODEV | libc::MS_NOSUID,
+        Some("mode=0755"),
+    )
+    .map_err(|e| err("tmpfs", e))?;
+    for dir in ["/tmp/oldroot", "/tmp/newroot"] {
+        std::fs::create_dir(dir).map_err(|e| err(dir, e))?;
+    }
+    pivot_root("/tmp", "/tmp/oldroot").map_err(|e| err("pivot_root", e))?;
+    std::env::set_current_dir("/").map_err(|e| err("chdir", e))?;
+    mount(
+        Some("tmpfs"),
+        NEW_ROOT,
+        Some("tmpfs"),
+        libc::MS_NODEV | libc::MS_NOSUID,
+        Some("mode=0755"),
+    )
+    .map_err(|e| err("tmpfs", e))?;
+
+    for (path, access) in ordered_paths(policy) {
+        bind(&path, access).map_err(|e| err(&path.display().to_string(), e))?;
+    }
+    for path in &policy.deny_write {
+        protect(path).map_err(|e| err(&path.display().to_string(), e))?;
+    }
+    for path in &policy.denied {
+        hide(path).map_err(|e| err(&path.display().to_string(), e))?;
+    }
+
+    // Seal the new root and switch to it, dropping the host root.
+    remount_read_only(Path::new(NEW_ROOT)).map_err(|e| err("seal root", e))?;
+    std::env::set_current_dir(NEW_ROOT).map_err(|e| err("chdir", e))?;
+    pivot_root(".", ".").map_err(|e| err("pivot_root", e))?;
+    umount_detach(".").map_err(|e| err("unmount host root", e))?;
+
+    let cwd = cwd
+        .filter(|c| c.is_dir())
+        .unwrap_or_else(|| PathBuf::from("/"));
+    if std::env::set_current_dir(&cwd).is_err() {
+        std::env::set_current_dir("/").map_err(|e| err("chdir", e))?;
+    }
+    Ok(())
+}
+
+/// Policy paths in mount order: parents before children, so a writable
+/// directory inside a readable one (or the reverse) ends up on top.
+fn ordered_paths(policy: &SandboxPolicy) -> Vec<(PathBuf, Access)> {
+    let mut paths: Vec<(PathBuf, Access)> = policy
+        .readable
+        .iter()
+        .map(|p| (p.clone(), Access::Read))
+        .chain(policy.writable.iter().map(|p| (p.clone(), Access::Write)))
+        .filter(|(p, _)| p.is_absolute())
+        .collect();
+    // Stable: for the same path, writable (listed later) wins.
+    paths.sort_by_key(|(p, _)| p.components().count());
+    paths
+}
+
+/// `path` under `root`, e.g. `/oldroot/usr`.
+fn under(root: &str, path: &Path) -> PathBuf {
+    let mut out = PathBuf::from(root);
+    out.extend(
+        path.components()
+            .filter(|c| !matches!(c, Component::RootDir)),
+    );
+    out
+}
+
+/// Make `path` from the host visible in the new root.
+fn bind(path: &Path, access: Access) -> io::Result<()> {
+    let src = under(OLD_ROOT, path);
+    let dst = under(NEW_ROOT, path);
+    let meta = match std::fs::symlink_metadata(&src) {
+        Ok(m) => m,
+        // Missing paths can't be accessed anyway
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    // Recreate symlinks (e.g. /lib -> usr/lib) rather than binding their
+    // targets; the targets are listed in canonical form as well.
+    if meta.file_type().is_symlink() {
+        if std::fs::symlink_metadata(&dst).is_err() {
+            if let Some(parent) = dst.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::os::unix::fs::symlink(std::fs::read_link(&src)?, &dst)?;
+        }
+        return Ok(());
+    }
+
+    mount_point(&dst, meta.is_dir())?;
+    mount(
+        Some(&src.to_string_lossy()),
+        &dst.to_string_lossy(),
+        None,
+        libc::MS_BIND | libc::MS_REC,
+        None,
+    )?;
+    if access == Access::Read {
+        remount_read_only(&dst)?;
+    }
+    Ok(())
+}
+
+/// Re-bind a path already in the new root read-only.
+fn protect(path: &Path) -> io::Result<()> {
+    let dst = under(NEW_ROOT, path);
+    if std::fs::symlink_metadata(&dst).is_err() {
+        return Ok(());
+    }
+    let dst = dst.to_string_lossy();
+    mount(Some(&dst), &dst, None, libc::MS_BIND, None)?;
+    remount_read_only(Path::new(&*dst))
+}
+
+/// Cover a path in the new root: directories with an empty read-only
+/// tmpfs, anything else with `/dev/null`.
+fn hide(path: &Path) -> io::Result<()> {
+    let dst = under(NEW_ROOT, path);
+    let meta = match std::fs::symlink_metadata(&dst) {
+        Ok(m) => m,
+        Err(_) => return Ok(()),
+    };
+    let target = dst.to_string_lossy();
+    if meta.is_dir() {
+        mount(
+            Some("tmpfs"),
+            &target,
+            Some("tmpfs"),
+            libc::MS_RDONLY | libc::MS_NODEV | libc::MS_NOSUID | libc::MS_NOEXEC,
+            Some("mode=0000,size=0"),
+        )
+    } else {
+        let null = under(OLD_ROOT, Path::new("/dev/null"));
+        mount(
+            Some(&null.to_string_lossy()),
+            &target,
+            None,
+            libc::MS_BIND,
+            None,
+        )
+    }
+}
+
+/// Create an empty directory or file to mount over, unless one exists.
+fn mount_point(dst: &Path, is_dir: bool) -> io::Result<()> {
+    if std::fs::symlink_metadata(dst).is_ok() {
+        return Ok(());
+    }
+    if is_dir {
+        std::fs::create_dir_all(dst)
+    } else {
+        if let Some(parent) = dst.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::File::create(dst).map(|_| ())
+    }
+}
+
+/// Remount a bind mount read-only. Flags the kernel locked on the original
+/// mount (nosuid, nodev, noexec, atime) must be carried over, or an
+/// unprivileged remount fails with EPERM.
+fn remount_read_only(path: &Path) -> io::Result<()> {
+    let c_path = cstring(&path.to_string_lossy())?;
+    let mut st: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut st) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut flags = libc::MS_REMOUNT | libc::MS_BIND | libc::MS_RDONLY;
+    for (st_flag, ms_flag) in [
+        (libc::ST_NOSUID, libc::MS_NOSUID),
+        (libc::ST_NODEV, libc::MS_NODEV),
+        (libc::ST_NOEXEC, libc::MS_NOEXEC),
+        (libc::ST_NOATIME, libc::MS_NOATIME),
+        (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        (libc::ST_RELATIME, libc::MS_RELATIME),
+    ] {
+        if st.f_flag & st_flag != 0 {
+            flags |= ms_flag;
+        }
+    }
+    mount(None, &path.to_string_lossy(), None, flags, None)
+}
+
+fn cstring(s: &str) -> io::Result<CString> {
+    CString::new(s).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+fn mount(
+    source: Option<&str>,
+    target: &str,
+    fstype: Option<&str>,
+    flags: libc::c_ulong,
+    data: Option<&str>,
+) -> io::Result<()> {
+    let source = source.map(cstring).transpose()?;
+    let target = cstring(target)?;
+    let fstype = fstype.map(cstring).transpose()?;
+    let data = data.map(cstring).transpose()?;
+    let ptr = |c: &Option<CString>| c.as_ref().map_or(std::ptr::null(), |c| c.as_ptr());
+    let rc = unsafe {
+        libc::mount(
+            ptr(&source),
+            target.as_ptr(),
+            ptr(&fstype),
+            flags,
+            ptr(&data) as *const libc::c_void,
+        )
+    };
+    if rc != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn pivot_root(new_root: &str, put_old: &str) -> io::Result<()> {
+    let new_root = cstring(new_root)?;
+    let put_old = cstring(put_old)?;
+    if unsafe { libc::syscall(libc::SYS_pivot_root, new_root.as_ptr(), put_old.as_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn umount_detach(target: &str) -> io::Result<()> {
+    let target = cstring(target)?;
+    if unsafe { libc::umount2(target.as_ptr(), libc::MNT_DETACH) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn under_joins_absolute_paths() {
+        assert_eq!(
+            under(OLD_ROOT, Path::new("/usr/lib")),
+            PathBuf::from("/oldroot/usr/lib")
+        );
+        assert_eq!(under(NEW_ROOT, Path::new("/")), PathBuf::from("/newroot"));
+    }
+
+    #[test]
+    fn parents_are_mounted_before_children() {
+        let policy = SandboxPolicy::from_config(
+            &["/home/user/project".to_string(), "/tmp".to_string()],
+            &["/home/user".to_string(), "/usr".to_string()],
+            &[],
+        );
+        let order: Vec<_> = ordered_paths(&policy)
+            .into_iter()
+            .map(|(p, a)| (p.display().to_string(), a))
+            .collect();
+        let pos = |p: &str| order.iter().position(|(q, _)| q == p).unwrap();
+        assert!(pos("/home/user") < pos("/home/user/project"));
+        assert_eq!(order[pos("/home/user/project")].1, Access::Write);
+        assert_eq!(order[pos("/usr")].1, Access::Read);
+    }
+}
@@ -0,0 +1,146 @@
+//! Linux network egress restrictions.
+//!
+//! Applied by `--sandbox-exec` on top of the filesystem sandbox:
+//! - `deny`: a seccomp filter refuses `AF_INET`/`AF_INET6` sockets, and a
+//!   Landlock ruleset (ABI v4+) denies TCP bind/connect as a second layer.
+//! - `loopback`: the command gets a fresh network namespace with only `lo`
+//!   up. Falls back to `deny` when namespaces are unavailable.
+//! - `ports`: a Landlock ruleset allows TCP connect to the listed ports
+//!   only. Fails closed if the kernel lacks Landlock network support.
+
+use std::collections::BTreeMap;
+use std::io;
+
+use crate::diagnostics;
+use crate::policy::{NetworkMode, NetworkPolicy};
+use crate::SandboxError;
+
+use landlock::{
+    Access, AccessNet, BitFlags, CompatLevel, Compatible, NetPort, Ruleset, RulesetAttr,
+    RulesetCreatedAttr, RulesetStatus, ABI,
+};
+use seccompiler::{
+    BpfProgram, SeccompAction, SeccompCmpArgLen, SeccompCmpOp, SeccompCondition, SeccompFilter,
+    SeccompRule, TargetArch,
+};
+
+/// Apply the network section of the policy to the current process. Irreversible.
+pub fn apply_network(policy: &NetworkPolicy) -> Result<(), SandboxError> {
+    match policy.mode {
+        NetworkMode::Allow => Ok(()),
+        NetworkMode::Deny => deny_all(),
+        NetworkMode::Loopback => match enter_network_namespace() {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                diagnostics::report(&format!(
+                    "[ua:sandbox] warning: {e}; denying all network access"
+                ));
+                deny_all()
+            }
+        },
+        NetworkMode::Ports => restrict_tcp(AccessNet::ConnectTcp.into(), &policy.allow_ports),
+    }
+}
+
+fn deny_all() -> Result<(), SandboxError> {
+    deny_inet_sockets()?;
+    // Best effort: seccomp already covers kernels without Landlock networking.
+    if let Err(e) = restrict_tcp(AccessNet::from_all(ABI::V5), &[]) {
+        diagnostics::report(&format!("[ua:sandbox] warning: {e}"));
+    }
+    Ok(())
+}
+
+/// Landlock ruleset handling `access`, with connect allowed to `ports`.
+fn restrict_tcp(access: BitFlags<AccessNet>, ports: &[u16]) -> Result<(), SandboxError> {
+    let err = |e: &dyn std::fmt::Display| {
+        SandboxError::Platform(format!("Landlock network ruleset failed: {e}"))
+    };
+
+    let mut ruleset = Ruleset::default()
+        .set_compatibility(CompatLevel::HardRequirement)
+        .handle_access(access)
+        .map_err(|e| err(&e))?
+        .create()
+        .map_err(|e| err(&e))?;
+    for &port in ports {
+        ruleset = ruleset
+            .add_rule(NetPort::new(port, AccessNet::ConnectTcp))
+            .map_err(|e| err(&e))?;
+    }
+
+    let status = ruleset.restrict_self().map_err(|e| err(&e))?;
+    match status.ruleset {
+        RulesetStatus::FullyEnforced => Ok(()),
+        _ => Err(SandboxError::Platform(
+            "Landlock network rules not enforced (kernel 6.7+ required)".to_string(),
+        )),
+    }
+}
+
+/// Seccomp filter: `socket()` with an IPv4 or IPv6 family fails with EACCES.
+fn deny_inet_sockets() -> Result<(), SandboxError> {
+    let err = |e: &dyn std::fmt::Display| {
+        SandboxError::Platform(format!("seccomp network filter failed: {e}"))
+    };
+    let arch = TargetArch::try_from(std::env::consts::ARCH).map_err(|e| err(&e))?;
+
+    let family = |af: i32| {
+        SeccompCondition::new(0, SeccompCmpArgLen::Dword, SeccompCmpOp::Eq, af as u64)
+            .and_then(|c| SeccompRule::new(vec![c]))
+    };
+    let rules = vec![
+        family(libc::AF_INET).map_err(|e| err(&e))?,
+        family(libc::AF_INET6).map_err(|e| err(&e))?,
+    ];
+
+    let filter = SeccompFilter::new(
+        BTreeMap::from([(libc::SYS_socket, rules)]),
+        SeccompAction::Allow,
+        SeccompAction::Errno(libc::EACCES as u32),
+        arch,
+    )
+    .map_err(|e| err(&e))?;
+    let program: BpfProgram = filter.try_into().map_err(|e| err(&e))?;
+    seccompiler::apply_filter(&program).map_err(|e| err(&e))
+}
+
+/// Move into a new network namespace and bring its loopback interface up.
+///
+/// Tries a plain network namespace first (needs CAP_SYS_ADMIN), then a user
+/// namespace mapping only our own uid/gid.
+fn enter_network_namespace() -> Result<(), SandboxError> {
+    let err = |what: &str, e: io::Error| {
+        SandboxError::Platform(format!("cannot create network namespace: {what}: {e}"))
+    };
+
+    crate::namespace::unshare(libc::CLONE_NEWNET).map_err(|e| err("unshare", e))?;
+    loopback_up().map_err(|e| err("loopback", e))
+}
+
+fn loopback_up() -> io::Result<()> {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut req: libc::ifreq = unsafe { std::mem::zeroed() };
+    for (dst, &src) in req.ifr_name.iter_mut().zip(b"lo\0") {
+        *dst = src as libc::c_char;
+    }
+
+    let result = unsafe {
+        if libc::ioctl(fd, libc::SIOCGIFFLAGS, &mut req) < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            req.ifr_ifru.ifru_flags |= (libc::IFF_UP | libc::IFF_RUNNING) as libc::c_short;
+            if libc::ioctl(fd, libc::SIOCSIFFLAGS, &req) < 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        }
+    };
+    unsafe { libc::close(fd) };
+    result
+}
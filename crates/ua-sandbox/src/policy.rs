@@ -11,12 +11,150 @@ pub const SANDBOX_ENV_VAR: &str = "__UA_SANDBOX_POLICY";
 /// Default-deny: any path not listed in `writable` or `readable` is inaccessible.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct SandboxPolicy {
+    /// Which Linux mechanism enforces the filesystem rules.
+    #[serde(default)]
+    pub backend: SandboxBackend,
     /// Paths the child may read and write.
     pub writable: Vec<PathBuf>,
     /// Paths the child may read (but not write).
     pub readable: Vec<PathBuf>,
     /// Paths explicitly denied (overrides readable/writable on platforms that support it).
     pub denied: Vec<PathBuf>,
+    /// Paths the child may never write, even beneath a writable path (on
+    /// platforms that support it). Used to protect the agent's own config.
+    #[serde(default)]
+    pub deny_write: Vec<PathBuf>,
+    /// Network egress rules. Applied by `--sandbox-exec` only — the agent
+    /// process itself still needs to reach the LLM API.
+    #[serde(default)]
+    pub network: NetworkPolicy,
+    /// Syscall filter applied by `--sandbox-exec` (Linux).
+    #[serde(default)]
+    pub seccomp: SeccompProfile,
+    /// Resource limits and wall-clock timeout applied by `--sandbox-exec`.
+    #[serde(default)]
+    pub limits: ResourceLimits,
+    /// Log denied accesses to the kernel audit log (Landlock ABI v7+).
+    #[serde(default)]
+    pub debug: bool,
+    /// Inherited pipe `--sandbox-exec` sends its status reports to instead
+    /// of stderr. See [`crate::diagnostics`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report_fd: Option<i32>,
+}
+
+/// Per-command resource limits. Zero means unlimited.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResourceLimits {
+    /// CPU time in seconds (`RLIMIT_CPU`).
+    pub cpu_secs: u64,
+    /// Address space in MiB (`RLIMIT_AS`).
+    pub memory_mb: u64,
+    /// Processes for the user, counted system-wide by the kernel
+    /// (`RLIMIT_NPROC`).
+    pub max_processes: u64,
+    /// Largest file the command may write, in MiB (`RLIMIT_FSIZE`).
+    pub max_file_size_mb: u64,
+    /// Wall-clock seconds before the command's process group gets SIGTERM,
+    /// then SIGKILL.
+    pub timeout_secs: u64,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            cpu_secs: 0,
+            memory_mb: 0,
+            max_processes: 0,
+            max_file_size_mb: 0,
+            timeout_secs: 300,
+        }
+    }
+}
+
+/// Filesystem sandbox implementation on Linux. Ignored on macOS.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SandboxBackend {
+    /// Landlock rulesets. Cannot deny a path beneath a writable one.
+    #[default]
+    Landlock,
+    /// A private mount namespace holding only the allowed paths. Honors
+    /// `denied` and `deny_write` everywhere; needs unprivileged user
+    /// namespaces.
+    Namespace,
+}
+
+/// Which syscalls the seccomp filter blocks.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SeccompProfile {
+    /// No syscall filter.
+    Off,
+    /// Block ptrace, mount, keyctl, bpf, kexec, new user namespaces, and
+    /// unusual personalities.
+    #[default]
+    Default,
+    /// Also block module loading, setns, chroot, io_uring, perf, and any
+    /// new namespace.
+    Strict,
+}
+
+impl SeccompProfile {
+    /// The config name, e.g. `"strict"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SeccompProfile::Off => "off",
+            SeccompProfile::Default => "default",
+            SeccompProfile::Strict => "strict",
+        }
+    }
+}
+
+/// How much network access a sandboxed command gets.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkMode {
+    /// No network restrictions.
+    #[default]
+    Allow,
+    /// No IPv4/IPv6 sockets at all.
+    Deny,
+    /// A private network namespace with only its own loopback interface.
+    /// Services listening on the host's loopback are not reachable.
+    Loopback,
+    /// Outbound TCP only to `allow_ports`. UDP is left open so name
+    /// resolution keeps working.
+    Ports,
+}
+
+/// Network section of the sandbox policy.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkPolicy {
+    pub mode: NetworkMode,
+    /// TCP ports the child may connect to in `ports` mode.
+    pub allow_ports: Vec<u16>,
+}
+
+impl NetworkMode {
+    /// The config name, e.g. `"loopback"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NetworkMode::Allow => "allow",
+            NetworkMode::Deny => "deny",
+            NetworkMode::Loopback => "loopback",
+            NetworkMode::Ports => "ports",
+        }
+    }
+}
+
+impl NetworkPolicy {
+    /// Whether this policy restricts anything.
+    pub fn is_restricted(&self) -> bool {
+        self.mode != NetworkMode::Allow
+    }
 }
 
 impl Default for SandboxPolicy {
@@ -67,12 +205,70 @@ impl SandboxPolicy {
     /// included in writable/readable lists to handle either form.
     pub fn from_config(writable: &[String], readable: &[String], denied: &[String]) -> Self {
         Self {
+            backend: SandboxBackend::default(),
             writable: resolve_and_canonicalize(writable),
             readable: resolve_and_canonicalize(readable),
             denied: denied.iter().map(|s| resolve_path(s)).collect(),
+            deny_write: Vec::new(),
+            network: NetworkPolicy::default(),
+            seccomp: SeccompProfile::default(),
+            limits: ResourceLimits::default(),
+            debug: false,
+            report_fd: None,
         }
     }
 
+    /// Add write-protected paths, resolving `$CWD` and `$HOME` placeholders.
+    pub fn with_deny_write(mut self, paths: &[String]) -> Self {
+        self.deny_write.extend(resolve_and_canonicalize(paths));
+        self
+    }
+
+    /// Set the filesystem backend.
+    pub fn with_backend(mut self, backend: SandboxBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Set the network section.
+    pub fn with_network(mut self, network: NetworkPolicy) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Set the resource limits.
+    pub fn with_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Enable audit logging of denied accesses.
+    pub fn with_debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    /// Send status reports to the inherited pipe `fd`.
+    pub fn with_report_fd(mut self, fd: Option<i32>) -> Self {
+        self.report_fd = fd;
+        self
+    }
+
+    /// Set the seccomp profile.
+    pub fn with_seccomp(mut self, seccomp: SeccompProfile) -> Self {
+        self.seccomp = seccomp;
+        self
+    }
+
+    /// Write-protected paths that fall under a writable path. Landlock can
+    /// only grant access, so these cannot be protected there.
+    pub fn unenforceable_deny_write(&self) -> Vec<&PathBuf> {
+        self.deny_write
+            .iter()
+            .filter(|p| self.writable.iter().any(|w| p.starts_with(w)))
+            .collect()
+    }
+
     /// Serialize to JSON for transport via environment variable.
     pub fn to_json(&self) -> String {
         serde_json::to_string(self).expect("SandboxPolicy serialization cannot fail")
@@ -111,7 +307,7 @@ fn resolve_and_canonicalize(paths: &[String]) -> Vec<PathBuf> {
 }
 
 /// Resolve path placeholders: `$CWD` → current_dir(), `$HOME` → $HOME env var.
-fn resolve_path(s: &str) -> PathBuf {
+pub(crate) fn resolve_path(s: &str) -> PathBuf {
     match s {
         "$CWD" => env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
         "$HOME" => env::var("HOME")
@@ -196,6 +392,85 @@ mod tests {
         assert!(policy.writable.contains(&home));
     }
 
+    #[test]
+    fn with_deny_write_resolves_home() {
+        let policy = SandboxPolicy::from_config(&[], &[], &[])
+            .with_deny_write(&["$HOME/.config/unixagent/config.toml".to_string()]);
+        let home = env::var("HOME").unwrap();
+        assert!(policy.deny_write.contains(&PathBuf::from(format!(
+            "{home}/.config/unixagent/config.toml"
+        ))));
+    }
+
+    #[test]
+    fn unenforceable_deny_write_under_writable() {
+        let policy =
+            SandboxPolicy::from_config(&["/work".to_string()], &[], &[]).with_deny_write(&[
+                "/work/.unixagent.toml".to_string(),
+                "/etc/unixagent.toml".to_string(),
+            ]);
+        assert_eq!(
+            policy.unenforceable_deny_write(),
+            vec![&PathBuf::from("/work/.unixagent.toml")]
+        );
+    }
+
+    #[test]
+    fn json_without_deny_write_still_parses() {
+        let json = r#"{"writable":[],"readable":[],"denied":[]}"#;
+        let policy = SandboxPolicy::from_json(json).unwrap();
+        assert!(policy.deny_write.is_empty());
+        assert_eq!(policy.backend, SandboxBackend::Landlock);
+        assert_eq!(policy.network, NetworkPolicy::default());
+        assert_eq!(policy.seccomp, SeccompProfile::Default);
+        assert_eq!(policy.limits.timeout_secs, 300);
+    }
+
+    #[test]
+    fn seccomp_profile_json() {
+        let policy = SandboxPolicy::default().with_seccomp(SeccompProfile::Strict);
+        let json = policy.to_json();
+        assert!(json.contains(r#""seccomp":"strict""#), "{json}");
+        assert!(json.contains(SeccompProfile::Strict.as_str()));
+        assert_eq!(SandboxPolicy::from_json(&json).unwrap(), policy);
+    }
+
+    #[test]
+    fn backend_json() {
+        let policy = SandboxPolicy::default().with_backend(SandboxBackend::Namespace);
+        let json = policy.to_json();
+        assert!(json.contains(r#""backend":"namespace""#), "{json}");
+        assert_eq!(SandboxPolicy::from_json(&json).unwrap(), policy);
+    }
+
+    #[test]
+    fn limits_json_fills_defaults() {
+        let json = r#"{"writable":[],"readable":[],"denied":[],"limits":{"cpu_secs":10}}"#;
+        let policy = SandboxPolicy::from_json(json).unwrap();
+        assert_eq!(policy.limits.cpu_secs, 10);
+        assert_eq!(policy.limits.memory_mb, 0);
+        assert_eq!(policy.limits.timeout_secs, 300);
+    }
+
+    #[test]
+    fn network_defaults_to_allow() {
+        let policy = SandboxPolicy::default();
+        assert_eq!(policy.network.mode, NetworkMode::Allow);
+        assert!(!policy.network.is_restricted());
+    }
+
+    #[test]
+    fn network_json_round_trip() {
+        let policy = SandboxPolicy::default().with_network(NetworkPolicy {
+            mode: NetworkMode::Ports,
+            allow_ports: vec![443, 8080],
+        });
+        let json = policy.to_json();
+        assert!(json.contains(r#""mode":"ports""#), "{json}");
+        assert!(json.contains(NetworkMode::Ports.as_str()));
+        assert_eq!(SandboxPolicy::from_json(&json).unwrap(), policy);
+    }
+
     #[test]
     fn from_env_returns_none_when_unset() {
         // Ensure the env var is not set
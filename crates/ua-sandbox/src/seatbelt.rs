@@ -62,6 +62,7 @@ pub fn apply_seatbelt(policy: &SandboxPolicy) -> Result<(), SandboxError> {
 /// 3. Allows all file operations, then denies file-write* globally
 /// 4. Selectively re-allows file-write* for policy writable paths
 /// 5. Explicitly denies file-read* and file-write* for sensitive paths
+/// 6. Denies file-write* for write-protected paths (e.g. the agent's config)
 ///
 /// This gives: read everywhere (except denied), write only to whitelisted paths.
 pub fn generate_sbpl(policy: &SandboxPolicy) -> String {
@@ -102,6 +103,12 @@ pub fn generate_sbpl(policy: &SandboxPolicy) -> String {
         sbpl.push_str(&format!("(deny file-write* (subpath \"{p}\"))\n"));
     }
 
+    // --- Write-protected paths: readable, never writable ---
+    for path in &policy.deny_write {
+        let p = path.display();
+        sbpl.push_str(&format!("(deny file-write* (subpath \"{p}\"))\n"));
+    }
+
     sbpl
 }
 
@@ -116,6 +123,13 @@ mod tests {
             writable: vec![],
             readable: vec![],
             denied: vec![],
+            deny_write: vec![],
+            network: Default::default(),
+            seccomp: Default::default(),
+            backend: Default::default(),
+            limits: Default::default(),
+            debug: false,
+            report_fd: None,
         };
         let sbpl = generate_sbpl(&policy);
         assert!(sbpl.contains("(version 1)"));
@@ -128,6 +142,13 @@ mod tests {
             writable: vec![],
             readable: vec![],
             denied: vec![],
+            deny_write: vec![],
+            network: Default::default(),
+            seccomp: Default::default(),
+            backend: Default::default(),
+            limits: Default::default(),
+            debug: false,
+            report_fd: None,
         };
         let sbpl = generate_sbpl(&policy);
         assert!(sbpl.contains("(allow process*)"));
@@ -144,6 +165,13 @@ mod tests {
             writable: vec![],
             readable: vec![],
             denied: vec![],
+            deny_write: vec![],
+            network: Default::default(),
+            seccomp: Default::default(),
+            backend: Default::default(),
+            limits: Default::default(),
+            debug: false,
+            report_fd: None,
         };
         let sbpl = generate_sbpl(&policy);
         assert!(sbpl.contains("(allow file*)"));
@@ -156,6 +184,13 @@ mod tests {
             writable: vec![PathBuf::from("/tmp"), PathBuf::from("/home/user/project")],
             readable: vec![],
             denied: vec![],
+            deny_write: vec![],
+            network: Default::default(),
+            seccomp: Default::default(),
+            backend: Default::default(),
+            limits: Default::default(),
+            debug: false,
+            report_fd: None,
         };
         let sbpl = generate_sbpl(&policy);
         assert!(sbpl.contains("(allow file-write* (subpath \"/tmp\"))"));
@@ -168,18 +203,59 @@ mod tests {
             writable: vec![],
             readable: vec![],
             denied: vec![PathBuf::from("/home/user/.ssh")],
+            deny_write: vec![],
+            network: Default::default(),
+            seccomp: Default::default(),
+            backend: Default::default(),
+            limits: Default::default(),
+            debug: false,
+            report_fd: None,
         };
         let sbpl = generate_sbpl(&policy);
         assert!(sbpl.contains("(deny file-read* (subpath \"/home/user/.ssh\"))"));
         assert!(sbpl.contains("(deny file-write* (subpath \"/home/user/.ssh\"))"));
     }
 
+    #[test]
+    fn sbpl_denies_writes_to_protected_paths() {
+        let policy = SandboxPolicy {
+            writable: vec![PathBuf::from("/home/user")],
+            readable: vec![],
+            denied: vec![],
+            deny_write: vec![PathBuf::from("/home/user/.config/unixagent/config.toml")],
+            network: Default::default(),
+            seccomp: Default::default(),
+            backend: Default::default(),
+            limits: Default::default(),
+            debug: false,
+            report_fd: None,
+        };
+        let sbpl = generate_sbpl(&policy);
+        let allow = sbpl
+            .find("(allow file-write* (subpath \"/home/user\"))")
+            .unwrap();
+        let deny = sbpl
+            .find("(deny file-write* (subpath \"/home/user/.config/unixagent/config.toml\"))")
+            .unwrap();
+        assert!(
+            deny > allow,
+            "deny must come after allow to take precedence"
+        );
+    }
+
     #[test]
     fn sbpl_allows_dev_writes() {
         let policy = SandboxPolicy {
             writable: vec![],
             readable: vec![],
             denied: vec![],
+            deny_write: vec![],
+            network: Default::default(),
+            seccomp: Default::default(),
+            backend: Default::default(),
+            limits: Default::default(),
+            debug: false,
+            report_fd: None,
         };
         let sbpl = generate_sbpl(&policy);
         assert!(sbpl.contains("(allow file-write* (subpath \"/dev\"))"));
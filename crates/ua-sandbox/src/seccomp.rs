@@ -0,0 +1,407 @@
+//! Linux seccomp-bpf syscall filter.
+//!
+//! Landlock only covers the filesystem and TCP ports, so a sandboxed
+//! process could still ptrace a sibling shell owned by the same user, mount
+//! over paths, or load BPF programs. This filter closes those off.
+//!
+//! Blocked syscalls fail with EPERM. A small supervisor process receives
+//! each one through a seccomp user notification and reports it as
+//! `[ua:sandbox] blocked by sandbox: <syscall>` (see [`crate::diagnostics`]). When a listener can't be
+//! installed (a sandbox nested in another one), the filter still applies
+//! but violations go unreported.
+
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+
+use crate::policy::SeccompProfile;
+use crate::{diagnostics, SandboxError, VIOLATION_PREFIX};
+
+use seccompiler::{
+    BpfProgram, SeccompAction, SeccompCmpArgLen, SeccompCmpOp, SeccompCondition, SeccompFilter,
+    SeccompRule, TargetArch,
+};
+
+/// `_IOWR('!', 0, struct seccomp_notif)`
+const SECCOMP_IOCTL_NOTIF_RECV: libc::c_ulong = 0xc050_2100;
+/// `_IOWR('!', 1, struct seccomp_notif_resp)`
+const SECCOMP_IOCTL_NOTIF_SEND: libc::c_ulong = 0xc018_2101;
+
+/// Syscalls blocked outright by the default profile.
+const DEFAULT_BLOCKED: &[(&str, libc::c_long)] = &[
+    ("ptrace", libc::SYS_ptrace),
+    ("process_vm_readv", libc::SYS_process_vm_readv),
+    ("process_vm_writev", libc::SYS_process_vm_writev),
+    ("mount", libc::SYS_mount),
+    ("umount2", libc::SYS_umount2),
+    ("pivot_root", libc::SYS_pivot_root),
+    ("fsopen", libc::SYS_fsopen),
+    ("fsconfig", libc::SYS_fsconfig),
+    ("fsmount", libc::SYS_fsmount),
+    ("move_mount", libc::SYS_move_mount),
+    ("open_tree", libc::SYS_open_tree),
+    ("mount_setattr", libc::SYS_mount_setattr),
+    ("keyctl", libc::SYS_keyctl),
+    ("add_key", libc::SYS_add_key),
+    ("request_key", libc::SYS_request_key),
+    ("bpf", libc::SYS_bpf),
+    ("kexec_load", libc::SYS_kexec_load),
+    #[cfg(not(target_arch = "riscv64"))]
+    ("kexec_file_load", libc::SYS_kexec_file_load),
+];
+
+/// Additional syscalls blocked by the strict profile.
+const STRICT_BLOCKED: &[(&str, libc::c_long)] = &[
+    ("init_module", libc::SYS_init_module),
+    ("finit_module", libc::SYS_finit_module),
+    ("delete_module", libc::SYS_delete_module),
+    ("reboot", libc::SYS_reboot),
+    ("swapon", libc::SYS_swapon),
+    ("swapoff", libc::SYS_swapoff),
+    ("setns", libc::SYS_setns),
+    ("chroot", libc::SYS_chroot),
+    ("acct", libc::SYS_acct),
+    ("quotactl", libc::SYS_quotactl),
+    ("userfaultfd", libc::SYS_userfaultfd),
+    ("perf_event_open", libc::SYS_perf_event_open),
+    ("open_by_handle_at", libc::SYS_open_by_handle_at),
+    ("name_to_handle_at", libc::SYS_name_to_handle_at),
+    ("io_uring_setup", libc::SYS_io_uring_setup),
+    ("io_uring_enter", libc::SYS_io_uring_enter),
+    ("io_uring_register", libc::SYS_io_uring_register),
+    ("syslog", libc::SYS_syslog),
+    ("settimeofday", libc::SYS_settimeofday),
+    ("clock_settime", libc::SYS_clock_settime),
+    #[cfg(target_arch = "x86_64")]
+    ("iopl", libc::SYS_iopl),
+    #[cfg(target_arch = "x86_64")]
+    ("ioperm", libc::SYS_ioperm),
+];
+
+/// Namespace flags `unshare`/`clone` may not use. Default blocks new user
+/// namespaces (the usual first step of a privilege escalation); strict
+/// blocks every new namespace.
+fn namespace_flags(profile: SeccompProfile) -> u64 {
+    let flags = match profile {
+        SeccompProfile::Strict => {
+            libc::CLONE_NEWUSER
+                | libc::CLONE_NEWNS
+                | libc::CLONE_NEWNET
+                | libc::CLONE_NEWPID
+                | libc::CLONE_NEWIPC
+                | libc::CLONE_NEWUTS
+                | libc::CLONE_NEWCGROUP
+        }
+        _ => libc::CLONE_NEWUSER,
+    };
+    flags as u64
+}
+
+/// Personalities that may be set: `PER_LINUX`, `PER_LINUX32` (used by
+/// `setarch`), and the query value. Anything else — disabling ASLR, making
+/// memory executable — is a trick, not a need.
+const ALLOWED_PERSONALITIES: &[u64] = &[0x0000, 0x0008, 0xffff_ffff];
+
+/// Name of a syscall blocked by some profile, for reporting.
+pub fn syscall_name(nr: i64) -> &'static str {
+    DEFAULT_BLOCKED
+        .iter()
+        .chain(STRICT_BLOCKED)
+        .chain(&[
+            ("unshare", libc::SYS_unshare),
+            ("clone", libc::SYS_clone),
+            ("personality", libc::SYS_personality),
+        ])
+        .find(|(_, n)| *n == nr)
+        .map_or("unknown syscall", |(name, _)| name)
+}
+
+fn backend_err(e: impl std::fmt::Display) -> SandboxError {
+    SandboxError::Platform(format!("seccomp filter failed: {e}"))
+}
+
+/// Syscall rules for `profile`. An empty rule list blocks the syscall
+/// regardless of arguments.
+fn rules(profile: SeccompProfile) -> Result<BTreeMap<i64, Vec<SeccompRule>>, SandboxError> {
+    let mut rules = BTreeMap::new();
+    let mut blocked = DEFAULT_BLOCKED.to_vec();
+    if profile == SeccompProfile::Strict {
+        blocked.extend_from_slice(STRICT_BLOCKED);
+    }
+    for (_, nr) in blocked {
+        rules.insert(nr, Vec::new());
+    }
+
+    // unshare(flags) and clone(flags, ...) — flags is the first argument on
+    // every supported architecture. One rule per namespace bit: a call
+    // matches if any of them is set.
+    let flags = namespace_flags(profile);
+    let ns_rules = || -> Result<Vec<SeccompRule>, SandboxError> {
+        (0..64)
+            .map(|bit| 1u64 << bit)
+            .filter(|bit| flags & bit != 0)
+            .map(|bit| {
+                SeccompCondition::new(0, SeccompCmpArgLen::Qword, SeccompCmpOp::MaskedEq(bit), bit)
+                    .and_then(|c| SeccompRule::new(vec![c]))
+                    .map_err(backend_err)
+            })
+            .collect()
+    };
+    rules.insert(libc::SYS_unshare, ns_rules()?);
+    rules.insert(libc::SYS_clone, ns_rules()?);
+
+    // personality(p) with p outside the allowed set.
+    let conditions = ALLOWED_PERSONALITIES
+        .iter()
+        .map(|&p| SeccompCondition::new(0, SeccompCmpArgLen::Dword, SeccompCmpOp::Ne, p))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(backend_err)?;
+    rules.insert(
+        libc::SYS_personality,
+        vec![SeccompRule::new(conditions).map_err(backend_err)?],
+    );
+
+    Ok(rules)
+}
+
+fn compile(
+    rules: BTreeMap<i64, Vec<SeccompRule>>,
+    action: SeccompAction,
+) -> Result<BpfProgram, SandboxError> {
+    let arch = TargetArch::try_from(std::env::consts::ARCH).map_err(backend_err)?;
+    SeccompFilter::new(rules, SeccompAction::Allow, action, arch)
+        .and_then(|f| f.try_into())
+        .map_err(backend_err)
+}
+
+/// Apply the seccomp profile to the current process. Irreversible.
+///
+/// Must be called from a single-threaded process: it forks the supervisor.
+pub fn apply_seccomp(profile: SeccompProfile) -> Result<(), SandboxError> {
+    if profile == SeccompProfile::Off {
+        return Ok(());
+    }
+
+    // clone3 passes its flags in a struct the filter can't inspect. Report
+    // it as missing so libc falls back to clone, which is filtered.
+    let clone3 = compile(
+        BTreeMap::from([(libc::SYS_clone3, Vec::new())]),
+        SeccompAction::Errno(libc::ENOSYS as u32),
+    )?;
+    seccompiler::apply_filter(&clone3).map_err(backend_err)?;
+
+    // seccompiler has no user-notification action: compile with Trap and
+    // rewrite those returns.
+    let mut notify = compile(rules(profile)?, SeccompAction::Trap)?;
+    for insn in &mut notify {
+        if insn.code == (libc::BPF_RET | libc::BPF_K) as u16 && insn.k == libc::SECCOMP_RET_TRAP {
+            insn.k = libc::SECCOMP_RET_USER_NOTIF;
+        }
+    }
+
+    let mut socks = [0; 2];
+    if unsafe {
+        libc::socketpair(
+            libc::AF_UNIX,
+            libc::SOCK_SEQPACKET | libc::SOCK_CLOEXEC,
+            0,
+            socks.as_mut_ptr(),
+        )
+    } != 0
+    {
+        return Err(backend_err(io::Error::last_os_error()));
+    }
+    let [parent_sock, child_sock] = socks;
+
+    // Fork the supervisor before installing the filter so it isn't
+    // filtered itself. Double fork: it must not be our child after exec.
+    match unsafe { libc::fork() } {
+        -1 => return Err(backend_err(io::Error::last_os_error())),
+        0 => {
+            unsafe { libc::close(parent_sock) };
+            if unsafe { libc::fork() } == 0 {
+                supervise(child_sock);
+            }
+            unsafe { libc::_exit(0) };
+        }
+        pid => unsafe {
+            libc::close(child_sock);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        },
+    }
+
+    let result = match install_with_listener(&notify) {
+        Ok(listener) => {
+            send_fd(parent_sock, listener);
+            unsafe { libc::close(listener) };
+            Ok(())
+        }
+        Err(_) => {
+            // Unreported fallback: plain EPERM.
+            compile(rules(profile)?, SeccompAction::Errno(libc::EPERM as u32))
+                .and_then(|p| seccompiler::apply_filter(&p).map_err(backend_err))
+        }
+    };
+    unsafe { libc::close(parent_sock) };
+    result
+}
+
+/// Install `program` and return its notification listener fd.
+fn install_with_listener(program: &BpfProgram) -> io::Result<libc::c_int> {
+    let prog = libc::sock_fprog {
+        len: program.len() as libc::c_ushort,
+        filter: program.as_ptr() as *mut libc::sock_filter,
+    };
+    unsafe {
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = libc::syscall(
+            libc::SYS_seccomp,
+            libc::SECCOMP_SET_MODE_FILTER,
+            libc::SECCOMP_FILTER_FLAG_NEW_LISTENER,
+            &prog as *const libc::sock_fprog,
+        );
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(fd as libc::c_int)
+    }
+}
+
+/// Pass `fd` over a Unix socket.
+fn send_fd(sock: libc::c_int, fd: libc::c_int) {
+    unsafe {
+        let mut byte = 0u8;
+        let mut iov = libc::iovec {
+            iov_base: &mut byte as *mut u8 as *mut libc::c_void,
+            iov_len: 1,
+        };
+        let space = libc::CMSG_SPACE(std::mem::size_of::<libc::c_int>() as u32) as usize;
+        let mut control = vec![0u8; space];
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = space as _;
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(std::mem::size_of::<libc::c_int>() as u32) as _;
+        std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut libc::c_int, fd);
+        libc::sendmsg(sock, &msg, 0);
+    }
+}
+
+/// Receive an fd sent by `send_fd`. `None` if the sender closed without one.
+fn recv_fd(sock: libc::c_int) -> Option<libc::c_int> {
+    unsafe {
+        let mut byte = 0u8;
+        let mut iov = libc::iovec {
+            iov_base: &mut byte as *mut u8 as *mut libc::c_void,
+            iov_len: 1,
+        };
+        let space = libc::CMSG_SPACE(std::mem::size_of::<libc::c_int>() as u32) as usize;
+        let mut control = vec![0u8; space];
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = space as _;
+        if libc::recvmsg(sock, &mut msg, 0) <= 0 {
+            return None;
+        }
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        if cmsg.is_null() || (*cmsg).cmsg_type != libc::SCM_RIGHTS {
+            return None;
+        }
+        Some(std::ptr::read_unaligned(
+            libc::CMSG_DATA(cmsg) as *const libc::c_int
+        ))
+    }
+}
+
+/// Supervisor loop: answer every notification with EPERM and report each
+/// blocked syscall once. Exits when no sandboxed process is left.
+fn supervise(sock: libc::c_int) -> ! {
+    unsafe {
+        // Out of the command's session and off its stdin/stdout, so job
+        // control signals and pipe EOFs don't involve us.
+        libc::setsid();
+        libc::close(0);
+        libc::close(1);
+    }
+    let Some(listener) = recv_fd(sock) else {
+        unsafe { libc::_exit(0) };
+    };
+    unsafe { libc::close(sock) };
+
+    let mut reported = HashSet::new();
+    loop {
+        let mut pfd = libc::pollfd {
+            fd: listener,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut pfd, 1, -1) } < 0 {
+            if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            break;
+        }
+        // POLLHUP without POLLIN: every filtered process has exited.
+        if pfd.revents & libc::POLLIN == 0 {
+            break;
+        }
+
+        let mut req: libc::seccomp_notif = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(listener, SECCOMP_IOCTL_NOTIF_RECV as _, &mut req) } < 0 {
+            // ENOENT: the caller died before we read it.
+            continue;
+        }
+        let name = syscall_name(req.data.nr as i64);
+        if reported.insert(name) {
+            diagnostics::report(&format!("{VIOLATION_PREFIX}{name}"));
+        }
+        let resp = libc::seccomp_notif_resp {
+            id: req.id,
+            val: 0,
+            error: -libc::EPERM,
+            flags: 0,
+        };
+        unsafe { libc::ioctl(listener, SECCOMP_IOCTL_NOTIF_SEND as _, &resp) };
+    }
+    unsafe { libc::_exit(0) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn syscall_names_cover_all_profiles() {
+        assert_eq!(syscall_name(libc::SYS_ptrace), "ptrace");
+        assert_eq!(syscall_name(libc::SYS_unshare), "unshare");
+        assert_eq!(syscall_name(libc::SYS_init_module), "init_module");
+        assert_eq!(syscall_name(libc::SYS_read), "unknown syscall");
+    }
+
+    #[test]
+    fn strict_blocks_more_than_default() {
+        let default = rules(SeccompProfile::Default).unwrap();
+        let strict = rules(SeccompProfile::Strict).unwrap();
+        assert!(default.contains_key(&libc::SYS_ptrace));
+        assert!(!default.contains_key(&libc::SYS_setns));
+        assert!(strict.contains_key(&libc::SYS_setns));
+        // Default only cares about user namespaces; strict about all of them.
+        assert_eq!(default[&libc::SYS_unshare].len(), 1);
+        assert!(strict[&libc::SYS_unshare].len() > 1);
+    }
+
+    #[test]
+    fn profiles_compile() {
+        for profile in [SeccompProfile::Default, SeccompProfile::Strict] {
+            let program = compile(rules(profile).unwrap(), SeccompAction::Trap).unwrap();
+            assert!(!program.is_empty());
+        }
+    }
+}
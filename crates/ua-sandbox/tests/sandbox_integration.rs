@@ -1,7 +1,8 @@
 //! Integration tests for sandbox enforcement.
 //!
 //! These tests spawn a child process that applies the sandbox and attempts
-//! file operations, verifying that the sandbox correctly allows/denies access.
+//! file and network operations, verifying that the sandbox correctly
+//! allows/denies access.
 //!
 //! The tests use the `unixagent` binary's `--sandbox-exec` subcommand.
 
@@ -145,3 +146,532 @@ fn sandbox_stderr_shows_active() {
         "stderr should contain sandbox active message. stderr: {stderr}"
     );
 }
+
+/// Run `args` under the default policy with `profile`; returns stderr.
+#[cfg(target_os = "linux")]
+fn seccomp_stderr(profile: ua_sandbox::SeccompProfile, args: &[&str]) -> String {
+    let policy = SandboxPolicy::default().with_seccomp(profile);
+    let output = Command::new(unixagent_bin())
+        .arg("--sandbox-exec")
+        .args(args)
+        .env(SANDBOX_ENV_VAR, policy.to_json())
+        .output()
+        .expect("failed to execute");
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn seccomp_blocks_user_namespace() {
+    use ua_sandbox::SeccompProfile;
+
+    let stderr = seccomp_stderr(SeccompProfile::Default, &["unshare", "-U", "true"]);
+    assert!(
+        stderr.contains("[ua:sandbox] blocked by sandbox: unshare"),
+        "stderr: {stderr}"
+    );
+
+    let stderr = seccomp_stderr(SeccompProfile::Off, &["unshare", "-U", "true"]);
+    assert!(!stderr.contains("blocked by sandbox"), "stderr: {stderr}");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn seccomp_strict_blocks_any_namespace() {
+    use ua_sandbox::SeccompProfile;
+
+    let stderr = seccomp_stderr(SeccompProfile::Default, &["unshare", "-n", "true"]);
+    assert!(!stderr.contains("blocked by sandbox"), "stderr: {stderr}");
+
+    let stderr = seccomp_stderr(SeccompProfile::Strict, &["unshare", "-n", "true"]);
+    assert!(
+        stderr.contains("blocked by sandbox: unshare"),
+        "stderr: {stderr}"
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn seccomp_allows_threads_and_ordinary_commands() {
+    use ua_sandbox::SeccompProfile;
+
+    let policy = SandboxPolicy::default().with_seccomp(SeccompProfile::Strict);
+    // sort --parallel spawns threads (clone3 → clone fallback)
+    let output = Command::new(unixagent_bin())
+        .arg("--sandbox-exec")
+        .arg("sh")
+        .arg("-c")
+        .arg("printf 'b\\na\\n' | sort --parallel=4")
+        .env(SANDBOX_ENV_VAR, policy.to_json())
+        .output()
+        .expect("failed to execute");
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a\nb\n");
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("blocked by sandbox"));
+}
+
+/// Run `sh -c script` under the default policy with `limits`.
+fn run_limited(limits: ua_sandbox::ResourceLimits, script: &str) -> std::process::Output {
+    let policy = SandboxPolicy::default().with_limits(limits);
+    Command::new(unixagent_bin())
+        .arg("--sandbox-exec")
+        .arg("sh")
+        .arg("-c")
+        .arg(script)
+        .env(SANDBOX_ENV_VAR, policy.to_json())
+        .output()
+        .expect("failed to execute")
+}
+
+#[test]
+fn timeout_kills_process_group() {
+    let marker = format!("/tmp/ua-timeout-test-{}", std::process::id());
+    let start = std::time::Instant::now();
+    // The background job must die with the shell, or it would write the marker.
+    let output = run_limited(
+        ua_sandbox::ResourceLimits {
+            timeout_secs: 1,
+            ..Default::default()
+        },
+        &format!("(sleep 3; echo leaked > {marker}) & sleep 30"),
+    );
+    let elapsed = start.elapsed();
+    std::thread::sleep(std::time::Duration::from_secs(3));
+    let leaked = std::path::Path::new(&marker).exists();
+    let _ = std::fs::remove_file(&marker);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(124), "stderr: {stderr}");
+    assert!(
+        stderr.contains("[ua:sandbox] killed: timeout after 1s"),
+        "stderr: {stderr}"
+    );
+    assert!(elapsed.as_secs() < 10, "took {elapsed:?}");
+    assert!(!leaked, "background job outlived the timeout");
+}
+
+#[test]
+fn timeout_escalates_to_sigkill() {
+    let output = run_limited(
+        ua_sandbox::ResourceLimits {
+            timeout_secs: 1,
+            ..Default::default()
+        },
+        "trap '' TERM; while :; do sleep 1; done",
+    );
+    assert_eq!(output.status.code(), Some(124));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("killed: timeout after 1s"));
+}
+
+/// With a timeout and a terminal, the command must be able to read from
+/// the terminal rather than stop in a background process group.
+#[test]
+fn timeout_leaves_the_terminal_to_the_command() {
+    if Command::new("script").arg("--version").output().is_err() {
+        return;
+    }
+    let policy = SandboxPolicy::default().with_limits(ua_sandbox::ResourceLimits {
+        timeout_secs: 20,
+        ..Default::default()
+    });
+    let inner = format!(
+        "{} --sandbox-exec sh -c 'read x; echo got $x'",
+        unixagent_bin().display()
+    );
+    let start = std::time::Instant::now();
+    let mut child = Command::new("script")
+        .args(["-qec", &inner, "/dev/null"])
+        .env(SANDBOX_ENV_VAR, policy.to_json())
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .expect("failed to execute");
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    {
+        use std::io::Write;
+        let mut stdin = child.stdin.take().unwrap();
+        stdin.write_all(b"hello\n").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(500));
+    }
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("got hello"), "stdout: {stdout}");
+    assert!(start.elapsed().as_secs() < 10, "took {:?}", start.elapsed());
+}
+
+#[test]
+fn timeout_terminates_a_stopped_command() {
+    let start = std::time::Instant::now();
+    let output = run_limited(
+        ua_sandbox::ResourceLimits {
+            timeout_secs: 1,
+            ..Default::default()
+        },
+        "kill -STOP $$",
+    );
+    assert_eq!(output.status.code(), Some(124));
+    // SIGTERM works without waiting for the SIGKILL grace period.
+    assert!(start.elapsed() < ua_sandbox::limits::KILL_GRACE);
+}
+
+#[test]
+fn limits_preserve_exit_code() {
+    let output = run_limited(ua_sandbox::ResourceLimits::default(), "exit 3");
+    assert_eq!(output.status.code(), Some(3));
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("killed"));
+}
+
+#[test]
+fn file_size_limit_truncates_writes() {
+    let file = format!("/tmp/ua-fsize-test-{}", std::process::id());
+    let output = run_limited(
+        ua_sandbox::ResourceLimits {
+            max_file_size_mb: 1,
+            ..Default::default()
+        },
+        &format!("yes | head -c 3000000 > {file}"),
+    );
+    let size = std::fs::metadata(&file).map(|m| m.len()).unwrap_or(0);
+    let _ = std::fs::remove_file(&file);
+    assert!(!output.status.success());
+    assert_eq!(size, 1024 * 1024);
+}
+
+/// Network egress rules (Linux only).
+#[cfg(target_os = "linux")]
+mod network {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use ua_sandbox::{NetworkMode, NetworkPolicy};
+
+    /// Serve a fixed HTTP response on 127.0.0.1 from a background thread.
+    /// Returns the port.
+    fn local_http_listener() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf);
+                let _ = stream.write_all(b"HTTP/1.0 200 OK\r\n\r\nhello from host");
+            }
+        });
+        port
+    }
+
+    fn has_binary(name: &str) -> bool {
+        Command::new("sh")
+            .arg("-c")
+            .arg(format!("command -v {name}"))
+            .output()
+            .is_ok_and(|o| o.status.success())
+    }
+
+    /// Run `curl` to the local listener under `network`.
+    fn curl_local(network: NetworkPolicy, port: u16) -> std::process::Output {
+        let policy = SandboxPolicy::default().with_network(network);
+        Command::new(unixagent_bin())
+            .arg("--sandbox-exec")
+            .arg("curl")
+            .arg("-sS")
+            .arg("--max-time")
+            .arg("5")
+            .arg(format!("http://127.0.0.1:{port}/"))
+            .env(SANDBOX_ENV_VAR, policy.to_json())
+            .output()
+            .expect("failed to execute")
+    }
+
+    #[test]
+    fn sandbox_network_allow_reaches_local_listener() {
+        if !has_binary("curl") {
+            eprintln!("skipping test: curl not installed");
+            return;
+        }
+        let port = local_http_listener();
+        let output = curl_local(NetworkPolicy::default(), port);
+        assert!(
+            String::from_utf8_lossy(&output.stdout).contains("hello from host"),
+            "curl should reach the listener. stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    #[test]
+    fn sandbox_network_deny_blocks_curl() {
+        if !has_binary("curl") {
+            eprintln!("skipping test: curl not installed");
+            return;
+        }
+        let port = local_http_listener();
+        let output = curl_local(
+            NetworkPolicy {
+                mode: NetworkMode::Deny,
+                allow_ports: vec![],
+            },
+            port,
+        );
+        assert!(
+            !output.status.success(),
+            "curl should fail with network denied. stdout: {}, stderr: {}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(!String::from_utf8_lossy(&output.stdout).contains("hello from host"));
+    }
+
+    #[test]
+    fn sandbox_network_deny_blocks_nc() {
+        if !has_binary("nc") {
+            eprintln!("skipping test: nc not installed");
+            return;
+        }
+        let port = local_http_listener();
+        let policy = SandboxPolicy::default().with_network(NetworkPolicy {
+            mode: NetworkMode::Deny,
+            allow_ports: vec![],
+        });
+        let output = Command::new(unixagent_bin())
+            .arg("--sandbox-exec")
+            .arg("sh")
+            .arg("-c")
+            .arg(format!("echo GET | nc -w 3 127.0.0.1 {port}"))
+            .env(SANDBOX_ENV_VAR, policy.to_json())
+            .output()
+            .expect("failed to execute");
+        assert!(
+            !String::from_utf8_lossy(&output.stdout).contains("hello from host"),
+            "nc should not reach the listener. stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    #[test]
+    fn sandbox_network_loopback_hides_host_listener() {
+        if !has_binary("curl") {
+            eprintln!("skipping test: curl not installed");
+            return;
+        }
+        let port = local_http_listener();
+        let output = curl_local(
+            NetworkPolicy {
+                mode: NetworkMode::Loopback,
+                allow_ports: vec![],
+            },
+            port,
+        );
+        assert!(
+            !output.status.success(),
+            "host loopback should be unreachable. stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    #[test]
+    fn sandbox_network_ports_allows_only_listed() {
+        if !has_binary("curl") {
+            eprintln!("skipping test: curl not installed");
+            return;
+        }
+        let port = local_http_listener();
+
+        let listed = curl_local(
+            NetworkPolicy {
+                mode: NetworkMode::Ports,
+                allow_ports: vec![port],
+            },
+            port,
+        );
+        let stderr = String::from_utf8_lossy(&listed.stderr);
+        if stderr.contains("kernel 6.7+ required") {
+            eprintln!("skipping test: Landlock network rules unsupported");
+            return;
+        }
+        assert!(
+            String::from_utf8_lossy(&listed.stdout).contains("hello from host"),
+            "listed port should be reachable. stderr: {stderr}"
+        );
+
+        let unlisted = curl_local(
+            NetworkPolicy {
+                mode: NetworkMode::Ports,
+                allow_ports: vec![port.wrapping_add(1)],
+            },
+            port,
+        );
+        assert!(
+            !unlisted.status.success(),
+            "unlisted port should be blocked. stdout: {}",
+            String::from_utf8_lossy(&unlisted.stdout)
+        );
+    }
+}
+
+/// Filesystem backends (Linux only).
+#[cfg(target_os = "linux")]
+mod backends {
+    use super::*;
+    use std::path::{Path, PathBuf};
+    use ua_sandbox::SandboxBackend;
+
+    /// Scratch tree: `work/` is writable and holds a secret dir, a token
+    /// file, and a config file that are denied or write-protected; `other/`
+    /// is outside the policy.
+    fn scratch(name: &str) -> PathBuf {
+        let root = PathBuf::from(format!("/tmp/ua-backend-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("work/secret")).unwrap();
+        std::fs::create_dir_all(root.join("other")).unwrap();
+        std::fs::write(root.join("work/secret/key"), "topsecret").unwrap();
+        std::fs::write(root.join("work/token"), "tokenvalue").unwrap();
+        std::fs::write(root.join("work/config.toml"), "original").unwrap();
+        std::fs::write(root.join("other/file"), "outside").unwrap();
+        root
+    }
+
+    fn policy(backend: SandboxBackend, root: &Path) -> SandboxPolicy {
+        let work = root.join("work").display().to_string();
+        SandboxPolicy::from_config(
+            std::slice::from_ref(&work),
+            &[
+                "/usr".to_string(),
+                "/bin".to_string(),
+                "/lib".to_string(),
+                "/lib64".to_string(),
+                "/etc".to_string(),
+                "/dev/null".to_string(),
+            ],
+            &[format!("{work}/secret"), format!("{work}/token")],
+        )
+        .with_deny_write(&[format!("{work}/config.toml")])
+        .with_backend(backend)
+    }
+
+    fn run(policy: &SandboxPolicy, cwd: &Path, script: &str) -> std::process::Output {
+        Command::new(unixagent_bin())
+            .arg("--sandbox-exec")
+            .arg("sh")
+            .arg("-c")
+            .arg(script)
+            .current_dir(cwd)
+            .env(SANDBOX_ENV_VAR, policy.to_json())
+            .output()
+            .expect("failed to execute")
+    }
+
+    /// Whether this machine can create the namespaces the backend needs.
+    fn namespace_available() -> bool {
+        let root = scratch("probe");
+        let output = run(
+            &policy(SandboxBackend::Namespace, &root),
+            &root.join("work"),
+            "true",
+        );
+        let _ = std::fs::remove_dir_all(&root);
+        output.status.success()
+    }
+
+    #[test]
+    fn both_backends_allow_writable_and_readable_paths() {
+        for backend in [SandboxBackend::Landlock, SandboxBackend::Namespace] {
+            if backend == SandboxBackend::Namespace && !namespace_available() {
+                eprintln!("skipping namespace backend: user namespaces unavailable");
+                continue;
+            }
+            let root = scratch(&format!("{backend:?}-allow"));
+            let output = run(
+                &policy(backend, &root),
+                &root.join("work"),
+                "echo new > new.txt && cat new.txt && test -r /etc/passwd && pwd",
+            );
+            let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+            let _ = std::fs::remove_dir_all(&root);
+            assert!(
+                output.status.success(),
+                "{backend:?}: stderr: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            assert!(stdout.starts_with("new\n"), "{backend:?}: {stdout}");
+            assert!(
+                stdout.trim_end().ends_with("/work"),
+                "{backend:?}: {stdout}"
+            );
+        }
+    }
+
+    #[test]
+    fn both_backends_deny_paths_outside_policy() {
+        for backend in [SandboxBackend::Landlock, SandboxBackend::Namespace] {
+            if backend == SandboxBackend::Namespace && !namespace_available() {
+                eprintln!("skipping namespace backend: user namespaces unavailable");
+                continue;
+            }
+            let root = scratch(&format!("{backend:?}-outside"));
+            let other = root.join("other");
+            let output = run(
+                &policy(backend, &root),
+                &root.join("work"),
+                &format!(
+                    "cat {0}/file; echo x > {0}/new; cat /etc/passwd > {0}/copy",
+                    other.display()
+                ),
+            );
+            let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+            let leaked = other.join("new").exists() || other.join("copy").exists();
+            let _ = std::fs::remove_dir_all(&root);
+            assert!(!stdout.contains("outside"), "{backend:?}: {stdout}");
+            assert!(!leaked, "{backend:?}: wrote outside the policy");
+        }
+    }
+
+    #[test]
+    fn namespace_backend_denies_beneath_writable() {
+        if !namespace_available() {
+            eprintln!("skipping test: user namespaces unavailable");
+            return;
+        }
+        let root = scratch("deny-under-writable");
+        let output = run(
+            &policy(SandboxBackend::Namespace, &root),
+            &root.join("work"),
+            "ls secret; cat secret/key; cat token; echo tampered > config.toml; \
+             touch secret/planted; echo ok",
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let config = std::fs::read_to_string(root.join("work/config.toml")).unwrap();
+        let planted = root.join("work/secret/planted").exists();
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert!(stdout.ends_with("ok\n"), "stdout: {stdout}");
+        assert!(!stdout.contains("topsecret"), "stdout: {stdout}");
+        assert!(!stdout.contains("tokenvalue"), "stdout: {stdout}");
+        assert!(!stdout.contains("key"), "secret dir listed: {stdout}");
+        assert_eq!(config, "original");
+        assert!(!planted, "wrote into a denied directory");
+    }
+
+    #[test]
+    fn namespace_backend_hides_unlisted_root_entries() {
+        if !namespace_available() {
+            eprintln!("skipping test: user namespaces unavailable");
+            return;
+        }
+        let root = scratch("root-entries");
+        let output = run(
+            &policy(SandboxBackend::Namespace, &root),
+            &root.join("work"),
+            "ls /",
+        );
+        let _ = std::fs::remove_dir_all(&root);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let entries: Vec<&str> = stdout.lines().collect();
+        assert!(entries.contains(&"usr"), "{entries:?}");
+        assert!(!entries.contains(&"root"), "{entries:?}");
+        assert!(!entries.contains(&"proc"), "{entries:?}");
+    }
+}